@@ -0,0 +1,8 @@
+#![no_main]
+
+use esharp::vm::bin::def::constant::ConstantTable;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	let _ = ConstantTable::parse(data);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use esharp::vm::insn;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(insns) = insn::decode_all(data) {
+		for (_, insn) in insns {
+			insn.to_string();
+		}
+	}
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use esharp::vm::{Engine, Vm};
+use esharp::vm::bin::{Executable, inspect};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(exec) = Executable::parse(data) {
+		inspect::to_json(&exec);
+		let mut vm = Vm::with_engine(Engine::Interpreter);
+		let _ = vm.load(exec);
+	}
+});
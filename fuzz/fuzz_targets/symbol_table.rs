@@ -0,0 +1,8 @@
+#![no_main]
+
+use esharp::vm::bin::def::symbol::SymbolTable;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	let _ = SymbolTable::parse(data);
+});
@@ -0,0 +1,39 @@
+#![no_main]
+
+use std::collections::HashMap;
+use esharp::vm::Vm;
+use esharp::vm::bin::Executable;
+use esharp::vm::jit::{Context, Traps};
+use esharp::vm::meta::platform::Platform;
+use esharp::vm::types::function::Signature;
+use esharp::vm::verify;
+use libfuzzer_sys::fuzz_target;
+
+thread_local! {
+	/// A VM with the classes and functions of `test.esbin`, for the code to refer to
+	static VM: Vm = {
+		let mut vm = Vm::new();
+		vm.load(Executable::parse(include_bytes!("../../test.esbin")).unwrap()).unwrap();
+		vm
+	};
+}
+
+// the code is verified as the body of `#bar`, so it can use its arguments
+fuzz_target!(|data: &[u8]| {
+	VM.with(|vm| {
+		let (constants, bar) = vm.definition("#bar").unwrap();
+		let hidden = HashMap::new();
+		let traps = Traps::new();
+		let ctx = Context {
+			constants,
+			classes: vm.classes(),
+			functions: vm.functions(),
+			heap: vm.heap(),
+			traps: &traps,
+			hidden: &hidden,
+			exec: 0,
+			platform: Platform::current(),
+		};
+		let _ = verify::verify(data, &Signature::of(bar), &ctx);
+	});
+});
@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::exit;
+
+use esharp::compiler::build;
+use esharp::compiler::repl::{self, Output, Repl};
+use esharp::vm::{Engine, Vm};
+use esharp::vm::bin::{asm, inspect, link, DynamicLibrary, Executable};
+use esharp::vm::bin::buffer::Bytes;
+use esharp::vm::types::{TypeDesc, TypeFlags, TypeId};
+use esharp::vm::types::str::Str;
+
+const USAGE: &str = "usage: esharp run [--jit | --interp] [--lazy] [--entry <name>] [--lib <lib.esbin>]... <file.esbin> [args...]
+       esharp inspect <file.esbin>
+       esharp link [--checksum] -o <out.esbin> <file.esbin>...
+       esharp asm -o <out.esbin> <file.esasm>
+       esharp compile [--lib] -o <out.esbin> <file.es>...
+       esharp build [--lib] [--cache <dir>] -o <out.esbin> <dir>
+       esharp repl [--jit | --interp]";
+
+/// The options of `esharp run`
+struct RunOptions {
+	engine: Engine,
+	lazy: bool,
+	entry: String,
+	libs: Vec<String>,
+	path: String,
+	args: Vec<String>,
+}
+
+fn main() {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	match args.first().map(String::as_str) {
+		Some("run") => match parse_run(&args[1..]) {
+			Ok(options) => exit(run(options)),
+			Err(msg) => usage(&msg),
+		},
+		Some("inspect") => match &args[1..] {
+			[path] => println!("{}", inspect::to_json(&parse(path).unwrap_or_else(|msg| fail(msg)))),
+			_ => usage("`inspect` takes exactly one executable"),
+		},
+		Some("link") => {
+			let checksum = args.get(1).is_some_and(|flag| flag == "--checksum");
+			match &args[1 + checksum as usize..] {
+				[flag, out, inputs @ ..] if flag == "-o" && !inputs.is_empty() => link(out, inputs, checksum),
+				_ => usage("`link` takes an output file and at least one executable"),
+			}
+		},
+		Some("asm") => match &args[1..] {
+			[flag, out, input] if flag == "-o" => assemble(out, input),
+			_ => usage("`asm` takes an output file and exactly one assembly file"),
+		},
+		Some("compile") => match parse_build(&args[1..], false) {
+			Ok(options) if !options.inputs.is_empty() => compile(options),
+			Ok(_) => usage("`compile` takes at least one source file"),
+			Err(msg) => usage(&msg),
+		},
+		Some("build") => match parse_build(&args[1..], true) {
+			Ok(options) if options.inputs.len() == 1 => build(options),
+			Ok(_) => usage("`build` takes exactly one directory"),
+			Err(msg) => usage(&msg),
+		},
+		Some("repl") => match &args[1..] {
+			[] => repl(Engine::Interpreter),
+			[flag] if flag == "--interp" => repl(Engine::Interpreter),
+			[flag] if flag == "--jit" => repl(Engine::Jit),
+			_ => usage("`repl` only takes an engine"),
+		},
+		Some("-h" | "--help") => println!("{}", USAGE),
+		Some(command) => usage(&format!("unknown command `{}`", command)),
+		None => usage("missing command"),
+	}
+}
+
+fn usage(msg: &str) -> ! {
+	eprintln!("error: {}\n{}", msg, USAGE);
+	exit(2)
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+	eprintln!("error: {}", msg);
+	exit(1)
+}
+
+/// The options of `esharp compile` and `esharp build`
+struct BuildOptions {
+	library: bool,
+	cache: Option<PathBuf>,
+	out: String,
+	inputs: Vec<PathBuf>,
+}
+
+fn parse_build(args: &[String], cache: bool) -> Result<BuildOptions, String> {
+	let mut options = BuildOptions {
+		library: false,
+		cache: None,
+		out: String::new(),
+		inputs: Vec::new(),
+	};
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--lib" => options.library = true,
+			"--cache" if cache => options.cache = Some(PathBuf::from(args.next().ok_or("missing directory after `--cache`")?)),
+			"-o" => options.out = args.next().ok_or("missing file after `-o`")?.clone(),
+			flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+			input => options.inputs.push(PathBuf::from(input)),
+		}
+	}
+	if options.out.is_empty() {
+		return Err(String::from("missing output file"))
+	}
+	Ok(options)
+}
+
+fn parse_run(args: &[String]) -> Result<RunOptions, String> {
+	let mut engine = Engine::default();
+	let mut lazy = false;
+	let mut entry = String::from("main");
+	let mut libs = Vec::new();
+	let mut args = args.iter();
+	let path = loop {
+		match args.next().map(String::as_str) {
+			Some("--jit") => engine = Engine::Jit,
+			Some("--interp") => engine = Engine::Interpreter,
+			Some("--lazy") => lazy = true,
+			Some("--entry") => entry = args.next().ok_or("missing name after `--entry`")?.clone(),
+			Some("--lib") => libs.push(args.next().ok_or("missing library after `--lib`")?.clone()),
+			Some(flag) if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+			Some(path) => break path.to_string(),
+			None => return Err(String::from("missing executable")),
+		}
+	};
+	Ok(RunOptions {
+		engine,
+		lazy,
+		entry,
+		libs,
+		path,
+		args: args.cloned().collect(),
+	})
+}
+
+/// Maps an executable into memory, reporting malformed input as an error
+fn parse(path: &str) -> Result<Executable, String> {
+	let file = File::open(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+	// the executable is only read from while the CLI runs
+	let bytes = unsafe { Bytes::map(&file) }.map_err(|err| format!("failed to read {}: {}", path, err))?;
+	Executable::parse_shared(bytes).map_err(|err| format!("{} is not a valid executable: {}", path, err))
+}
+
+/// Links executables into one, and writes it to `out`
+fn link(out: &str, inputs: &[String], checksum: bool) {
+	let execs = inputs.iter().map(|path| parse(path)).collect::<Result<Vec<_>, _>>().unwrap_or_else(|msg| fail(msg));
+	let mut linked = link::link(&execs).unwrap_or_else(|err| fail(format!("failed to link: {}", err)));
+	linked.checksum = checksum;
+	// unmap the inputs before writing, `out` can be one of them
+	drop(execs);
+	std::fs::write(out, linked.build()).unwrap_or_else(|err| fail(format!("failed to write {}: {}", out, err)));
+}
+
+/// Assembles `input`, and writes the executable to `out`
+fn assemble(out: &str, input: &str) {
+	let src = std::fs::read_to_string(input).unwrap_or_else(|err| fail(format!("failed to read {}: {}", input, err)));
+	let builder = asm::assemble(&src).unwrap_or_else(|err| fail(format!("{}: {}", input, err)));
+	std::fs::write(out, builder.build()).unwrap_or_else(|err| fail(format!("failed to write {}: {}", out, err)));
+}
+
+/// Compiles source files, and writes the executable to `out`
+fn compile(options: BuildOptions) {
+	let builder = build::compile(&options.inputs, options.library).unwrap_or_else(|err| fail(err));
+	std::fs::write(&options.out, builder.build()).unwrap_or_else(|err| fail(format!("failed to write {}: {}", options.out, err)));
+}
+
+/// Builds a directory of packages, and writes the executable to `out`
+fn build(options: BuildOptions) {
+	let root = &options.inputs[0];
+	let cache = options.cache.unwrap_or_else(|| root.join(".esharp"));
+	let build = build::build(root, &cache, options.library).unwrap_or_else(|err| fail(err));
+	std::fs::write(&options.out, build.builder.build()).unwrap_or_else(|err| fail(format!("failed to write {}: {}", options.out, err)));
+	eprintln!("compiled {} packages, {} up to date", build.compiled.len(), build.cached.len());
+}
+
+/// Reads inputs from stdin until it ends or `:quit` is entered, and prints what each evaluated to<br>
+/// An input continues on the next line while it has unclosed brackets or comments.
+fn repl(engine: Engine) {
+	let mut repl = Repl::new(engine);
+	let mut stdin = std::io::stdin().lock();
+	let mut input = String::new();
+	loop {
+		print!("{}", if input.is_empty() { ">> " } else { ".. " });
+		let _ = std::io::stdout().flush();
+		let mut line = String::new();
+		match stdin.read_line(&mut line) {
+			Ok(0) => break println!(),
+			Ok(_) => {},
+			Err(err) => fail(format!("failed to read stdin: {}", err)),
+		}
+		if input.is_empty() && line.trim() == ":quit" {
+			break
+		}
+		input.push_str(&line);
+		if !repl::is_complete(&input) {
+			continue
+		}
+		if !input.trim().is_empty() {
+			match repl.eval(&input) {
+				Ok(Output::Defined(names)) => println!("defined {}", names.join(", ")),
+				Ok(Output::Value(value, ty)) => println!("{}: {}", value, ty),
+				Ok(Output::Void) => {},
+				Err(err) => eprintln!("error: {}", err),
+			}
+		}
+		input.clear();
+	}
+}
+
+/// Converts a command line argument to the argument word of a value of type `ty`
+fn argument(vm: &Vm, ty: TypeDesc, arg: &str) -> Result<u64, String> {
+	let invalid = || format!("invalid argument `{}`, expected {}", arg, type_name(ty));
+	match ty.id() {
+		_ if ty.is_int() && ty.is_unsigned() => arg.parse::<u64>().map_err(|_| invalid()),
+		_ if ty.is_int() => arg.parse::<i64>().map(|int| int as u64).map_err(|_| invalid()),
+		TypeFlags::F32 => arg.parse::<f32>().map(|float| float.to_bits() as u64).map_err(|_| invalid()),
+		TypeFlags::F64 => arg.parse::<f64>().map(f64::to_bits).map_err(|_| invalid()),
+		TypeFlags::STR => {
+			let str = vm.new_str(arg);
+			vm.heap().root(str as *const u8);
+			Ok(str as u64)
+		},
+		_ => Err(format!("arguments of type {} cannot be passed from the command line", type_name(ty))),
+	}
+}
+
+fn type_name(ty: TypeDesc) -> &'static str {
+	match ty.id() {
+		TypeFlags::I8 | TypeFlags::I16 | TypeFlags::I32 | TypeFlags::I64 if ty.is_unsigned() => "an unsigned integer",
+		TypeFlags::I8 | TypeFlags::I16 | TypeFlags::I32 | TypeFlags::I64 => "an integer",
+		TypeFlags::F32 | TypeFlags::F64 => "a float",
+		TypeFlags::STR => "str",
+		TypeFlags::OBJECT => "object",
+		TypeFlags::FUNCTION => "function",
+		TypeFlags::ARRAY => "array",
+		TypeFlags::DYN => "dyn",
+		_ => "void",
+	}
+}
+
+/// Runs the entry function, printing its return value, and returns the exit code
+fn run(options: RunOptions) -> i32 {
+	let mut vm = Vm::with_engine(options.engine);
+	vm.set_lazy(options.lazy);
+	for path in &options.libs {
+		let lib = DynamicLibrary::try_from(parse(path).unwrap_or_else(|msg| fail(msg)))
+			.unwrap_or_else(|err| fail(format!("{} is not a valid library: {}", path, err)));
+		vm.load_library(lib).unwrap_or_else(|err| fail(format!("failed to load {}: {}", path, err)));
+	}
+	let exec = parse(&options.path).unwrap_or_else(|msg| fail(msg));
+	vm.load(exec).unwrap_or_else(|err| fail(format!("failed to load {}: {}", options.path, err)));
+
+	let signature = match vm.functions().get(&options.entry) {
+		Some(slot) => slot.signature().clone(),
+		None => fail(format!("{} has no function {}", options.path, options.entry)),
+	};
+	if options.args.len() != signature.args.len() {
+		fail(format!("function {} takes {} arguments, but {} were given", options.entry, signature.args.len(), options.args.len()))
+	}
+	let args = signature.args.iter().zip(&options.args)
+		.map(|(ty, arg)| argument(&vm, *ty, arg))
+		.collect::<Result<Vec<_>, _>>()
+		.unwrap_or_else(|msg| usage(&msg));
+
+	let ret = vm.call(&options.entry, &args).unwrap_or_else(|err| fail(err));
+	let ty = signature.ret;
+	match ty.id() {
+		TypeFlags::VOID => 0,
+		TypeFlags::F32 => {
+			println!("{}", f32::from_bits(ret as u32));
+			0
+		},
+		TypeFlags::F64 => {
+			println!("{}", f64::from_bits(ret));
+			0
+		},
+		TypeFlags::STR => {
+			println!("{}", unsafe { (*(ret as *const Str)).as_str() });
+			0
+		},
+		_ if ty.is_int() && ty.is_unsigned() => {
+			println!("{}", ret);
+			ret as i32
+		},
+		_ if ty.is_int() => {
+			println!("{}", ret as i64);
+			ret as i32
+		},
+		_ => {
+			println!("{:#X}", ret);
+			0
+		},
+	}
+}
@@ -0,0 +1,291 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use crate::compiler::error::build::BuildError;
+use crate::compiler::syntax::ast;
+use crate::compiler::syntax::lexer::{self, Punct, TokenKind};
+use crate::compiler::typed::Program;
+use crate::compiler::{check, codegen, syntax};
+use crate::vm::bin::Executable;
+use crate::vm::bin::builder::{ExecutableBuilder, Symbol};
+use crate::vm::bin::def::symbol::SymbolKind;
+use crate::vm::bin::link::Linker;
+
+/// The extension of E# source files
+pub const EXTENSION: &str = "es";
+
+/// The result of [`build`]
+#[derive(Debug)]
+pub struct Build {
+	pub builder: ExecutableBuilder,
+	/// The packages that were compiled
+	pub compiled: Vec<String>,
+	/// The packages whose executable was taken from the cache
+	pub cached: Vec<String>,
+}
+
+/// A source file, and the package it is
+struct Source {
+	package: String,
+	path: PathBuf,
+	src: String,
+}
+
+/// Compiles source files into an executable, a library exporting every class and function if
+/// `library` is set<br>
+/// The files can be in any package, which they declare with `package`.
+pub fn compile(paths: &[PathBuf], library: bool) -> Result<ExecutableBuilder, BuildError> {
+	let mut sources = Vec::new();
+	for path in paths {
+		let src = fs::read_to_string(path).map_err(|err| BuildError::Io(path.clone(), err))?;
+		sources.push(Source {
+			package: String::new(),
+			path: path.clone(),
+			src,
+		});
+	}
+	let files = parse(&sources)?;
+	let program = check::check(&files).map_err(|diagnostics| {
+		BuildError::Diagnostics(diagnostics.iter().map(|(file, diagnostic)| diagnostic.format(&sources[*file].path.display().to_string(), &sources[*file].src)).collect())
+	})?;
+	let mut builder = codegen::generate(&program).map_err(BuildError::Codegen)?;
+	if library {
+		export(&mut builder);
+	}
+	Ok(builder)
+}
+
+/// Builds the directory tree of packages at `root`, where `foo/bar.es` is package `foo.bar`<br>
+/// Each package is compiled to its own executable, which is kept in `cache` under a hash of the
+/// sources of the package and of every package it depends on, so only packages that changed (or
+/// whose dependencies changed) are compiled again. The executables are then linked into one,
+/// a library exporting every class and function if `library` is set.<br>
+/// ***Note:** A package depends on the packages it names, by a `use` or a fully-qualified path.
+/// Directories starting with `.` are skipped, so the cache can be kept in `root`.*
+pub fn build(root: &Path, cache: &Path, library: bool) -> Result<Build, BuildError> {
+	let mut sources = Vec::new();
+	discover(root, root, &mut sources)?;
+	sources.sort_by(|a, b| a.package.cmp(&b.package));
+	let mut files = parse(&sources)?;
+	for (source, file) in sources.iter().zip(&mut files) {
+		match &file.package {
+			Some(declared) if declared.to_string() != source.package => {
+				return Err(BuildError::PackageMismatch(source.path.clone(), declared.to_string(), source.package.clone()))
+			},
+			Some(_) => {},
+			None => file.package = Some(ast::Path {
+				segments: source.package.split('.').map(|name| ast::Ident {
+					name: name.to_string(),
+					span: Default::default(),
+				}).collect(),
+				span: Default::default(),
+			}),
+		}
+	}
+
+	let indices = sources.iter().enumerate().map(|(index, source)| (source.package.as_str(), index)).collect::<HashMap<_, _>>();
+	let dependencies = sources.iter().map(|source| dependencies(&source.src, &indices)).collect::<Vec<_>>();
+	fs::create_dir_all(cache).map_err(|err| BuildError::Io(cache.to_path_buf(), err))?;
+
+	let mut build = Build {
+		builder: ExecutableBuilder::new(),
+		compiled: Vec::new(),
+		cached: Vec::new(),
+	};
+	let mut execs = Vec::new();
+	let mut diagnostics = Vec::<String>::new();
+	for (index, source) in sources.iter().enumerate() {
+		let closure = closure(index, &dependencies);
+		let path = cache.join(format!("{}.{:016x}.esbin", source.package, key(&closure, &sources)));
+		if let Some(exec) = fs::read(&path).ok().and_then(|bytes| Executable::parse(&bytes).ok()) {
+			build.cached.push(source.package.clone());
+			execs.push(exec);
+			continue
+		}
+
+		let closure_files = closure.iter().map(|index| files[*index].clone()).collect::<Vec<_>>();
+		let program = match check::check(&closure_files) {
+			Ok(program) => program,
+			Err(errors) => {
+				for (file, diagnostic) in errors {
+					let source = &sources[closure[file]];
+					let diagnostic = diagnostic.format(&source.path.display().to_string(), &source.src);
+					if !diagnostics.contains(&diagnostic) {
+						diagnostics.push(diagnostic);
+					}
+				}
+				continue
+			},
+		};
+		let bytes = codegen::generate(&only(program, &source.package)).map_err(BuildError::Codegen)?.build();
+		fs::write(&path, &bytes).map_err(|err| BuildError::Io(path.clone(), err))?;
+		evict(cache, &source.package, &path);
+		build.compiled.push(source.package.clone());
+		execs.push(Executable::parse(&bytes).expect("Failed to parse a built executable"));
+	}
+	if !diagnostics.is_empty() {
+		return Err(BuildError::Diagnostics(diagnostics))
+	}
+
+	let mut linker = Linker::new();
+	for exec in &execs {
+		linker.link(exec).map_err(BuildError::Link)?;
+	}
+	build.builder = linker.finish();
+	if library {
+		export(&mut build.builder);
+	}
+	Ok(build)
+}
+
+/// Adds the source files in `dir` (and its subdirectories) to `sources`
+fn discover(root: &Path, dir: &Path, sources: &mut Vec<Source>) -> Result<(), BuildError> {
+	let entries = fs::read_dir(dir).map_err(|err| BuildError::Io(dir.to_path_buf(), err))?;
+	for entry in entries {
+		let path = entry.map_err(|err| BuildError::Io(dir.to_path_buf(), err))?.path();
+		let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+		if path.is_dir() && !hidden {
+			discover(root, &path, sources)?;
+		} else if path.extension().is_some_and(|extension| extension == EXTENSION) {
+			let package = package(path.strip_prefix(root).unwrap()).ok_or_else(|| BuildError::InvalidPackage(path.clone()))?;
+			let src = fs::read_to_string(&path).map_err(|err| BuildError::Io(path.clone(), err))?;
+			sources.push(Source {
+				package,
+				path,
+				src,
+			});
+		}
+	}
+	Ok(())
+}
+
+/// The package of a source file at a path relative to the root of a build
+fn package(path: &Path) -> Option<String> {
+	let names = path.with_extension("").iter().map(|name| name.to_str().map(String::from)).collect::<Option<Vec<_>>>()?;
+	let valid = names.iter().all(|name| {
+		let mut chars = name.chars();
+		chars.next().is_some_and(|char| char.is_alphabetic() || char == '_') && chars.all(|char| char.is_alphanumeric() || char == '_')
+	});
+	valid.then(|| names.join("."))
+}
+
+/// Parses every source, failing with every syntax error if any has one
+fn parse(sources: &[Source]) -> Result<Vec<ast::File>, BuildError> {
+	let mut files = Vec::new();
+	let mut diagnostics = Vec::new();
+	for source in sources {
+		let (file, errors) = syntax::parse(&source.src);
+		diagnostics.extend(errors.iter().map(|diagnostic| diagnostic.format(&source.path.display().to_string(), &source.src)));
+		files.push(file);
+	}
+	match diagnostics.is_empty() {
+		true => Ok(files),
+		false => Err(BuildError::Diagnostics(diagnostics)),
+	}
+}
+
+/// The packages a source names, by the index of their source<br>
+/// Every prefix of every dotted name is looked up, which may find packages a name doesn't refer
+/// to (like a variable named after a package), but never misses one.
+fn dependencies(src: &str, packages: &HashMap<&str, usize>) -> BTreeSet<usize> {
+	let (tokens, _) = lexer::tokenize(src);
+	let mut dependencies = BTreeSet::new();
+	let mut name = String::new();
+	let mut dotted = false;
+	for token in tokens {
+		match token.kind {
+			TokenKind::Ident(ident) => {
+				if !dotted {
+					name.clear();
+				} else {
+					name.push('.');
+				}
+				name.push_str(&ident);
+				dependencies.extend(packages.get(name.as_str()));
+				dotted = false;
+			},
+			TokenKind::Punct(Punct::Dot) if !name.is_empty() => dotted = true,
+			_ => {
+				name.clear();
+				dotted = false;
+			},
+		}
+	}
+	dependencies
+}
+
+/// The source at `index` followed by every source it depends on, directly or not
+fn closure(index: usize, dependencies: &[BTreeSet<usize>]) -> Vec<usize> {
+	let mut closure = vec![index];
+	let mut next = 0;
+	while let Some(index) = closure.get(next) {
+		for dependency in &dependencies[*index] {
+			if !closure.contains(dependency) {
+				closure.push(*dependency);
+			}
+		}
+		next += 1;
+	}
+	closure
+}
+
+/// The cache key of the executable of the first package of a closure<br>
+/// ***Note:** The hasher of the standard library may change between releases of Rust, which only
+/// makes the cached executables miss.*
+fn key(closure: &[usize], sources: &[Source]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	env!("CARGO_PKG_VERSION").hash(&mut hasher);
+	sources[closure[0]].package.hash(&mut hasher);
+	let mut packages = closure.to_vec();
+	packages.sort_unstable();
+	for index in packages {
+		sources[index].package.hash(&mut hasher);
+		sources[index].src.hash(&mut hasher);
+	}
+	hasher.finish()
+}
+
+/// Removes the executables of `package` in the cache other than `keep`
+fn evict(cache: &Path, package: &str, keep: &Path) {
+	let Ok(entries) = fs::read_dir(cache) else {
+		return
+	};
+	for path in entries.flatten().map(|entry| entry.path()) {
+		let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+			continue
+		};
+		let stale = name.strip_prefix(package)
+			.and_then(|name| name.strip_prefix('.'))
+			.and_then(|name| name.strip_suffix(".esbin"))
+			.is_some_and(|hash| hash.len() == 16 && hash.chars().all(|char| char.is_ascii_hexdigit()));
+		if stale && path != keep {
+			// a stale executable that can't be removed is only wasted space
+			let _ = fs::remove_file(&path);
+		}
+	}
+}
+
+/// The items of a program that are in `package`
+fn only(program: Program, package: &str) -> Program {
+	let in_package = |name: &str| name.rsplit_once('.').map_or(package.is_empty(), |(prefix, _)| prefix == package);
+	Program {
+		structs: program.structs.into_iter().filter(|def| in_package(&def.name)).collect(),
+		traits: program.traits.into_iter().filter(|def| in_package(&def.name)).collect(),
+		functions: program.functions.into_iter().filter(|def| in_package(&def.sig.name)).collect(),
+	}
+}
+
+/// Exports every class and function of an executable
+fn export(builder: &mut ExecutableBuilder) {
+	let classes = builder.classes.iter().map(|class| Symbol {
+		kind: SymbolKind::Class,
+		name: class.name,
+	});
+	let functions = builder.functions.iter().map(|function| Symbol {
+		kind: SymbolKind::Function,
+		name: function.name,
+	});
+	builder.exports = classes.chain(functions).collect();
+}
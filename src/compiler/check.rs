@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+use crate::compiler::error::Diagnostic;
+use crate::compiler::error::check::CheckError;
+use crate::compiler::span::Span;
+use crate::compiler::syntax::ast;
+use crate::compiler::typed::{FieldDef, FnSig, Program, StructDef, TraitDef, Ty};
+use crate::vm::types::function::Signature;
+
+mod body;
+
+/// A diagnostic of the file at an index of the checked files
+pub type FileDiagnostic = (usize, Diagnostic<CheckError>);
+
+/// Checks a set of parsed source files, resolving their names and types into a [`Program`]<br>
+/// Items of other packages are resolved by their fully-qualified name (`a.b.Item`), or by their
+/// simple name if they are in the file's package or imported by a `use`. Integers of different
+/// widths or signedness never convert implicitly, and constructs the VM has no instructions for
+/// (like `if` and `while`) are reported, so a program without errors passes the VM's verifier.
+/// Every error of every file is returned, with the index of its file.
+pub fn check(files: &[ast::File]) -> Result<Program, Vec<FileDiagnostic>> {
+	let mut checker = Checker {
+		files,
+		packages: files.iter().map(|file| file.package.as_ref().map_or_else(String::new, ToString::to_string)).collect(),
+		uses: vec![HashMap::new(); files.len()],
+		items: HashMap::new(),
+		structs: Vec::new(),
+		traits: Vec::new(),
+		functions: Vec::new(),
+		diagnostics: Vec::new(),
+	};
+	checker.collect();
+	checker.resolve_uses();
+	checker.resolve_parents();
+	checker.resolve_signatures();
+	checker.resolve_impls();
+	checker.check_inheritance();
+	let program = checker.check_bodies();
+	match checker.diagnostics.is_empty() {
+		true => Ok(program),
+		false => {
+			checker.diagnostics.sort_by_key(|(file, diagnostic)| (*file, diagnostic.span.start));
+			Err(checker.diagnostics)
+		},
+	}
+}
+
+/// The fully-qualified name of `name` in `package`
+fn qualify(package: &str, name: &str) -> String {
+	match package.is_empty() {
+		true => name.to_string(),
+		false => format!("{}.{}", package, name),
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Item {
+	Struct(usize),
+	Trait(usize),
+	Fn(usize),
+}
+
+struct StructInfo<'a> {
+	name: String,
+	file: usize,
+	ast: &'a ast::Struct,
+	parent: Option<usize>,
+	fields: Vec<FieldDef>,
+	methods: Vec<MethodInfo<'a>>,
+	/// The traits the struct has an `impl` of
+	traits: Vec<usize>,
+}
+
+struct MethodInfo<'a> {
+	sig: FnSig,
+	file: usize,
+	ast: &'a ast::Fn,
+}
+
+struct TraitInfo<'a> {
+	name: String,
+	file: usize,
+	ast: &'a ast::Trait,
+	/// The signatures of the methods, the receiver is a `dyn` of the trait
+	methods: Vec<FnSig>,
+}
+
+struct FnInfo<'a> {
+	sig: FnSig,
+	file: usize,
+	ast: &'a ast::Fn,
+}
+
+struct Checker<'a> {
+	files: &'a [ast::File],
+	/// The package of each file
+	packages: Vec<String>,
+	/// The items each file imports, by simple name
+	uses: Vec<HashMap<String, String>>,
+	items: HashMap<String, Item>,
+	structs: Vec<StructInfo<'a>>,
+	traits: Vec<TraitInfo<'a>>,
+	functions: Vec<FnInfo<'a>>,
+	diagnostics: Vec<FileDiagnostic>,
+}
+
+impl<'a> Checker<'a> {
+	fn error(&mut self, file: usize, span: Span, error: CheckError) {
+		self.diagnostics.push((file, Diagnostic::new(span, error)));
+	}
+
+	fn simple_name(name: &str) -> &str {
+		name.rsplit('.').next().unwrap_or(name)
+	}
+
+	/// Registers the names of the items of every file
+	fn collect(&mut self) {
+		for (file, ast) in self.files.iter().enumerate() {
+			for item in &ast.items {
+				let (name, item) = match item {
+					ast::Item::Struct(def) => {
+						self.structs.push(StructInfo {
+							name: qualify(&self.packages[file], &def.name.name),
+							file,
+							ast: def,
+							parent: None,
+							fields: Vec::new(),
+							methods: Vec::new(),
+							traits: Vec::new(),
+						});
+						(&def.name, Item::Struct(self.structs.len() - 1))
+					},
+					ast::Item::Trait(def) => {
+						self.traits.push(TraitInfo {
+							name: qualify(&self.packages[file], &def.name.name),
+							file,
+							ast: def,
+							methods: Vec::new(),
+						});
+						(&def.name, Item::Trait(self.traits.len() - 1))
+					},
+					ast::Item::Fn(def) => {
+						self.functions.push(FnInfo {
+							sig: FnSig {
+								name: qualify(&self.packages[file], &def.name.name),
+								params: Vec::new(),
+								ret: Ty::Void,
+							},
+							file,
+							ast: def,
+						});
+						(&def.name, Item::Fn(self.functions.len() - 1))
+					},
+					ast::Item::Impl(_) => continue,
+				};
+				let qualified = qualify(&self.packages[file], &name.name);
+				if self.items.insert(qualified.clone(), item).is_some() {
+					self.error(file, name.span, CheckError::Duplicate(qualified));
+				}
+			}
+		}
+	}
+
+	fn resolve_uses(&mut self) {
+		for (file, ast) in self.files.iter().enumerate() {
+			for path in &ast.uses {
+				let name = path.to_string();
+				if !self.items.contains_key(&name) {
+					self.error(file, path.span, CheckError::Unresolved(name));
+					continue
+				}
+				let simple = path.segments[path.segments.len() - 1].name.clone();
+				if self.uses[file].insert(simple.clone(), name).is_some() {
+					self.error(file, path.span, CheckError::Duplicate(simple));
+				}
+			}
+		}
+	}
+
+	/// Resolves a path naming an item from `file`, to its fully-qualified name and the item
+	fn resolve(&self, file: usize, path: &ast::Path) -> Option<(String, Item)> {
+		let name = path.to_string();
+		let first = &path.segments[0].name;
+		let imported = self.uses[file].get(first).map(|import| format!("{}{}", import, &name[first.len()..]));
+		[Some(qualify(&self.packages[file], &name)), imported, Some(name)].into_iter()
+			.flatten()
+			.find_map(|name| self.items.get(&name).map(|item| (name, *item)))
+	}
+
+	/// Resolves a path to a struct, returning its index
+	fn resolve_struct(&self, file: usize, path: &ast::Path, diagnostics: &mut Vec<Diagnostic<CheckError>>) -> Option<usize> {
+		match self.resolve(file, path) {
+			Some((_, Item::Struct(index))) => Some(index),
+			Some((name, _)) => {
+				diagnostics.push(Diagnostic::new(path.span, CheckError::NotAStruct(name)));
+				None
+			},
+			None => {
+				diagnostics.push(Diagnostic::new(path.span, CheckError::Unresolved(path.to_string())));
+				None
+			},
+		}
+	}
+
+	/// Resolves a path to a trait, returning its index
+	fn resolve_trait(&self, file: usize, path: &ast::Path, diagnostics: &mut Vec<Diagnostic<CheckError>>) -> Option<usize> {
+		match self.resolve(file, path) {
+			Some((_, Item::Trait(index))) => Some(index),
+			Some((name, _)) => {
+				diagnostics.push(Diagnostic::new(path.span, CheckError::NotATrait(name)));
+				None
+			},
+			None => {
+				diagnostics.push(Diagnostic::new(path.span, CheckError::Unresolved(path.to_string())));
+				None
+			},
+		}
+	}
+
+	/// Resolves a type written in `file`, [`Ty::Error`] if it doesn't resolve
+	fn ty(&self, file: usize, ty: &ast::Type, diagnostics: &mut Vec<Diagnostic<CheckError>>) -> Ty {
+		let mut error = |error| {
+			diagnostics.push(Diagnostic::new(ty.span, error));
+			Ty::Error
+		};
+		match &ty.kind {
+			ast::TypeKind::Path(path) => {
+				if let Some(primitive) = Some(path).filter(|path| path.segments.len() == 1).and_then(|path| Ty::primitive(&path.segments[0].name)) {
+					return primitive
+				}
+				match self.resolve(file, path) {
+					Some((name, Item::Struct(_))) => Ty::Struct(name),
+					Some((name, Item::Trait(_))) => error(CheckError::BareTrait(name)),
+					Some((name, Item::Fn(_))) => error(CheckError::NotAType(name)),
+					None => error(CheckError::Unresolved(path.to_string())),
+				}
+			},
+			ast::TypeKind::Dyn(path) => match self.resolve_trait(file, path, diagnostics) {
+				Some(index) => Ty::Dyn(self.traits[index].name.clone()),
+				None => Ty::Error,
+			},
+			ast::TypeKind::Array(elem) => {
+				let elem = self.ty(file, elem, diagnostics);
+				if matches!(elem, Ty::Dyn(_)) {
+					diagnostics.push(Diagnostic::new(ty.span, CheckError::UnsupportedStorage(elem.clone(), "arrays")));
+				}
+				Ty::Array(Box::new(elem))
+			},
+		}
+	}
+
+	/// Resolves the types of the parameters and return type of a function
+	fn signature(&self, file: usize, name: String, receiver: Option<Ty>, def: &ast::Fn, diagnostics: &mut Vec<Diagnostic<CheckError>>) -> FnSig {
+		let receivers = receiver.iter().len();
+		let params = receiver.into_iter()
+			.chain(def.params.iter().map(|param| self.ty(file, &param.ty, diagnostics)))
+			.collect::<Vec<_>>();
+		// the receiver is passed as an object, even to the methods of a trait
+		let slots = receivers + params[receivers..].iter().map(Ty::slots).sum::<usize>();
+		if slots > Signature::MAX_ARG_SLOTS {
+			diagnostics.push(Diagnostic::new(def.name.span, CheckError::TooManyParams(name.clone(), slots)));
+		}
+		let ret = def.ret.as_ref().map_or(Ty::Void, |ret| self.ty(file, ret, diagnostics));
+		FnSig {
+			name,
+			params,
+			ret,
+		}
+	}
+
+	fn resolve_parents(&mut self) {
+		for index in 0..self.structs.len() {
+			let StructInfo { file, ast, .. } = self.structs[index];
+			let Some(path) = &ast.parent else { continue };
+			let mut diagnostics = Vec::new();
+			self.structs[index].parent = self.resolve_struct(file, path, &mut diagnostics);
+			self.diagnostics.extend(diagnostics.into_iter().map(|diagnostic| (file, diagnostic)));
+		}
+		for index in 0..self.structs.len() {
+			let mut ancestor = self.structs[index].parent;
+			for _ in 0..self.structs.len() {
+				ancestor = ancestor.and_then(|ancestor| self.structs[ancestor].parent);
+			}
+			// a chain longer than the number of structs has a cycle
+			if ancestor.is_some() {
+				let StructInfo { file, ast, .. } = self.structs[index];
+				self.error(file, ast.name.span, CheckError::CyclicInheritance(self.structs[index].name.clone()));
+				self.structs[index].parent = None;
+			}
+		}
+	}
+
+	fn resolve_signatures(&mut self) {
+		for index in 0..self.structs.len() {
+			let StructInfo { file, ast, .. } = self.structs[index];
+			let mut diagnostics = Vec::new();
+			let mut fields = Vec::<FieldDef>::new();
+			for field in &ast.fields {
+				let ty = self.ty(file, &field.ty, &mut diagnostics);
+				if matches!(ty, Ty::Dyn(_)) {
+					diagnostics.push(Diagnostic::new(field.ty.span, CheckError::UnsupportedStorage(ty.clone(), "fields")));
+				}
+				if fields.iter().any(|other| other.name == field.name.name) {
+					diagnostics.push(Diagnostic::new(field.name.span, CheckError::Duplicate(field.name.name.clone())));
+				}
+				fields.push(FieldDef {
+					name: field.name.name.clone(),
+					ty,
+				});
+			}
+			self.structs[index].fields = fields;
+			self.diagnostics.extend(diagnostics.into_iter().map(|diagnostic| (file, diagnostic)));
+		}
+
+		for index in 0..self.traits.len() {
+			let TraitInfo { file, ast, .. } = self.traits[index];
+			let receiver = Ty::Dyn(self.traits[index].name.clone());
+			let mut diagnostics = Vec::new();
+			let mut methods = Vec::<FnSig>::new();
+			for method in &ast.methods {
+				if method.receiver.is_none() {
+					diagnostics.push(Diagnostic::new(method.name.span, CheckError::MissingReceiver(method.name.name.clone())));
+				}
+				if method.body.is_some() {
+					diagnostics.push(Diagnostic::new(method.name.span, CheckError::TraitMethodBody(method.name.name.clone())));
+				}
+				if methods.iter().any(|other| Self::simple_name(&other.name) == method.name.name) {
+					diagnostics.push(Diagnostic::new(method.name.span, CheckError::Duplicate(method.name.name.clone())));
+				}
+				let name = format!("{}.{}", self.traits[index].name, method.name.name);
+				methods.push(self.signature(file, name, Some(receiver.clone()), method, &mut diagnostics));
+			}
+			self.traits[index].methods = methods;
+			self.diagnostics.extend(diagnostics.into_iter().map(|diagnostic| (file, diagnostic)));
+		}
+
+		for index in 0..self.functions.len() {
+			let FnInfo { file, ast, .. } = self.functions[index];
+			let mut diagnostics = Vec::new();
+			if let Some(receiver) = ast.receiver {
+				diagnostics.push(Diagnostic::new(receiver, CheckError::SelfOutsideMethod));
+			}
+			if ast.body.is_none() {
+				diagnostics.push(Diagnostic::new(ast.name.span, CheckError::MissingBody(ast.name.name.clone())));
+			}
+			let name = self.functions[index].sig.name.clone();
+			self.functions[index].sig = self.signature(file, name, None, ast, &mut diagnostics);
+			self.diagnostics.extend(diagnostics.into_iter().map(|diagnostic| (file, diagnostic)));
+		}
+	}
+
+	/// Adds the methods of impls to their structs, and checks that trait impls conform to their trait
+	fn resolve_impls(&mut self) {
+		for (file, ast) in self.files.iter().enumerate() {
+			for item in &ast.items {
+				let ast::Item::Impl(imp) = item else { continue };
+				let mut diagnostics = Vec::new();
+				let owner = self.resolve_struct(file, &imp.ty, &mut diagnostics);
+				let trait_index = imp.trait_name.as_ref().and_then(|path| self.resolve_trait(file, path, &mut diagnostics));
+				if let Some(owner) = owner {
+					self.implement(file, imp, owner, trait_index, &mut diagnostics);
+				}
+				self.diagnostics.extend(diagnostics.into_iter().map(|diagnostic| (file, diagnostic)));
+			}
+		}
+	}
+
+	fn implement(&mut self, file: usize, imp: &'a ast::Impl, owner: usize, trait_index: Option<usize>, diagnostics: &mut Vec<Diagnostic<CheckError>>) {
+		let receiver = Ty::Struct(self.structs[owner].name.clone());
+		for method in &imp.methods {
+			if method.receiver.is_none() {
+				diagnostics.push(Diagnostic::new(method.name.span, CheckError::MissingReceiver(method.name.name.clone())));
+			}
+			if method.body.is_none() {
+				diagnostics.push(Diagnostic::new(method.name.span, CheckError::MissingBody(method.name.name.clone())));
+			}
+			let name = format!("{}.{}", self.structs[owner].name, method.name.name);
+			let sig = self.signature(file, name, Some(receiver.clone()), method, diagnostics);
+			if self.structs[owner].methods.iter().any(|other| other.ast.name.name == method.name.name) {
+				diagnostics.push(Diagnostic::new(method.name.span, CheckError::Duplicate(sig.name.clone())));
+				continue
+			}
+			if let Some(trait_index) = trait_index {
+				let trait_info = &self.traits[trait_index];
+				match trait_info.methods.iter().find(|other| Self::simple_name(&other.name) == method.name.name) {
+					Some(other) if !Self::same_signature(&sig, other) => diagnostics.push(Diagnostic::new(method.name.span, CheckError::SignatureMismatch(sig.name.clone(), other.name.clone()))),
+					Some(_) => {},
+					None => diagnostics.push(Diagnostic::new(method.name.span, CheckError::NotTraitMember(method.name.name.clone(), trait_info.name.clone()))),
+				}
+			}
+			self.structs[owner].methods.push(MethodInfo {
+				sig,
+				file,
+				ast: method,
+			});
+		}
+
+		if let Some(trait_index) = trait_index {
+			for method in &self.traits[trait_index].methods {
+				let simple = Self::simple_name(&method.name);
+				if !imp.methods.iter().any(|def| def.name.name == simple) {
+					diagnostics.push(Diagnostic::new(imp.ty.span, CheckError::MissingTraitMethod(self.structs[owner].name.clone(), simple.to_string(), self.traits[trait_index].name.clone())));
+				}
+			}
+			self.structs[owner].traits.push(trait_index);
+		}
+	}
+
+	/// Whether two methods take the same parameters (after the receiver) and return the same type
+	fn same_signature(a: &FnSig, b: &FnSig) -> bool {
+		a.params.get(1..) == b.params.get(1..) && a.ret == b.ret
+	}
+
+	/// Checks that structs don't redeclare inherited fields, and that overriding methods match
+	/// the methods they override
+	fn check_inheritance(&mut self) {
+		let mut diagnostics = Vec::new();
+		for info in &self.structs {
+			let Some(parent) = info.parent else { continue };
+			for (field, def) in info.fields.iter().zip(&info.ast.fields) {
+				if self.field(parent, &field.name).is_some() {
+					diagnostics.push((info.file, Diagnostic::new(def.name.span, CheckError::Duplicate(field.name.clone()))));
+				}
+			}
+			for method in &info.methods {
+				let simple = Self::simple_name(&method.sig.name);
+				if let Some(overridden) = self.method(parent, simple) {
+					if !Self::same_signature(&method.sig, overridden) {
+						diagnostics.push((method.file, Diagnostic::new(method.ast.name.span, CheckError::SignatureMismatch(method.sig.name.clone(), overridden.name.clone()))));
+					}
+				}
+			}
+		}
+		self.diagnostics.extend(diagnostics);
+	}
+
+	/// The struct at `index` followed by its ancestors
+	fn ancestry(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+		std::iter::successors(Some(index), |index| self.structs[*index].parent)
+	}
+
+	/// Finds a field of a struct (or of its ancestors)
+	fn field(&self, index: usize, name: &str) -> Option<&FieldDef> {
+		self.ancestry(index).find_map(|index| self.structs[index].fields.iter().find(|field| field.name == name))
+	}
+
+	/// All fields of a struct, inherited ones first
+	fn all_fields(&self, index: usize) -> Vec<&FieldDef> {
+		let mut ancestry = self.ancestry(index).collect::<Vec<_>>();
+		ancestry.reverse();
+		ancestry.into_iter().flat_map(|index| &self.structs[index].fields).collect()
+	}
+
+	/// Finds the signature of a method of a struct (or of its ancestors) by its simple name
+	fn method(&self, index: usize, name: &str) -> Option<&FnSig> {
+		self.ancestry(index).find_map(|owner| {
+			self.structs[owner].methods.iter()
+				.find(|method| Self::simple_name(&method.sig.name) == name)
+				.map(|method| &method.sig)
+		})
+	}
+
+	fn struct_index(&self, name: &str) -> Option<usize> {
+		match self.items.get(name) {
+			Some(Item::Struct(index)) => Some(*index),
+			_ => None,
+		}
+	}
+
+	fn trait_index(&self, name: &str) -> Option<usize> {
+		match self.items.get(name) {
+			Some(Item::Trait(index)) => Some(*index),
+			_ => None,
+		}
+	}
+
+	/// Whether the struct named `name` (or one of its ancestors) implements the trait named `trait_name`
+	fn implements(&self, name: &str, trait_name: &str) -> bool {
+		match (self.struct_index(name), self.trait_index(trait_name)) {
+			(Some(index), Some(trait_index)) => self.ancestry(index).any(|index| self.structs[index].traits.contains(&trait_index)),
+			_ => false,
+		}
+	}
+
+	/// Whether the struct named `name` is `ancestor` or inherits from it
+	fn inherits(&self, name: &str, ancestor: &str) -> bool {
+		self.struct_index(name).is_some_and(|index| self.ancestry(index).any(|index| self.structs[index].name == ancestor))
+	}
+
+	fn check_bodies(&mut self) -> Program {
+		let mut program = Program::default();
+		let mut diagnostics = Vec::new();
+
+		for info in &self.structs {
+			let mut methods = Vec::new();
+			for method in &info.methods {
+				let (def, errors) = body::check(self, method.file, &method.sig, method.ast);
+				diagnostics.extend(errors.into_iter().map(|error| (method.file, error)));
+				methods.extend(def);
+			}
+			program.structs.push(StructDef {
+				name: info.name.clone(),
+				parent: info.parent.map(|parent| self.structs[parent].name.clone()),
+				fields: info.fields.clone(),
+				methods,
+			});
+		}
+		for info in &self.traits {
+			program.traits.push(TraitDef {
+				name: info.name.clone(),
+				methods: info.methods.clone(),
+			});
+		}
+		for info in &self.functions {
+			let (def, errors) = body::check(self, info.file, &info.sig, info.ast);
+			diagnostics.extend(errors.into_iter().map(|error| (info.file, error)));
+			program.functions.extend(def);
+		}
+
+		self.diagnostics.extend(diagnostics);
+		program
+	}
+}
+
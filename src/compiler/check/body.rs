@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+use crate::compiler::check::{Checker, Item};
+use crate::compiler::error::Diagnostic;
+use crate::compiler::error::check::CheckError;
+use crate::compiler::span::Span;
+use crate::compiler::syntax::ast;
+use crate::compiler::typed::{ArithOp, Block, Expr, ExprKind, FnDef, FnSig, IntTy, Local, LocalId, Place, Stmt, Ty};
+
+/// Checks the body of a function with the signature `sig`, `None` if it has no body
+pub(super) fn check(checker: &Checker, file: usize, sig: &FnSig, def: &ast::Fn) -> (Option<FnDef>, Vec<Diagnostic<CheckError>>) {
+	let Some(block) = &def.body else {
+		return (None, Vec::new())
+	};
+	let mut body = Body {
+		checker,
+		file,
+		locals: Vec::new(),
+		mutable: Vec::new(),
+		scopes: vec![HashMap::new()],
+		ret: sig.ret.clone(),
+		diagnostics: Vec::new(),
+	};
+
+	let mut params = sig.params.iter();
+	if def.receiver.is_some() {
+		body.declare("self", params.next().cloned().unwrap_or(Ty::Error), false);
+	}
+	for (param, ty) in def.params.iter().zip(params) {
+		if body.scopes[0].contains_key(&param.name.name) {
+			body.error(param.name.span, CheckError::Duplicate(param.name.name.clone()));
+		}
+		body.declare(&param.name.name, ty.clone(), false);
+	}
+
+	let mut checked = body.block(block, Some(&sig.ret));
+	match checked.expr.take() {
+		Some(expr) => checked.expr = Some(Box::new(body.coerce(*expr, &sig.ret))),
+		None if sig.ret != Ty::Void && !matches!(checked.stmts.last(), Some(Stmt::Return(_))) => {
+			body.error(def.name.span, CheckError::MissingReturn(sig.ret.clone()));
+		},
+		None => {},
+	}
+
+	let def = FnDef {
+		sig: sig.clone(),
+		locals: body.locals,
+		body: checked,
+		span: def.span,
+	};
+	(Some(def), body.diagnostics)
+}
+
+/// The intrinsic a method of `str` calls, with the types of its arguments (after the receiver)
+/// and its return type
+fn str_method(name: &str) -> Option<(&'static str, Vec<Ty>, Ty)> {
+	let u8 = Ty::Int(IntTy {
+		bits: 8,
+		signed: false,
+	});
+	match name {
+		"len" => Some(("str.len", vec![], Ty::Int(IntTy::I64))),
+		"concat" => Some(("str.concat", vec![Ty::Str], Ty::Str)),
+		"byte" => Some(("str.byte", vec![Ty::Int(IntTy::I64)], u8)),
+		"eq" => Some(("str.eq", vec![Ty::Str], Ty::Bool)),
+		"print" => Some(("str.print", vec![], Ty::Void)),
+		_ => None,
+	}
+}
+
+/// Whether an expression is an unsuffixed literal, whose type comes from the other operand
+fn is_flexible(expr: &ast::Expr) -> bool {
+	match &expr.kind {
+		ast::ExprKind::Int(_, None) | ast::ExprKind::Float(_, None) => true,
+		ast::ExprKind::Unary(ast::UnOp::Neg, operand) => is_flexible(operand),
+		_ => false,
+	}
+}
+
+fn arith_op(op: ast::BinOp) -> Option<ArithOp> {
+	match op {
+		ast::BinOp::Add => Some(ArithOp::Add),
+		ast::BinOp::Sub => Some(ArithOp::Sub),
+		ast::BinOp::Mul => Some(ArithOp::Mul),
+		ast::BinOp::Div => Some(ArithOp::Div),
+		_ => None,
+	}
+}
+
+/// The construct an operator the VM has no instructions for belongs to
+fn unsupported(op: ast::BinOp) -> &'static str {
+	match op {
+		ast::BinOp::Rem => "`%` operators",
+		ast::BinOp::And | ast::BinOp::Or | ast::BinOp::Xor | ast::BinOp::Shl | ast::BinOp::Shr => "bitwise operators",
+		ast::BinOp::LogicalAnd | ast::BinOp::LogicalOr => "logical operators",
+		_ => "comparisons other than `==` of strings",
+	}
+}
+
+struct Body<'c, 'a> {
+	checker: &'c Checker<'a>,
+	file: usize,
+	locals: Vec<Local>,
+	/// Whether each local is declared `mut`
+	mutable: Vec<bool>,
+	/// The locals in scope by name, innermost block last
+	scopes: Vec<HashMap<String, LocalId>>,
+	ret: Ty,
+	diagnostics: Vec<Diagnostic<CheckError>>,
+}
+
+impl Body<'_, '_> {
+	fn error(&mut self, span: Span, error: CheckError) {
+		self.diagnostics.push(Diagnostic::new(span, error));
+	}
+
+	/// An expression standing in for one that failed to check<br>
+	/// ***Note:** It never reaches a [`crate::compiler::typed::Program`], which is only returned without errors.*
+	fn invalid(span: Span) -> Expr {
+		Expr {
+			kind: ExprKind::Block(Block {
+				stmts: Vec::new(),
+				expr: None,
+			}),
+			ty: Ty::Error,
+			span,
+		}
+	}
+
+	fn declare(&mut self, name: &str, ty: Ty, mutable: bool) -> LocalId {
+		let id = self.locals.len();
+		self.locals.push(Local {
+			name: name.to_string(),
+			ty,
+		});
+		self.mutable.push(mutable);
+		self.scopes.last_mut().unwrap().insert(name.to_string(), id);
+		id
+	}
+
+	fn lookup(&self, name: &str) -> Option<LocalId> {
+		self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+	}
+
+	fn local(&self, id: LocalId, span: Span) -> Expr {
+		Expr {
+			kind: ExprKind::Local(id),
+			ty: self.locals[id].ty.clone(),
+			span,
+		}
+	}
+
+	fn block(&mut self, block: &ast::Block, expected: Option<&Ty>) -> Block {
+		self.scopes.push(HashMap::new());
+		let stmts = block.stmts.iter().filter_map(|stmt| self.stmt(stmt)).collect();
+		let expr = block.expr.as_ref().map(|expr| Box::new(self.expr(expr, expected)));
+		self.scopes.pop();
+		Block {
+			stmts,
+			expr,
+		}
+	}
+
+	fn stmt(&mut self, stmt: &ast::Stmt) -> Option<Stmt> {
+		match &stmt.kind {
+			ast::StmtKind::Let { name, mutable, ty, value } => {
+				let declared = ty.as_ref().map(|ty| self.checker.ty(self.file, ty, &mut self.diagnostics));
+				let value = match &declared {
+					Some(ty) => self.expect(value, ty),
+					None => self.expr(value, None),
+				};
+				if value.ty == Ty::Void {
+					self.error(value.span, CheckError::VoidValue);
+				}
+				let id = self.declare(&name.name, declared.unwrap_or_else(|| value.ty.clone()), *mutable);
+				Some(Stmt::Let(id, value))
+			},
+			ast::StmtKind::Expr(expr) => Some(Stmt::Expr(self.expr(expr, None))),
+			ast::StmtKind::While(cond, block) => {
+				self.expect(cond, &Ty::Bool);
+				self.block(block, Some(&Ty::Void));
+				self.error(stmt.span, CheckError::Unsupported("`while` loops"));
+				None
+			},
+			ast::StmtKind::Return(value) => {
+				let ret = self.ret.clone();
+				let value = match value {
+					Some(value) => Some(self.expect(value, &ret)),
+					None => {
+						if ret != Ty::Void {
+							self.error(stmt.span, CheckError::Mismatch(ret, Ty::Void));
+						}
+						None
+					},
+				};
+				Some(Stmt::Return(value))
+			},
+			ast::StmtKind::Break | ast::StmtKind::Continue => {
+				self.error(stmt.span, CheckError::Unsupported("`break` and `continue` expressions"));
+				None
+			},
+		}
+	}
+
+	/// Checks an expression that must have the type `ty`
+	fn expect(&mut self, expr: &ast::Expr, ty: &Ty) -> Expr {
+		let expr = self.expr(expr, Some(ty));
+		self.coerce(expr, ty)
+	}
+
+	/// Converts an expression to the type `ty`, upcasting objects to their ancestors and converting
+	/// them to `dyn` traits they implement, and reports any other mismatch
+	fn coerce(&mut self, expr: Expr, ty: &Ty) -> Expr {
+		if expr.ty == *ty || expr.ty == Ty::Error || *ty == Ty::Error {
+			return expr
+		}
+		match (&expr.ty, ty) {
+			(Ty::Struct(found), Ty::Struct(expected)) if self.checker.inherits(found, expected) => expr,
+			(Ty::Struct(found), Ty::Dyn(trait_name)) => {
+				if !self.checker.implements(found, trait_name) {
+					self.error(expr.span, CheckError::NotImplemented(found.clone(), trait_name.clone()));
+				}
+				let span = expr.span;
+				Expr {
+					kind: ExprKind::ToDyn(Box::new(expr), trait_name.clone()),
+					ty: ty.clone(),
+					span,
+				}
+			},
+			_ => {
+				self.error(expr.span, CheckError::Mismatch(ty.clone(), expr.ty.clone()));
+				expr
+			},
+		}
+	}
+
+	/// Checks an expression, using the type it is expected to have to infer the types of literals
+	fn expr(&mut self, expr: &ast::Expr, expected: Option<&Ty>) -> Expr {
+		let span = expr.span;
+		let (kind, ty) = match &expr.kind {
+			ast::ExprKind::Int(value, suffix) => return self.int(*value as i128, suffix, expected, span),
+			ast::ExprKind::Float(value, suffix) => return self.float(*value, suffix, expected, span),
+			ast::ExprKind::Str(value) => (ExprKind::Str(value.clone()), Ty::Str),
+			ast::ExprKind::Bool(value) => (ExprKind::Bool(*value), Ty::Bool),
+			ast::ExprKind::Path(path) => return self.path(path),
+			ast::ExprKind::SelfValue => match self.lookup("self") {
+				Some(id) => return self.local(id, span),
+				None => {
+					self.error(span, CheckError::SelfOutsideMethod);
+					return Self::invalid(span)
+				},
+			},
+			ast::ExprKind::Unary(ast::UnOp::Neg, operand) => return self.neg(operand, expected, span),
+			ast::ExprKind::Unary(ast::UnOp::Not, operand) => {
+				self.expr(operand, expected);
+				self.error(span, CheckError::Unsupported("`!` operators"));
+				return Self::invalid(span)
+			},
+			ast::ExprKind::Binary(op, lhs, rhs) => return self.binary(*op, lhs, rhs, expected, span),
+			ast::ExprKind::Assign(op, place, value) => return self.assign(*op, place, value, span),
+			ast::ExprKind::Cast(operand, ty) => {
+				let target = self.checker.ty(self.file, ty, &mut self.diagnostics);
+				let operand = self.expr(operand, None);
+				let valid = operand.ty == target
+					|| operand.ty == Ty::Error
+					|| target == Ty::Error
+					|| target.is_numeric() && (operand.ty.is_numeric() || operand.ty == Ty::Bool);
+				if !valid {
+					self.error(span, CheckError::InvalidCast(operand.ty.clone(), target.clone()));
+				}
+				(ExprKind::Cast(Box::new(operand)), target)
+			},
+			ast::ExprKind::Call(callee, args) => match &callee.kind {
+				ast::ExprKind::Path(path) => return self.call(path, args, span),
+				_ => {
+					self.expr(callee, None);
+					self.args(None, args);
+					self.error(callee.span, CheckError::Unsupported("function values"));
+					return Self::invalid(span)
+				},
+			},
+			ast::ExprKind::MethodCall(receiver, name, args) => {
+				let receiver = self.expr(receiver, None);
+				return self.method_call(receiver, name, args, span)
+			},
+			ast::ExprKind::Field(object, name) => {
+				let object = self.expr(object, None);
+				return self.field(object, name)
+			},
+			ast::ExprKind::Index(array, index) => {
+				let array = self.expr(array, None);
+				let index = self.index(index);
+				let ty = self.elem(&array, span);
+				(ExprKind::Index(Box::new(array), Box::new(index)), ty)
+			},
+			ast::ExprKind::StructLit(path, inits) => return self.struct_lit(path, inits, span),
+			ast::ExprKind::Array(elems) => return self.array(elems, expected, span),
+			ast::ExprKind::ArrayRepeat(value, len) => {
+				let hint = match expected {
+					Some(Ty::Array(elem)) => Some(&**elem),
+					_ => None,
+				};
+				let value = self.expr(value, hint);
+				let len = self.index(len);
+				let zero = match value.kind {
+					ExprKind::Int(value) => value == 0,
+					ExprKind::Float(value) => value == 0.0,
+					ExprKind::Bool(value) => !value,
+					_ => false,
+				};
+				if !zero && value.ty != Ty::Error {
+					self.error(value.span, CheckError::Unsupported("array repeat values other than zero"));
+				}
+				(ExprKind::NewArray(Box::new(len)), Ty::Array(Box::new(value.ty)))
+			},
+			ast::ExprKind::If(cond, then, otherwise) => {
+				self.expect(cond, &Ty::Bool);
+				self.block(then, expected);
+				if let Some(otherwise) = otherwise {
+					self.expr(otherwise, expected);
+				}
+				self.error(span, CheckError::Unsupported("`if` expressions"));
+				return Self::invalid(span)
+			},
+			ast::ExprKind::Block(block) => {
+				let block = self.block(block, expected);
+				let ty = block.expr.as_ref().map_or(Ty::Void, |expr| expr.ty.clone());
+				(ExprKind::Block(block), ty)
+			},
+			ast::ExprKind::Error => return Self::invalid(span),
+		};
+		Expr {
+			kind,
+			ty,
+			span,
+		}
+	}
+
+	/// Checks an integer literal (negated if `value` is negative), whose type is its suffix, the
+	/// expected integer type, or `i64`
+	fn int(&mut self, value: i128, suffix: &Option<String>, expected: Option<&Ty>, span: Span) -> Expr {
+		let ty = match (suffix, expected) {
+			(Some(suffix), _) => Ty::primitive(suffix).unwrap_or(Ty::Error),
+			(None, Some(ty)) if ty.is_numeric() => ty.clone(),
+			(None, _) => Ty::Int(IntTy::I64),
+		};
+		let kind = match &ty {
+			Ty::Int(int) => {
+				if !int.contains(value) {
+					self.error(span, CheckError::LiteralOutOfRange(ty.clone()));
+				}
+				ExprKind::Int(value as u64)
+			},
+			_ => ExprKind::Float(value as f64),
+		};
+		Expr {
+			kind,
+			ty,
+			span,
+		}
+	}
+
+	/// Checks a float literal, whose type is its suffix, the expected float type, or `f64`
+	fn float(&mut self, value: f64, suffix: &Option<String>, expected: Option<&Ty>, span: Span) -> Expr {
+		let ty = match (suffix, expected) {
+			(Some(suffix), _) => Ty::primitive(suffix).unwrap_or(Ty::Error),
+			(None, Some(ty)) if ty.is_float() => ty.clone(),
+			(None, _) => Ty::F64,
+		};
+		Expr {
+			kind: ExprKind::Float(value),
+			ty,
+			span,
+		}
+	}
+
+	/// Checks a negation, folding negated literals
+	fn neg(&mut self, operand: &ast::Expr, expected: Option<&Ty>, span: Span) -> Expr {
+		match &operand.kind {
+			ast::ExprKind::Int(value, suffix) => return self.int(-(*value as i128), suffix, expected, span),
+			ast::ExprKind::Float(value, suffix) => return self.float(-*value, suffix, expected, span),
+			_ => {},
+		}
+		let operand = self.expr(operand, expected);
+		let valid = match &operand.ty {
+			Ty::Int(int) => int.signed,
+			ty => ty.is_float() || *ty == Ty::Error,
+		};
+		if !valid {
+			self.error(span, CheckError::InvalidOperand("-", operand.ty.clone()));
+		}
+		let ty = operand.ty.clone();
+		Expr {
+			kind: ExprKind::Neg(Box::new(operand)),
+			ty,
+			span,
+		}
+	}
+
+	/// Checks the operands of a binary operator, inferring the type of an unsuffixed literal
+	/// from the other operand
+	fn operands(&mut self, lhs: &ast::Expr, rhs: &ast::Expr, expected: Option<&Ty>) -> (Expr, Expr) {
+		let hint = expected.filter(|ty| ty.is_numeric() || **ty == Ty::Str);
+		match is_flexible(lhs) && !is_flexible(rhs) {
+			true => {
+				let rhs = self.expr(rhs, hint);
+				let lhs = self.operand(lhs, &rhs.ty);
+				(lhs, rhs)
+			},
+			false => {
+				let lhs = self.expr(lhs, hint);
+				let rhs = self.operand(rhs, &lhs.ty);
+				(lhs, rhs)
+			},
+		}
+	}
+
+	/// Checks an operand that must have the type of the other operand, if that is numeric or `str`
+	fn operand(&mut self, expr: &ast::Expr, other: &Ty) -> Expr {
+		match other.is_numeric() || *other == Ty::Str {
+			true => self.expect(expr, other),
+			false => self.expr(expr, None),
+		}
+	}
+
+	fn binary(&mut self, op: ast::BinOp, lhs: &ast::Expr, rhs: &ast::Expr, expected: Option<&Ty>, span: Span) -> Expr {
+		let (lhs, rhs) = self.operands(lhs, rhs, expected);
+		let ty = lhs.ty.clone();
+		let (kind, ty) = match (op, &ty) {
+			(_, Ty::Error) => return Self::invalid(span),
+			(ast::BinOp::Add, Ty::Str) => (ExprKind::Call("str.concat".to_string(), vec![lhs, rhs]), Ty::Str),
+			(ast::BinOp::Eq, Ty::Str) => (ExprKind::Call("str.eq".to_string(), vec![lhs, rhs]), Ty::Bool),
+			(op, ty) => match arith_op(op) {
+				Some(arith) if ty.is_numeric() => (ExprKind::Arith(arith, Box::new(lhs), Box::new(rhs)), ty.clone()),
+				Some(_) => {
+					self.error(span, CheckError::InvalidOperand(op.as_str(), ty.clone()));
+					return Self::invalid(span)
+				},
+				None => {
+					self.error(span, CheckError::Unsupported(unsupported(op)));
+					return Self::invalid(span)
+				},
+			},
+		};
+		Expr {
+			kind,
+			ty,
+			span,
+		}
+	}
+
+	fn assign(&mut self, op: Option<ast::BinOp>, place: &ast::Expr, value: &ast::Expr, span: Span) -> Expr {
+		let place = self.place(place);
+		let op = match op {
+			Some(op) => match arith_op(op) {
+				Some(arith) => Some((op, arith)),
+				None => {
+					self.expr(value, None);
+					self.error(span, CheckError::Unsupported(unsupported(op)));
+					return Self::invalid(span)
+				},
+			},
+			None => None,
+		};
+		let Some((place, ty)) = place else {
+			self.expr(value, None);
+			return Self::invalid(span)
+		};
+		if let Some((op, _)) = op {
+			if !ty.is_numeric() && ty != Ty::Error {
+				self.error(span, CheckError::InvalidOperand(op.as_str(), ty.clone()));
+			}
+		}
+		let value = self.expect(value, &ty);
+		Expr {
+			kind: ExprKind::Assign(place, op.map(|(_, arith)| arith), Box::new(value)),
+			ty: Ty::Void,
+			span,
+		}
+	}
+
+	/// Checks the target of an assignment, returning it with its type
+	fn place(&mut self, expr: &ast::Expr) -> Option<(Place, Ty)> {
+		match &expr.kind {
+			ast::ExprKind::Path(path) => {
+				let first = &path.segments[0];
+				let Some(id) = self.lookup(&first.name) else {
+					match self.checker.resolve(self.file, path) {
+						Some(_) => self.error(expr.span, CheckError::InvalidPlace),
+						None => self.error(path.span, CheckError::Unresolved(path.to_string())),
+					}
+					return None
+				};
+				let Some((last, fields)) = path.segments[1..].split_last() else {
+					if !self.mutable[id] {
+						self.error(first.span, CheckError::Immutable(first.name.clone()));
+					}
+					return Some((Place::Local(id), self.locals[id].ty.clone()))
+				};
+				let mut object = self.local(id, first.span);
+				for field in fields {
+					object = self.field(object, field);
+				}
+				self.field_place(object, last)
+			},
+			ast::ExprKind::Field(object, name) => {
+				let object = self.expr(object, None);
+				self.field_place(object, name)
+			},
+			ast::ExprKind::Index(array, index) => {
+				let array = self.expr(array, None);
+				let index = self.index(index);
+				let ty = self.elem(&array, expr.span);
+				Some((Place::Index(Box::new(array), Box::new(index)), ty))
+			},
+			_ => {
+				self.expr(expr, None);
+				self.error(expr.span, CheckError::InvalidPlace);
+				None
+			},
+		}
+	}
+
+	fn field_place(&mut self, object: Expr, name: &ast::Ident) -> Option<(Place, Ty)> {
+		match self.field(object, name) {
+			Expr { kind: ExprKind::Field(object, class, field), ty, .. } => Some((Place::Field(object, class, field), ty)),
+			_ => None,
+		}
+	}
+
+	/// Checks an array index or length, which may be of any integer type
+	fn index(&mut self, expr: &ast::Expr) -> Expr {
+		let expr = self.expr(expr, Some(&Ty::Int(IntTy::I64)));
+		if !expr.ty.is_int() && expr.ty != Ty::Error {
+			self.error(expr.span, CheckError::Mismatch(Ty::Int(IntTy::I64), expr.ty.clone()));
+		}
+		expr
+	}
+
+	/// The element type of an indexed array
+	fn elem(&mut self, array: &Expr, span: Span) -> Ty {
+		match &array.ty {
+			Ty::Array(elem) => (**elem).clone(),
+			Ty::Error => Ty::Error,
+			ty => {
+				self.error(span, CheckError::InvalidOperand("[]", ty.clone()));
+				Ty::Error
+			},
+		}
+	}
+
+	/// Checks a path used as a value, a variable followed by any number of fields
+	fn path(&mut self, path: &ast::Path) -> Expr {
+		let first = &path.segments[0];
+		if let Some(id) = self.lookup(&first.name) {
+			let mut expr = self.local(id, first.span);
+			for segment in &path.segments[1..] {
+				expr = self.field(expr, segment);
+			}
+			return expr
+		}
+		match self.checker.resolve(self.file, path) {
+			Some((_, Item::Fn(_))) => self.error(path.span, CheckError::Unsupported("function values")),
+			Some((name, _)) => self.error(path.span, CheckError::NotAValue(name)),
+			None => self.error(path.span, CheckError::Unresolved(path.to_string())),
+		}
+		Self::invalid(path.span)
+	}
+
+	fn field(&mut self, object: Expr, name: &ast::Ident) -> Expr {
+		let span = object.span.to(name.span);
+		let field = match &object.ty {
+			Ty::Struct(class) => self.checker.struct_index(class)
+				.and_then(|index| self.checker.field(index, &name.name))
+				.map(|field| (class.clone(), field.ty.clone())),
+			Ty::Error => return Self::invalid(span),
+			_ => None,
+		};
+		match field {
+			Some((class, ty)) => Expr {
+				kind: ExprKind::Field(Box::new(object), class, name.name.clone()),
+				ty,
+				span,
+			},
+			None => {
+				self.error(name.span, CheckError::UnknownField(object.ty.to_string(), name.name.clone()));
+				Self::invalid(span)
+			},
+		}
+	}
+
+	/// Checks the arguments of a call, against the parameter types of the function if it resolved
+	fn args(&mut self, callee: Option<(&str, &[Ty])>, args: &[ast::Expr]) -> Vec<Expr> {
+		match callee {
+			Some((_, params)) if params.len() == args.len() => args.iter()
+				.zip(params)
+				.map(|(arg, param)| self.expect(arg, param))
+				.collect(),
+			Some((name, params)) => {
+				let span = args.first().map(|arg| arg.span.to(args[args.len() - 1].span));
+				let errors = args.iter().map(|arg| self.expr(arg, None)).collect();
+				if let Some(span) = span {
+					self.error(span, CheckError::ArgumentCount(name.to_string(), params.len(), args.len()));
+				}
+				errors
+			},
+			None => args.iter().map(|arg| self.expr(arg, None)).collect(),
+		}
+	}
+
+	/// Checks a call of a path, a function or a method of a variable (or of one of its fields)
+	fn call(&mut self, path: &ast::Path, args: &[ast::Expr], span: Span) -> Expr {
+		let first = &path.segments[0];
+		if let Some(id) = self.lookup(&first.name) {
+			let Some((method, fields)) = path.segments[1..].split_last() else {
+				self.args(None, args);
+				self.error(path.span, CheckError::NotAFunction(first.name.clone()));
+				return Self::invalid(span)
+			};
+			let mut receiver = self.local(id, first.span);
+			for field in fields {
+				receiver = self.field(receiver, field);
+			}
+			return self.method_call(receiver, method, args, span)
+		}
+		match self.checker.resolve(self.file, path) {
+			Some((_, Item::Fn(index))) => {
+				let sig = &self.checker.functions[index].sig;
+				let args = self.args(Some((&sig.name, &sig.params)), args);
+				let argc = args.len();
+				if argc != sig.params.len() {
+					return Self::invalid(span)
+				}
+				Expr {
+					kind: ExprKind::Call(sig.name.clone(), args),
+					ty: sig.ret.clone(),
+					span,
+				}
+			},
+			resolved => {
+				self.args(None, args);
+				match resolved {
+					Some((name, _)) => self.error(path.span, CheckError::NotAFunction(name)),
+					None => self.error(path.span, CheckError::Unresolved(path.to_string())),
+				}
+				Self::invalid(span)
+			},
+		}
+	}
+
+	fn method_call(&mut self, receiver: Expr, name: &ast::Ident, args: &[ast::Expr], span: Span) -> Expr {
+		let ty = receiver.ty.clone();
+		let (kind, ret) = match &ty {
+			Ty::Struct(class) => {
+				let sig = self.checker.struct_index(class).and_then(|index| self.checker.method(index, &name.name));
+				let Some(sig) = sig else {
+					return self.unknown_method(ty, name, args, span)
+				};
+				let Some(args) = self.method_args(receiver, sig, args) else {
+					return Self::invalid(span)
+				};
+				(ExprKind::CallVirt(class.clone(), sig.name.clone(), args), sig.ret.clone())
+			},
+			Ty::Dyn(trait_name) => {
+				let sig = self.checker.trait_index(trait_name)
+					.and_then(|index| self.checker.traits[index].methods.iter().find(|sig| Checker::simple_name(&sig.name) == name.name));
+				let Some(sig) = sig else {
+					return self.unknown_method(ty, name, args, span)
+				};
+				let Some(args) = self.method_args(receiver, sig, args) else {
+					return Self::invalid(span)
+				};
+				(ExprKind::CallTrait(trait_name.clone(), sig.name.clone(), args), sig.ret.clone())
+			},
+			Ty::Str => {
+				let Some((intrinsic, params, ret)) = str_method(&name.name) else {
+					return self.unknown_method(ty, name, args, span)
+				};
+				let sig = FnSig {
+					name: intrinsic.to_string(),
+					params: std::iter::once(Ty::Str).chain(params).collect(),
+					ret: ret.clone(),
+				};
+				let Some(args) = self.method_args(receiver, &sig, args) else {
+					return Self::invalid(span)
+				};
+				(ExprKind::Call(sig.name, args), ret)
+			},
+			Ty::Array(_) if name.name == "len" => {
+				let sig = FnSig {
+					name: "len".to_string(),
+					params: vec![ty.clone()],
+					ret: Ty::Int(IntTy::I64),
+				};
+				if self.method_args(receiver.clone(), &sig, args).is_none() {
+					return Self::invalid(span)
+				}
+				(ExprKind::Len(Box::new(receiver)), sig.ret)
+			},
+			Ty::Error => {
+				self.args(None, args);
+				return Self::invalid(span)
+			},
+			_ => return self.unknown_method(ty, name, args, span),
+		};
+		Expr {
+			kind,
+			ty: ret,
+			span,
+		}
+	}
+
+	/// Checks the arguments of a method call and prepends the receiver, `None` if their number is wrong
+	fn method_args(&mut self, receiver: Expr, sig: &FnSig, args: &[ast::Expr]) -> Option<Vec<Expr>> {
+		let args = self.args(Some((&sig.name, &sig.params[1..])), args);
+		match args.len() == sig.params.len() - 1 {
+			true => Some(std::iter::once(receiver).chain(args).collect()),
+			false => None,
+		}
+	}
+
+	fn unknown_method(&mut self, ty: Ty, name: &ast::Ident, args: &[ast::Expr], span: Span) -> Expr {
+		self.args(None, args);
+		self.error(name.span, CheckError::UnknownMethod(ty, name.name.clone()));
+		Self::invalid(span)
+	}
+
+	fn struct_lit(&mut self, path: &ast::Path, inits: &[ast::FieldInit], span: Span) -> Expr {
+		let Some(index) = self.checker.resolve_struct(self.file, path, &mut self.diagnostics) else {
+			for init in inits {
+				self.expr(&init.value, None);
+			}
+			return Self::invalid(span)
+		};
+		let name = self.checker.structs[index].name.clone();
+		let all = self.checker.all_fields(index);
+		let mut fields = Vec::<(String, Expr)>::new();
+		for init in inits {
+			let Some(field) = all.iter().find(|field| field.name == init.name.name) else {
+				self.expr(&init.value, None);
+				self.error(init.name.span, CheckError::UnknownField(name.clone(), init.name.name.clone()));
+				continue
+			};
+			if fields.iter().any(|(name, _)| *name == field.name) {
+				self.error(init.name.span, CheckError::Duplicate(field.name.clone()));
+			}
+			let value = self.expect(&init.value, &field.ty);
+			fields.push((field.name.clone(), value));
+		}
+		for field in &all {
+			if !fields.iter().any(|(name, _)| *name == field.name) {
+				self.error(path.span, CheckError::MissingField(name.clone(), field.name.clone()));
+			}
+		}
+		Expr {
+			kind: ExprKind::New(name.clone(), fields),
+			ty: Ty::Struct(name),
+			span,
+		}
+	}
+
+	fn array(&mut self, elems: &[ast::Expr], expected: Option<&Ty>, span: Span) -> Expr {
+		let hint = match expected {
+			Some(Ty::Array(elem)) => Some((**elem).clone()),
+			_ => None,
+		};
+		let Some((first, rest)) = elems.split_first() else {
+			return match hint {
+				Some(elem) => Expr {
+					kind: ExprKind::Array(Vec::new()),
+					ty: Ty::Array(Box::new(elem)),
+					span,
+				},
+				None => {
+					self.error(span, CheckError::AnnotationNeeded);
+					Self::invalid(span)
+				},
+			}
+		};
+		let first = match &hint {
+			Some(elem) => self.expect(first, elem),
+			None => self.expr(first, None),
+		};
+		let elem = hint.unwrap_or_else(|| first.ty.clone());
+		match &elem {
+			Ty::Void => self.error(first.span, CheckError::VoidValue),
+			Ty::Dyn(_) => self.error(span, CheckError::UnsupportedStorage(elem.clone(), "arrays")),
+			_ => {},
+		}
+		let elems = std::iter::once(first)
+			.chain(rest.iter().map(|value| self.expect(value, &elem)).collect::<Vec<_>>())
+			.collect();
+		Expr {
+			kind: ExprKind::Array(elems),
+			ty: Ty::Array(Box::new(elem)),
+			span,
+		}
+	}
+}
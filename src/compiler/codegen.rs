@@ -0,0 +1,442 @@
+use crate::compiler::error::codegen::CodegenError;
+use crate::compiler::typed::{ArithOp, Block, Expr, ExprKind, FnDef, FnSig, LocalId, Place, Program, Stmt, Ty};
+use crate::vm::bin::builder::{Class, Constant, ExecutableBuilder, Field, Function};
+use crate::vm::insn::Insn;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlag, TypeFlags, TypeId};
+
+/// Generates the bytecode of a checked program, as an executable with a class for each struct and
+/// trait and a function for each function<br>
+/// Locals are only ever appended by `pop`, so a variable that is assigned to is moved to a new local
+/// holding its new value. Traits become classes whose methods have no code and take an object of
+/// the trait's class as their receiver, as the VM expects of the targets of `calltrait`.<br>
+/// ***Note:** The program has no branches (the checker rejects them), so the code after a `return`
+/// is never emitted.*
+pub fn generate(program: &Program) -> Result<ExecutableBuilder, CodegenError> {
+	let mut generator = Generator {
+		builder: ExecutableBuilder::new(),
+	};
+	for def in &program.traits {
+		let name = generator.name(&def.name)?;
+		let methods = def.methods.iter()
+			.map(|sig| generator.signature(sig, Some(&def.name), Vec::new()))
+			.collect::<Result<_, _>>()?;
+		generator.builder.classes.push(Class {
+			name,
+			super_name: name,
+			fields: Vec::new(),
+			methods,
+		});
+	}
+	for def in &program.structs {
+		let name = generator.name(&def.name)?;
+		let super_name = match &def.parent {
+			Some(parent) => generator.name(parent)?,
+			None => name,
+		};
+		let fields = def.fields.iter()
+			.map(|field| Ok(Field {
+				name: generator.name(&field.name)?,
+				ty: generator.ty(&field.ty)?,
+			}))
+			.collect::<Result<_, _>>()?;
+		let methods = def.methods.iter()
+			.map(|method| generator.function(method))
+			.collect::<Result<_, _>>()?;
+		generator.builder.classes.push(Class {
+			name,
+			super_name,
+			fields,
+			methods,
+		});
+	}
+	for def in &program.functions {
+		let function = generator.function(def)?;
+		generator.builder.functions.push(function);
+	}
+	Ok(generator.builder)
+}
+
+struct Generator {
+	builder: ExecutableBuilder,
+}
+
+impl Generator {
+	fn constant(&mut self, constant: Constant) -> Result<ConstantIndex, CodegenError> {
+		if self.builder.constants.len() > ConstantIndex::MAX as usize && !self.builder.constants.contains(&constant) {
+			return Err(CodegenError::TooManyConstants)
+		}
+		Ok(self.builder.constant(constant))
+	}
+
+	fn name(&mut self, name: &str) -> Result<ConstantIndex, CodegenError> {
+		self.constant(Constant::name(name))
+	}
+
+	fn ty(&mut self, ty: &Ty) -> Result<TypeDesc, CodegenError> {
+		let flags = match ty {
+			Ty::Int(int) => {
+				let id = match int.bits {
+					8 => TypeFlags::I8,
+					16 => TypeFlags::I16,
+					32 => TypeFlags::I32,
+					_ => TypeFlags::I64,
+				};
+				if int.signed { id } else { TypeFlags::UNSIGNED << 4 | id }
+			},
+			Ty::F32 => TypeFlags::F32,
+			Ty::F64 => TypeFlags::F64,
+			Ty::Bool => TypeFlags::I8,
+			Ty::Str => TypeFlags::STR,
+			Ty::Void | Ty::Error => TypeFlags::VOID,
+			Ty::Struct(name) => return Ok(TypeDesc::new(TypeFlags::OBJECT, Some(self.name(name)?))),
+			Ty::Dyn(_) => TypeFlags::DYN,
+			// the element type of an array is a bare type id, without a class
+			Ty::Array(elem) => return Ok(TypeDesc::new(TypeFlags::ARRAY, Some(self.ty(elem)?.flags as u16))),
+		};
+		Ok(TypeDesc::new(flags, None))
+	}
+
+	/// The function of a signature with `code`, where the receiver of a method of the trait
+	/// `receiver` is an object of the trait's class
+	fn signature(&mut self, sig: &FnSig, receiver: Option<&str>, code: Vec<u8>) -> Result<Function, CodegenError> {
+		let mut args = Vec::with_capacity(sig.params.len());
+		for (i, param) in sig.params.iter().enumerate() {
+			args.push(match receiver {
+				Some(trait_name) if i == 0 => TypeDesc::new(TypeFlags::OBJECT, Some(self.name(trait_name)?)),
+				_ => self.ty(param)?,
+			});
+		}
+		Ok(Function {
+			name: self.name(&sig.name)?,
+			ret: self.ty(&sig.ret)?,
+			args,
+			code,
+		})
+	}
+
+	fn function(&mut self, def: &FnDef) -> Result<Function, CodegenError> {
+		let mut slots = Vec::new();
+		for param in &def.sig.params {
+			slots.push(self.ty(param)?);
+		}
+		let mut body = Body {
+			generator: self,
+			def,
+			code: Vec::new(),
+			locals: (0..def.sig.params.len()).map(|slot| Some(slot as u8)).chain(std::iter::repeat(None)).take(def.locals.len()).collect(),
+			slots,
+			returned: false,
+		};
+		body.block(&def.body)?;
+		if !body.returned {
+			let ret = body.generator.ty(&def.sig.ret)?;
+			body.emit(match ret.is_void() {
+				true => Insn::Ret,
+				false => Insn::VRet(ret),
+			});
+		}
+		let code = body.code;
+		self.signature(&def.sig, None, code)
+	}
+}
+
+/// The code generator of the body of a function
+struct Body<'g, 'd> {
+	generator: &'g mut Generator,
+	def: &'d FnDef,
+	code: Vec<u8>,
+	/// The local holding the current value of each variable
+	locals: Vec<Option<u8>>,
+	/// The type of each local
+	slots: Vec<TypeDesc>,
+	/// Whether a `return` was emitted, after which nothing is reachable
+	returned: bool,
+}
+
+impl Body<'_, '_> {
+	fn emit(&mut self, insn: Insn) {
+		if !self.returned {
+			insn.encode(&mut self.code);
+		}
+	}
+
+	fn ty(&mut self, ty: &Ty) -> Result<TypeDesc, CodegenError> {
+		self.generator.ty(ty)
+	}
+
+	fn name(&mut self, name: &str) -> Result<ConstantIndex, CodegenError> {
+		self.generator.name(name)
+	}
+
+	fn ldc(&mut self, constant: Constant) -> Result<(), CodegenError> {
+		let index = self.generator.constant(constant)?;
+		self.emit(Insn::Ldc(index));
+		Ok(())
+	}
+
+	/// Pops the value of type `ty` on top of the stack into a new local, returning it
+	fn pop(&mut self, ty: TypeDesc) -> Result<u8, CodegenError> {
+		let slot = u8::try_from(self.slots.len()).map_err(|_| CodegenError::TooManyLocals(self.def.sig.name.clone()))?;
+		self.slots.push(ty);
+		self.emit(Insn::Pop);
+		Ok(slot)
+	}
+
+	fn push(&mut self, slot: u8) {
+		self.emit(Insn::Push(self.slots[slot as usize], slot));
+	}
+
+	/// Pops a new value of a variable, which is moved to a new local
+	fn bind(&mut self, local: LocalId) -> Result<(), CodegenError> {
+		let ty = self.ty(&self.def.locals[local].ty)?;
+		self.locals[local] = Some(self.pop(ty)?);
+		Ok(())
+	}
+
+	fn block(&mut self, block: &Block) -> Result<(), CodegenError> {
+		for stmt in &block.stmts {
+			self.stmt(stmt)?;
+		}
+		if let Some(expr) = &block.expr {
+			self.expr(expr)?;
+		}
+		Ok(())
+	}
+
+	fn stmt(&mut self, stmt: &Stmt) -> Result<(), CodegenError> {
+		match stmt {
+			Stmt::Let(local, value) => {
+				self.expr(value)?;
+				self.bind(*local)?;
+			},
+			Stmt::Expr(expr) => {
+				self.expr(expr)?;
+				// the value is discarded into a local, the VM has no instruction to drop it
+				let ty = self.ty(&expr.ty)?;
+				if !ty.is_void() {
+					self.pop(ty)?;
+				}
+			},
+			Stmt::Return(value) => {
+				match value {
+					Some(value) => {
+						self.expr(value)?;
+						let ty = self.ty(&self.def.sig.ret)?;
+						self.emit(Insn::VRet(ty));
+					},
+					None => self.emit(Insn::Ret),
+				}
+				self.returned = true;
+			},
+		}
+		Ok(())
+	}
+
+	fn arith(&mut self, op: ArithOp, ty: TypeDesc) {
+		self.emit(match op {
+			ArithOp::Add => Insn::Add(ty),
+			ArithOp::Sub => Insn::Sub(ty),
+			ArithOp::Mul => Insn::Mul(ty),
+			ArithOp::Div => Insn::Div(ty),
+		});
+	}
+
+	/// Loads the zero of a numeric type
+	fn zero(&mut self, ty: TypeDesc) -> Result<(), CodegenError> {
+		self.ldc(Constant::primitive(ty, 0))
+	}
+
+	fn expr(&mut self, expr: &Expr) -> Result<(), CodegenError> {
+		let ty = self.ty(&expr.ty)?;
+		match &expr.kind {
+			ExprKind::Int(value) => self.ldc(Constant::primitive(ty, *value))?,
+			ExprKind::Float(value) => {
+				let bits = match expr.ty {
+					Ty::F32 => (*value as f32).to_bits() as u64,
+					_ => value.to_bits(),
+				};
+				self.ldc(Constant::primitive(ty, bits))?;
+			},
+			ExprKind::Str(value) => self.ldc(Constant {
+				ty: TypeDesc::new(TypeFlags::STR, None),
+				data: value.as_bytes().to_vec(),
+			})?,
+			ExprKind::Bool(value) => self.ldc(Constant::primitive(ty, *value as u64))?,
+			ExprKind::Local(local) => {
+				let slot = self.locals[*local].expect("A variable is used before it is declared");
+				self.push(slot);
+			},
+			ExprKind::Neg(operand) => {
+				// `0 - operand`, the VM has no negation
+				self.zero(ty)?;
+				self.expr(operand)?;
+				self.emit(Insn::Sub(ty));
+			},
+			ExprKind::Arith(op, lhs, rhs) => {
+				self.expr(lhs)?;
+				self.expr(rhs)?;
+				self.arith(*op, ty);
+			},
+			ExprKind::Cast(operand) => {
+				self.expr(operand)?;
+				let from = self.ty(&operand.ty)?;
+				if from.flags != ty.flags {
+					self.emit(Insn::Cast(from, ty));
+				}
+			},
+			ExprKind::Call(function, args) => {
+				self.exprs(args)?;
+				let function = self.name(function)?;
+				self.emit(Insn::Call(function));
+			},
+			ExprKind::CallVirt(class, method, args) => {
+				self.exprs(args)?;
+				let class = self.name(class)?;
+				let method = self.name(method)?;
+				self.emit(Insn::CallVirt(class, method));
+			},
+			ExprKind::CallTrait(trait_name, method, args) => {
+				self.exprs(args)?;
+				let trait_name = self.name(trait_name)?;
+				let method = self.name(method)?;
+				self.emit(Insn::CallTrait(trait_name, method));
+			},
+			ExprKind::New(class, fields) => {
+				let class = self.name(class)?;
+				self.emit(Insn::New(class));
+				if !fields.is_empty() {
+					let object = self.pop(ty)?;
+					for (field, value) in fields {
+						self.push(object);
+						self.expr(value)?;
+						let field = self.name(field)?;
+						self.emit(Insn::SetField(class, field));
+					}
+					self.push(object);
+				}
+			},
+			ExprKind::Field(object, class, field) => {
+				self.expr(object)?;
+				let class = self.name(class)?;
+				let field = self.name(field)?;
+				self.emit(Insn::GetField(class, field));
+			},
+			ExprKind::ToDyn(object, trait_name) => {
+				self.expr(object)?;
+				let trait_name = self.name(trait_name)?;
+				self.emit(Insn::ToDyn(trait_name));
+			},
+			ExprKind::Index(array, index) => {
+				self.expr(array)?;
+				self.expr(index)?;
+				self.emit(Insn::ALoad(ty));
+			},
+			ExprKind::Len(array) => {
+				self.expr(array)?;
+				self.emit(Insn::ALen);
+			},
+			ExprKind::NewArray(len) => {
+				self.expr(len)?;
+				let elem = self.elem(&expr.ty)?;
+				self.emit(Insn::NewArray(elem));
+			},
+			ExprKind::Array(elems) => {
+				let int = TypeDesc::new(TypeFlags::I64, None);
+				let elem = self.elem(&expr.ty)?;
+				self.ldc(Constant::primitive(int, elems.len() as u64))?;
+				self.emit(Insn::NewArray(elem));
+				if !elems.is_empty() {
+					let array = self.pop(ty)?;
+					for (i, value) in elems.iter().enumerate() {
+						self.push(array);
+						self.ldc(Constant::primitive(int, i as u64))?;
+						self.expr(value)?;
+						self.emit(Insn::AStore(elem));
+					}
+					self.push(array);
+				}
+			},
+			ExprKind::Assign(place, op, value) => self.assign(place, *op, value)?,
+			ExprKind::Block(block) => self.block(block)?,
+		}
+		Ok(())
+	}
+
+	fn exprs(&mut self, exprs: &[Expr]) -> Result<(), CodegenError> {
+		exprs.iter().try_for_each(|expr| self.expr(expr))
+	}
+
+	/// The element type of an array type
+	fn elem(&mut self, ty: &Ty) -> Result<TypeDesc, CodegenError> {
+		match ty {
+			Ty::Array(elem) => self.ty(elem),
+			_ => unreachable!("Not an array type: {}", ty),
+		}
+	}
+
+	fn assign(&mut self, place: &Place, op: Option<ArithOp>, value: &Expr) -> Result<(), CodegenError> {
+		match place {
+			Place::Local(local) => {
+				if let Some(op) = op {
+					let ty = self.ty(&self.def.locals[*local].ty)?;
+					self.push(self.locals[*local].expect("A variable is assigned before it is declared"));
+					self.expr(value)?;
+					self.arith(op, ty);
+				} else {
+					self.expr(value)?;
+				}
+				self.bind(*local)?;
+			},
+			Place::Field(object, class, field) => {
+				let class = self.name(class)?;
+				let field = self.name(field)?;
+				match op {
+					Some(op) => {
+						// the object is needed twice, and the VM has no instruction to duplicate it
+						self.expr(object)?;
+						let ty = self.ty(&object.ty)?;
+						let slot = self.pop(ty)?;
+						self.push(slot);
+						self.push(slot);
+						self.emit(Insn::GetField(class, field));
+						self.expr(value)?;
+						let ty = self.ty(&value.ty)?;
+						self.arith(op, ty);
+					},
+					None => {
+						self.expr(object)?;
+						self.expr(value)?;
+					},
+				}
+				self.emit(Insn::SetField(class, field));
+			},
+			Place::Index(array, index) => {
+				let elem = self.elem(&array.ty)?;
+				match op {
+					Some(op) => {
+						self.expr(array)?;
+						let ty = self.ty(&array.ty)?;
+						let array = self.pop(ty)?;
+						self.expr(index)?;
+						let ty = self.ty(&index.ty)?;
+						let index = self.pop(ty)?;
+						self.push(array);
+						self.push(index);
+						self.push(array);
+						self.push(index);
+						self.emit(Insn::ALoad(elem));
+						self.expr(value)?;
+						self.arith(op, elem);
+					},
+					None => {
+						self.expr(array)?;
+						self.expr(index)?;
+						self.expr(value)?;
+					},
+				}
+				self.emit(Insn::AStore(elem));
+			},
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,29 @@
+use std::fmt::{Debug, Display};
+use crate::compiler::span::Span;
+
+pub mod build;
+pub mod check;
+pub mod codegen;
+pub mod repl;
+pub mod syntax;
+
+/// An error at a span of a source file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic<E> {
+	pub span: Span,
+	pub error: E,
+}
+
+impl<E: Display> Diagnostic<E> {
+	pub fn new(span: Span, error: E) -> Self {
+		Diagnostic {
+			span,
+			error,
+		}
+	}
+
+	/// Formats the diagnostic as `<file>:<line>:<column>: <error>`, with `src` the contents of `file`
+	pub fn format(&self, file: &str, src: &str) -> String {
+		format!("{}:{}: {}", file, self.span.location(src), self.error)
+	}
+}
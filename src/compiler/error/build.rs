@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::PathBuf;
+use crate::compiler::error::codegen::CodegenError;
+use crate::vm::error::link::LinkError;
+
+/// A failure to build E# source into an executable
+pub enum BuildError {
+	Io(PathBuf, std::io::Error),
+	/// The syntax and type errors of the sources, formatted as `<file>:<line>:<column>: <error>`
+	Diagnostics(Vec<String>),
+	/// A source file whose path isn't a package name (like `foo/bar.es` for `foo.bar`)
+	InvalidPackage(PathBuf),
+	/// A source file, the package it declares, and the package of its path
+	PackageMismatch(PathBuf, String, String),
+	Codegen(CodegenError),
+	Link(LinkError),
+}
+
+impl Debug for BuildError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(path, err) => f.write_fmt(format_args!("{}: {}", path.display(), err)),
+			Self::Diagnostics(diagnostics) => f.write_str(&diagnostics.join("\n")),
+			Self::InvalidPackage(path) => f.write_fmt(format_args!("{}: the path is not a package name", path.display())),
+			Self::PackageMismatch(path, declared, expected) => f.write_fmt(format_args!("{}: declares package `{}`, but its path is package `{}`", path.display(), declared, expected)),
+			Self::Codegen(err) => Debug::fmt(err, f),
+			Self::Link(err) => f.write_fmt(format_args!("failed to link: {}", err)),
+		}
+	}
+}
+
+impl Display for BuildError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for BuildError {}
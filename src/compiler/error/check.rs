@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::compiler::typed::Ty;
+use crate::vm::types::function::Signature;
+
+/// A semantic error in E# source, found by the type checker
+#[derive(Clone, PartialEq)]
+pub enum CheckError {
+	/// An item, field or variable name that is defined more than once in the same scope
+	Duplicate(String),
+	Unresolved(String),
+	NotAType(String),
+	NotAStruct(String),
+	NotATrait(String),
+	/// A trait used as a type without `dyn`
+	BareTrait(String),
+	CyclicInheritance(String),
+	/// A type the VM can't store in an array or a field, and which of the two it is
+	UnsupportedStorage(Ty, &'static str),
+	/// The expected type and the type found instead
+	Mismatch(Ty, Ty),
+	/// A function whose parameters take more argument slots than the VM supports, and how many
+	TooManyParams(String, usize),
+	/// An operator and the type of its operand
+	InvalidOperand(&'static str, Ty),
+	InvalidCast(Ty, Ty),
+	LiteralOutOfRange(Ty),
+	/// A struct and the name of a field it doesn't have
+	UnknownField(String, String),
+	/// A struct and the field its literal doesn't initialize
+	MissingField(String, String),
+	/// A type and the name of a method it doesn't have
+	UnknownMethod(Ty, String),
+	/// A function, the number of arguments it takes, and the number it was called with
+	ArgumentCount(String, usize, usize),
+	NotAFunction(String),
+	/// An item used as a value
+	NotAValue(String),
+	/// An expression that isn't a variable, field or array element
+	InvalidPlace,
+	Immutable(String),
+	/// The return type of a function whose body doesn't return a value
+	MissingReturn(Ty),
+	SelfOutsideMethod,
+	MissingReceiver(String),
+	MissingBody(String),
+	TraitMethodBody(String),
+	/// A struct and a trait it doesn't implement
+	NotImplemented(String, String),
+	/// A struct, and a method of a trait (with the trait) it doesn't implement
+	MissingTraitMethod(String, String, String),
+	/// A method and the trait it isn't a member of
+	NotTraitMember(String, String),
+	/// A method whose signature doesn't match the method it implements or overrides, and that method
+	SignatureMismatch(String, String),
+	/// A construct the VM has no instructions for
+	Unsupported(&'static str),
+	/// An expression whose type can't be inferred without an annotation
+	AnnotationNeeded,
+	/// A value of type `void` used as a variable or argument
+	VoidValue,
+}
+
+impl Debug for CheckError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Duplicate(name) => f.write_fmt(format_args!("`{}` is defined more than once", name)),
+			Self::Unresolved(name) => f.write_fmt(format_args!("cannot find `{}`", name)),
+			Self::NotAType(name) => f.write_fmt(format_args!("`{}` is not a type", name)),
+			Self::NotAStruct(name) => f.write_fmt(format_args!("`{}` is not a struct", name)),
+			Self::NotATrait(name) => f.write_fmt(format_args!("`{}` is not a trait", name)),
+			Self::BareTrait(name) => f.write_fmt(format_args!("trait `{}` must be used as `dyn {}`", name, name)),
+			Self::CyclicInheritance(name) => f.write_fmt(format_args!("struct `{}` inherits from itself", name)),
+			Self::UnsupportedStorage(ty, storage) => f.write_fmt(format_args!("{} of type `{}` are not supported by the VM", storage, ty)),
+			Self::Mismatch(expected, found) => f.write_fmt(format_args!("mismatched types: expected `{}`, found `{}`", expected, found)),
+			Self::TooManyParams(function, slots) => f.write_fmt(format_args!("the parameters of `{}` take {} argument slots, but the VM supports at most {} (`dyn` parameters take 2)", function, slots, Signature::MAX_ARG_SLOTS)),
+			Self::InvalidOperand(op, ty) => f.write_fmt(format_args!("`{}` cannot be applied to `{}`", op, ty)),
+			Self::InvalidCast(from, to) => f.write_fmt(format_args!("cannot cast `{}` as `{}`", from, to)),
+			Self::LiteralOutOfRange(ty) => f.write_fmt(format_args!("literal out of range for `{}`", ty)),
+			Self::UnknownField(ty, field) => f.write_fmt(format_args!("struct `{}` has no field `{}`", ty, field)),
+			Self::MissingField(ty, field) => f.write_fmt(format_args!("missing field `{}` in literal of `{}`", field, ty)),
+			Self::UnknownMethod(ty, method) => f.write_fmt(format_args!("`{}` has no method `{}`", ty, method)),
+			Self::ArgumentCount(function, expected, found) => f.write_fmt(format_args!("`{}` takes {} arguments but {} were given", function, expected, found)),
+			Self::NotAFunction(name) => f.write_fmt(format_args!("`{}` is not a function", name)),
+			Self::NotAValue(name) => f.write_fmt(format_args!("`{}` is not a value", name)),
+			Self::InvalidPlace => f.write_str("only variables, fields and array elements can be assigned to"),
+			Self::Immutable(name) => f.write_fmt(format_args!("cannot assign to immutable variable `{}`", name)),
+			Self::MissingReturn(ty) => f.write_fmt(format_args!("missing return value of type `{}`", ty)),
+			Self::SelfOutsideMethod => f.write_str("`self` can only be used in methods"),
+			Self::MissingReceiver(name) => f.write_fmt(format_args!("method `{}` must take `self` as its first parameter", name)),
+			Self::MissingBody(name) => f.write_fmt(format_args!("function `{}` has no body", name)),
+			Self::TraitMethodBody(name) => f.write_fmt(format_args!("trait method `{}` can't have a body", name)),
+			Self::NotImplemented(ty, trait_name) => f.write_fmt(format_args!("`{}` does not implement trait `{}`", ty, trait_name)),
+			Self::MissingTraitMethod(ty, method, trait_name) => f.write_fmt(format_args!("`{}` is missing method `{}` of trait `{}`", ty, method, trait_name)),
+			Self::NotTraitMember(method, trait_name) => f.write_fmt(format_args!("method `{}` is not a member of trait `{}`", method, trait_name)),
+			Self::SignatureMismatch(method, other) => f.write_fmt(format_args!("the signature of `{}` doesn't match `{}`", method, other)),
+			Self::Unsupported(construct) => f.write_fmt(format_args!("{} are not supported, the VM has no instructions for them", construct)),
+			Self::AnnotationNeeded => f.write_str("type annotations needed"),
+			Self::VoidValue => f.write_str("expression has no value"),
+		}
+	}
+}
+
+impl Display for CheckError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for CheckError {}
@@ -0,0 +1,27 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+/// A checked program that doesn't fit in an executable
+pub enum CodegenError {
+	/// A function (by its `fn-id`) that needs more than 256 locals
+	TooManyLocals(String),
+	/// The executable needs more than 65536 constants
+	TooManyConstants,
+}
+
+impl Debug for CodegenError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooManyLocals(function) => f.write_fmt(format_args!("function `{}` has more than 256 locals", function)),
+			Self::TooManyConstants => f.write_str("the executable has more than 65536 constants"),
+		}
+	}
+}
+
+impl Display for CodegenError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for CodegenError {}
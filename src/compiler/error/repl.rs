@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::compiler::error::codegen::CodegenError;
+use crate::vm::error::vm::VmError;
+
+/// A failure to evaluate an input of a REPL
+pub enum ReplError {
+	/// The syntax and type errors of the input, formatted as `<line>:<column>: <error>`
+	Diagnostics(Vec<String>),
+	/// An input with a `package` declaration, inputs are always in the root package
+	Package,
+	/// An `impl` of a struct declared by an earlier input, whose class is already loaded
+	ReopenedStruct(String),
+	/// An item with the name of an item declared by an earlier input, which is already loaded
+	Redeclared(String),
+	Codegen(CodegenError),
+	Vm(VmError),
+}
+
+impl Debug for ReplError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Diagnostics(diagnostics) => f.write_str(&diagnostics.join("\n")),
+			Self::Package => f.write_str("inputs can't declare a package"),
+			Self::ReopenedStruct(name) => f.write_fmt(format_args!("`{}` was declared by an earlier input, impls must be entered with their struct", name)),
+			Self::Redeclared(name) => f.write_fmt(format_args!("`{}` was declared by an earlier input, and can't be redeclared", name)),
+			Self::Codegen(err) => Debug::fmt(err, f),
+			Self::Vm(err) => Display::fmt(err, f),
+		}
+	}
+}
+
+impl Display for ReplError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for ReplError {}
@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+/// A lexical or grammatical error in E# source
+#[derive(Clone, PartialEq)]
+pub enum SyntaxError {
+	UnexpectedChar(char),
+	UnterminatedString,
+	UnterminatedComment,
+	InvalidEscape(char),
+	/// A number literal that is malformed, doesn't fit in 64 bits or has an unknown suffix
+	InvalidNumber(String),
+	/// What was expected, and the token that was found instead
+	Expected(String, String),
+	/// A `package` declaration after other items
+	MisplacedPackage,
+	/// Comparisons like `a < b < c`, which need parentheses
+	ChainedComparison,
+}
+
+impl Debug for SyntaxError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnexpectedChar(char) => f.write_fmt(format_args!("unexpected character `{}`", char.escape_debug())),
+			Self::UnterminatedString => f.write_str("unterminated string"),
+			Self::UnterminatedComment => f.write_str("unterminated block comment"),
+			Self::InvalidEscape(char) => f.write_fmt(format_args!("invalid escape `\\{}`", char.escape_debug())),
+			Self::InvalidNumber(literal) => f.write_fmt(format_args!("invalid number literal `{}`", literal)),
+			Self::Expected(expected, found) => f.write_fmt(format_args!("expected {}, found {}", expected, found)),
+			Self::MisplacedPackage => f.write_str("the package declaration must come before any other item"),
+			Self::ChainedComparison => f.write_str("comparison operators cannot be chained, use parentheses"),
+		}
+	}
+}
+
+impl Display for SyntaxError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for SyntaxError {}
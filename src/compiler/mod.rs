@@ -0,0 +1,8 @@
+pub mod build;
+pub mod check;
+pub mod codegen;
+pub mod error;
+pub mod repl;
+pub mod span;
+pub mod syntax;
+pub mod typed;
@@ -0,0 +1,195 @@
+use crate::compiler::error::repl::ReplError;
+use crate::compiler::error::syntax::SyntaxError;
+use crate::compiler::syntax::ast;
+use crate::compiler::syntax::lexer::{self, Keyword, Punct, TokenKind};
+use crate::compiler::typed::{Program, Stmt, Ty};
+use crate::compiler::{check, codegen, syntax};
+use crate::vm::types::str::Str;
+use crate::vm::{Engine, Vm};
+
+/// What an input evaluated to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+	/// The names of the items the input declared
+	Defined(Vec<String>),
+	/// The value of the input, formatted, and its type
+	Value(String, Ty),
+	/// An input without a value
+	Void,
+}
+
+/// A read-eval-print loop, which compiles each input and loads it into a live VM<br>
+/// An input is either declarations (items and `use`s), which every later input can use, or
+/// statements ending with an optional expression, which are compiled into a fresh function that
+/// is called right away. Inputs are always in the root package.<br>
+/// ***Note:** The variables of an input are local to its function, so they can't be used by later
+/// inputs, and items can't be redeclared, since their classes and functions are already loaded.*
+pub struct Repl {
+	vm: Vm,
+	/// The declarations of every input so far
+	decls: ast::File,
+	inputs: usize,
+}
+
+impl Repl {
+	pub fn new(engine: Engine) -> Self {
+		Repl {
+			vm: Vm::with_engine(engine),
+			decls: ast::File::default(),
+			inputs: 0,
+		}
+	}
+
+	pub fn vm(&self) -> &Vm {
+		&self.vm
+	}
+
+	pub fn eval(&mut self, input: &str) -> Result<Output, ReplError> {
+		self.inputs += 1;
+		let (tokens, _) = lexer::tokenize(input);
+		match tokens[0].kind {
+			TokenKind::Keyword(Keyword::Package) => Err(ReplError::Package),
+			TokenKind::Keyword(Keyword::Struct | Keyword::Trait | Keyword::Impl | Keyword::Fn | Keyword::Use) => self.declare(input),
+			_ => self.run(input),
+		}
+	}
+
+	fn declare(&mut self, input: &str) -> Result<Output, ReplError> {
+		let (file, diagnostics) = syntax::parse(input);
+		if !diagnostics.is_empty() {
+			return Err(ReplError::Diagnostics(diagnostics.iter().map(|diagnostic| format!("{}: {}", diagnostic.span.location(input), diagnostic.error)).collect()))
+		}
+		if file.package.is_some() {
+			return Err(ReplError::Package)
+		}
+		let names = file.items.iter().filter_map(item_name).cloned().collect::<Vec<_>>();
+		if let Some(name) = names.iter().find(|name| self.decls.items.iter().any(|item| item_name(item) == Some(name))) {
+			return Err(ReplError::Redeclared(name.clone()))
+		}
+		for item in &file.items {
+			if let ast::Item::Impl(imp) = item {
+				let name = imp.ty.to_string();
+				if !names.contains(&name) && self.decls.items.iter().any(|item| matches!(item, ast::Item::Struct(def) if def.name.name == name)) {
+					return Err(ReplError::ReopenedStruct(name))
+				}
+			}
+		}
+
+		let mut decls = self.decls.clone();
+		decls.uses.extend(file.uses);
+		decls.items.extend(file.items);
+		let program = self.check(&decls, input)?;
+		self.load(only(program, &names))?;
+		self.decls = decls;
+		Ok(Output::Defined(names))
+	}
+
+	fn run(&mut self, input: &str) -> Result<Output, ReplError> {
+		let (mut body, diagnostics) = syntax::parse_body(input);
+		if !diagnostics.is_empty() {
+			return Err(ReplError::Diagnostics(diagnostics.iter().map(|diagnostic| format!("{}: {}", diagnostic.span.location(input), diagnostic.error)).collect()))
+		}
+		// the value is checked as a statement, so its type can be read from the checked body
+		let has_value = body.expr.is_some();
+		body.stmts.extend(body.expr.take().map(|expr| ast::Stmt {
+			span: expr.span,
+			kind: ast::StmtKind::Expr(*expr),
+		}));
+		// `$` can't start an identifier, so the function can't clash with a declaration
+		let name = format!("$input{}", self.inputs);
+		let mut decls = self.decls.clone();
+		decls.items.push(ast::Item::Fn(ast::Fn {
+			name: ast::Ident {
+				name: name.clone(),
+				span: Default::default(),
+			},
+			receiver: None,
+			params: Vec::new(),
+			ret: None,
+			span: body.span,
+			body: Some(body),
+		}));
+
+		let mut program = only(self.check(&decls, input)?, std::slice::from_ref(&name));
+		let def = &mut program.functions[0];
+		match def.body.stmts.pop() {
+			Some(Stmt::Expr(expr)) if has_value && expr.ty != Ty::Void => {
+				def.sig.ret = expr.ty.clone();
+				def.body.expr = Some(Box::new(expr));
+			},
+			stmt => def.body.stmts.extend(stmt),
+		}
+		let ty = def.sig.ret.clone();
+		self.load(program)?;
+		let ret = self.vm.call(&name, &[]).map_err(ReplError::Vm)?;
+		Ok(match ty {
+			Ty::Void => Output::Void,
+			ty => Output::Value(format(&self.vm, ret, &ty), ty),
+		})
+	}
+
+	fn check(&self, decls: &ast::File, input: &str) -> Result<Program, ReplError> {
+		check::check(std::slice::from_ref(decls)).map_err(|diagnostics| {
+			ReplError::Diagnostics(diagnostics.iter().map(|(_, diagnostic)| format!("{}: {}", diagnostic.span.location(input), diagnostic.error)).collect())
+		})
+	}
+
+	fn load(&mut self, program: Program) -> Result<(), ReplError> {
+		let builder = codegen::generate(&program).map_err(ReplError::Codegen)?;
+		self.vm.load(builder.finish()).map_err(ReplError::Vm)
+	}
+}
+
+/// Whether an input is complete, or needs more lines to close its brackets or comments
+pub fn is_complete(input: &str) -> bool {
+	let (tokens, diagnostics) = lexer::tokenize(input);
+	let depth = tokens.iter().fold(0isize, |depth, token| match token.kind {
+		TokenKind::Punct(Punct::LParen | Punct::LBracket | Punct::LBrace) => depth + 1,
+		TokenKind::Punct(Punct::RParen | Punct::RBracket | Punct::RBrace) => depth - 1,
+		_ => depth,
+	});
+	depth <= 0 && !diagnostics.iter().any(|diagnostic| diagnostic.error == SyntaxError::UnterminatedComment)
+}
+
+/// The name an item declares, if it isn't an impl
+fn item_name(item: &ast::Item) -> Option<&String> {
+	match item {
+		ast::Item::Struct(item) => Some(&item.name.name),
+		ast::Item::Trait(item) => Some(&item.name.name),
+		ast::Item::Fn(item) => Some(&item.name.name),
+		ast::Item::Impl(_) => None,
+	}
+}
+
+/// The items of a program named `names`
+fn only(program: Program, names: &[String]) -> Program {
+	Program {
+		structs: program.structs.into_iter().filter(|def| names.contains(&def.name)).collect(),
+		traits: program.traits.into_iter().filter(|def| names.contains(&def.name)).collect(),
+		functions: program.functions.into_iter().filter(|def| names.contains(&def.sig.name)).collect(),
+	}
+}
+
+/// Formats a value of type `ty` returned by the VM
+fn format(vm: &Vm, ret: u64, ty: &Ty) -> String {
+	match ty {
+		Ty::Int(int) => {
+			let shift = 64 - int.bits as u32;
+			match int.signed {
+				true => (((ret << shift) as i64) >> shift).to_string(),
+				false => ((ret << shift) >> shift).to_string(),
+			}
+		},
+		Ty::F32 => f32::from_bits(ret as u32).to_string(),
+		Ty::F64 => f64::from_bits(ret).to_string(),
+		Ty::Bool => (ret as u8 != 0).to_string(),
+		Ty::Str => {
+			// nothing references the string once it's returned, so it's rooted while it's read
+			vm.heap().root(ret as *const u8);
+			let value = format!("{:?}", unsafe { (*(ret as *const Str)).as_str() });
+			vm.heap().unroot(ret as *const u8);
+			value
+		},
+		ty => format!("<{} at {:#X}>", ty, ret),
+	}
+}
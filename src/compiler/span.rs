@@ -0,0 +1,45 @@
+use std::fmt::{Display, Formatter};
+
+/// A range of bytes in a source file
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+}
+
+impl Span {
+	pub fn new(start: usize, end: usize) -> Self {
+		Span {
+			start,
+			end,
+		}
+	}
+
+	/// The span from the start of this span to the end of `other`
+	pub fn to(self, other: Span) -> Span {
+		Span::new(self.start, other.end.max(self.start))
+	}
+
+	/// The line and column (starting at `1`, counted in characters) of the start of the span in `src`
+	pub fn location(&self, src: &str) -> Location {
+		let before = &src[..self.start.min(src.len())];
+		let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+		Location {
+			line: before.matches('\n').count() + 1,
+			column: before[line_start..].chars().count() + 1,
+		}
+	}
+}
+
+/// A line and column in a source file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+	pub line: usize,
+	pub column: usize,
+}
+
+impl Display for Location {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}", self.line, self.column)
+	}
+}
@@ -0,0 +1,31 @@
+use crate::compiler::error::Diagnostic;
+use crate::compiler::error::syntax::SyntaxError;
+use crate::compiler::syntax::ast::{Block, File};
+use crate::compiler::syntax::parser::Parser;
+
+pub mod ast;
+pub mod lexer;
+pub mod parser;
+
+/// Parses an E# source file<br>
+/// The file is returned even if it has errors, without the items (or statements) that failed to
+/// parse. The diagnostics of every error are returned in the order they occur in the source.
+pub fn parse(src: &str) -> (File, Vec<Diagnostic<SyntaxError>>) {
+	let (tokens, mut diagnostics) = lexer::tokenize(src);
+	let mut parser = Parser::new(tokens);
+	let file = parser.file();
+	diagnostics.extend(parser.diagnostics());
+	diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+	(file, diagnostics)
+}
+
+/// Parses statements like the inside of a block, such as the input of a REPL<br>
+/// The statements are returned even if they have errors, like by [`parse`].
+pub fn parse_body(src: &str) -> (Block, Vec<Diagnostic<SyntaxError>>) {
+	let (tokens, mut diagnostics) = lexer::tokenize(src);
+	let mut parser = Parser::new(tokens);
+	let body = parser.body();
+	diagnostics.extend(parser.diagnostics());
+	diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+	(body, diagnostics)
+}
@@ -0,0 +1,276 @@
+use std::fmt::{Display, Formatter};
+use crate::compiler::span::Span;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ident {
+	pub name: String,
+	pub span: Span,
+}
+
+/// A name of identifiers separated by `.`, like `shapes.Square` or `x.side`<br>
+/// ***Note:** The parser can't tell packages from values, so a path may also be a local followed by
+/// fields, or a function followed by nothing. Name resolution splits it.*
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+	pub segments: Vec<Ident>,
+	pub span: Span,
+}
+
+impl Display for Path {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		for (i, segment) in self.segments.iter().enumerate() {
+			if i > 0 {
+				f.write_str(".")?;
+			}
+			f.write_str(&segment.name)?;
+		}
+		Ok(())
+	}
+}
+
+/// A source file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct File {
+	/// The package the items of the file are in (`package a.b;`), the root package if there is none
+	pub package: Option<Path>,
+	/// The fully-qualified names of the items of other packages the file uses (`use a.b.C;`)
+	pub uses: Vec<Path>,
+	pub items: Vec<Item>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+	Struct(Struct),
+	Trait(Trait),
+	Impl(Impl),
+	Fn(Fn),
+}
+
+impl Item {
+	pub fn span(&self) -> Span {
+		match self {
+			Item::Struct(item) => item.span,
+			Item::Trait(item) => item.span,
+			Item::Impl(item) => item.span,
+			Item::Fn(item) => item.span,
+		}
+	}
+}
+
+/// `struct Name [: Super] { field: type, ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Struct {
+	pub name: Ident,
+	pub parent: Option<Path>,
+	pub fields: Vec<Field>,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+	pub name: Ident,
+	pub ty: Type,
+	pub span: Span,
+}
+
+/// `trait Name { fn method(self, ...) -> type; ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trait {
+	pub name: Ident,
+	pub methods: Vec<Fn>,
+	pub span: Span,
+}
+
+/// `impl [Trait for] Type { fn method(self, ...) { ... } ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Impl {
+	pub trait_name: Option<Path>,
+	pub ty: Path,
+	pub methods: Vec<Fn>,
+	pub span: Span,
+}
+
+/// A function, or a method if it has a receiver
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fn {
+	pub name: Ident,
+	/// The span of `self` if the first parameter is the receiver
+	pub receiver: Option<Span>,
+	pub params: Vec<Param>,
+	pub ret: Option<Type>,
+	/// The body, `None` if it is declared with `;` (in traits)
+	pub body: Option<Block>,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Param {
+	pub name: Ident,
+	pub ty: Type,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Type {
+	pub kind: TypeKind,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeKind {
+	/// A primitive (like `i32` or `str`) or the name of a struct or trait
+	Path(Path),
+	/// `[elem]`
+	Array(Box<Type>),
+	/// `dyn Trait`
+	Dyn(Path),
+}
+
+/// `{ stmt... [expr] }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+	pub stmts: Vec<Stmt>,
+	/// The value of the block
+	pub expr: Option<Box<Expr>>,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stmt {
+	pub kind: StmtKind,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StmtKind {
+	/// `let [mut] name [: type] = value;`
+	Let {
+		name: Ident,
+		mutable: bool,
+		ty: Option<Type>,
+		value: Expr,
+	},
+	/// An expression whose value is discarded
+	Expr(Expr),
+	/// `while cond { ... }`
+	While(Expr, Block),
+	Return(Option<Expr>),
+	Break,
+	Continue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+	pub kind: ExprKind,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+	/// An integer literal and its suffix
+	Int(u64, Option<String>),
+	/// A float literal and its suffix
+	Float(f64, Option<String>),
+	Str(String),
+	Bool(bool),
+	Path(Path),
+	SelfValue,
+	Unary(UnOp, Box<Expr>),
+	Binary(BinOp, Box<Expr>, Box<Expr>),
+	/// `place = value`, or a compound assignment like `place += value` with its operator
+	Assign(Option<BinOp>, Box<Expr>, Box<Expr>),
+	/// `value as type`
+	Cast(Box<Expr>, Type),
+	Call(Box<Expr>, Vec<Expr>),
+	/// `receiver.method(args...)` with a receiver that isn't a path
+	MethodCall(Box<Expr>, Ident, Vec<Expr>),
+	/// `value.field` with a value that isn't a path
+	Field(Box<Expr>, Ident),
+	Index(Box<Expr>, Box<Expr>),
+	/// `Struct { field: value, ... }`
+	StructLit(Path, Vec<FieldInit>),
+	/// `[a, b, c]`
+	Array(Vec<Expr>),
+	/// `[value; len]`
+	ArrayRepeat(Box<Expr>, Box<Expr>),
+	/// `if cond { ... } [else ...]`, where the else branch is a block or another `if`
+	If(Box<Expr>, Block, Option<Box<Expr>>),
+	Block(Block),
+	/// An expression that failed to parse
+	Error,
+}
+
+impl ExprKind {
+	/// Whether the expression ends with a block, so it can be a statement without a `;`
+	pub fn is_block_like(&self) -> bool {
+		matches!(self, ExprKind::If(..) | ExprKind::Block(_))
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldInit {
+	pub name: Ident,
+	pub value: Expr,
+	pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnOp {
+	/// `-`
+	Neg,
+	/// `!`, logical for `bool` and bitwise for integers
+	Not,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Rem,
+	And,
+	Or,
+	Xor,
+	Shl,
+	Shr,
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+	/// `&&`
+	LogicalAnd,
+	/// `||`
+	LogicalOr,
+}
+
+impl BinOp {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			BinOp::Add => "+",
+			BinOp::Sub => "-",
+			BinOp::Mul => "*",
+			BinOp::Div => "/",
+			BinOp::Rem => "%",
+			BinOp::And => "&",
+			BinOp::Or => "|",
+			BinOp::Xor => "^",
+			BinOp::Shl => "<<",
+			BinOp::Shr => ">>",
+			BinOp::Eq => "==",
+			BinOp::Ne => "!=",
+			BinOp::Lt => "<",
+			BinOp::Le => "<=",
+			BinOp::Gt => ">",
+			BinOp::Ge => ">=",
+			BinOp::LogicalAnd => "&&",
+			BinOp::LogicalOr => "||",
+		}
+	}
+
+	pub fn is_comparison(&self) -> bool {
+		matches!(self, BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge)
+	}
+}
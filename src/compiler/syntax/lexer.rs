@@ -0,0 +1,350 @@
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::CharIndices;
+use crate::compiler::error::Diagnostic;
+use crate::compiler::error::syntax::SyntaxError;
+use crate::compiler::span::Span;
+
+/// The suffixes number literals may have, which give them a type
+pub const SUFFIXES: [&str; 10] = ["i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+	Ident(String),
+	/// An integer literal and its suffix
+	Int(u64, Option<String>),
+	/// A float literal and its suffix
+	Float(f64, Option<String>),
+	Str(String),
+	Keyword(Keyword),
+	Punct(Punct),
+	Eof,
+}
+
+impl Display for TokenKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TokenKind::Ident(ident) => write!(f, "`{}`", ident),
+			TokenKind::Int(value, suffix) => write!(f, "`{}{}`", value, suffix.as_deref().unwrap_or("")),
+			TokenKind::Float(value, suffix) => write!(f, "`{:?}{}`", value, suffix.as_deref().unwrap_or("")),
+			TokenKind::Str(str) => write!(f, "`{:?}`", str),
+			TokenKind::Keyword(keyword) => write!(f, "`{}`", keyword.as_str()),
+			TokenKind::Punct(punct) => write!(f, "`{}`", punct.as_str()),
+			TokenKind::Eof => f.write_str("end of file"),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+	pub kind: TokenKind,
+	pub span: Span,
+}
+
+macro_rules! keywords {
+	($($keyword:ident => $str:literal),* $(,)?) => {
+		#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+		pub enum Keyword {
+			$($keyword),*
+		}
+
+		impl Keyword {
+			pub fn as_str(&self) -> &'static str {
+				match self {
+					$(Self::$keyword => $str),*
+				}
+			}
+
+			fn from_str(str: &str) -> Option<Self> {
+				match str {
+					$($str => Some(Self::$keyword),)*
+					_ => None,
+				}
+			}
+		}
+	};
+}
+
+keywords! {
+	Package => "package",
+	Use => "use",
+	Struct => "struct",
+	Trait => "trait",
+	Impl => "impl",
+	For => "for",
+	Fn => "fn",
+	Let => "let",
+	Mut => "mut",
+	If => "if",
+	Else => "else",
+	While => "while",
+	Break => "break",
+	Continue => "continue",
+	Return => "return",
+	As => "as",
+	SelfValue => "self",
+	Dyn => "dyn",
+	True => "true",
+	False => "false",
+}
+
+macro_rules! puncts {
+	($($punct:ident => $str:literal),* $(,)?) => {
+		#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+		pub enum Punct {
+			$($punct),*
+		}
+
+		impl Punct {
+			/// All punctuation, longest first so that the lexer matches greedily
+			const ALL: &'static [Punct] = &[$(Self::$punct),*];
+
+			pub fn as_str(&self) -> &'static str {
+				match self {
+					$(Self::$punct => $str),*
+				}
+			}
+		}
+	};
+}
+
+puncts! {
+	ShlEq => "<<=",
+	ShrEq => ">>=",
+	Arrow => "->",
+	EqEq => "==",
+	Ne => "!=",
+	Le => "<=",
+	Ge => ">=",
+	AndAnd => "&&",
+	OrOr => "||",
+	Shl => "<<",
+	Shr => ">>",
+	PlusEq => "+=",
+	MinusEq => "-=",
+	StarEq => "*=",
+	SlashEq => "/=",
+	PercentEq => "%=",
+	AndEq => "&=",
+	OrEq => "|=",
+	CaretEq => "^=",
+	LParen => "(",
+	RParen => ")",
+	LBrace => "{",
+	RBrace => "}",
+	LBracket => "[",
+	RBracket => "]",
+	Comma => ",",
+	Semi => ";",
+	Colon => ":",
+	Dot => ".",
+	Eq => "=",
+	Lt => "<",
+	Gt => ">",
+	Plus => "+",
+	Minus => "-",
+	Star => "*",
+	Slash => "/",
+	Percent => "%",
+	Not => "!",
+	And => "&",
+	Or => "|",
+	Caret => "^",
+}
+
+/// Splits E# source into tokens, the last of which is [`TokenKind::Eof`]<br>
+/// Malformed tokens are reported and skipped, so the rest of the source is still tokenized.
+pub fn tokenize(src: &str) -> (Vec<Token>, Vec<Diagnostic<SyntaxError>>) {
+	let mut lexer = Lexer {
+		src,
+		chars: src.char_indices().peekable(),
+		diagnostics: Vec::new(),
+	};
+	let mut tokens = Vec::new();
+	loop {
+		let token = lexer.next_token();
+		let eof = token.kind == TokenKind::Eof;
+		tokens.push(token);
+		if eof {
+			return (tokens, lexer.diagnostics)
+		}
+	}
+}
+
+struct Lexer<'a> {
+	src: &'a str,
+	chars: Peekable<CharIndices<'a>>,
+	diagnostics: Vec<Diagnostic<SyntaxError>>,
+}
+
+impl<'a> Lexer<'a> {
+	/// The offset of the next character
+	fn offset(&mut self) -> usize {
+		let len = self.src.len();
+		self.chars.peek().map_or(len, |(offset, _)| *offset)
+	}
+
+	fn error(&mut self, start: usize, error: SyntaxError) {
+		let end = self.offset();
+		self.diagnostics.push(Diagnostic::new(Span::new(start, end), error));
+	}
+
+	fn next_token(&mut self) -> Token {
+		loop {
+			self.skip_trivia();
+			let start = self.offset();
+			let char = match self.chars.peek() {
+				Some((_, char)) => *char,
+				None => return Token {
+					kind: TokenKind::Eof,
+					span: Span::new(start, start),
+				},
+			};
+
+			let kind = if char.is_alphabetic() || char == '_' {
+				let word = self.take_while(start, |char| char.is_alphanumeric() || char == '_');
+				Some(Keyword::from_str(word).map_or_else(|| TokenKind::Ident(word.to_string()), TokenKind::Keyword))
+			} else if char.is_ascii_digit() {
+				self.number(start)
+			} else if char == '"' {
+				self.string(start)
+			} else {
+				let rest = &self.src[start..];
+				match Punct::ALL.iter().find(|punct| rest.starts_with(punct.as_str())) {
+					Some(punct) => {
+						for _ in 0..punct.as_str().len() {
+							self.chars.next();
+						}
+						Some(TokenKind::Punct(*punct))
+					},
+					None => {
+						self.chars.next();
+						self.error(start, SyntaxError::UnexpectedChar(char));
+						None
+					},
+				}
+			};
+			if let Some(kind) = kind {
+				return Token {
+					kind,
+					span: Span::new(start, self.offset()),
+				}
+			}
+		}
+	}
+
+	/// Consumes the characters matching `predicate`, returning them (with the characters from `start`)
+	fn take_while(&mut self, start: usize, predicate: impl Fn(char) -> bool) -> &'a str {
+		while self.chars.next_if(|(_, char)| predicate(*char)).is_some() {}
+		let end = self.offset();
+		&self.src[start..end]
+	}
+
+	/// Skips whitespace, line comments (`//`) and block comments (`/* */`)
+	fn skip_trivia(&mut self) {
+		loop {
+			while self.chars.next_if(|(_, char)| char.is_whitespace()).is_some() {}
+			let start = self.offset();
+			let rest = &self.src[start..];
+			if rest.starts_with("//") {
+				while self.chars.next_if(|(_, char)| *char != '\n').is_some() {}
+			} else if let Some(comment) = rest.strip_prefix("/*") {
+				match comment.find("*/") {
+					Some(end) => {
+						while self.offset() < start + end + 4 {
+							self.chars.next();
+						}
+					},
+					None => {
+						while self.chars.next().is_some() {}
+						self.error(start, SyntaxError::UnterminatedComment);
+					},
+				}
+			} else {
+				return
+			}
+		}
+	}
+
+	/// Lexes an integer (decimal, `0x` hexadecimal or `0b` binary) or float literal, with an optional suffix
+	fn number(&mut self, start: usize) -> Option<TokenKind> {
+		let radix = match &self.src[start..] {
+			rest if rest.starts_with("0x") => 16,
+			rest if rest.starts_with("0b") => 2,
+			_ => 10,
+		};
+		if radix != 10 {
+			self.chars.next();
+			self.chars.next();
+		}
+		let digits_start = self.offset();
+		self.take_while(digits_start, |char| char.is_digit(radix) || char == '_');
+		let mut float = false;
+		if radix == 10 {
+			// a fraction needs a digit after the `.`, so `1.foo` stays a field access
+			let mut lookahead = self.chars.clone();
+			if matches!(lookahead.next(), Some((_, '.'))) && matches!(lookahead.next(), Some((_, char)) if char.is_ascii_digit()) {
+				float = true;
+				self.chars.next();
+				self.take_while(start, |char| char.is_ascii_digit() || char == '_');
+			}
+			let mut lookahead = self.chars.clone();
+			if matches!(lookahead.next(), Some((_, 'e' | 'E'))) {
+				let sign = lookahead.next_if(|(_, char)| *char == '+' || *char == '-').is_some();
+				if matches!(lookahead.next(), Some((_, char)) if char.is_ascii_digit()) {
+					float = true;
+					self.chars.next();
+					if sign {
+						self.chars.next();
+					}
+					self.take_while(start, |char| char.is_ascii_digit() || char == '_');
+				}
+			}
+		}
+		let digits_end = self.offset();
+		let suffix = self.take_while(digits_end, |char| char.is_alphanumeric() || char == '_');
+		let literal = &self.src[start..self.offset()];
+		let digits = self.src[digits_start..digits_end].replace('_', "");
+		let suffix = (!suffix.is_empty()).then(|| suffix.to_string());
+
+		let valid_suffix = suffix.as_deref().is_none_or(|suffix| SUFFIXES.contains(&suffix) && (!float || suffix.starts_with('f')));
+		let kind = if !valid_suffix || digits.is_empty() {
+			None
+		} else if float {
+			digits.parse::<f64>().ok().map(|value| TokenKind::Float(value, suffix))
+		} else {
+			u64::from_str_radix(&digits, radix).ok().map(|value| TokenKind::Int(value, suffix))
+		};
+		if kind.is_none() {
+			let literal = literal.to_string();
+			self.error(start, SyntaxError::InvalidNumber(literal));
+		}
+		kind
+	}
+
+	/// Lexes a string literal, which ends on the line it starts and may contain the escapes `\n`, `\t`,
+	/// `\r`, `\0`, `\\` and `\"`
+	fn string(&mut self, start: usize) -> Option<TokenKind> {
+		self.chars.next();
+		let mut str = String::new();
+		loop {
+			match self.chars.next() {
+				Some((_, '"')) => return Some(TokenKind::Str(str)),
+				Some((offset, '\\')) => match self.chars.next() {
+					Some((_, 'n')) => str.push('\n'),
+					Some((_, 't')) => str.push('\t'),
+					Some((_, 'r')) => str.push('\r'),
+					Some((_, '0')) => str.push('\0'),
+					Some((_, '\\')) => str.push('\\'),
+					Some((_, '"')) => str.push('"'),
+					Some((_, char)) => self.error(offset, SyntaxError::InvalidEscape(char)),
+					None => break,
+				},
+				Some((_, '\n')) | None => break,
+				Some((_, char)) => str.push(char),
+			}
+		}
+		self.error(start, SyntaxError::UnterminatedString);
+		None
+	}
+}
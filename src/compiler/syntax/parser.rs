@@ -0,0 +1,808 @@
+use std::mem;
+use crate::compiler::error::Diagnostic;
+use crate::compiler::error::syntax::SyntaxError;
+use crate::compiler::span::Span;
+use crate::compiler::syntax::ast::{BinOp, Block, Expr, ExprKind, Field, FieldInit, File, Fn, Ident, Impl, Item, Param, Path, Stmt, StmtKind, Struct, Trait, Type, TypeKind, UnOp};
+use crate::compiler::syntax::lexer::{Keyword, Punct, Token, TokenKind};
+
+/// The keywords that start an item, where the parser resumes after a malformed item
+const ITEMS: &[Keyword] = &[Keyword::Package, Keyword::Use, Keyword::Struct, Keyword::Trait, Keyword::Impl, Keyword::Fn];
+/// The keywords that start a statement, where the parser resumes after a malformed statement
+const STMTS: &[Keyword] = &[Keyword::Let, Keyword::While, Keyword::Return];
+
+/// A recursive-descent parser of E# source<br>
+/// Parsing functions return `None` after reporting an error, and the loops over items, members,
+/// statements and list elements skip to the next one, so a file reports every error it has.
+pub struct Parser {
+	tokens: Vec<Token>,
+	head: usize,
+	/// Whether a path followed by `{` isn't a struct literal, in the conditions of `if` and `while`
+	no_struct: bool,
+	/// The token the last error was reported at, errors at the same token are dropped
+	last_error: Option<usize>,
+	diagnostics: Vec<Diagnostic<SyntaxError>>,
+}
+
+/// What a statement parses to, the last expression of a block without `;` is the value of the block
+enum Parsed {
+	Stmt(Stmt),
+	Tail(Expr),
+}
+
+impl Parser {
+	/// A parser of `tokens`, which must end with [`TokenKind::Eof`]
+	pub fn new(tokens: Vec<Token>) -> Self {
+		Parser {
+			tokens,
+			head: 0,
+			no_struct: false,
+			last_error: None,
+			diagnostics: Vec::new(),
+		}
+	}
+
+	pub fn diagnostics(self) -> Vec<Diagnostic<SyntaxError>> {
+		self.diagnostics
+	}
+
+	fn peek(&self) -> &TokenKind {
+		self.peek_nth(0)
+	}
+
+	fn peek_nth(&self, n: usize) -> &TokenKind {
+		&self.tokens[(self.head + n).min(self.tokens.len() - 1)].kind
+	}
+
+	/// The span of the next token
+	fn span(&self) -> Span {
+		self.tokens[self.head].span
+	}
+
+	/// The span of the last token consumed
+	fn prev_span(&self) -> Span {
+		self.head.checked_sub(1).map_or_else(|| self.span(), |prev| self.tokens[prev].span)
+	}
+
+	fn bump(&mut self) -> Token {
+		let token = self.tokens[self.head].clone();
+		if token.kind != TokenKind::Eof {
+			self.head += 1;
+		}
+		token
+	}
+
+	fn is(&self, punct: Punct) -> bool {
+		*self.peek() == TokenKind::Punct(punct)
+	}
+
+	fn is_keyword(&self, keyword: Keyword) -> bool {
+		*self.peek() == TokenKind::Keyword(keyword)
+	}
+
+	fn is_eof(&self) -> bool {
+		*self.peek() == TokenKind::Eof
+	}
+
+	fn eat(&mut self, punct: Punct) -> bool {
+		let found = self.is(punct);
+		if found {
+			self.bump();
+		}
+		found
+	}
+
+	fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+		let found = self.is_keyword(keyword);
+		if found {
+			self.bump();
+		}
+		found
+	}
+
+	fn error(&mut self, span: Span, error: SyntaxError) {
+		if self.last_error != Some(self.head) {
+			self.last_error = Some(self.head);
+			self.diagnostics.push(Diagnostic::new(span, error));
+		}
+	}
+
+	/// Reports that `expected` was expected instead of the next token
+	fn expected<T>(&mut self, expected: &str) -> Option<T> {
+		let found = self.peek().to_string();
+		self.error(self.span(), SyntaxError::Expected(expected.to_string(), found));
+		None
+	}
+
+	fn expect(&mut self, punct: Punct) -> Option<Span> {
+		match self.is(punct) {
+			true => Some(self.bump().span),
+			false => self.expected(&format!("`{}`", punct.as_str())),
+		}
+	}
+
+	/// Parses with struct literals allowed (or not, with `no_struct`) until `parse` returns
+	fn restrict<T>(&mut self, no_struct: bool, parse: impl FnOnce(&mut Self) -> T) -> T {
+		let outer = mem::replace(&mut self.no_struct, no_struct);
+		let parsed = parse(self);
+		self.no_struct = outer;
+		parsed
+	}
+
+	/// Skips tokens up to (not including) one of `puncts` or `keywords` outside of brackets, or a
+	/// closing bracket of the enclosing brackets<br>
+	/// At least one token is skipped if none were consumed since `from`, so callers always advance.
+	fn skip(&mut self, from: usize, puncts: &[Punct], keywords: &[Keyword]) {
+		let mut depth = 0usize;
+		loop {
+			let stop = match self.peek() {
+				TokenKind::Eof => return,
+				TokenKind::Punct(punct) if depth == 0 && puncts.contains(punct) => true,
+				TokenKind::Keyword(keyword) if depth == 0 && keywords.contains(keyword) => true,
+				TokenKind::Punct(Punct::LParen | Punct::LBrace | Punct::LBracket) => {
+					depth += 1;
+					false
+				},
+				TokenKind::Punct(Punct::RParen | Punct::RBrace | Punct::RBracket) if depth == 0 => true,
+				TokenKind::Punct(Punct::RParen | Punct::RBrace | Punct::RBracket) => {
+					depth -= 1;
+					false
+				},
+				_ => false,
+			};
+			if stop && self.head > from {
+				return
+			}
+			self.bump();
+		}
+	}
+
+	fn ident(&mut self, expected: &str) -> Option<Ident> {
+		match self.peek().clone() {
+			TokenKind::Ident(name) => Some(Ident {
+				name,
+				span: self.bump().span,
+			}),
+			_ => self.expected(expected),
+		}
+	}
+
+	/// A path `a.b.c`, with `expected` the description of the path
+	fn path(&mut self, expected: &str) -> Option<Path> {
+		let mut segments = vec![self.ident(expected)?];
+		while self.is(Punct::Dot) && matches!(self.peek_nth(1), TokenKind::Ident(_)) {
+			self.bump();
+			segments.push(self.ident(expected)?);
+		}
+		Some(Path {
+			span: segments[0].span.to(segments[segments.len() - 1].span),
+			segments,
+		})
+	}
+
+	pub fn file(&mut self) -> File {
+		let mut file = File::default();
+		if self.eat_keyword(Keyword::Package) {
+			file.package = self.path("a package name").filter(|_| self.expect(Punct::Semi).is_some());
+		}
+		while !self.is_eof() {
+			let from = self.head;
+			let parsed = match self.peek() {
+				TokenKind::Keyword(Keyword::Package) => {
+					self.error(self.span(), SyntaxError::MisplacedPackage);
+					None
+				},
+				TokenKind::Keyword(Keyword::Use) => {
+					self.bump();
+					let path = self.path("the name of an item");
+					path.filter(|_| self.expect(Punct::Semi).is_some()).map(|path| file.uses.push(path))
+				},
+				TokenKind::Keyword(Keyword::Struct) => self.structure().map(|item| file.items.push(Item::Struct(item))),
+				TokenKind::Keyword(Keyword::Trait) => self.trait_def().map(|item| file.items.push(Item::Trait(item))),
+				TokenKind::Keyword(Keyword::Impl) => self.implementation().map(|item| file.items.push(Item::Impl(item))),
+				TokenKind::Keyword(Keyword::Fn) => self.function().map(|item| file.items.push(Item::Fn(item))),
+				_ => self.expected("an item"),
+			};
+			if parsed.is_none() {
+				self.recover_item(from);
+			}
+		}
+		file
+	}
+
+	/// Skips to the next item, past closing brackets without an opening one
+	fn recover_item(&mut self, from: usize) {
+		self.skip(from, &[], ITEMS);
+		while matches!(self.peek(), TokenKind::Punct(Punct::RParen | Punct::RBrace | Punct::RBracket)) {
+			let from = self.head;
+			self.skip(from, &[], ITEMS);
+		}
+	}
+
+	fn structure(&mut self) -> Option<Struct> {
+		let start = self.bump().span;
+		let name = self.ident("a struct name")?;
+		let parent = match self.eat(Punct::Colon) {
+			true => Some(self.path("a supertype")?),
+			false => None,
+		};
+		self.expect(Punct::LBrace)?;
+		let mut fields = Vec::new();
+		while !self.is(Punct::RBrace) && !self.is_eof() {
+			let from = self.head;
+			match self.field() {
+				Some(field) => fields.push(field),
+				None => self.skip(from, &[Punct::Comma], &[]),
+			}
+			if !self.eat(Punct::Comma) {
+				break
+			}
+		}
+		if !self.is(Punct::RBrace) {
+			return self.expected("`,` or `}`")
+		}
+		let end = self.bump().span;
+		Some(Struct {
+			name,
+			parent,
+			fields,
+			span: start.to(end),
+		})
+	}
+
+	fn field(&mut self) -> Option<Field> {
+		let name = self.ident("a field name")?;
+		self.expect(Punct::Colon)?;
+		let ty = self.ty()?;
+		Some(Field {
+			span: name.span.to(ty.span),
+			name,
+			ty,
+		})
+	}
+
+	/// The methods of a trait or impl, between `{` and `}`
+	fn methods(&mut self) -> Option<(Vec<Fn>, Span)> {
+		self.expect(Punct::LBrace)?;
+		let mut methods = Vec::new();
+		while !self.is(Punct::RBrace) && !self.is_eof() {
+			let from = self.head;
+			let method = match self.is_keyword(Keyword::Fn) {
+				true => self.function(),
+				false => self.expected("`fn` or `}`"),
+			};
+			match method {
+				Some(method) => methods.push(method),
+				None => self.skip(from, &[], &[Keyword::Fn]),
+			}
+		}
+		let end = self.expect(Punct::RBrace)?;
+		Some((methods, end))
+	}
+
+	fn trait_def(&mut self) -> Option<Trait> {
+		let start = self.bump().span;
+		let name = self.ident("a trait name")?;
+		let (methods, end) = self.methods()?;
+		Some(Trait {
+			name,
+			methods,
+			span: start.to(end),
+		})
+	}
+
+	fn implementation(&mut self) -> Option<Impl> {
+		let start = self.bump().span;
+		let first = self.path("a type or trait")?;
+		let (trait_name, ty) = match self.eat_keyword(Keyword::For) {
+			true => (Some(first), self.path("a type")?),
+			false => (None, first),
+		};
+		let (methods, end) = self.methods()?;
+		Some(Impl {
+			trait_name,
+			ty,
+			methods,
+			span: start.to(end),
+		})
+	}
+
+	fn function(&mut self) -> Option<Fn> {
+		let start = self.bump().span;
+		let name = self.ident("a function name")?;
+		self.expect(Punct::LParen)?;
+		let mut receiver = None;
+		if self.is_keyword(Keyword::SelfValue) {
+			receiver = Some(self.bump().span);
+			if !self.is(Punct::RParen) {
+				self.expect(Punct::Comma)?;
+			}
+		}
+		let mut params = Vec::new();
+		while !self.is(Punct::RParen) && !self.is_eof() {
+			let from = self.head;
+			match self.param() {
+				Some(param) => params.push(param),
+				None => self.skip(from, &[Punct::Comma, Punct::LBrace], &[]),
+			}
+			if !self.eat(Punct::Comma) {
+				break
+			}
+		}
+		if !self.is(Punct::RParen) {
+			return self.expected("`,` or `)`")
+		}
+		self.bump();
+		let ret = match self.eat(Punct::Arrow) {
+			true => Some(self.ty()?),
+			false => None,
+		};
+		let body = match self.eat(Punct::Semi) {
+			true => None,
+			false if self.is(Punct::LBrace) => Some(self.block()?),
+			false => return self.expected("`{` or `;`"),
+		};
+		Some(Fn {
+			name,
+			receiver,
+			params,
+			ret,
+			body,
+			span: start.to(self.prev_span()),
+		})
+	}
+
+	fn param(&mut self) -> Option<Param> {
+		let name = self.ident("a parameter name")?;
+		self.expect(Punct::Colon)?;
+		let ty = self.ty()?;
+		Some(Param {
+			span: name.span.to(ty.span),
+			name,
+			ty,
+		})
+	}
+
+	fn ty(&mut self) -> Option<Type> {
+		let start = self.span();
+		let kind = if self.eat(Punct::LBracket) {
+			let elem = self.ty()?;
+			self.expect(Punct::RBracket)?;
+			TypeKind::Array(Box::new(elem))
+		} else if self.eat_keyword(Keyword::Dyn) {
+			TypeKind::Dyn(self.path("a trait")?)
+		} else if matches!(self.peek(), TokenKind::Ident(_)) {
+			TypeKind::Path(self.path("a type")?)
+		} else {
+			return self.expected("a type")
+		};
+		Some(Type {
+			kind,
+			span: start.to(self.prev_span()),
+		})
+	}
+
+	/// A block, which is returned even if it has errors (or isn't closed)
+	fn block(&mut self) -> Option<Block> {
+		let start = self.expect(Punct::LBrace)?;
+		self.restrict(false, |parser| {
+			let (stmts, expr) = parser.stmts();
+			let end = parser.expect(Punct::RBrace).unwrap_or_else(|| parser.prev_span());
+			Some(Block {
+				stmts,
+				expr: expr.map(Box::new),
+				span: start.to(end),
+			})
+		})
+	}
+
+	/// Statements like the inside of a block, up to the end of the source<br>
+	/// The last expression is the value of the statements if it has no `;`.
+	pub fn body(&mut self) -> Block {
+		let start = self.span();
+		let mut stmts = Vec::new();
+		let mut expr = None;
+		while !self.is_eof() {
+			let (more, tail) = self.stmts();
+			stmts.extend(expr.take().map(|expr: Expr| Stmt {
+				span: expr.span,
+				kind: StmtKind::Expr(expr),
+			}));
+			stmts.extend(more);
+			expr = tail;
+			if self.is(Punct::RBrace) {
+				self.expected::<()>("a statement");
+				self.bump();
+			}
+		}
+		Block {
+			stmts,
+			expr: expr.map(Box::new),
+			span: start.to(self.prev_span()),
+		}
+	}
+
+	/// The statements of a block up to its `}` (or the end of the source), and its value
+	fn stmts(&mut self) -> (Vec<Stmt>, Option<Expr>) {
+		let mut stmts = Vec::new();
+		let mut expr = None::<Expr>;
+		loop {
+			match self.peek() {
+				TokenKind::Punct(Punct::RBrace) | TokenKind::Eof => break,
+				TokenKind::Punct(Punct::Semi) => {
+					self.bump();
+					continue
+				},
+				_ => {},
+			}
+			// a block-like expression followed by more statements is a statement
+			if let Some(expr) = expr.take() {
+				stmts.push(Stmt {
+					span: expr.span,
+					kind: StmtKind::Expr(expr),
+				});
+			}
+			let from = self.head;
+			match self.stmt() {
+				Some(Parsed::Stmt(stmt)) => stmts.push(stmt),
+				Some(Parsed::Tail(tail)) => expr = Some(tail),
+				None => {
+					self.skip(from, &[Punct::Semi], STMTS);
+					self.eat(Punct::Semi);
+				},
+			}
+		}
+		(stmts, expr)
+	}
+
+	/// The end of a statement, a `;` or the `}` of the block (which isn't consumed)
+	fn stmt_end(&mut self) -> Option<()> {
+		match self.eat(Punct::Semi) || self.is(Punct::RBrace) || self.is_eof() {
+			true => Some(()),
+			false => self.expected("`;`"),
+		}
+	}
+
+	fn stmt(&mut self) -> Option<Parsed> {
+		let start = self.span();
+		let kind = match self.peek() {
+			TokenKind::Keyword(Keyword::Let) => {
+				self.bump();
+				let mutable = self.eat_keyword(Keyword::Mut);
+				let name = self.ident("a variable name")?;
+				let ty = match self.eat(Punct::Colon) {
+					true => Some(self.ty()?),
+					false => None,
+				};
+				if !self.eat(Punct::Eq) {
+					return self.expected(if ty.is_some() { "`=`" } else { "`:` or `=`" })
+				}
+				let value = self.expr()?;
+				self.expect(Punct::Semi)?;
+				StmtKind::Let {
+					name,
+					mutable,
+					ty,
+					value,
+				}
+			},
+			TokenKind::Keyword(Keyword::While) => {
+				self.bump();
+				let cond = self.restrict(true, Self::expr)?;
+				StmtKind::While(cond, self.block()?)
+			},
+			TokenKind::Keyword(Keyword::Return) => {
+				self.bump();
+				let value = match self.is(Punct::Semi) || self.is(Punct::RBrace) || self.is_eof() {
+					true => None,
+					false => Some(self.expr()?),
+				};
+				self.stmt_end()?;
+				StmtKind::Return(value)
+			},
+			TokenKind::Keyword(Keyword::Break) => {
+				self.bump();
+				self.stmt_end()?;
+				StmtKind::Break
+			},
+			TokenKind::Keyword(Keyword::Continue) => {
+				self.bump();
+				self.stmt_end()?;
+				StmtKind::Continue
+			},
+			_ => {
+				let expr = self.expr()?;
+				if self.is(Punct::RBrace) || self.is_eof() || expr.kind.is_block_like() && !self.is(Punct::Semi) {
+					return Some(Parsed::Tail(expr))
+				}
+				self.expect(Punct::Semi)?;
+				StmtKind::Expr(expr)
+			},
+		};
+		Some(Parsed::Stmt(Stmt {
+			kind,
+			span: start.to(self.prev_span()),
+		}))
+	}
+
+	pub fn expr(&mut self) -> Option<Expr> {
+		let place = self.binary(0)?;
+		let op = match self.peek() {
+			TokenKind::Punct(Punct::Eq) => None,
+			TokenKind::Punct(Punct::PlusEq) => Some(BinOp::Add),
+			TokenKind::Punct(Punct::MinusEq) => Some(BinOp::Sub),
+			TokenKind::Punct(Punct::StarEq) => Some(BinOp::Mul),
+			TokenKind::Punct(Punct::SlashEq) => Some(BinOp::Div),
+			TokenKind::Punct(Punct::PercentEq) => Some(BinOp::Rem),
+			TokenKind::Punct(Punct::AndEq) => Some(BinOp::And),
+			TokenKind::Punct(Punct::OrEq) => Some(BinOp::Or),
+			TokenKind::Punct(Punct::CaretEq) => Some(BinOp::Xor),
+			TokenKind::Punct(Punct::ShlEq) => Some(BinOp::Shl),
+			TokenKind::Punct(Punct::ShrEq) => Some(BinOp::Shr),
+			_ => return Some(place),
+		};
+		self.bump();
+		let value = self.expr()?;
+		Some(Expr {
+			span: place.span.to(value.span),
+			kind: ExprKind::Assign(op, Box::new(place), Box::new(value)),
+		})
+	}
+
+	/// The binary operator of the next token and its precedence (higher binds tighter)
+	fn binop(&self) -> Option<(BinOp, u8)> {
+		let TokenKind::Punct(punct) = self.peek() else { return None };
+		Some(match punct {
+			Punct::OrOr => (BinOp::LogicalOr, 1),
+			Punct::AndAnd => (BinOp::LogicalAnd, 2),
+			Punct::EqEq => (BinOp::Eq, 3),
+			Punct::Ne => (BinOp::Ne, 3),
+			Punct::Lt => (BinOp::Lt, 3),
+			Punct::Le => (BinOp::Le, 3),
+			Punct::Gt => (BinOp::Gt, 3),
+			Punct::Ge => (BinOp::Ge, 3),
+			Punct::Or => (BinOp::Or, 4),
+			Punct::Caret => (BinOp::Xor, 5),
+			Punct::And => (BinOp::And, 6),
+			Punct::Shl => (BinOp::Shl, 7),
+			Punct::Shr => (BinOp::Shr, 7),
+			Punct::Plus => (BinOp::Add, 8),
+			Punct::Minus => (BinOp::Sub, 8),
+			Punct::Star => (BinOp::Mul, 9),
+			Punct::Slash => (BinOp::Div, 9),
+			Punct::Percent => (BinOp::Rem, 9),
+			_ => return None,
+		})
+	}
+
+	/// Parses binary operators of at least precedence `min`, left-associatively
+	fn binary(&mut self, min: u8) -> Option<Expr> {
+		let mut lhs = self.cast()?;
+		let mut compared = false;
+		while let Some((op, precedence)) = self.binop().filter(|(_, precedence)| *precedence >= min) {
+			let span = self.bump().span;
+			if op.is_comparison() {
+				if compared {
+					self.error(span, SyntaxError::ChainedComparison);
+				}
+				compared = true;
+			}
+			let rhs = self.binary(precedence + 1)?;
+			lhs = Expr {
+				span: lhs.span.to(rhs.span),
+				kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+			};
+		}
+		Some(lhs)
+	}
+
+	/// A unary expression followed by any number of `as <type>`
+	fn cast(&mut self) -> Option<Expr> {
+		let mut expr = self.unary()?;
+		while self.eat_keyword(Keyword::As) {
+			let ty = self.ty()?;
+			expr = Expr {
+				span: expr.span.to(ty.span),
+				kind: ExprKind::Cast(Box::new(expr), ty),
+			};
+		}
+		Some(expr)
+	}
+
+	fn unary(&mut self) -> Option<Expr> {
+		let op = match self.peek() {
+			TokenKind::Punct(Punct::Minus) => UnOp::Neg,
+			TokenKind::Punct(Punct::Not) => UnOp::Not,
+			_ => return self.postfix(),
+		};
+		let start = self.bump().span;
+		let operand = self.unary()?;
+		Some(Expr {
+			span: start.to(operand.span),
+			kind: ExprKind::Unary(op, Box::new(operand)),
+		})
+	}
+
+	/// The elements of a list up to `close`, after its opening bracket
+	fn list(&mut self, close: Punct) -> Option<Vec<Expr>> {
+		self.restrict(false, |parser| {
+			let mut elems = Vec::new();
+			while !parser.is(close) && !parser.is_eof() {
+				let from = parser.head;
+				match parser.expr() {
+					Some(elem) => elems.push(elem),
+					None => parser.skip(from, &[Punct::Comma], &[]),
+				}
+				if !parser.eat(Punct::Comma) {
+					break
+				}
+			}
+			match parser.is(close) {
+				true => {
+					parser.bump();
+					Some(elems)
+				},
+				false => parser.expected(&format!("`,` or `{}`", close.as_str())),
+			}
+		})
+	}
+
+	/// A primary expression followed by calls, indexing and field accesses
+	fn postfix(&mut self) -> Option<Expr> {
+		let mut expr = self.primary()?;
+		loop {
+			let start = expr.span;
+			let kind = if self.eat(Punct::LParen) {
+				ExprKind::Call(Box::new(expr), self.list(Punct::RParen)?)
+			} else if self.eat(Punct::LBracket) {
+				let index = self.restrict(false, Self::expr)?;
+				self.expect(Punct::RBracket)?;
+				ExprKind::Index(Box::new(expr), Box::new(index))
+			} else if self.eat(Punct::Dot) {
+				let name = self.ident("a field or method name")?;
+				match self.eat(Punct::LParen) {
+					true => ExprKind::MethodCall(Box::new(expr), name, self.list(Punct::RParen)?),
+					false => ExprKind::Field(Box::new(expr), name),
+				}
+			} else {
+				return Some(expr)
+			};
+			expr = Expr {
+				span: start.to(self.prev_span()),
+				kind,
+			};
+		}
+	}
+
+	fn primary(&mut self) -> Option<Expr> {
+		let start = self.span();
+		let kind = match self.peek().clone() {
+			TokenKind::Int(value, suffix) => {
+				self.bump();
+				ExprKind::Int(value, suffix)
+			},
+			TokenKind::Float(value, suffix) => {
+				self.bump();
+				ExprKind::Float(value, suffix)
+			},
+			TokenKind::Str(str) => {
+				self.bump();
+				ExprKind::Str(str)
+			},
+			TokenKind::Keyword(Keyword::True) | TokenKind::Keyword(Keyword::False) => ExprKind::Bool(self.bump().kind == TokenKind::Keyword(Keyword::True)),
+			TokenKind::Keyword(Keyword::SelfValue) => {
+				self.bump();
+				ExprKind::SelfValue
+			},
+			TokenKind::Ident(_) => {
+				let path = self.path("a name")?;
+				match self.is(Punct::LBrace) && !self.no_struct {
+					true => ExprKind::StructLit(path, self.field_inits()?),
+					false => ExprKind::Path(path),
+				}
+			},
+			TokenKind::Punct(Punct::LParen) => {
+				self.bump();
+				let expr = self.restrict(false, Self::expr)?;
+				self.expect(Punct::RParen)?;
+				expr.kind
+			},
+			TokenKind::Punct(Punct::LBracket) => {
+				self.bump();
+				self.array()?
+			},
+			TokenKind::Punct(Punct::LBrace) => ExprKind::Block(self.block()?),
+			TokenKind::Keyword(Keyword::If) => return self.if_expr(),
+			_ => return self.expected("an expression"),
+		};
+		Some(Expr {
+			kind,
+			span: start.to(self.prev_span()),
+		})
+	}
+
+	/// An array literal after its `[`, either `[a, b, ...]` or `[value; len]`
+	fn array(&mut self) -> Option<ExprKind> {
+		if self.eat(Punct::RBracket) {
+			return Some(ExprKind::Array(Vec::new()))
+		}
+		let first = self.restrict(false, Self::expr)?;
+		if self.eat(Punct::Semi) {
+			let len = self.restrict(false, Self::expr)?;
+			self.expect(Punct::RBracket)?;
+			return Some(ExprKind::ArrayRepeat(Box::new(first), Box::new(len)))
+		}
+		let mut elems = vec![first];
+		if self.eat(Punct::Comma) {
+			elems.extend(self.list(Punct::RBracket)?);
+		} else {
+			self.expect(Punct::RBracket)?;
+		}
+		Some(ExprKind::Array(elems))
+	}
+
+	/// The fields of a struct literal, `{ name: value, ... }` where `name` alone is short for `name: name`
+	fn field_inits(&mut self) -> Option<Vec<FieldInit>> {
+		self.bump();
+		self.restrict(false, |parser| {
+			let mut fields = Vec::new();
+			while !parser.is(Punct::RBrace) && !parser.is_eof() {
+				let from = parser.head;
+				match parser.field_init() {
+					Some(field) => fields.push(field),
+					None => parser.skip(from, &[Punct::Comma], &[]),
+				}
+				if !parser.eat(Punct::Comma) {
+					break
+				}
+			}
+			match parser.is(Punct::RBrace) {
+				true => {
+					parser.bump();
+					Some(fields)
+				},
+				false => parser.expected("`,` or `}`"),
+			}
+		})
+	}
+
+	fn field_init(&mut self) -> Option<FieldInit> {
+		let name = self.ident("a field name")?;
+		let value = match self.eat(Punct::Colon) {
+			true => self.expr()?,
+			false => Expr {
+				kind: ExprKind::Path(Path {
+					segments: vec![name.clone()],
+					span: name.span,
+				}),
+				span: name.span,
+			},
+		};
+		Some(FieldInit {
+			span: name.span.to(value.span),
+			name,
+			value,
+		})
+	}
+
+	/// `if cond { ... } [else { ... } | else if ...]`
+	fn if_expr(&mut self) -> Option<Expr> {
+		let start = self.bump().span;
+		let cond = self.restrict(true, Self::expr)?;
+		let then = self.block()?;
+		let otherwise = match self.eat_keyword(Keyword::Else) {
+			true if self.is_keyword(Keyword::If) => Some(Box::new(self.if_expr()?)),
+			true => {
+				let block = self.block()?;
+				Some(Box::new(Expr {
+					span: block.span,
+					kind: ExprKind::Block(block),
+				}))
+			},
+			false => None,
+		};
+		Some(Expr {
+			kind: ExprKind::If(Box::new(cond), then, otherwise),
+			span: start.to(self.prev_span()),
+		})
+	}
+}
@@ -0,0 +1,241 @@
+use std::fmt::{Display, Formatter};
+use crate::compiler::span::Span;
+
+/// An integer type, `i8` to `i64` or `u8` to `u64`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IntTy {
+	pub bits: u8,
+	pub signed: bool,
+}
+
+impl IntTy {
+	pub const I64: IntTy = IntTy {
+		bits: 64,
+		signed: true,
+	};
+
+	/// Whether `value` (an integer extended to 128 bits) is a value of the type
+	pub fn contains(&self, value: i128) -> bool {
+		let bits = self.bits as u32;
+		match self.signed {
+			true => value >= -(1i128 << (bits - 1)) && value < 1i128 << (bits - 1),
+			false => value >= 0 && value < 1i128 << bits,
+		}
+	}
+}
+
+/// The type of an E# value
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Ty {
+	Int(IntTy),
+	F32,
+	F64,
+	/// `true` or `false`, an `i8` in the VM
+	Bool,
+	Str,
+	/// The type of expressions without a value
+	Void,
+	/// An object of the struct with the fully-qualified name
+	Struct(String),
+	/// An object of a struct implementing the trait with the fully-qualified name
+	Dyn(String),
+	Array(Box<Ty>),
+	/// The type of an expression that failed to check, which is compatible with every type so that
+	/// an error isn't reported again
+	Error,
+}
+
+impl Ty {
+	pub fn is_int(&self) -> bool {
+		matches!(self, Ty::Int(_))
+	}
+
+	pub fn is_float(&self) -> bool {
+		matches!(self, Ty::F32 | Ty::F64)
+	}
+
+	pub fn is_numeric(&self) -> bool {
+		self.is_int() || self.is_float()
+	}
+
+	/// The number of operand slots a value of the type takes in the VM (a `dyn` is an object and a vtable)
+	pub fn slots(&self) -> usize {
+		match self {
+			Ty::Void => 0,
+			Ty::Dyn(_) => 2,
+			_ => 1,
+		}
+	}
+
+	/// The primitive type named `name`, like `i32` or `str`
+	pub fn primitive(name: &str) -> Option<Ty> {
+		let int = |bits, signed| Some(Ty::Int(IntTy {
+			bits,
+			signed,
+		}));
+		match name {
+			"i8" => int(8, true),
+			"i16" => int(16, true),
+			"i32" => int(32, true),
+			"i64" => int(64, true),
+			"u8" => int(8, false),
+			"u16" => int(16, false),
+			"u32" => int(32, false),
+			"u64" => int(64, false),
+			"f32" => Some(Ty::F32),
+			"f64" => Some(Ty::F64),
+			"bool" => Some(Ty::Bool),
+			"str" => Some(Ty::Str),
+			_ => None,
+		}
+	}
+}
+
+impl Display for Ty {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Ty::Int(int) => write!(f, "{}{}", if int.signed { "i" } else { "u" }, int.bits),
+			Ty::F32 => f.write_str("f32"),
+			Ty::F64 => f.write_str("f64"),
+			Ty::Bool => f.write_str("bool"),
+			Ty::Str => f.write_str("str"),
+			Ty::Void => f.write_str("void"),
+			Ty::Struct(name) => f.write_str(name),
+			Ty::Dyn(name) => write!(f, "dyn {}", name),
+			Ty::Array(elem) => write!(f, "[{}]", elem),
+			Ty::Error => f.write_str("{error}"),
+		}
+	}
+}
+
+/// The checked items of a set of source files
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+	pub structs: Vec<StructDef>,
+	pub traits: Vec<TraitDef>,
+	pub functions: Vec<FnDef>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructDef {
+	/// The fully-qualified name, the `class-id` of the struct
+	pub name: String,
+	pub parent: Option<String>,
+	/// The fields declared by the struct (not the inherited ones)
+	pub fields: Vec<FieldDef>,
+	/// The methods of the impls of the struct
+	pub methods: Vec<FnDef>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDef {
+	pub name: String,
+	pub ty: Ty,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraitDef {
+	pub name: String,
+	pub methods: Vec<FnSig>,
+}
+
+/// The signature of a function, where the receiver of a method is its first parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct FnSig {
+	/// The fully-qualified name, the `fn-id` of the function
+	pub name: String,
+	pub params: Vec<Ty>,
+	pub ret: Ty,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FnDef {
+	pub sig: FnSig,
+	/// The parameters followed by the variables of the function, indexed by [`LocalId`]
+	pub locals: Vec<Local>,
+	pub body: Block,
+	pub span: Span,
+}
+
+/// The index of a variable in [`FnDef::locals`]
+pub type LocalId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Local {
+	pub name: String,
+	pub ty: Ty,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+	pub stmts: Vec<Stmt>,
+	pub expr: Option<Box<Expr>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+	Let(LocalId, Expr),
+	Expr(Expr),
+	Return(Option<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+	pub kind: ExprKind,
+	pub ty: Ty,
+	pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArithOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+	/// An integer of the type of the expression, as its two's complement bits
+	Int(u64),
+	Float(f64),
+	Str(String),
+	Bool(bool),
+	Local(LocalId),
+	Neg(Box<Expr>),
+	Arith(ArithOp, Box<Expr>, Box<Expr>),
+	/// A conversion to the type of the expression
+	Cast(Box<Expr>),
+	/// A call of the function (or intrinsic) with the `fn-id`
+	Call(String, Vec<Expr>),
+	/// A call of a method through the vtable of the struct with the `class-id`, the receiver
+	/// is the first argument
+	CallVirt(String, String, Vec<Expr>),
+	/// A call of a method of the trait with the `class-id`, the `dyn` receiver is the first argument
+	CallTrait(String, String, Vec<Expr>),
+	/// A struct literal of the struct with the `class-id`, with every field (inherited ones included)
+	New(String, Vec<(String, Expr)>),
+	/// A field of an object of the struct with the `class-id`
+	Field(Box<Expr>, String, String),
+	/// An object converted to a `dyn` of the trait with the `class-id`
+	ToDyn(Box<Expr>, String),
+	Index(Box<Expr>, Box<Expr>),
+	/// The length of an array
+	Len(Box<Expr>),
+	/// A zero-initialized array of the type of the expression, with a length
+	NewArray(Box<Expr>),
+	/// An array of the elements
+	Array(Vec<Expr>),
+	/// An assignment, or a compound assignment (`place += value`) with its operator
+	Assign(Place, Option<ArithOp>, Box<Expr>),
+	Block(Block),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Place {
+	Local(LocalId),
+	/// A field of an object, of the struct with the `class-id`
+	Field(Box<Expr>, String, String),
+	/// An element of an array, and its index
+	Index(Box<Expr>, Box<Expr>),
+}
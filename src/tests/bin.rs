@@ -0,0 +1,418 @@
+use std::fs::{self, File};
+use serde_json::{json, Value};
+use crate::vm::bin::{inspect, link, DynamicLibrary, Executable};
+use crate::vm::bin::buffer::Bytes;
+use crate::vm::bin::builder::{Class, Constant, DebugInfo, ExecutableBuilder, Field, Function, Symbol};
+use crate::vm::bin::def::debug::{LineEntry, LocalEntry, SourceLocation};
+use crate::vm::bin::def::symbol::SymbolKind;
+use crate::vm::bin::header::{self, Feature, Features, Version};
+use crate::vm::error::jit::ExecutableFormatError;
+use crate::vm::bin::link::Linker;
+use crate::vm::error::link::LinkError;
+use crate::vm::error::verify::VerifyError;
+use crate::vm::error::vm::VmError;
+use crate::vm::insn::Insn;
+use crate::vm::types::{TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::str::Str;
+use crate::vm::{Engine, Vm};
+
+const I64: TypeDesc = TypeDesc::new(TypeFlags::I64, None);
+const F64: TypeDesc = TypeDesc::new(TypeFlags::F64, None);
+const STR: TypeDesc = TypeDesc::new(TypeFlags::STR, None);
+
+/// Encodes the bytecode of `insns`
+fn code(insns: &[Insn]) -> Vec<u8> {
+	let mut code = Vec::new();
+	for insn in insns {
+		insn.encode(&mut code);
+	}
+	code
+}
+
+fn function(builder: &mut ExecutableBuilder, name: &str, args: Vec<TypeDesc>, ret: TypeDesc, insns: &[Insn]) -> Function {
+	Function {
+		name: builder.name(name),
+		ret,
+		args,
+		code: code(insns),
+	}
+}
+
+/// Adds a function `name() -> str` returning `value`
+fn str_function(builder: &mut ExecutableBuilder, name: &str, value: &str) {
+	let value = builder.constant(Constant {
+		ty: STR,
+		data: value.as_bytes().to_vec(),
+	});
+	let function = function(builder, name, vec![], STR, &[Insn::Ldc(value), Insn::VRet(STR)]);
+	builder.functions.push(function);
+}
+
+/// A class `shapes.Square` with an `i64` field `side` and a method `shapes.Square.area`, and
+/// functions `scaled(f64, shapes.Square) -> f64` and `greeting` returning `"hi"`
+fn shapes() -> ExecutableBuilder {
+	let mut builder = ExecutableBuilder::new();
+	let (square, side) = (builder.name("shapes.Square"), builder.name("side"));
+	let object = TypeDesc::new(TypeFlags::OBJECT, Some(square));
+	let area = function(&mut builder, "shapes.Square.area", vec![object], I64, &[
+		Insn::Push(object, 0),
+		Insn::GetField(square, side),
+		Insn::Push(object, 0),
+		Insn::GetField(square, side),
+		Insn::Mul(I64),
+		Insn::VRet(I64),
+	]);
+	builder.classes.push(Class {
+		name: square,
+		super_name: square,
+		fields: vec![Field {
+			name: side,
+			ty: I64,
+		}],
+		methods: vec![area],
+	});
+	let factor = builder.constant(Constant::primitive(F64, (-1.5f64).to_bits()));
+	let scaled = function(&mut builder, "scaled", vec![F64, object], F64, &[Insn::Ldc(factor), Insn::VRet(F64)]);
+	builder.functions.push(scaled);
+	str_function(&mut builder, "greeting", "hi");
+	builder
+}
+
+/// Functions calling `shapes.Square.area` of a square defined by [`shapes`]
+fn areas() -> ExecutableBuilder {
+	let mut builder = ExecutableBuilder::new();
+	let (square, side, area) = (builder.name("shapes.Square"), builder.name("side"), builder.name("shapes.Square.area"));
+	let object = TypeDesc::new(TypeFlags::OBJECT, Some(square));
+	let area_of = function(&mut builder, "area_of", vec![I64], I64, &[
+		Insn::New(square),
+		Insn::Pop,
+		Insn::Push(object, 1),
+		Insn::Push(I64, 0),
+		Insn::SetField(square, side),
+		Insn::Push(object, 1),
+		Insn::CallVirt(square, area),
+		Insn::VRet(I64),
+	]);
+	let (area_of_name, two) = (area_of.name, builder.constant(Constant::primitive(I64, 2)));
+	let doubled_area = function(&mut builder, "doubled_area", vec![I64], I64, &[
+		Insn::Push(I64, 0),
+		Insn::Call(area_of_name),
+		Insn::Ldc(two),
+		Insn::Mul(I64),
+		Insn::VRet(I64),
+	]);
+	builder.functions.extend([area_of, doubled_area]);
+	str_function(&mut builder, "farewell", "hi");
+	builder
+}
+
+/// An executable with a function `name() -> i64` returning `value`
+fn constant_function(name: &str, value: u64) -> Executable {
+	let mut builder = ExecutableBuilder::new();
+	let value = builder.constant(Constant::primitive(I64, value));
+	let function = function(&mut builder, name, vec![], I64, &[Insn::Ldc(value), Insn::VRet(I64)]);
+	builder.functions.push(function);
+	builder.finish()
+}
+
+#[test]
+fn inspect_json() {
+	let exec = Executable::from(File::open("test.esbin").unwrap());
+	let json = serde_json::from_str::<Value>(&inspect::to_json(&exec)).unwrap();
+	assert_eq!(json["version"], "0.9");
+	assert_eq!(json["features"], 0);
+	assert_eq!(json["offsets"], json!({
+		"constant_table": exec.offsets().constant_table(),
+		"class_table": exec.offsets().class_table(),
+		"function_table": exec.offsets().function_table(),
+		"field_table": exec.offsets().field_table(),
+		"export_table": 0,
+		"import_table": 0,
+	}));
+	// constants are decoded by their type, names are byte arrays
+	assert_eq!(json["constants"], json!([
+		{ "index": 0, "type": "[i8]", "value": "#bar" },
+		{ "index": 1, "type": "u32", "value": 3 },
+		{ "index": 2, "type": "[i8]", "value": "Sylv" },
+	]));
+	assert_eq!(json["classes"], json!([]));
+	assert_eq!(json["functions"], json!([
+		{ "name": "#bar", "args": ["u32", "u32"], "ret": "u32", "code_size": 10 },
+	]));
+	assert_eq!(json["fields"], json!([]));
+	assert_eq!(json["exports"], json!([]));
+	assert_eq!(json["imports"], json!([]));
+	assert_eq!(json["debug_info"], json!([]));
+}
+
+#[test]
+fn link_executables() {
+	let linked = link::link(&[shapes().finish(), areas().finish()]).unwrap().build();
+	let exec = Executable::parse(&linked).unwrap();
+
+	// equal constants are merged
+	let constants = exec.constant_table().constants();
+	assert_eq!(constants.iter().filter(|constant| constant.str() == Some("hi")).count(), 1);
+	assert_eq!(constants.iter().filter(|constant| constant.str() == Some("shapes.Square")).count(), 1);
+
+	let mut vm = Vm::new();
+	vm.load(exec).unwrap();
+	assert_eq!(vm.call("doubled_area", &[3]).unwrap(), 18);
+	let greeting = vm.call("greeting", &[]).unwrap() as *const Str;
+	let farewell = vm.call("farewell", &[]).unwrap() as *const Str;
+	unsafe {
+		assert_eq!(((*greeting).as_str(), (*farewell).as_str()), ("hi", "hi"));
+	}
+}
+
+#[test]
+fn link_conflicts() {
+	let err = link::link(&[shapes().finish(), shapes().finish()]).unwrap_err();
+	assert!(matches!(&err, LinkError::DuplicateClass(class) if class == "shapes.Square"), "{}", err);
+	let err = link::link(&[areas().finish(), constant_function("area_of", 0)]).unwrap_err();
+	assert!(matches!(&err, LinkError::DuplicateFunction(function) if function == "area_of"), "{}", err);
+	// methods share the namespace of functions
+	let err = link::link(&[shapes().finish(), constant_function("shapes.Square.area", 0)]).unwrap_err();
+	assert!(matches!(&err, LinkError::DuplicateFunction(function) if function == "shapes.Square.area"), "{}", err);
+
+	// a failed executable adds nothing, so linking can go on
+	let mut linker = Linker::new();
+	linker.link(&shapes().finish()).unwrap();
+	let mut conflicting = ExecutableBuilder::from(&constant_function("extra", 1));
+	let square = conflicting.name("shapes.Square");
+	conflicting.classes.push(Class {
+		name: square,
+		super_name: square,
+		fields: vec![],
+		methods: vec![],
+	});
+	assert!(linker.link(&conflicting.finish()).is_err());
+	linker.link(&areas().finish()).unwrap();
+	let exec = linker.finish().finish();
+	assert!(exec.function_table().functions().iter().all(|function| exec.constant_table().str(function.name()) != Some("extra")));
+	assert!(exec.constant_table().constants().iter().all(|constant| constant.str() != Some("extra")));
+	let mut vm = Vm::new();
+	vm.load(exec).unwrap();
+	assert_eq!(vm.call("area_of", &[4]).unwrap(), 16);
+}
+
+/// Adds an export (or an import) of the symbol of `kind` named `name`
+fn symbol(builder: &mut ExecutableBuilder, kind: SymbolKind, name: &str, export: bool) {
+	let symbol = Symbol {
+		kind,
+		name: builder.name(name),
+	};
+	if export {
+		builder.exports.push(symbol);
+	} else {
+		builder.imports.push(symbol);
+	}
+}
+
+/// A library exporting a function `square` and a class `shapes.Point`, but not a function `helper`
+fn library() -> DynamicLibrary {
+	let mut builder = ExecutableBuilder::new();
+	symbol(&mut builder, SymbolKind::Function, "square", true);
+	symbol(&mut builder, SymbolKind::Class, "shapes.Point", true);
+	let (point, x) = (builder.name("shapes.Point"), builder.name("x"));
+	builder.classes.push(Class {
+		name: point,
+		super_name: point,
+		fields: vec![Field {
+			name: x,
+			ty: I64,
+		}],
+		methods: vec![],
+	});
+	let square = function(&mut builder, "square", vec![I64], I64, &[Insn::Push(I64, 0), Insn::Push(I64, 0), Insn::Mul(I64), Insn::VRet(I64)]);
+	let one = builder.constant(Constant::primitive(I64, 1));
+	let helper = function(&mut builder, "helper", vec![], I64, &[Insn::Ldc(one), Insn::VRet(I64)]);
+	builder.functions.extend([square, helper]);
+	DynamicLibrary::parse(&builder.build()).unwrap()
+}
+
+/// An executable importing the function `callee`, with a function `name(i64) -> i64` calling it
+/// (and multiplying the result with the argument if `multiply` is set)
+fn importer(name: &str, callee: &str, multiply: bool) -> ExecutableBuilder {
+	let mut builder = ExecutableBuilder::new();
+	symbol(&mut builder, SymbolKind::Function, callee, false);
+	let callee = builder.name(callee);
+	let mut insns = vec![Insn::Push(I64, 0), Insn::Call(callee)];
+	if multiply {
+		insns.extend([Insn::Push(I64, 0), Insn::Mul(I64)]);
+	}
+	insns.push(Insn::VRet(I64));
+	let function = function(&mut builder, name, vec![I64], I64, &insns);
+	builder.functions.push(function);
+	builder
+}
+
+#[test]
+fn load_libraries() {
+	let mut app = importer("main", "square", false);
+	symbol(&mut app, SymbolKind::Class, "shapes.Point", false);
+	let mut vm = Vm::new();
+	let err = vm.load(app.finish()).unwrap_err();
+	assert!(matches!(&err, VmError::UnresolvedImport(name) if name == "square"), "{}", err);
+
+	vm.load_library(library()).unwrap();
+	vm.load(app.finish()).unwrap();
+	assert_eq!(vm.call("main", &[5]).unwrap(), 25);
+	assert!(vm.instantiate("shapes.Point").is_some());
+
+	// functions that aren't exported can't be imported or called
+	let err = vm.load(importer("imports_helper", "helper", false).finish()).unwrap_err();
+	assert!(matches!(&err, VmError::UnresolvedImport(name) if name == "helper"), "{}", err);
+	let mut sneaky = importer("sneaky", "helper", false);
+	sneaky.imports.clear();
+	let err = vm.load(sneaky.finish()).unwrap_err();
+	assert!(matches!(&err, VmError::Verify(_, VerifyError::UnknownFunction(_, name)) if name == "helper"), "{}", err);
+}
+
+#[test]
+fn library_errors() {
+	// an executable without exports isn't a library
+	assert!(DynamicLibrary::parse(&shapes().build()).is_err());
+
+	let mut vm = Vm::new();
+	let mut missing = ExecutableBuilder::from(&constant_function("present", 0));
+	symbol(&mut missing, SymbolKind::Function, "missing", true);
+	let err = vm.load_library(DynamicLibrary::parse(&missing.build()).unwrap()).unwrap_err();
+	assert!(matches!(&err, VmError::UnresolvedExport(name) if name == "missing"), "{}", err);
+	let mut missing = ExecutableBuilder::new();
+	symbol(&mut missing, SymbolKind::Class, "shapes.Missing", true);
+	let err = vm.load_library(DynamicLibrary::parse(&missing.build()).unwrap()).unwrap_err();
+	assert!(matches!(&err, VmError::UnresolvedExport(name) if name == "shapes.Missing"), "{}", err);
+
+	// libraries can import from the libraries loaded before them
+	let mut cube = importer("cube", "square", true);
+	symbol(&mut cube, SymbolKind::Function, "cube", true);
+	let err = vm.load_library(DynamicLibrary::parse(&cube.build()).unwrap()).unwrap_err();
+	assert!(matches!(&err, VmError::UnresolvedImport(name) if name == "square"), "{}", err);
+	vm.load_library(library()).unwrap();
+	vm.load_library(DynamicLibrary::parse(&cube.build()).unwrap()).unwrap();
+	assert_eq!(vm.call("cube", &[3]).unwrap(), 27);
+}
+
+/// The message of the error of parsing `bytes`
+fn parse_error(bytes: &[u8]) -> String {
+	Executable::parse(bytes).unwrap_err().to_string()
+}
+
+#[test]
+fn version_and_features() {
+	let mut bytes = shapes().build();
+	let exec = Executable::parse(&bytes).unwrap();
+	assert_eq!(exec.version(), Version::CURRENT);
+	assert_eq!(exec.features(), 0);
+	assert_eq!(u16::from_be_bytes([bytes[28], bytes[29]]), Version::CURRENT.to_bits());
+	assert_eq!(library().executable().features(), Features::LIBRARY);
+
+	// executables that predate the version field are still loaded
+	bytes[28..30].copy_from_slice(&0u16.to_be_bytes());
+	assert_eq!(Executable::parse(&bytes).unwrap().version(), Version::UNVERSIONED);
+	// as are older revisions, but not newer ones
+	bytes[28..30].copy_from_slice(&Version::new(0, 9).to_bits().to_be_bytes());
+	assert_eq!(Executable::parse(&bytes).unwrap().version(), Version::new(0, 9));
+	for version in [Version::new(0, 11), Version::new(1, 0)] {
+		bytes[28..30].copy_from_slice(&version.to_bits().to_be_bytes());
+		assert_eq!(parse_error(&bytes), ExecutableFormatError::UnsupportedVersion(version).to_string());
+	}
+
+	bytes[28..30].copy_from_slice(&Version::CURRENT.to_bits().to_be_bytes());
+	bytes[30..32].copy_from_slice(&(Features::LIBRARY | 0x0100).to_be_bytes());
+	assert_eq!(parse_error(&bytes), ExecutableFormatError::UnsupportedFeatures(0x0100).to_string());
+}
+
+#[test]
+fn checksum_mismatch() {
+	let mut builder = shapes();
+	builder.checksum = true;
+	let bytes = builder.build();
+	let exec = Executable::parse(&bytes).unwrap();
+	assert_eq!(exec.features() & Features::CHECKSUM, Features::CHECKSUM);
+	let expected = u32::from_be_bytes(bytes[32..36].try_into().unwrap());
+	assert_eq!(header::checksum(&bytes), expected);
+
+	// a flipped bit anywhere in the body is reported before any table is parsed
+	for offset in [40, bytes.len() / 2, bytes.len() - 1] {
+		let mut corrupt = bytes.clone();
+		corrupt[offset] ^= 0x10;
+		let found = header::checksum(&corrupt);
+		assert_ne!(found, expected);
+		assert_eq!(parse_error(&corrupt), ExecutableFormatError::ChecksumMismatch(expected, found).to_string(), "{}", offset);
+	}
+	// as is a corrupt checksum
+	let mut corrupt = bytes.clone();
+	corrupt[35] ^= 0x01;
+	assert_eq!(parse_error(&corrupt), ExecutableFormatError::ChecksumMismatch(expected ^ 0x01, expected).to_string());
+}
+
+#[test]
+fn debug_info() {
+	let mut builder = shapes();
+	let file = builder.name("shapes.es");
+	let (area, scaled, factor) = (builder.name("shapes.Square.area"), builder.name("scaled"), builder.name("factor"));
+	builder.debug_info.push(DebugInfo {
+		function: area,
+		file,
+		lines: vec![LineEntry { offset: 0, line: 10 }, LineEntry { offset: 12, line: 11 }],
+		locals: Vec::new(),
+	});
+	builder.debug_info.push(DebugInfo {
+		function: scaled,
+		file,
+		lines: vec![LineEntry { offset: 0, line: 20 }],
+		locals: vec![LocalEntry { local: 0, name: factor }],
+	});
+	let bytes = builder.build();
+
+	// linking renumbers the constants of the debug info, which must still resolve
+	let linked = link::link(&[areas().finish(), Executable::parse(&bytes).unwrap()]).unwrap().build();
+	for exec in [Executable::parse(&bytes).unwrap(), Executable::parse(&linked).unwrap()] {
+		assert_eq!(exec.features() & Features::DEBUG_INFO, Features::DEBUG_INFO);
+		let location = |function, offset| exec.source_location(function, offset);
+		assert_eq!(location("shapes.Square.area", 0), Some(SourceLocation { file: "shapes.es", line: 10 }));
+		assert_eq!(location("shapes.Square.area", 11), Some(SourceLocation { file: "shapes.es", line: 10 }));
+		assert_eq!(location("shapes.Square.area", 20), Some(SourceLocation { file: "shapes.es", line: 11 }));
+		assert_eq!(location("scaled", 4), Some(SourceLocation { file: "shapes.es", line: 20 }));
+		assert_eq!(location("greeting", 0), None);
+		assert_eq!(exec.local_name("scaled", 0), Some("factor"));
+		assert_eq!(exec.local_name("scaled", 1), None);
+		assert_eq!(exec.local_name("shapes.Square.area", 0), None);
+
+		// the VM doesn't need debug info to run the code
+		let mut vm = Vm::new();
+		vm.load(exec).unwrap();
+		assert!(vm.functions().get("scaled").is_some());
+	}
+}
+
+#[test]
+fn map_executables() {
+	let path = std::env::temp_dir().join(format!("esharp-map-{}.esbin", std::process::id()));
+	let bytes = link::link(&[shapes().finish(), areas().finish()]).unwrap().build();
+	fs::write(&path, &bytes).unwrap();
+
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let map = unsafe { Bytes::map(&File::open(&path).unwrap()) }.unwrap();
+		assert!(map.is_mapped());
+		let exec = Executable::parse_shared(map.clone()).unwrap();
+		assert!(exec.bytes().is_mapped());
+
+		// code and strings point into the mapping rather than being copied
+		let within = |ptr: *const u8| map.as_ptr_range().contains(&ptr);
+		assert!(exec.function_table().functions().iter().all(|function| within(function.code().as_ptr())));
+		assert!(exec.constant_table().constants().iter().filter_map(|constant| constant.str()).all(|str| within(str.as_ptr())));
+
+		let mut vm = Vm::with_engine(engine);
+		vm.load(exec).unwrap();
+		assert_eq!(vm.call("doubled_area", &[5]).unwrap(), 50, "{:?}", engine);
+	}
+
+	// a truncated file is rejected like a truncated buffer
+	fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+	let map = unsafe { Bytes::map(&File::open(&path).unwrap()) }.unwrap();
+	assert!(Executable::parse_shared(map).is_err());
+	fs::remove_file(&path).unwrap();
+}
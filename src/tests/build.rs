@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::compiler::build;
+use crate::compiler::error::build::BuildError;
+use crate::vm::Vm;
+
+/// A fresh directory for the sources and cache of a test
+fn workspace(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("esharp-{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+fn write(root: &Path, path: &str, src: &str) {
+	let path = root.join(path);
+	fs::create_dir_all(path.parent().unwrap()).unwrap();
+	fs::write(path, src).unwrap();
+}
+
+fn run(build: &build::Build, function: &str, args: &[u64]) -> u64 {
+	let mut vm = Vm::new();
+	vm.load(build.builder.finish()).unwrap();
+	vm.call(function, args).unwrap()
+}
+
+#[test]
+fn incremental_build() {
+	let root = workspace("incremental");
+	let cache = root.join(".esharp");
+	write(&root, "geo/shapes.es", "
+struct Square {
+	side: i64,
+}
+
+fn area(square: Square) -> i64 {
+	square.side * square.side
+}
+");
+	write(&root, "geo/util.es", "
+fn double(x: i64) -> i64 {
+	x * 2
+}
+");
+	write(&root, "app.es", "
+use geo.shapes.Square;
+
+fn main(side: i64) -> i64 {
+	geo.shapes.area(Square { side }) + geo.util.double(side)
+}
+");
+
+	let first = build::build(&root, &cache, false).unwrap();
+	assert_eq!(first.compiled, ["app", "geo.shapes", "geo.util"]);
+	assert_eq!(run(&first, "app.main", &[3]), 15);
+
+	let unchanged = build::build(&root, &cache, false).unwrap();
+	assert!(unchanged.compiled.is_empty());
+	assert_eq!(run(&unchanged, "app.main", &[3]), 15);
+
+	// only the changed package and the packages that depend on it are compiled again
+	write(&root, "geo/shapes.es", "
+struct Square {
+	side: i64,
+}
+
+fn area(square: Square) -> i64 {
+	square.side * 4
+}
+");
+	let changed = build::build(&root, &cache, false).unwrap();
+	assert_eq!(changed.compiled, ["app", "geo.shapes"]);
+	assert_eq!(changed.cached, ["geo.util"]);
+	assert_eq!(run(&changed, "app.main", &[3]), 18);
+	assert_eq!(fs::read_dir(&cache).unwrap().count(), 3);
+
+	write(&root, "geo/util.es", "package geo.utils;");
+	let err = build::build(&root, &cache, false).unwrap_err();
+	assert!(matches!(&err, BuildError::PackageMismatch(_, declared, expected) if declared == "geo.utils" && expected == "geo.util"), "{}", err);
+	write(&root, "geo/util.es", "fn double(x: i64) -> i64 { x * 2.0 }");
+	let err = build::build(&root, &cache, false).unwrap_err();
+	assert!(matches!(&err, BuildError::Diagnostics(diagnostics) if diagnostics.len() == 1 && diagnostics[0].ends_with("util.es:1:32: mismatched types: expected `i64`, found `f64`")), "{}", err);
+
+	fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,153 @@
+use crate::compiler::check;
+use crate::compiler::syntax;
+use crate::compiler::syntax::ast;
+use crate::compiler::typed::{ExprKind, Stmt, Ty};
+
+fn parse(sources: &[&str]) -> Vec<ast::File> {
+	sources.iter().map(|src| {
+		let (file, diagnostics) = syntax::parse(src);
+		assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+		file
+	}).collect()
+}
+
+/// The formatted errors of checking the sources, named `0.es`, `1.es`, ...
+fn errors(sources: &[&str]) -> Vec<String> {
+	let diagnostics = check::check(&parse(sources)).expect_err("expected errors");
+	diagnostics.iter().map(|(file, diagnostic)| diagnostic.format(&format!("{}.es", file), sources[*file])).collect()
+}
+
+const SHAPES: &str = "
+package shapes;
+
+trait Shape {
+	fn area(self) -> f64;
+}
+
+struct Square {
+	side: f64,
+}
+
+struct Rect: Square {
+	height: f64,
+}
+
+impl Shape for Square {
+	fn area(self) -> f64 {
+		self.side * self.side
+	}
+}
+
+impl Rect {
+	fn area(self) -> f64 {
+		self.side * self.height
+	}
+}
+";
+
+const MAIN: &str = "
+use shapes.Rect;
+
+fn area(shape: dyn shapes.Shape) -> f64 {
+	shape.area()
+}
+
+fn main() -> f64 {
+	let rect = Rect { side: 2.0, height: 3.0 };
+	let mut total = area(rect) + 2 * rect.area();
+	total -= -1.5;
+	let mut sides = [0.0; 2];
+	sides = [0.0, rect.height];
+	return total / sides.len() as f64;
+}
+";
+
+#[test]
+fn check_packages() {
+	let program = check::check(&parse(&[SHAPES, MAIN])).unwrap();
+	assert_eq!(program.structs.iter().map(|def| def.name.as_str()).collect::<Vec<_>>(), ["shapes.Square", "shapes.Rect"]);
+	assert_eq!(program.structs[1].parent.as_deref(), Some("shapes.Square"));
+	assert_eq!(program.traits[0].methods[0].params, [Ty::Dyn(String::from("shapes.Shape"))]);
+
+	let main = program.functions.iter().find(|def| def.sig.name == "main").unwrap();
+	assert_eq!(main.locals.iter().map(|local| local.ty.to_string()).collect::<Vec<_>>(), ["shapes.Rect", "f64", "[f64]"]);
+	let Stmt::Let(_, total) = &main.body.stmts[1] else { panic!("expected a `let`") };
+	let ExprKind::Arith(_, call, mul) = &total.kind else { panic!("expected `+`") };
+	assert!(matches!(&call.kind, ExprKind::Call(name, args) if name == "area" && matches!(args[0].kind, ExprKind::ToDyn(..))));
+	assert!(matches!(&mul.kind, ExprKind::Arith(_, two, method)
+		if matches!(two.kind, ExprKind::Float(value) if value == 2.0)
+		&& matches!(&method.kind, ExprKind::CallVirt(class, name, _) if class == "shapes.Rect" && name == "shapes.Rect.area")));
+
+	let area = program.functions.iter().find(|def| def.sig.name == "area").unwrap();
+	assert!(matches!(&area.body.expr.as_ref().unwrap().kind, ExprKind::CallTrait(name, method, _) if name == "shapes.Shape" && method == "shapes.Shape.area"));
+}
+
+#[test]
+fn report_errors() {
+	let errors = errors(&[SHAPES, "
+use shapes.Square;
+
+struct Circle {
+	radius: f32,
+}
+
+fn f(x: i32, y: i64) -> i32 {
+	let z = x + y;
+	let w = 300u8;
+	x = 1;
+	let c = Circle { radius: 1.0, side: 2.0 };
+	let s: dyn shapes.Shape = c;
+	if x > 1 { 1 } else { 2 };
+	Square { side: 1.0 }.perimeter()
+}
+
+fn g() -> str {
+	\"a\" + 1
+}
+
+fn h() -> i64 {
+	let x = 1;
+}
+"]);
+	assert_eq!(errors, [
+		"1.es:9:14: mismatched types: expected `i32`, found `i64`",
+		"1.es:10:10: literal out of range for `u8`",
+		"1.es:11:2: cannot assign to immutable variable `x`",
+		"1.es:12:32: struct `Circle` has no field `side`",
+		"1.es:13:28: `Circle` does not implement trait `shapes.Shape`",
+		"1.es:14:2: `if` expressions are not supported, the VM has no instructions for them",
+		"1.es:14:5: comparisons other than `==` of strings are not supported, the VM has no instructions for them",
+		"1.es:15:23: `shapes.Square` has no method `perimeter`",
+		"1.es:19:8: mismatched types: expected `str`, found `i64`",
+		"1.es:22:4: missing return value of type `i64`",
+	]);
+}
+
+#[test]
+fn report_too_many_params() {
+	let errors = errors(&[SHAPES, "
+use shapes.Shape;
+
+fn six(a: dyn Shape, b: dyn Shape, c: i64, d: i64) -> i64 {
+	c + d
+}
+
+fn seven(a: i64, b: i64, c: i64, d: i64, e: i64, f: i64, g: i64) -> i64 {
+	a
+}
+
+struct Point {
+	x: i64,
+}
+
+impl Point {
+	fn scale(self, a: dyn Shape, b: dyn Shape, c: i64, d: i64) -> i64 {
+		c
+	}
+}
+"]);
+	assert_eq!(errors, [
+		"1.es:8:4: the parameters of `seven` take 7 argument slots, but the VM supports at most 6 (`dyn` parameters take 2)",
+		"1.es:17:5: the parameters of `Point.scale` take 7 argument slots, but the VM supports at most 6 (`dyn` parameters take 2)",
+	]);
+}
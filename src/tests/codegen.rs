@@ -0,0 +1,101 @@
+use crate::compiler::{check, codegen, syntax};
+use crate::vm::bin::Executable;
+use crate::vm::{Engine, Vm};
+
+const SHAPES: &str = "
+package shapes;
+
+trait Shape {
+	fn area(self) -> i64;
+}
+
+struct Square {
+	side: i64,
+}
+
+struct Rect: Square {
+	height: i64,
+}
+
+impl Shape for Square {
+	fn area(self) -> i64 {
+		self.side * self.side
+	}
+}
+
+impl Rect {
+	fn area(self) -> i64 {
+		self.side * self.height
+	}
+}
+";
+
+const MAIN: &str = "
+use shapes.Rect;
+use shapes.Square;
+
+fn area(shape: dyn shapes.Shape) -> i64 {
+	shape.area()
+}
+
+fn shapes(side: i64) -> i64 {
+	let square = Square { side };
+	let rect = Rect { side, height: 10 };
+	let mut total = area(square) + area(rect);
+	total -= rect.area() / 2;
+	total
+}
+
+fn fields(n: i32) -> i64 {
+	let mut a: i32 = 1;
+	let b = a;
+	a = a + 1;
+	let rect = Rect { side: 3, height: 4 };
+	rect.height += 10;
+	rect.side = rect.height * -2;
+	(n + a * 10 + b * 100) as i64 + rect.side + { let c = 7; c }
+}
+
+fn arrays(n: i64) -> f64 {
+	let mut xs = [1.5, 2.0, n as f64];
+	xs[1] *= 4.0;
+	let zeros = [0u8; 3];
+	let rects: [Rect] = [];
+	xs[0] + xs[1] + xs[2] + (zeros.len() + rects.len()) as f64
+}
+
+fn strings() -> i64 {
+	let s = \"foo\" + \"bar\";
+	if_eq(s == \"foobar\") + s.len() + s.byte(1) as i64
+}
+
+fn if_eq(eq: bool) -> i64 {
+	return eq as i64 * 1000;
+	2
+}
+";
+
+fn compile(sources: &[&str]) -> Executable {
+	let files = sources.iter().map(|src| {
+		let (file, diagnostics) = syntax::parse(src);
+		assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+		file
+	}).collect::<Vec<_>>();
+	let program = check::check(&files).unwrap_or_else(|diagnostics| panic!("{:?}", diagnostics));
+	codegen::generate(&program).unwrap().finish()
+}
+
+#[test]
+fn compile_and_run() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(compile(&[SHAPES, MAIN])).unwrap();
+
+		// 5 * 5 + 5 * 10 - 5 * 10 / 2
+		assert_eq!(vm.call("shapes", &[5]).unwrap() as i64, 50, "{:?}", engine);
+		// 1 + 2 * 10 + 1 * 100 + (4 + 10) * -2 + 7
+		assert_eq!(vm.call("fields", &[1]).unwrap() as i64, 100, "{:?}", engine);
+		assert_eq!(f64::from_bits(vm.call("arrays", &[3]).unwrap()), 1.5 + 8.0 + 3.0 + 3.0, "{:?}", engine);
+		assert_eq!(vm.call("strings", &[]).unwrap() as i64, 1000 + 6 + b'o' as i64, "{:?}", engine);
+	}
+}
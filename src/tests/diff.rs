@@ -0,0 +1,244 @@
+//! Differential testing of the execution engines<br>
+//! Every program in `src/tests/golden` (E# assembly or an executable) is run with the interpreter and
+//! the JIT, and the outcome of each call in its `.expected` file must agree between the engines and
+//! with the file. What the calls print is captured from stdout.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::vm::{Engine, Vm};
+use crate::vm::bin::{asm, Executable};
+use crate::vm::bin::builder::{ExecutableBuilder, Function};
+use crate::vm::error::vm::VmError;
+use crate::vm::insn::{self, Insn};
+use crate::vm::types::{TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::function::Signature;
+use crate::vm::types::str::Str;
+use crate::vm::verify;
+
+const GOLDEN: &str = "src/tests/golden";
+
+#[derive(Clone, Debug)]
+struct Call {
+	function: String,
+	args: Vec<u64>,
+}
+
+/// Something a call does that can be observed by the host
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Event {
+	/// A line written to stdout
+	Output(String),
+	Return(String),
+	/// The message of the trap the call failed with
+	Trap(String),
+}
+
+/// A call in an `.expected` file, and the events it should produce
+struct Case {
+	line: usize,
+	call: Call,
+	expected: Vec<Event>,
+}
+
+/// Formats a return value, references other than strings aren't comparable between VMs
+fn format(ty: TypeDesc, word: u64) -> String {
+	match ty.id() {
+		TypeFlags::VOID => String::from("void"),
+		TypeFlags::F32 => format!("{:?}", f32::from_bits(word as u32)),
+		TypeFlags::F64 => format!("{:?}", f64::from_bits(word)),
+		TypeFlags::STR => format!("{:?}", unsafe { (*(word as *const Str)).as_str() }),
+		_ if ty.is_int() && ty.is_unsigned() => word.to_string(),
+		_ if ty.is_int() => (word as i64).to_string(),
+		_ => String::from("ref"),
+	}
+}
+
+/// Makes `calls` to the executable in `bytes` with an engine, returning what they did
+fn run(engine: Engine, bytes: &[u8], calls: &[Call]) -> Vec<Event> {
+	let mut vm = Vm::with_engine(engine);
+	vm.load(Executable::parse(bytes).unwrap()).unwrap();
+	let stdout = Arc::new(Mutex::new(Vec::new()));
+	let mut events = Vec::new();
+	for call in calls {
+		let ret = vm.functions().get(&call.function).unwrap().signature().ret;
+		let outer = io::set_output_capture(Some(stdout.clone()));
+		let result = vm.call(&call.function, &call.args);
+		io::set_output_capture(outer);
+
+		let output = std::mem::take(&mut *stdout.lock().unwrap());
+		events.extend(String::from_utf8_lossy(&output).lines().map(|line| Event::Output(line.to_string())));
+		events.push(match result {
+			Ok(word) => Event::Return(format(ret, word)),
+			Err(VmError::Trap(trap)) => Event::Trap(trap.to_string()),
+			Err(err) => panic!("{}", err),
+		});
+	}
+	events
+}
+
+/// Splits events into the events of each call, which end with a return or a trap
+fn per_call(events: &[Event]) -> Vec<&[Event]> {
+	events.split_inclusive(|event| !matches!(event, Event::Output(_))).collect()
+}
+
+fn parse_word(word: &str) -> Option<u64> {
+	match word.strip_prefix("0x") {
+		Some(hex) => u64::from_str_radix(hex, 16).ok(),
+		None => word.parse::<u64>().ok().or_else(|| word.parse::<i64>().ok().map(|word| word as u64)),
+	}
+}
+
+/// Parses an `.expected` file<br>
+/// Each call is a line `<function> <args>... => <result>`, where the result is the return value (as
+/// formatted by [`format`]) or `trap <message>`. The lines a call prints follow it, each starting
+/// with `> `. Lines starting with `;` are comments.
+fn parse_expected(src: &str) -> Result<Vec<Case>, String> {
+	let mut cases = Vec::<Case>::new();
+	for (index, line) in src.lines().enumerate() {
+		let number = index + 1;
+		if line.trim().is_empty() || line.starts_with(';') {
+			continue
+		}
+		if let Some(output) = line.strip_prefix('>') {
+			let case = cases.last_mut().ok_or_else(|| format!("line {}: output before the first call", number))?;
+			let output = output.strip_prefix(' ').unwrap_or(output);
+			let result = case.expected.len() - 1;
+			case.expected.insert(result, Event::Output(output.to_string()));
+			continue
+		}
+
+		let (call, result) = line.split_once("=>").ok_or_else(|| format!("line {}: expected `=>`", number))?;
+		let mut words = call.split_whitespace();
+		let function = words.next().ok_or_else(|| format!("line {}: expected a function", number))?;
+		let args = words
+			.map(|word| parse_word(word).ok_or_else(|| format!("line {}: invalid argument `{}`", number, word)))
+			.collect::<Result<Vec<_>, _>>()?;
+		let result = result.trim();
+		let result = match result.strip_prefix("trap ") {
+			Some(message) => Event::Trap(message.to_string()),
+			None => Event::Return(result.to_string()),
+		};
+		cases.push(Case {
+			line: number,
+			call: Call {
+				function: function.to_string(),
+				args,
+			},
+			expected: vec![result],
+		});
+	}
+	Ok(cases)
+}
+
+/// The bytes of the golden program at `path`
+fn golden_bytes(path: &Path) -> Result<Vec<u8>, String> {
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some("esasm") => {
+			let src = fs::read_to_string(path).map_err(|err| err.to_string())?;
+			Ok(asm::assemble(&src).map_err(|err| err.to_string())?.build())
+		},
+		_ => fs::read(path).map_err(|err| err.to_string()),
+	}
+}
+
+/// Runs the calls in the `.expected` file of the golden program at `path`, returning the failures
+fn check_golden(path: &Path) -> Result<Vec<String>, String> {
+	let bytes = golden_bytes(path)?;
+	let mut vm = Vm::with_engine(Engine::Interpreter);
+	vm.load(Executable::parse(&bytes).map_err(|err| format!("{:?}", err))?).map_err(|err| err.to_string())?;
+	let expected = fs::read_to_string(path.with_extension("expected")).map_err(|err| format!("{}.expected: {}", path.with_extension("").display(), err))?;
+	let cases = parse_expected(&expected)?;
+
+	let mut failures = Vec::new();
+	for case in cases {
+		let interp = run(Engine::Interpreter, &bytes, &[case.call.clone()]);
+		let jit = run(Engine::Jit, &bytes, &[case.call.clone()]);
+		if interp != jit {
+			let divergence = divergence(&vm, &case.call);
+			failures.push(format!("line {}: the engines disagree on {}, interpreter: {:?}, jit: {:?}\n\t{}", case.line, case.call.function, interp, jit, divergence));
+		} else if interp != case.expected {
+			failures.push(format!("line {}: expected {:?}, found {:?}", case.line, case.expected, interp));
+		}
+	}
+	Ok(failures)
+}
+
+/// Finds the first instruction of the called function after which the value on top of the operand
+/// stack differs between the engines<br>
+/// Each instruction that leaves a number on top of the stack gets a probe, a copy of the function
+/// that returns that number right after it. The probes run in order with each engine, and the first
+/// one whose outcome differs is the divergent instruction.
+fn divergence(vm: &Vm, call: &Call) -> String {
+	let (constants, def) = match vm.definition(&call.function) {
+		Some(definition) => definition,
+		None => return format!("{} has no bytecode", call.function),
+	};
+	let signature = Signature::of(def, constants);
+	let types = match verify::stack_types(def.code(), &signature, &vm.context(0)) {
+		Ok(types) => types,
+		Err(err) => return format!("{} fails verification: {}", call.function, err),
+	};
+	let insns = insn::decode_all(def.code()).unwrap();
+
+	let mut builder = ExecutableBuilder::from(&vm.executables()[0]);
+	let mut probes = Vec::new();
+	let mut calls = Vec::new();
+	for (i, ((offset, insn), (_, stack))) in insns.iter().zip(&types).enumerate() {
+		let top = match stack.last() {
+			Some(top) if top.is_int() || top.is_float() => *top,
+			_ => continue,
+		};
+		let end = insns.get(i + 1).map_or(def.code().len(), |(next, _)| *next);
+		let mut code = def.code()[..end].to_vec();
+		Insn::VRet(top).encode(&mut code);
+		let name = format!("{}.probe{}", call.function, offset);
+		let function = Function {
+			name: builder.name(&name),
+			ret: top,
+			args: signature.args.clone(),
+			code,
+		};
+		builder.functions.push(function);
+		probes.push((*offset, *insn));
+		calls.push(Call {
+			function: name,
+			args: call.args.clone(),
+		});
+	}
+
+	let bytes = builder.build();
+	let interp = run(Engine::Interpreter, &bytes, &calls);
+	let jit = run(Engine::Jit, &bytes, &calls);
+
+	let (interp, jit) = (per_call(&interp), per_call(&jit));
+	let divergent = (0..probes.len()).find(|i| interp.get(*i) != jit.get(*i));
+	match divergent {
+		Some(i) => {
+			let (offset, insn) = probes[i];
+			format!("first divergence after `{}` at offset {}, interpreter: {:?}, jit: {:?}", insn, offset, interp.get(i), jit.get(i))
+		},
+		None => String::from("the values on the operand stack don't diverge"),
+	}
+}
+
+#[test]
+fn golden_programs() {
+	let mut paths = fs::read_dir(GOLDEN).unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| matches!(path.extension().and_then(|ext| ext.to_str()), Some("esasm" | "esbin")))
+		.collect::<Vec<_>>();
+	paths.sort();
+	assert!(!paths.is_empty(), "no golden programs in {}", GOLDEN);
+
+	let mut failures = Vec::new();
+	for path in &paths {
+		match check_golden(path) {
+			Ok(errors) => failures.extend(errors.into_iter().map(|err| format!("{}: {}", path.display(), err))),
+			Err(err) => failures.push(format!("{}: {}", path.display(), err)),
+		}
+	}
+	assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use crate::compiler::{check, codegen, syntax};
+use crate::vm::bin::asm;
+use crate::vm::error::jit::TranspileError;
+use crate::vm::error::verify::VerifyError;
+use crate::vm::error::vm::VmError;
+use crate::vm::jit;
+use crate::vm::jit::Arith;
+use crate::vm::jit::{aarch64, riscv64};
+use crate::vm::jit::aarch64::{cond, reg};
+use crate::vm::meta::platform::{CpuArch, CpuFeature, CpuFeatures, Platform};
+use crate::vm::types::{TypeDesc, TypeFlag, TypeFlags, TypeId};
+use crate::vm::{Engine, Vm};
+
+const I8: TypeDesc = TypeDesc::new(TypeFlags::I8, None);
+const U16: TypeDesc = TypeDesc::new(TypeFlags::UNSIGNED << 4 | TypeFlags::I16, None);
+const I32: TypeDesc = TypeDesc::new(TypeFlags::I32, None);
+const I64: TypeDesc = TypeDesc::new(TypeFlags::I64, None);
+const F32: TypeDesc = TypeDesc::new(TypeFlags::F32, None);
+const F64: TypeDesc = TypeDesc::new(TypeFlags::F64, None);
+
+const FLOATS: &str = "
+fn floats(n: i64) -> f64 {
+	let x = n as f64 * 1.5 + 0.25;
+	let y = (x as f32 / 2.0f32 - 0.5f32) as f64;
+	y * 4.0 - (x as i64) as f64
+}
+";
+
+#[test]
+fn detect_platform() {
+	let platform = Platform::current();
+	assert_eq!(platform, Platform::current());
+	if cfg!(target_arch = "x86_64") {
+		assert_eq!(platform.arch, CpuArch::X86_64);
+	}
+	assert_eq!(platform.with_features(0).features, 0);
+	assert_eq!(platform.with_features(CpuFeatures::ALL), platform);
+}
+
+#[test]
+fn fallback_features() {
+	let (file, _) = syntax::parse(FLOATS);
+	let program = check::check(&[file]).unwrap_or_else(|diagnostics| panic!("{:?}", diagnostics));
+	let exec = codegen::generate(&program).unwrap();
+	// x = 4.75, y = 1.875
+	for features in [CpuFeatures::ALL, 0] {
+		let mut vm = Vm::with_engine(Engine::Jit);
+		vm.set_cpu_features(features);
+		assert!(!vm.platform().has(CpuFeatures::AVX2) || features == CpuFeatures::ALL);
+		vm.load(exec.finish()).unwrap();
+		assert_eq!(f64::from_bits(vm.call("floats", &[3]).unwrap()), 1.875 * 4.0 - 4.0, "{:#X}", features);
+	}
+}
+
+#[test]
+fn avx_encodings() {
+	let (file, _) = syntax::parse(FLOATS);
+	let program = check::check(&[file]).unwrap_or_else(|diagnostics| panic!("{:?}", diagnostics));
+	let mut vm = Vm::with_engine(Engine::Interpreter);
+	vm.load(codegen::generate(&program).unwrap().finish()).unwrap();
+	let (_, def) = vm.definition("floats").unwrap();
+	let mut ctx = vm.context(0);
+	ctx.platform.arch = CpuArch::X86_64;
+	let mut transpile = |features| {
+		ctx.platform.features = features;
+		jit::transpile(def.code(), &def.arg_types(), &ctx).unwrap().code
+	};
+	let (vex, legacy) = (transpile(CpuFeatures::AVX2), transpile(0));
+	assert_ne!(vex, legacy);
+	// cvtsi2sd xmm0, rax, then its VEX encoding vcvtsi2sd xmm0, xmm0, rax
+	let cvtsi2sd = [0xF2, 0x48, 0x0F, 0x2A, 0xC0];
+	let vcvtsi2sd = [0xC4, 0xE1, 0xFB, 0x2A, 0xC0];
+	assert!(legacy.windows(5).any(|insn| insn == cvtsi2sd));
+	assert!(!legacy.windows(5).any(|insn| insn == vcvtsi2sd));
+	assert!(vex.windows(5).any(|insn| insn == vcvtsi2sd));
+	assert!(!vex.windows(5).any(|insn| insn == cvtsi2sd));
+}
+
+/// The AArch64 assembler, against encodings from `llvm-mc -triple=aarch64 -show-encoding`
+#[test]
+fn aarch64_encodings() {
+	let cases: &[(fn(&mut aarch64::Assembler), &[u32])] = &[
+		// stp x29, x30, [sp, #-16]!; mov x29, sp
+		(|asm| asm.enter(), &[0xA9BF7BFD, 0x910003FD]),
+		// mov sp, x29; ldp x29, x30, [sp], #16; ret
+		(|asm| asm.leave(), &[0x910003BF, 0xA8C17BFD, 0xD65F03C0]),
+		(|asm| asm.sub_sp(reg::X16), &[0xCB3063FF]),
+		(|asm| asm.add(reg::X0, reg::X1, reg::X2), &[0x8B020020]),
+		// add x0, x0, x1, lsl #3
+		(|asm| asm.add_shifted(reg::X0, reg::X0, reg::X1, 3), &[0x8B010C00]),
+		(|asm| asm.mul(reg::X0, reg::X0, reg::X1), &[0x9B017C00]),
+		(|asm| asm.sdiv(reg::X0, reg::X0, reg::X1), &[0x9AC10C00]),
+		(|asm| asm.udiv(reg::X0, reg::X0, reg::X1), &[0x9AC10800]),
+		(|asm| asm.cmp(reg::X1, reg::X2), &[0xEB02003F]),
+		(|asm| asm.mov(reg::X2, reg::FP), &[0xAA1D03E2]),
+		// movz x0, #0x5678; movk x0, #0x1234, lsl #16
+		(|asm| asm.mov_imm(reg::X0, 0x12345678), &[0xD28ACF00, 0xF2A24680]),
+		// movz x0, #0x8000, lsl #48
+		(|asm| asm.mov_imm(reg::X0, 1 << 63), &[0xD2F00000]),
+		// movn x0, #0xedcb
+		(|asm| asm.mov_imm(reg::X0, 0xFFFFFFFF_FFFF1234), &[0x929DB960]),
+		// sxtb x0, w0; uxth w0, w0; sxtw x0, w0
+		(|asm| asm.normalize(reg::X0, I8), &[0x93401C00]),
+		(|asm| asm.normalize(reg::X0, U16), &[0x53003C00]),
+		(|asm| asm.normalize(reg::X0, I32), &[0x93407C00]),
+		// sturb w0, [x29, #-8]; stur x0, [x29, #-256]
+		(|asm| asm.store(reg::X0, reg::FP, -8, 1), &[0x381F83A0]),
+		(|asm| asm.store(reg::X0, reg::FP, -256, 8), &[0xF81003A0]),
+		// ldursb x0, [x1, #255]; ldurh w0, [x1, #2]; ldur w0, [x1, #4]
+		(|asm| asm.load(reg::X0, reg::X1, 255, I8), &[0x388FF020]),
+		(|asm| asm.load(reg::X0, reg::X1, 2, U16), &[0x78402020]),
+		(|asm| asm.load(reg::X0, reg::X1, 4, F32), &[0xB8404020]),
+		// mov x17, #-4096; add x17, x29, x17; ldur x0, [x17]
+		(|asm| asm.load(reg::X0, reg::FP, -4096, I64), &[0x9281FFF1, 0x8B1103B1, 0xF8400220]),
+		// fmov d0, x0; fmov w0, s0
+		(|asm| asm.to_float(0, reg::X0, F64), &[0x9E670000]),
+		(|asm| asm.from_float(reg::X0, 0, F32), &[0x1E260000]),
+		// fadd d0, d0, d1; fdiv s0, s0, s1
+		(|asm| asm.float_op(Arith::Add, 0, 0, 1, F64), &[0x1E612800]),
+		(|asm| asm.float_op(Arith::Div, 0, 0, 1, F32), &[0x1E211800]),
+		// fcmp d0, d1; scvtf s0, x0; fcvtzs x0, d0; fcvt d0, s0
+		(|asm| asm.fcmp(0, 1), &[0x1E612000]),
+		(|asm| asm.scvtf(0, reg::X0, F32), &[0x9E220000]),
+		(|asm| asm.fcvtzs(reg::X0, 0), &[0x9E780000]),
+		(|asm| asm.fcvt(0, 0, F64), &[0x1E22C000]),
+		(|asm| asm.blr(reg::X16), &[0xD63F0200]),
+		// b.lo #8; nop
+		(|asm| {
+			let pos = asm.b_cond(cond::LO);
+			asm.nop();
+			asm.patch(pos, asm.code.len());
+		}, &[0x54000043, 0xD503201F]),
+		// cbnz x1, #8; nop
+		(|asm| {
+			let pos = asm.cbnz(reg::X1);
+			asm.nop();
+			asm.patch(pos, asm.code.len());
+		}, &[0xB5000041, 0xD503201F]),
+		// cbz x16, #8; nop
+		(|asm| {
+			let pos = asm.cbz(reg::X16);
+			asm.nop();
+			asm.patch(pos, asm.code.len());
+		}, &[0xB4000050, 0xD503201F]),
+		// adr x3, #12; nop; nop
+		(|asm| {
+			let pos = asm.adr(reg::X3);
+			asm.nop();
+			asm.nop();
+			asm.patch(pos, asm.code.len());
+		}, &[0x10000063, 0xD503201F, 0xD503201F]),
+	];
+	for (i, (emit, expected)) in cases.iter().enumerate() {
+		let mut asm = aarch64::Assembler::default();
+		emit(&mut asm);
+		assert_eq!(&words(&asm.code), expected, "case {}", i);
+	}
+}
+
+/// The RISC-V assembler, against encodings from `llvm-mc -triple=riscv64 -mattr=+m,+d -show-encoding`
+#[test]
+fn riscv64_encodings() {
+	use crate::vm::jit::riscv64::reg;
+
+	let cases: &[(fn(&mut riscv64::Assembler), &[u32])] = &[
+		// addi sp, sp, -16; sd ra, 8(sp); sd s0, 0(sp); mv s0, sp
+		(|asm| asm.enter(), &[0xFF010113, 0x00113423, 0x00813023, 0x00010413]),
+		// mv sp, s0; ld ra, 8(sp); ld s0, 0(sp); addi sp, sp, 16; ret
+		(|asm| asm.leave(), &[0x00040113, 0x00813083, 0x00013403, 0x01010113, 0x00008067]),
+		(|asm| asm.add(reg::A0, reg::A1, reg::A2), &[0x00C58533]),
+		(|asm| asm.sub(reg::A0, reg::A0, reg::A1), &[0x40B50533]),
+		(|asm| asm.mul(reg::A0, reg::A0, reg::A1), &[0x02B50533]),
+		(|asm| asm.div(reg::A0, reg::A0, reg::A1), &[0x02B54533]),
+		(|asm| asm.divu(reg::A0, reg::A0, reg::A1), &[0x02B55533]),
+		// lui a0, 1; addiw a0, a0, -2048
+		(|asm| asm.li(reg::A0, 0x800), &[0x00001537, 0x8005051B]),
+		// lui a0, 524288; addiw a0, a0, -2048
+		(|asm| asm.li(reg::A0, 0x7FFFF800), &[0x80000537, 0x8005051B]),
+		// li a0, 1; slli a0, a0, 31
+		(|asm| asm.li(reg::A0, 0x80000000), &[0x00100513, 0x01F51513]),
+		// li a0, -1; slli a0, a0, 63; addi a0, a0, -1
+		(|asm| asm.li(reg::A0, i64::MAX), &[0xFFF00513, 0x03F51513, 0xFFF50513]),
+		// slli a0, a0, 48; srli a0, a0, 48
+		(|asm| asm.normalize(reg::A0, U16), &[0x03051513, 0x03055513]),
+		// sext.w a0, a0
+		(|asm| asm.normalize(reg::A0, I32), &[0x0005051B]),
+		// sb a0, -8(s0); sd a0, -2048(s0)
+		(|asm| asm.store(reg::A0, reg::FP, -8, 1), &[0xFEA40C23]),
+		(|asm| asm.store(reg::A0, reg::FP, -2048, 8), &[0x80A43023]),
+		// lui t6, 1048575; add t6, s0, t6; sd a0, 0(t6)
+		(|asm| asm.store(reg::A0, reg::FP, -4096, 8), &[0xFFFFFFB7, 0x01F40FB3, 0x00AFB023]),
+		// lb a0, 1(a1); lhu a0, 2(a1); lwu a0, 4(a1); ld a0, -8(a1)
+		(|asm| asm.load(reg::A0, reg::A1, 1, I8), &[0x00158503]),
+		(|asm| asm.load(reg::A0, reg::A1, 2, U16), &[0x0025D503]),
+		(|asm| asm.load(reg::A0, reg::A1, 4, F32), &[0x0045E503]),
+		(|asm| asm.load(reg::A0, reg::A1, -8, I64), &[0xFF85B503]),
+		// fmv.d.x ft0, a0; fmv.x.w a0, ft0; slli a0, a0, 32; srli a0, a0, 32
+		(|asm| asm.to_float(reg::FT0, reg::A0, F64), &[0xF2050053]),
+		(|asm| asm.from_float(reg::A0, reg::FT0, F32), &[0xE0000553, 0x02051513, 0x02055513]),
+		// fadd.d ft0, ft0, ft1; fdiv.s ft0, ft0, ft1
+		(|asm| asm.float_op(Arith::Add, reg::FT0, reg::FT0, reg::FT1, F64), &[0x02107053]),
+		(|asm| asm.float_op(Arith::Div, reg::FT0, reg::FT0, reg::FT1, F32), &[0x18107053]),
+		// flt.d t1, ft0, ft1
+		(|asm| asm.flt(reg::T1, reg::FT0, reg::FT1), &[0xA2101353]),
+		// fcvt.d.l ft0, a0; fcvt.l.d a0, ft0, rtz; fcvt.d.s ft0, ft0; fcvt.s.d ft0, ft0
+		(|asm| asm.fcvt_from_int(reg::FT0, reg::A0, F64), &[0xD2257053]),
+		(|asm| asm.fcvt_to_int(reg::A0, reg::FT0), &[0xC2201553]),
+		(|asm| asm.fcvt(reg::FT0, reg::FT0, F64), &[0x42000053]),
+		(|asm| asm.fcvt(reg::FT0, reg::FT0, F32), &[0x40107053]),
+		// jalr t0
+		(|asm| asm.call(reg::T0), &[0x000280E7]),
+		// bltu a1, a2, 12; nop; nop
+		(|asm| {
+			let pos = asm.bltu(reg::A1, reg::A2);
+			asm.addi(reg::ZERO, reg::ZERO, 0);
+			asm.addi(reg::ZERO, reg::ZERO, 0);
+			asm.patch(pos, asm.code.len());
+		}, &[0x00C5E663, 0x00000013, 0x00000013]),
+		// beqz t0, 8; nop
+		(|asm| {
+			let pos = asm.beqz(reg::T0);
+			asm.addi(reg::ZERO, reg::ZERO, 0);
+			asm.patch(pos, asm.code.len());
+		}, &[0x00028463, 0x00000013]),
+		// auipc a3, 0; addi a3, a3, 16; nop; nop
+		(|asm| {
+			let pos = asm.la(reg::ARGS[3]);
+			asm.addi(reg::ZERO, reg::ZERO, 0);
+			asm.addi(reg::ZERO, reg::ZERO, 0);
+			asm.patch(pos, asm.code.len());
+		}, &[0x00000697, 0x01068693, 0x00000013, 0x00000013]),
+		// lui t0, 74566; addiw t0, t0, -16
+		(|asm| {
+			asm.lui(reg::T0, 0);
+			asm.addiw(reg::T0, reg::T0, 0);
+			asm.patch_li(0, 0x12345FF0);
+		}, &[0x123462B7, 0xFF02829B]),
+	];
+	for (i, (emit, expected)) in cases.iter().enumerate() {
+		let mut asm = riscv64::Assembler::default();
+		emit(&mut asm);
+		assert_eq!(&words(&asm.code), expected, "case {}", i);
+	}
+}
+
+/// Every function of the golden programs can be compiled for every architecture, whatever the host is
+#[test]
+fn cross_compile_golden_programs() {
+	// the first instruction of the prologue of each architecture
+	let archs = [
+		// push rbp
+		(CpuArch::X86_64, 0x55u32, 1),
+		// stp x29, x30, [sp, #-16]!
+		(CpuArch::Aarch64, 0xA9BF7BFD, 4),
+		// addi sp, sp, -16
+		(CpuArch::RiscV64, 0xFF010113, 4),
+	];
+	let mut compiled = 0;
+	for entry in fs::read_dir("src/tests/golden").unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().and_then(|ext| ext.to_str()) != Some("esasm") {
+			continue
+		}
+		let mut vm = Vm::with_engine(Engine::Interpreter);
+		vm.load(asm::assemble(&fs::read_to_string(&path).unwrap()).unwrap().finish()).unwrap();
+		for (arch, prologue, size) in archs {
+			let mut ctx = vm.context(0);
+			ctx.platform.arch = arch;
+			for name in vm.functions().names() {
+				// intrinsics have no bytecode
+				let Some((_, def)) = vm.definition(name) else { continue };
+				let code = jit::transpile(def.code(), &def.arg_types(), &ctx).unwrap_or_else(|err| panic!("{:?} {}: {}", arch, name, err)).code;
+				assert_eq!(code.len() % size, 0);
+				assert_eq!(code[..size], prologue.to_le_bytes()[..size], "{:?} {}", arch, name);
+				compiled += 1;
+			}
+		}
+	}
+	assert!(compiled > 0);
+}
+
+#[test]
+fn argument_slot_limit() {
+	let six = "fn six(i64, i64, i64, i64, i64, i64) -> i64\n\tpush i64 5\n\tvret i64\nend\n";
+	let seven = "fn seven(i64, i64, i64, i64, i64, i64, i64) -> i64\n\tpush i64 6\n\tvret i64\nend\n";
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(asm::assemble(six).unwrap().finish()).unwrap();
+		assert_eq!(vm.call("six", &[1, 2, 3, 4, 5, 6]).unwrap(), 6, "{:?}", engine);
+		let err = Vm::with_engine(engine).load(asm::assemble(seven).unwrap().finish()).unwrap_err();
+		assert!(matches!(err, VmError::Verify(_, VerifyError::TooManyArgs(7))), "{:?}: {}", engine, err);
+	}
+
+	// every backend has the same limit, although AArch64 and RISC-V have more argument registers
+	let mut vm = Vm::with_engine(Engine::Interpreter);
+	vm.load(asm::assemble(six).unwrap().finish()).unwrap();
+	let (_, def) = vm.definition("six").unwrap();
+	for arch in [CpuArch::X86_64, CpuArch::Aarch64, CpuArch::RiscV64] {
+		let mut ctx = vm.context(0);
+		ctx.platform.arch = arch;
+		assert!(jit::transpile(def.code(), &[I64; 6], &ctx).is_ok(), "{:?}", arch);
+		let err = jit::transpile(def.code(), &[I64; 7], &ctx).unwrap_err();
+		assert!(matches!(err, TranspileError::TooManyArgs(7)), "{:?}: {}", arch, err);
+	}
+}
+
+/// The 32-bit words of RISC code
+fn words(code: &[u8]) -> Vec<u32> {
+	code.chunks(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect()
+}
@@ -6,6 +6,28 @@ use crate::vm::error::jit::TranspileError::IllegalInsn;
 use crate::vm::bin::{BinaryFile, Executable};
 use crate::vm::types::function;
 use crate::vm::types::function::{Function, RawFn};
+use crate::vm::Vm;
+
+#[cfg(test)]
+mod bin;
+#[cfg(test)]
+mod build;
+#[cfg(test)]
+mod check;
+#[cfg(test)]
+mod codegen;
+#[cfg(test)]
+mod jit;
+#[cfg(test)]
+mod diff;
+#[cfg(test)]
+mod prop;
+#[cfg(test)]
+mod repl;
+#[cfg(test)]
+mod runtime;
+#[cfg(test)]
+mod syntax;
 
 #[test]
 fn vm_test() {
@@ -16,9 +38,21 @@ fn vm_test() {
 	println!("Initializing JIT");
 
 	// initialize JIT
-	function::init_page_size();
+	let mut vm = Vm::new();
+	vm.load(exec).unwrap();
 
 	println!("Starting JIT");
 
 	// start JIT
+	let function = vm.function("#bar").unwrap();
+	assert_eq!(unsafe { function.call(&[1, 2]) }, 3);
+}
+
+#[test]
+fn fuzz_regressions() {
+	for entry in std::fs::read_dir("fuzz/regressions").unwrap() {
+		let path = entry.unwrap().path();
+		let bytes = std::fs::read(&path).unwrap();
+		assert!(Executable::parse(&bytes).is_err(), "{} was parsed", path.display());
+	}
 }
@@ -0,0 +1,479 @@
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use crate::vm::{Engine, Vm};
+use crate::vm::bin::Executable;
+use crate::vm::bin::builder::{Class, Constant, DebugInfo, ExecutableBuilder, Field, Function};
+use crate::vm::bin::def::debug::{LineEntry, LocalEntry};
+use crate::vm::insn::Insn;
+use crate::vm::types::{TypeDesc, TypeFlag, TypeFlags, TypeId};
+
+/// An integer type of the VM
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum IntTy {
+	I8,
+	I16,
+	I32,
+	I64,
+	U8,
+	U16,
+	U32,
+	U64,
+}
+
+impl IntTy {
+	const ALL: [IntTy; 8] = [IntTy::I8, IntTy::I16, IntTy::I32, IntTy::I64, IntTy::U8, IntTy::U16, IntTy::U32, IntTy::U64];
+
+	fn type_desc(self) -> TypeDesc {
+		let (id, unsigned) = match self {
+			IntTy::I8 => (TypeFlags::I8, false),
+			IntTy::I16 => (TypeFlags::I16, false),
+			IntTy::I32 => (TypeFlags::I32, false),
+			IntTy::I64 => (TypeFlags::I64, false),
+			IntTy::U8 => (TypeFlags::I8, true),
+			IntTy::U16 => (TypeFlags::I16, true),
+			IntTy::U32 => (TypeFlags::I32, true),
+			IntTy::U64 => (TypeFlags::I64, true),
+		};
+		let modifier = if unsigned { TypeFlags::UNSIGNED } else { 0 };
+		TypeDesc::new(modifier << 4 | id, None)
+	}
+
+	/// Truncates `value` to the width of the type, and extends it back to 64 bits
+	fn wrap(self, value: u64) -> u64 {
+		match self {
+			IntTy::I8 => value as i8 as u64,
+			IntTy::I16 => value as i16 as u64,
+			IntTy::I32 => value as i32 as u64,
+			IntTy::I64 => value,
+			IntTy::U8 => value as u8 as u64,
+			IntTy::U16 => value as u16 as u64,
+			IntTy::U32 => value as u32 as u64,
+			IntTy::U64 => value,
+		}
+	}
+
+	/// A divisor that can't trap (`0` and `-1` are replaced with `2`)
+	fn divisor(self, value: u64) -> u64 {
+		match self.wrap(value) {
+			0 | u64::MAX => 2,
+			value => value,
+		}
+	}
+}
+
+/// Applies a method of the integer types to two values of type `$ty`
+macro_rules! apply {
+	( $ty:expr, $a:expr, $b:expr, $op:ident ) => {
+		match $ty {
+			IntTy::I8 => ($a as i8).$op($b as i8) as u64,
+			IntTy::I16 => ($a as i16).$op($b as i16) as u64,
+			IntTy::I32 => ($a as i32).$op($b as i32) as u64,
+			IntTy::I64 => ($a as i64).$op($b as i64) as u64,
+			IntTy::U8 => ($a as u8).$op($b as u8) as u64,
+			IntTy::U16 => ($a as u16).$op($b as u16) as u64,
+			IntTy::U32 => ($a as u32).$op($b as u32) as u64,
+			IntTy::U64 => ($a as u64).$op($b as u64),
+		}
+	};
+}
+
+/// An arithmetic expression over the arguments of a function<br>
+/// Variables are resolved modulo the number of variables in scope, so every expression is valid.
+#[derive(Clone, Debug)]
+enum Expr {
+	Var(u8),
+	Const(u64),
+	Add(Box<Expr>, Box<Expr>),
+	Sub(Box<Expr>, Box<Expr>),
+	Mul(Box<Expr>, Box<Expr>),
+	/// Division by a constant (see [`IntTy::divisor`])
+	Div(Box<Expr>, u64),
+	Inc(Box<Expr>),
+	Dec(Box<Expr>),
+	/// Stores the value in a new local, which is in scope in the body
+	Let(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+	/// What the expression evaluates to in the VM, computed with Rust's integer types
+	fn eval(&self, ty: IntTy, scope: &mut Vec<u64>) -> u64 {
+		match self {
+			Expr::Var(var) => scope[*var as usize % scope.len()],
+			Expr::Const(value) => ty.wrap(*value),
+			Expr::Add(a, b) => apply!(ty, a.eval(ty, scope), b.eval(ty, scope), wrapping_add),
+			Expr::Sub(a, b) => apply!(ty, a.eval(ty, scope), b.eval(ty, scope), wrapping_sub),
+			Expr::Mul(a, b) => apply!(ty, a.eval(ty, scope), b.eval(ty, scope), wrapping_mul),
+			Expr::Div(a, b) => apply!(ty, a.eval(ty, scope), ty.divisor(*b), wrapping_div),
+			Expr::Inc(a) => apply!(ty, a.eval(ty, scope), 1, wrapping_add),
+			Expr::Dec(a) => apply!(ty, a.eval(ty, scope), 1, wrapping_sub),
+			Expr::Let(value, body) => {
+				let value = value.eval(ty, scope);
+				scope.push(value);
+				let result = body.eval(ty, scope);
+				scope.pop();
+				result
+			},
+		}
+	}
+}
+
+/// Emits the bytecode of expressions
+struct Emitter<'a> {
+	builder: &'a mut ExecutableBuilder,
+	ty: IntTy,
+	code: Vec<u8>,
+	/// The locals of the variables in scope
+	scope: Vec<u8>,
+	/// The number of locals (arguments and `pop`ped values)
+	locals: u8,
+}
+
+impl Emitter<'_> {
+	fn emit(&mut self, expr: &Expr) {
+		let ty = self.ty.type_desc();
+		match expr {
+			Expr::Var(var) => Insn::Push(ty, self.scope[*var as usize % self.scope.len()]).encode(&mut self.code),
+			Expr::Const(value) => {
+				let index = self.builder.constant(Constant::primitive(ty, *value));
+				Insn::Ldc(index).encode(&mut self.code);
+			},
+			Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) => {
+				self.emit(a);
+				self.emit(b);
+				match expr {
+					Expr::Add(..) => Insn::Add(ty),
+					Expr::Sub(..) => Insn::Sub(ty),
+					_ => Insn::Mul(ty),
+				}.encode(&mut self.code);
+			},
+			Expr::Div(a, b) => {
+				self.emit(a);
+				let index = self.builder.constant(Constant::primitive(ty, self.ty.divisor(*b)));
+				Insn::Ldc(index).encode(&mut self.code);
+				Insn::Div(ty).encode(&mut self.code);
+			},
+			Expr::Inc(a) => {
+				self.emit(a);
+				Insn::Inc(ty).encode(&mut self.code);
+			},
+			Expr::Dec(a) => {
+				self.emit(a);
+				Insn::Dec(ty).encode(&mut self.code);
+			},
+			Expr::Let(value, body) => {
+				self.emit(value);
+				Insn::Pop.encode(&mut self.code);
+				self.scope.push(self.locals);
+				self.locals += 1;
+				self.emit(body);
+				self.scope.pop();
+			},
+		}
+	}
+}
+
+/// A function that returns an arithmetic expression over its arguments, which are all of type `ty`
+#[derive(Clone, Debug)]
+struct ArithFn {
+	ty: IntTy,
+	body: Expr,
+	/// The arguments it is called with
+	args: Vec<u64>,
+	/// The source lines of the first instructions, for the debug info table
+	lines: Vec<u32>,
+}
+
+impl ArithFn {
+	/// The value the function returns when called with `args`
+	fn eval(&self) -> u64 {
+		self.body.eval(self.ty, &mut self.args())
+	}
+
+	fn args(&self) -> Vec<u64> {
+		self.args.iter().map(|arg| self.ty.wrap(*arg)).collect()
+	}
+
+	/// Encodes the function, with a receiver of class `receiver` (a method) before the arguments if it's `Some`
+	fn build(&self, builder: &mut ExecutableBuilder, name: &str, receiver: Option<TypeDesc>) -> Function {
+		let ty = self.ty.type_desc();
+		let first = receiver.is_some() as u8;
+		let args = receiver.into_iter().chain(self.args.iter().map(|_| ty)).collect::<Vec<_>>();
+		let mut emitter = Emitter {
+			builder,
+			ty: self.ty,
+			code: Vec::new(),
+			scope: (first..args.len() as u8).collect(),
+			locals: args.len() as u8,
+		};
+		emitter.emit(&self.body);
+		Insn::VRet(ty).encode(&mut emitter.code);
+		let code = emitter.code;
+
+		if !self.lines.is_empty() {
+			let offsets = crate::vm::insn::decode_all(&code).unwrap();
+			let debug_info = DebugInfo {
+				function: builder.name(name),
+				file: builder.name("gen.es"),
+				lines: offsets.iter().zip(&self.lines).map(|((offset, _), line)| LineEntry {
+					offset: *offset as u32,
+					line: *line,
+				}).collect(),
+				locals: (first..args.len() as u8).map(|local| LocalEntry {
+					local,
+					name: builder.name(&format!("a{}", local)),
+				}).collect(),
+			};
+			builder.debug_info.push(debug_info);
+		}
+		Function {
+			name: builder.name(name),
+			ret: ty,
+			args,
+			code,
+		}
+	}
+}
+
+/// The type of a field, whose class (of an object) is resolved modulo the number of classes
+#[derive(Clone, Debug)]
+enum FieldTy {
+	Int(IntTy),
+	F32,
+	F64,
+	Str,
+	Dyn,
+	Array(IntTy),
+	Object(Index),
+}
+
+#[derive(Clone, Debug)]
+struct ClassSpec {
+	/// The class it extends (resolved modulo the number of classes before it), if any
+	super_class: Option<Index>,
+	fields: Vec<FieldTy>,
+	methods: Vec<ArithFn>,
+}
+
+/// A constant that no instruction refers to
+#[derive(Clone, Debug)]
+enum ConstantSpec {
+	Int(IntTy, u64),
+	F32(u32),
+	F64(u64),
+	Str(String),
+	/// An array of integers (a name if they're `i8`s)
+	Array(IntTy, Vec<u8>),
+	/// Opaque data of an object of a class (resolved modulo the number of classes)
+	Object(Index, Vec<u8>),
+}
+
+/// A generated module, and the arithmetic functions it's made of
+#[derive(Clone, Debug)]
+struct Module {
+	builder: ExecutableBuilder,
+	/// The functions by name
+	functions: Vec<(String, ArithFn)>,
+	/// The methods by name
+	methods: Vec<(String, ArithFn)>,
+}
+
+fn int_ty() -> impl Strategy<Value = IntTy> {
+	prop::sample::select(IntTy::ALL.to_vec())
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+	let leaf = prop_oneof![
+		any::<u8>().prop_map(Expr::Var),
+		any::<u64>().prop_map(Expr::Const),
+	];
+	leaf.prop_recursive(6, 48, 2, |inner| prop_oneof![
+		(inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Add(Box::new(a), Box::new(b))),
+		(inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Sub(Box::new(a), Box::new(b))),
+		(inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Mul(Box::new(a), Box::new(b))),
+		(inner.clone(), any::<u64>()).prop_map(|(a, b)| Expr::Div(Box::new(a), b)),
+		inner.clone().prop_map(|a| Expr::Inc(Box::new(a))),
+		inner.clone().prop_map(|a| Expr::Dec(Box::new(a))),
+		(inner.clone(), inner).prop_map(|(value, body)| Expr::Let(Box::new(value), Box::new(body))),
+	])
+}
+
+fn arith_fn() -> impl Strategy<Value = ArithFn> {
+	(int_ty(), expr(), prop::collection::vec(any::<u64>(), 1..6), prop::collection::vec(1..1000u32, 0..4))
+		.prop_map(|(ty, body, args, lines)| ArithFn {
+			ty,
+			body,
+			args,
+			lines,
+		})
+}
+
+fn field_ty() -> impl Strategy<Value = FieldTy> {
+	prop_oneof![
+		int_ty().prop_map(FieldTy::Int),
+		Just(FieldTy::F32),
+		Just(FieldTy::F64),
+		Just(FieldTy::Str),
+		Just(FieldTy::Dyn),
+		int_ty().prop_map(FieldTy::Array),
+		any::<Index>().prop_map(FieldTy::Object),
+	]
+}
+
+fn class_spec() -> impl Strategy<Value = ClassSpec> {
+	(
+		prop::option::of(any::<Index>()),
+		prop::collection::vec(field_ty(), 0..5),
+		prop::collection::vec(arith_fn(), 0..3),
+	).prop_map(|(super_class, fields, methods)| ClassSpec {
+		super_class,
+		fields,
+		methods,
+	})
+}
+
+fn constant_spec() -> impl Strategy<Value = ConstantSpec> {
+	prop_oneof![
+		(int_ty(), any::<u64>()).prop_map(|(ty, value)| ConstantSpec::Int(ty, value)),
+		any::<u32>().prop_map(ConstantSpec::F32),
+		any::<u64>().prop_map(ConstantSpec::F64),
+		".*".prop_map(ConstantSpec::Str),
+		(int_ty(), prop::collection::vec(any::<u8>(), 0..16)).prop_map(|(elem, data)| ConstantSpec::Array(elem, data)),
+		(any::<Index>(), prop::collection::vec(any::<u8>(), 0..16)).prop_map(|(class, data)| ConstantSpec::Object(class, data)),
+	]
+}
+
+/// Generates valid modules, of constants of every type a constant can have, classes with fields and
+/// methods, functions, global fields and debug info
+fn module() -> impl Strategy<Value = Module> {
+	(
+		prop::collection::vec(constant_spec(), 0..8),
+		prop::collection::vec(class_spec(), 0..4),
+		prop::collection::vec(arith_fn(), 0..6),
+		prop::collection::vec(field_ty(), 0..4),
+		any::<bool>(),
+	).prop_map(|(constants, classes, functions, fields, checksum)| {
+		let mut builder = ExecutableBuilder::new();
+		builder.checksum = checksum;
+		let class_names = (0..classes.len()).map(|class| format!("gen.C{}", class)).collect::<Vec<_>>();
+		let field_ty = |builder: &mut ExecutableBuilder, ty: &FieldTy| match ty {
+			FieldTy::Int(ty) => ty.type_desc(),
+			FieldTy::F32 => TypeDesc::new(TypeFlags::F32, None),
+			FieldTy::F64 => TypeDesc::new(TypeFlags::F64, None),
+			FieldTy::Str => TypeDesc::new(TypeFlags::STR, None),
+			FieldTy::Dyn => TypeDesc::new(TypeFlags::DYN, None),
+			FieldTy::Array(elem) => TypeDesc::new(TypeFlags::ARRAY, Some(elem.type_desc().flags as u16)),
+			// an object needs a class
+			FieldTy::Object(_) if class_names.is_empty() => TypeDesc::new(TypeFlags::STR, None),
+			FieldTy::Object(class) => TypeDesc::new(TypeFlags::OBJECT, Some(builder.name(class.get::<String>(&class_names)))),
+		};
+
+		for constant in constants {
+			let constant = match constant {
+				ConstantSpec::Int(ty, value) => Constant::primitive(ty.type_desc(), value),
+				ConstantSpec::F32(bits) => Constant::primitive(TypeDesc::new(TypeFlags::F32, None), bits as u64),
+				ConstantSpec::F64(bits) => Constant::primitive(TypeDesc::new(TypeFlags::F64, None), bits),
+				ConstantSpec::Str(str) => Constant {
+					ty: TypeDesc::new(TypeFlags::STR, None),
+					data: str.into_bytes(),
+				},
+				ConstantSpec::Array(elem, data) => Constant {
+					ty: TypeDesc::new(TypeFlags::ARRAY, Some(elem.type_desc().flags as u16)),
+					data,
+				},
+				ConstantSpec::Object(_, _) if class_names.is_empty() => continue,
+				ConstantSpec::Object(class, data) => Constant {
+					ty: TypeDesc::new(TypeFlags::OBJECT, Some(builder.name(class.get::<String>(&class_names)))),
+					data,
+				},
+			};
+			builder.constant(constant);
+		}
+
+		let mut methods = Vec::new();
+		for (index, class) in classes.iter().enumerate() {
+			let name = builder.name(&class_names[index]);
+			let super_name = match class.super_class {
+				Some(super_class) if index > 0 => builder.name(super_class.get::<String>(&class_names[..index])),
+				_ => name,
+			};
+			// fields can't share a name with inherited ones
+			let fields = class.fields.iter().enumerate().map(|(field, ty)| Field {
+				name: builder.name(&format!("f{}_{}", index, field)),
+				ty: field_ty(&mut builder, ty),
+			}).collect();
+			let receiver = TypeDesc::new(TypeFlags::OBJECT, Some(name));
+			let defs = class.methods.iter().enumerate().map(|(method, function)| {
+				let method_name = format!("{}.m{}", class_names[index], method);
+				let def = function.build(&mut builder, &method_name, Some(receiver));
+				methods.push((method_name, function.clone()));
+				def
+			}).collect();
+			builder.classes.push(Class {
+				name,
+				super_name,
+				fields,
+				methods: defs,
+			});
+		}
+		let functions = functions.into_iter().enumerate().map(|(index, function)| {
+			let name = format!("gen.f{}", index);
+			let def = function.build(&mut builder, &name, None);
+			builder.functions.push(def);
+			(name, function)
+		}).collect();
+		for (index, ty) in fields.iter().enumerate() {
+			let field = Field {
+				name: builder.name(&format!("gen.g{}", index)),
+				ty: field_ty(&mut builder, ty),
+			};
+			builder.fields.push(field);
+		}
+
+		Module {
+			builder,
+			functions,
+			methods,
+		}
+	})
+}
+
+proptest! {
+	#[test]
+	fn parse_recovers_module(module in module()) {
+		let bytes = module.builder.build();
+		let exec = Executable::parse(&bytes).unwrap();
+		prop_assert_eq!(&ExecutableBuilder::from(&exec), &module.builder);
+		for (index, constant) in module.builder.constants.iter().enumerate() {
+			let is_str = constant.ty.id() == TypeFlags::STR || constant.ty.operand == Some(TypeFlags::I8 as u16);
+			if let (true, Ok(str)) = (is_str, std::str::from_utf8(&constant.data)) {
+				prop_assert_eq!(exec.constant_table().str(index as u16), Some(str));
+			}
+		}
+	}
+
+	#[test]
+	fn jit_matches_model(module in module()) {
+		let mut vm = Vm::with_engine(Engine::Jit);
+		vm.load(Executable::parse(&module.builder.build()).unwrap()).unwrap();
+		for (name, function) in &module.functions {
+			prop_assert_eq!(vm.call(name, &function.args()).unwrap(), function.eval(), "{} returned the wrong value", name);
+		}
+		for (name, method) in &module.methods {
+			// the body doesn't use the receiver
+			let args = [&[0][..], &method.args()].concat();
+			prop_assert_eq!(vm.call(name, &args).unwrap(), method.eval(), "{} returned the wrong value", name);
+		}
+	}
+
+	#[test]
+	fn illegal_constant_type_is_rejected(id in prop::sample::select(vec![TypeFlags::FUNCTION, TypeFlags::DYN, TypeFlags::VOID, 0xB, 0xC, 0xD, 0xE])) {
+		let mut builder = ExecutableBuilder::new();
+		let operand = (id == TypeFlags::FUNCTION).then(|| 0);
+		builder.constant(Constant {
+			ty: TypeDesc::new(id, operand),
+			data: vec![0; 8],
+		});
+		prop_assert!(Executable::parse(&builder.build()).is_err());
+	}
+}
@@ -0,0 +1,46 @@
+use crate::compiler::error::repl::ReplError;
+use crate::compiler::repl::{self, Output, Repl};
+use crate::compiler::typed::{IntTy, Ty};
+use crate::vm::Engine;
+
+fn value(output: Result<Output, ReplError>) -> String {
+	match output {
+		Ok(Output::Value(value, ty)) => format!("{}: {}", value, ty),
+		output => panic!("expected a value, found {:?}", output.map_err(|err| err.to_string())),
+	}
+}
+
+#[test]
+fn eval_inputs() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut repl = Repl::new(engine);
+		// collect at every safepoint of compiled code, so values that aren't rooted are freed
+		repl.vm().heap().set_threshold(0);
+		assert_eq!(repl.eval("1 + 2").unwrap(), Output::Value(String::from("3"), Ty::Int(IntTy::I64)));
+		assert_eq!(value(repl.eval("let x = -5i8; x * 2")), "-10: i8");
+		assert_eq!(value(repl.eval("250u8 + 5")), "255: u8");
+		assert_eq!(value(repl.eval("\"foo\" + \"bar\"")), "\"foobar\": str");
+		assert_eq!(value(repl.eval("\"a\" == \"a\"")), "true: bool");
+		assert_eq!(repl.eval("let y = 1;").unwrap(), Output::Void);
+
+		// declarations persist across inputs
+		let defined = repl.eval("struct Point {\n\tx: f64,\n}\n\nimpl Point {\n\tfn scale(self, by: f64) -> f64 { self.x * by }\n}").unwrap();
+		assert_eq!(defined, Output::Defined(vec![String::from("Point")]));
+		assert_eq!(repl.eval("fn half(x: f64) -> f64 { x / 2.0 }").unwrap(), Output::Defined(vec![String::from("half")]));
+		assert_eq!(value(repl.eval("half(Point { x: 3.0 }.scale(3.0))")), "4.5: f64");
+		assert!(value(repl.eval("Point { x: 1.0 }")).starts_with("<Point at 0x"));
+
+		let errors = |output: Result<Output, ReplError>| output.unwrap_err().to_string();
+		assert_eq!(errors(repl.eval("half(\"a\")")), "1:6: mismatched types: expected `f64`, found `str`");
+		assert_eq!(errors(repl.eval("fn half() {}")), "`half` was declared by an earlier input, and can't be redeclared");
+		// variables are local to their input
+		assert_eq!(errors(repl.eval("y")), "1:1: cannot find `y`");
+		assert_eq!(errors(repl.eval("impl Point {\n\tfn y(self) -> f64 { 0.0 }\n}")), "`Point` was declared by an earlier input, impls must be entered with their struct");
+		assert!(matches!(repl.eval("1 / 0"), Err(ReplError::Vm(_))));
+		// failed inputs don't change the declarations
+		assert_eq!(value(repl.eval("half(5.0)")), "2.5: f64");
+	}
+
+	assert!(!repl::is_complete("fn f() {\n\tlet x = (1"));
+	assert!(repl::is_complete("fn f() {\n\tlet x = (1)\n}"));
+}
@@ -0,0 +1,474 @@
+use std::fs;
+use crate::compiler::{check, codegen, syntax};
+use crate::vm::bin::{asm, Executable};
+use crate::vm::error::interp::Trap;
+use crate::vm::error::verify::VerifyError;
+use crate::vm::error::vm::VmError;
+use crate::vm::types::{TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::object::{ClassLayout, ObjectHeader};
+use crate::vm::types::str::Str;
+use crate::vm::{Engine, LoadStats, Vm};
+
+const RUNTIME: &str = "
+fn garbage(n: i64) -> i64 {
+	let xs = [0i64; n];
+	xs.len() as i64
+}
+
+fn element(xs: [i64], i: i64) -> i64 {
+	xs[i]
+}
+
+fn third(i: i64) -> i64 {
+	let xs = [1, 2, 3];
+	element(xs, i) * 10
+}
+
+fn quotient(a: i64, b: i64) -> i64 {
+	a / b
+}
+
+fn discard(n: i64) {
+	let x = n * 7;
+}
+
+fn nested(s: str) -> str {
+	(s + \"-\" + s) + (s.concat(\"+\") + s)
+}
+";
+
+const ARRAYS: &str = "
+fn get(xs: [i16], i: i64) -> i16 {
+	xs[i]
+}
+
+fn set(xs: [i16], i: i64, value: i16) {
+	xs[i] = value;
+}
+
+fn len(xs: [i16]) -> i64 {
+	xs.len() as i64
+}
+";
+
+const OBJECTS: &str = "
+class Base
+	field flag i8
+	field count i64
+end
+
+class Derived : Base
+	field small i16
+	field ratio f32
+end
+
+fn count(Derived) -> i64
+	push Derived 0
+	getfield Derived count
+	vret i64
+end
+
+fn set_small(Derived, i16)
+	push Derived 0
+	push i16 1
+	setfield Derived small
+	ret
+end
+";
+
+/// The classes of the adversarial modules, which a verifier that took any object (or array) for
+/// another would let access memory out of bounds
+const ADVERSARIAL: &str = "
+class Shape
+	method Shape.area(Shape) -> i64
+	end
+end
+
+class Small
+	field x i64
+	method Small.get(Small) -> i64
+		push Small 0
+		getfield Small x
+		vret i64
+	end
+end
+
+class Big
+	field x i64
+	field far i64
+	method Big.get(Big) -> i64
+		push Big 0
+		getfield Big far
+		vret i64
+	end
+end
+
+; every field is left null
+class Holder
+	field small Small
+	field xs [i64]
+	field shape dyn
+	field name str
+end
+";
+
+fn compile(src: &str) -> Executable {
+	let (file, diagnostics) = syntax::parse(src);
+	assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+	let program = check::check(&[file]).unwrap_or_else(|diagnostics| panic!("{:?}", diagnostics));
+	codegen::generate(&program).unwrap().finish()
+}
+
+#[test]
+fn object_fields() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(asm::assemble(OBJECTS).unwrap().finish()).unwrap();
+
+		// fields are aligned to their size after the header, inherited fields first
+		let base = vm.classes().get("Base").unwrap();
+		let derived = vm.classes().get("Derived").unwrap();
+		let offsets = |class: &ClassLayout| class.fields().iter().map(|field| (field.name().to_string(), field.offset())).collect::<Vec<_>>();
+		assert_eq!(offsets(base), [(String::from("flag"), 8), (String::from("count"), 16)]);
+		assert_eq!(offsets(derived), [(String::from("flag"), 8), (String::from("count"), 16), (String::from("small"), 24), (String::from("ratio"), 28)]);
+		assert_eq!((base.size(), base.align()), (24, 8));
+		assert_eq!((derived.size(), derived.align()), (32, 8));
+		assert_eq!(derived.super_name(), Some("Base"));
+
+		let obj = vm.instantiate("Derived").unwrap();
+		vm.heap().root(obj as *const u8);
+		unsafe {
+			let count = ObjectHeader::field_ptr(obj, derived.field("count").unwrap()) as *mut i64;
+			let small = ObjectHeader::field_ptr(obj, derived.field("small").unwrap()) as *mut i16;
+			assert_eq!(vm.call("count", &[obj as u64]).unwrap(), 0, "{:?}", engine);
+			*count = -42;
+			assert_eq!(vm.call("count", &[obj as u64]).unwrap() as i64, -42, "{:?}", engine);
+			vm.call("set_small", &[obj as u64, -2i16 as u16 as u64]).unwrap();
+			assert_eq!(*small, -2, "{:?}", engine);
+			// the neighbouring fields are left alone
+			assert_eq!(*count, -42);
+			assert_eq!(*(ObjectHeader::field_ptr(obj, derived.field("ratio").unwrap()) as *const f32), 0.0);
+			assert_eq!((*obj).class().name(), "Derived");
+		}
+		vm.heap().unroot(obj as *const u8);
+	}
+}
+
+#[test]
+fn collect_while_allocating() {
+	let mut vm = Vm::with_engine(Engine::Jit);
+	vm.load(compile(RUNTIME)).unwrap();
+	vm.heap().set_threshold(1 << 16);
+	let constants = vm.heap_stats().bytes;
+	// a live set larger than the threshold, which the heap grows with
+	let live = vm.new_array(TypeDesc::new(TypeFlags::I64, None), 1 << 17);
+	vm.heap().root(live as *const u8);
+	let rooted = vm.heap_stats().bytes;
+
+	for _ in 0..256 {
+		assert_eq!(vm.call("garbage", &[1 << 12]).unwrap(), 1 << 12);
+	}
+	let stats = vm.heap_stats();
+	// 8 MiB were allocated, a collection is due after each MiB
+	assert!(stats.collections > 4, "{:?}", stats);
+	assert!(stats.bytes < 2 * rooted + (1 << 16), "{:?}", stats);
+
+	vm.heap().unroot(live as *const u8);
+	vm.gc();
+	assert_eq!(vm.heap_stats().bytes, constants);
+}
+
+#[test]
+fn concat_temporaries_survive_collections() {
+	let mut vm = Vm::with_engine(Engine::Jit);
+	vm.load(compile(RUNTIME)).unwrap();
+	// collect as often as the live set allows, the operands of each concatenation are unreachable temporaries
+	vm.heap().set_threshold(0);
+	let arg = vm.new_str("abcdefgh");
+	vm.heap().root(arg as *const u8);
+	for _ in 0..64 {
+		let str = vm.call("nested", &[arg as u64]).unwrap() as *const Str;
+		assert_eq!(unsafe { (*str).as_str() }, "abcdefgh-abcdefghabcdefgh+abcdefgh");
+	}
+	assert!(vm.heap_stats().collections > 64, "{:?}", vm.heap_stats());
+}
+
+#[test]
+fn traps_unwind_to_the_caller() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(compile(RUNTIME)).unwrap();
+		let trap = |name: &str, args: &[u64]| match vm.call(name, args) {
+			Err(VmError::Trap(trap)) => trap.to_string(),
+			result => panic!("{:?} {}: expected a trap, found {:?}", engine, name, result.map_err(|err| err.to_string())),
+		};
+
+		// through the frame of `third`
+		assert_eq!(trap("third", &[3]), "index 3 out of bounds for length 3");
+		assert_eq!(trap("third", &[-1i64 as u64]), "index -1 out of bounds for length 3");
+		assert_eq!(trap("quotient", &[1, 0]), "division by zero");
+		assert_eq!(trap("garbage", &[-2i64 as u64]), "negative array length -2");
+		// the VM is still usable
+		assert_eq!(vm.call("third", &[2]).unwrap(), 30, "{:?}", engine);
+		assert_eq!(vm.call("quotient", &[7, 2]).unwrap(), 3, "{:?}", engine);
+	}
+}
+
+#[test]
+fn array_elements_are_bounds_checked() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(compile(ARRAYS)).unwrap();
+		let xs = vm.new_array(TypeDesc::new(TypeFlags::I16, None), 4);
+		vm.heap().root(xs as *const u8);
+		unsafe {
+			// the elements are stored unboxed, so the host views them in place
+			(*xs).as_mut_slice::<i16>().unwrap().copy_from_slice(&[1, 2, 3, 4]);
+			assert!((*xs).as_slice::<i64>().is_none());
+			assert_eq!(vm.call("len", &[xs as u64]).unwrap(), 4, "{:?}", engine);
+			assert_eq!(vm.call("get", &[xs as u64, 1]).unwrap() as i16, 2, "{:?}", engine);
+			vm.call("set", &[xs as u64, 2, -7i16 as u16 as u64]).unwrap();
+			assert_eq!((*xs).as_slice::<i16>().unwrap(), [1, 2, -7, 4], "{:?}", engine);
+
+			for (name, args) in [("get", vec![xs as u64, 4]), ("set", vec![xs as u64, 4, 9]), ("set", vec![xs as u64, -1i64 as u64, 9])] {
+				let err = vm.call(name, &args).unwrap_err();
+				assert!(matches!(err, VmError::Trap(Trap::IndexOutOfBounds(_, 4))), "{:?} {}: {}", engine, name, err);
+			}
+			// out-of-bounds stores don't write anything
+			assert_eq!((*xs).as_slice::<i16>().unwrap(), [1, 2, -7, 4], "{:?}", engine);
+		}
+		vm.heap().unroot(xs as *const u8);
+	}
+}
+
+#[test]
+fn missing_trait_impl_traps() {
+	let src = fs::read_to_string("src/tests/golden/objects.esasm").unwrap();
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(asm::assemble(&src).unwrap().finish()).unwrap();
+		// a rect dispatches to its own area, through the vtable of its superclass's method
+		assert_eq!(vm.call("rect", &[3, 4]).unwrap(), 12, "{:?}", engine);
+		assert_eq!(vm.call("via_trait", &[5]).unwrap(), 25, "{:?}", engine);
+		match vm.call("not_shape", &[]) {
+			Err(VmError::Trap(Trap::NotImplemented(class, trait_name))) => {
+				assert_eq!((class.as_str(), trait_name.as_str()), ("shapes.Point", "shapes.Shape"));
+			},
+			result => panic!("{:?}: expected a trap, found {:?}", engine, result.map_err(|err| err.to_string())),
+		}
+	}
+}
+
+#[test]
+fn void_functions_return_zero() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(compile(RUNTIME)).unwrap();
+		assert_eq!(vm.call("discard", &[1]).unwrap(), 0, "{:?}", engine);
+	}
+}
+
+#[test]
+fn lazy_functions_materialize_on_first_call() {
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.set_lazy(true);
+		vm.load(compile(RUNTIME)).unwrap();
+		assert_eq!(vm.load_stats(), LoadStats { functions: 6, materialized: 0 }, "{:?}", engine);
+
+		// materializing a function leaves the functions it calls to their first call
+		vm.materialize("third").unwrap();
+		assert_eq!(vm.load_stats(), LoadStats { functions: 6, materialized: 1 }, "{:?}", engine);
+		assert_eq!(vm.call("third", &[2]).unwrap(), 30);
+		assert_eq!(vm.load_stats(), LoadStats { functions: 6, materialized: 2 }, "{:?}", engine);
+		assert_eq!(vm.call("third", &[0]).unwrap(), 10);
+		assert_eq!(vm.call("quotient", &[7, 2]).unwrap(), 3);
+		assert_eq!(vm.load_stats(), LoadStats { functions: 6, materialized: 3 }, "{:?}", engine);
+	}
+}
+
+#[test]
+fn lazy_callees_materialize_on_first_call() {
+	let module = format!("{}{}", ADVERSARIAL, "
+fn get() -> i64
+ new Small
+ callvirt Small Small.get
+ vret i64
+end
+
+fn bad() -> i64
+ call confuse
+ vret i64
+end
+
+fn confuse() -> i64
+ new Small
+ getfield Big far
+ vret i64
+end
+");
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.set_lazy(true);
+		vm.load(asm::assemble(&module).unwrap().finish()).unwrap();
+
+		// methods are materialized when they're dispatched to
+		assert_eq!(vm.call("get", &[]).unwrap(), 0, "{:?}", engine);
+		assert_eq!(vm.load_stats().materialized, 2, "{:?}", engine);
+		// and functions that fail to verify only fail once they're called
+		let err = vm.call("bad", &[]).unwrap_err();
+		assert!(matches!(&err, VmError::Verify(name, VerifyError::ClassMismatch(..)) if name == "confuse"), "{:?}: {}", engine, err);
+		assert_eq!(vm.call("get", &[]).unwrap(), 0, "{:?}", engine);
+	}
+}
+
+#[test]
+fn adversarial_modules_are_rejected() {
+	// whether each module is rejected for the class of an object (rather than for its type)
+	let modules = [
+		// an integer read as an object
+		("fn forge() -> i64\n ldc i64 1\n newarray i64\n ldc i64 0\n aload Small\n getfield Small x\n vret i64\nend", false),
+		("fn forge() -> i64\n ldc i64 1\n newarray Small\n ldc i64 0\n ldc i64 4096\n astore i64\n ldc i64 0\n vret i64\nend", false),
+		("fn forge() -> i64\n ldc i64 1\n newarray i16\n ldc i64 0\n aload i64\n vret i64\nend", false),
+		// a small object used as a bigger one
+		("fn confuse() -> i64\n ldc i64 1\n newarray Small\n ldc i64 0\n aload Big\n getfield Big far\n vret i64\nend", true),
+		("fn confuse() -> i64\n new Small\n getfield Big far\n vret i64\nend", true),
+		("fn confuse()\n new Small\n ldc i64 1\n setfield Big far\n ret\nend", true),
+		("fn confuse() -> i64\n new Small\n callvirt Big Big.get\n vret i64\nend", true),
+		("fn confuse() -> i64\n new Small\n call Big.get\n vret i64\nend", true),
+		("fn confuse() -> Big\n new Small\n vret Big\nend", true),
+	];
+	for (function, class) in modules {
+		for engine in [Engine::Interpreter, Engine::Jit] {
+			let mut vm = Vm::with_engine(engine);
+			let err = vm.load(asm::assemble(&format!("{}\n{}", ADVERSARIAL, function)).unwrap().finish()).unwrap_err();
+			let rejected = match &err {
+				VmError::Verify(_, VerifyError::ClassMismatch(..)) => class,
+				VmError::Verify(_, VerifyError::TypeMismatch(..)) => !class,
+				_ => false,
+			};
+			assert!(rejected, "{:?} {}: {}", engine, function, err);
+		}
+	}
+}
+
+#[test]
+fn failed_loads_are_undone() {
+	let bad = [
+		// fails to verify
+		format!("{}{}", ADVERSARIAL, "fn confuse() -> i64\n new Small\n getfield Big far\n vret i64\nend"),
+		// defines a function that is already defined
+		format!("{}{}", ADVERSARIAL, "fn third() -> i64\n ldc i64 0\n vret i64\nend"),
+		// extends a class that doesn't exist
+		format!("{}{}", ADVERSARIAL, "class Orphan : Missing\nend"),
+	];
+	let good = format!("{}{}", ADVERSARIAL, "fn get() -> i64\n new Small\n callvirt Small Small.get\n vret i64\nend");
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(compile(RUNTIME)).unwrap();
+		let stats = vm.load_stats();
+		for src in &bad {
+			assert!(vm.load(asm::assemble(src).unwrap().finish()).is_err(), "{:?} {}", engine, src);
+			// nothing of the executable stays defined
+			assert!(vm.classes().get("Small").is_none(), "{:?} {}", engine, src);
+			assert!(vm.functions().get("Small.get").is_none() && vm.definition("Small.get").is_none(), "{:?} {}", engine, src);
+			assert_eq!((vm.executables().len(), vm.load_stats()), (1, stats), "{:?} {}", engine, src);
+		}
+
+		// so its classes and functions can still be defined, and the VM is still usable
+		vm.load(asm::assemble(&good).unwrap().finish()).unwrap();
+		assert_eq!(vm.call("get", &[]).unwrap(), 0, "{:?}", engine);
+		assert_eq!(vm.call("third", &[2]).unwrap(), 30, "{:?}", engine);
+	}
+}
+
+#[test]
+fn null_references_trap() {
+	let src = format!("{}{}", ADVERSARIAL, "
+fn get_field() -> i64
+	new Holder
+	getfield Holder small
+	getfield Small x
+	vret i64
+end
+
+fn set_field()
+	new Holder
+	getfield Holder small
+	ldc i64 1
+	setfield Small x
+	ret
+end
+
+fn load() -> i64
+	new Holder
+	getfield Holder xs
+	ldc i64 0
+	aload i64
+	vret i64
+end
+
+fn store()
+	new Holder
+	getfield Holder xs
+	ldc i64 0
+	ldc i64 1
+	astore i64
+	ret
+end
+
+fn len() -> i64
+	new Holder
+	getfield Holder xs
+	alen
+	vret i64
+end
+
+fn virtual() -> i64
+	new Holder
+	getfield Holder small
+	callvirt Small Small.get
+	vret i64
+end
+
+fn to_dyn() -> i64
+	new Holder
+	getfield Holder small
+	todyn Shape
+	calltrait Shape Shape.area
+	vret i64
+end
+
+fn trait() -> i64
+	new Holder
+	getfield Holder shape
+	calltrait Shape Shape.area
+	vret i64
+end
+
+fn str_len() -> i64
+	new Holder
+	getfield Holder name
+	call str.len
+	vret i64
+end
+
+fn abstract() -> i64
+	new Shape
+	call Shape.area
+	vret i64
+end
+");
+	for engine in [Engine::Interpreter, Engine::Jit] {
+		let mut vm = Vm::with_engine(engine);
+		vm.load(asm::assemble(&src).unwrap().finish()).unwrap();
+		for name in ["get_field", "set_field", "load", "store", "len", "virtual", "to_dyn", "trait", "str_len"] {
+			let err = vm.call(name, &[]).unwrap_err();
+			assert!(matches!(err, VmError::Trap(Trap::NullReference)), "{:?} {}: {}", engine, name, err);
+		}
+		let err = vm.call("abstract", &[]).unwrap_err();
+		assert!(matches!(err, VmError::Trap(Trap::UndefinedFunction(_))), "{:?}: {}", engine, err);
+		// the VM is still usable
+		assert_eq!(vm.call("Small.get", &[vm.instantiate("Small").unwrap() as u64]).unwrap(), 0, "{:?}", engine);
+	}
+}
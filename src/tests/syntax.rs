@@ -0,0 +1,137 @@
+use crate::compiler::error::syntax::SyntaxError;
+use crate::compiler::syntax;
+use crate::compiler::syntax::ast::{BinOp, ExprKind, Item, StmtKind, TypeKind};
+use crate::compiler::syntax::lexer::{self, TokenKind};
+
+const SHAPES: &str = r#"
+package shapes;
+
+use std.io.print;
+
+trait Shape {
+	fn area(self) -> f64;
+}
+
+struct Square {
+	side: f64,
+}
+
+struct Rect: Square {
+	height: f64,
+}
+
+impl Shape for Rect {
+	fn area(self) -> f64 {
+		self.side * self.height
+	}
+}
+
+fn total(shapes: [dyn Shape]) -> f64 {
+	let mut sum = 0.0;
+	let mut i: i64 = 0;
+	while i < shapes.len() {
+		sum += shapes[i].area();
+		i = i + 1;
+	}
+	if sum > 100.0 { print("big"); } else if sum < 0f64 { return -1.0; }
+	sum
+}
+
+fn square(side: f64) -> Square {
+	Square { side }
+}
+"#;
+
+#[test]
+fn parse_file() {
+	let (file, diagnostics) = syntax::parse(SHAPES);
+	assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+	assert_eq!(file.package.unwrap().to_string(), "shapes");
+	assert_eq!(file.uses.iter().map(ToString::to_string).collect::<Vec<_>>(), ["std.io.print"]);
+	assert_eq!(file.items.len(), 6);
+
+	let Item::Trait(shape) = &file.items[0] else { panic!("expected a trait") };
+	assert!(shape.methods[0].receiver.is_some() && shape.methods[0].body.is_none());
+	let Item::Struct(rect) = &file.items[2] else { panic!("expected a struct") };
+	assert_eq!(rect.parent.as_ref().unwrap().to_string(), "Square");
+	let Item::Impl(imp) = &file.items[3] else { panic!("expected an impl") };
+	assert_eq!(imp.trait_name.as_ref().unwrap().to_string(), "Shape");
+
+	let Item::Fn(total) = &file.items[4] else { panic!("expected a function") };
+	assert!(matches!(&total.params[0].ty.kind, TypeKind::Array(elem) if matches!(elem.kind, TypeKind::Dyn(_))));
+	let body = total.body.as_ref().unwrap();
+	assert_eq!(body.stmts.len(), 4);
+	assert!(matches!(&body.stmts[2].kind, StmtKind::While(..)));
+	assert!(matches!(&body.stmts[3].kind, StmtKind::Expr(expr) if matches!(expr.kind, ExprKind::If(..))));
+	assert!(matches!(&body.expr.as_ref().unwrap().kind, ExprKind::Path(path) if path.to_string() == "sum"));
+	assert_eq!(&SHAPES[body.stmts[0].span.start..body.stmts[0].span.end], "let mut sum = 0.0;");
+}
+
+#[test]
+fn precedence() {
+	let (file, diagnostics) = syntax::parse("fn f() -> i32 { 1 + 2 * -x as i32 << 1 == y && z }");
+	assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+	let Item::Fn(f) = &file.items[0] else { panic!("expected a function") };
+	let ExprKind::Binary(BinOp::LogicalAnd, eq, _) = &f.body.as_ref().unwrap().expr.as_ref().unwrap().kind else { panic!("expected `&&`") };
+	let ExprKind::Binary(BinOp::Eq, shl, _) = &eq.kind else { panic!("expected `==`") };
+	let ExprKind::Binary(BinOp::Shl, add, _) = &shl.kind else { panic!("expected `<<`") };
+	let ExprKind::Binary(BinOp::Add, _, mul) = &add.kind else { panic!("expected `+`") };
+	let ExprKind::Binary(BinOp::Mul, _, cast) = &mul.kind else { panic!("expected `*`") };
+	assert!(matches!(&cast.kind, ExprKind::Cast(neg, _) if matches!(neg.kind, ExprKind::Unary(..))));
+}
+
+#[test]
+fn lex_numbers() {
+	let (tokens, diagnostics) = lexer::tokenize("0xFFu8 1_000 2.5e3f32 7.len 1e 300i9 /* comment */ 18446744073709551616");
+	let kinds = tokens.into_iter().map(|token| token.kind).collect::<Vec<_>>();
+	assert_eq!(kinds[..4], [TokenKind::Int(255, Some(String::from("u8"))), TokenKind::Int(1000, None), TokenKind::Float(2500.0, Some(String::from("f32"))), TokenKind::Int(7, None)]);
+	let invalid = diagnostics.iter().map(|diagnostic| diagnostic.error.clone()).collect::<Vec<_>>();
+	assert_eq!(invalid, [
+		SyntaxError::InvalidNumber(String::from("1e")),
+		SyntaxError::InvalidNumber(String::from("300i9")),
+		SyntaxError::InvalidNumber(String::from("18446744073709551616")),
+	]);
+}
+
+#[test]
+fn recover_from_errors() {
+	let src = "
+struct A {
+	x: i32,
+	y i32,
+}
+
+fn broken(a: i32 {
+}
+
+fn f() {
+	let = 1;
+	let b = (1 + ;
+	g(1, , 3);
+	a < b < c;
+	\"unterminated
+}
+
+package late;
+
+fn ok() -> i32 { 1 }
+";
+	let (file, diagnostics) = syntax::parse(src);
+	let messages = diagnostics.iter().map(|diagnostic| diagnostic.format("test.es", src)).collect::<Vec<_>>();
+	assert_eq!(messages, [
+		"test.es:4:4: expected `:`, found `i32`",
+		"test.es:7:18: expected `,` or `)`, found `{`",
+		"test.es:11:6: expected a variable name, found `=`",
+		"test.es:12:15: expected an expression, found `;`",
+		"test.es:13:7: expected an expression, found `,`",
+		"test.es:14:8: comparison operators cannot be chained, use parentheses",
+		"test.es:15:2: unterminated string",
+		"test.es:18:1: the package declaration must come before any other item",
+	]);
+	let names = file.items.iter().filter_map(|item| match item {
+		Item::Struct(item) => Some(item.name.name.as_str()),
+		Item::Fn(item) => Some(item.name.name.as_str()),
+		_ => None,
+	}).collect::<Vec<_>>();
+	assert_eq!(names, ["A", "f", "ok"]);
+}
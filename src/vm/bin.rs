@@ -6,17 +6,29 @@ use std::marker::PhantomData;
 use std::os::raw::c_char;
 
 use serde::{Deserialize, Deserializer};
-use serde::de::{Error, Unexpected, Visitor};
+use serde::de::{DeserializeSeed, Error, Unexpected, Visitor};
 use serde::de::value::BytesDeserializer;
 
 use crate::util;
 use def::constant::ConstantTable;
+use crate::vm::bin::buffer::{Bytes, Shared};
 use crate::vm::bin::def::class::ClassTable;
+use crate::vm::bin::def::field::FieldTable;
+use crate::vm::bin::def::function::FunctionTable;
+use crate::vm::bin::def::debug::{DebugInfoTable, SourceLocation};
+use crate::vm::bin::def::symbol::SymbolTable;
+use crate::vm::bin::header::{Feature, Features, Version};
 use crate::vm::bin::offset::Offsets;
 use crate::vm::error::jit::{ExecutableFormatError, FormatError};
 
+pub mod buffer;
 pub mod def;
 pub mod offset;
+pub mod inspect;
+pub mod header;
+pub mod builder;
+pub mod link;
+pub mod asm;
 
 #[macro_export]
 macro_rules! page_align {
@@ -28,17 +40,25 @@ macro_rules! page_align {
 /// An E# binary (executable, library, etc.)
 pub trait BinaryFile {
 	/// Returns the contents of the file
-	fn buf(&mut self) -> &mut [u8];
+	fn buf(&self) -> &[u8];
 	fn size(&self) -> usize;
 }
 
 /// An E# executable<br>
 #[derive(Debug)]
 pub struct Executable {
-	buf: Box<[u8]>,
+	buf: Bytes,
 	size: usize,
 	offsets: Offsets,
+	version: Version,
+	features: Features,
 	constant_table: ConstantTable,
+	class_table: ClassTable,
+	function_table: FunctionTable,
+	field_table: FieldTable,
+	export_table: SymbolTable,
+	import_table: SymbolTable,
+	debug_info: DebugInfoTable,
 }
 
 impl Executable {
@@ -48,9 +68,63 @@ impl Executable {
 		self.offsets
 	}
 	
+	/// The revision of the E# standard the executable was built for
+	pub fn version(&self) -> Version {
+		self.version
+	}
+	
+	pub fn features(&self) -> Features {
+		self.features
+	}
+	
 	pub fn constant_table(&self) -> &ConstantTable {
 		&self.constant_table
 	}
+	
+	pub fn class_table(&self) -> &ClassTable {
+		&self.class_table
+	}
+	
+	pub fn function_table(&self) -> &FunctionTable {
+		&self.function_table
+	}
+	
+	pub fn field_table(&self) -> &FieldTable {
+		&self.field_table
+	}
+	
+	/// The classes and functions exported (if the executable is a [`DynamicLibrary`])
+	pub fn export_table(&self) -> &SymbolTable {
+		&self.export_table
+	}
+	
+	/// The classes and functions that must be exported by a loaded library
+	pub fn import_table(&self) -> &SymbolTable {
+		&self.import_table
+	}
+	
+	/// The debug information of the functions (empty if the executable has none)
+	pub fn debug_info(&self) -> &DebugInfoTable {
+		&self.debug_info
+	}
+	
+	/// Where the instruction at the bytecode offset of the function (or method) named `function` is in
+	/// the source, if the executable has debug information for it
+	pub fn source_location(&self, function: &str, offset: usize) -> Option<SourceLocation<'_>> {
+		let info = self.debug_info.find(&self.constant_table, function)?;
+		Some(SourceLocation {
+			file: self.constant_table.str(info.file())?,
+			line: info.line(offset)?,
+		})
+	}
+	
+	/// The name of a local variable of the function (or method) named `function`, if the executable
+	/// has debug information for it
+	pub fn local_name(&self, function: &str, local: u8) -> Option<&str> {
+		let info = self.debug_info.find(&self.constant_table, function)?;
+		let entry = info.locals().iter().find(|entry| entry.local == local)?;
+		self.constant_table.str(entry.name)
+	}
 }
 
 impl From<File> for Executable {
@@ -59,21 +133,43 @@ impl From<File> for Executable {
 
 		file.read_to_end(&mut buf).expect("Failed to read from executable file");
 
-		Executable::from(buf.as_slice())
+		Executable::parse_shared(Bytes::from(buf)).unwrap()
+	}
+}
+
+impl Executable {
+	/// Parses an executable, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<Executable, FormatError> {
+		Executable::parse_shared(Bytes::from(bytes))
+	}
+	
+	/// Parses an executable without copying it, the code and strings of its definitions borrow from
+	/// `bytes` (which can be a mapped file, see [`Bytes::map`])<br>
+	/// ***Note:** Every table is parsed (and checked to be well-formed) here, only the verification and
+	/// compilation of the functions can be deferred (see [`Vm::set_lazy`](crate::vm::Vm::set_lazy)).*
+	pub fn parse_shared(bytes: Bytes) -> Result<Executable, FormatError> {
+		Shared::<Executable>::new(&bytes).parse()
+	}
+	
+	/// The contents of the executable
+	pub fn bytes(&self) -> &Bytes {
+		&self.buf
 	}
 }
 
 impl From<&[u8]> for Executable {
 	fn from(bytes: &[u8]) -> Self {
-		Executable::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		Executable::parse(bytes).unwrap()
 	}
 }
 
-impl<'de> Deserialize<'de> for Executable {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct ExecutableVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, Executable> {
+	type Value = Executable;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct ExecutableVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for ExecutableVisitor {
+		impl<'de> Visitor<'de> for ExecutableVisitor<'_> {
 			type Value = Executable;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -81,31 +177,74 @@ impl<'de> Deserialize<'de> for Executable {
 			}
 			
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
-				let magic = util::deserialize::<u32>(&v[0..4]).unwrap();
+				let magic = def::read::<u32, E>(v, 0)?;
 				if magic != Executable::MAGIC {
 					return Err(Error::custom(ExecutableFormatError::InvalidMagic(magic)))
 				}
-				let buf = v.to_vec().into_boxed_slice();
+				let buf = self.0.slice_ref(v);
 				let size = buf.len();
-				let offsets = util::deserialize_trailing::<Offsets>(&v[4..36]).unwrap();
-				let constant_table = ConstantTable::from(v.split_at(36).1);
-				let class_table = ClassTable::from(&v[offsets.class_table() as usize..]);
+				let offsets = util::deserialize_trailing::<Offsets>(def::slice::<E>(v, 4, 32)?).map_err(Error::custom)?;
+				// the version and the features share the first reserved offset
+				let version = Version::from_bits(def::read::<u16, E>(v, 28)?);
+				if !version.is_supported() {
+					return Err(Error::custom(ExecutableFormatError::UnsupportedVersion(version)))
+				}
+				let features = def::read::<u16, E>(v, 30)?;
+				if features & !Features::KNOWN != 0 {
+					return Err(Error::custom(ExecutableFormatError::UnsupportedFeatures(features & !Features::KNOWN)))
+				}
+				if features & Features::CHECKSUM != 0 {
+					let expected = def::read::<u32, E>(v, 32)?;
+					let found = header::checksum(v);
+					if found != expected {
+						return Err(Error::custom(ExecutableFormatError::ChecksumMismatch(expected, found)))
+					}
+				}
+				let table = |offset: u32| match offset as usize {
+					offset if offset < size => Ok(buf.slice(offset..)),
+					_ => Err(Error::custom(ExecutableFormatError::InvalidOffset(offset))),
+				};
+				let constant_table = Shared::<ConstantTable>::new(&table(offsets.constant_table())?).parse::<E>()?;
+				let class_table = Shared::<ClassTable>::new(&table(offsets.class_table())?).parse::<E>()?;
+				let function_table = Shared::<FunctionTable>::new(&table(offsets.function_table())?).parse::<E>()?;
+				let field_table = FieldTable::deserialize(BytesDeserializer::<E>::new(&table(offsets.field_table())?))?;
+				let symbols = |offset: u32| match offset {
+					0 => Ok(SymbolTable::default()),
+					offset => SymbolTable::deserialize(BytesDeserializer::<E>::new(&table(offset)?)),
+				};
+				let export_table = symbols(offsets.export_table())?;
+				let import_table = symbols(offsets.import_table())?;
+				// the offsets of optional tables follow the header
+				let debug_info = if features & Features::DEBUG_INFO != 0 {
+					let offset = def::read::<u32, E>(v, 36)?;
+					DebugInfoTable::deserialize(BytesDeserializer::<E>::new(&table(offset)?))?
+				} else {
+					DebugInfoTable::default()
+				};
 				Ok(Executable {
 					buf,
 					size,
 					offsets,
+					version,
+					features,
 					constant_table,
+					class_table,
+					function_table,
+					field_table,
+					export_table,
+					import_table,
+					debug_info,
 				})
 			}
 		}
 		
-		deserializer.deserialize_struct("Executable", &["buf", "size", "offsets", "constant_table"], ExecutableVisitor)
+		deserializer.deserialize_struct("Executable", &["buf", "size", "offsets", "version", "features", "constant_table", "class_table", "function_table", "field_table", "export_table", "import_table", "debug_info"], ExecutableVisitor(self.bytes()))
 	}
 }
 
 impl BinaryFile for Executable {
-	fn buf(&mut self) -> &mut [u8] {
-		&mut *self.buf
+	fn buf(&self) -> &[u8] {
+		&self.buf
 	}
 
 	fn size(&self) -> usize {
@@ -113,20 +252,59 @@ impl BinaryFile for Executable {
 	}
 }
 
-/// An E# DyLib (Dynamic Library)
+/// An E# DyLib (Dynamic Library)<br>
+/// A library is an executable with an export table, only the classes and functions it exports can be
+/// used by other executables (see [`Vm::load_library`](crate::vm::Vm::load_library)).
+#[derive(Debug)]
 pub struct DynamicLibrary {
-	buf: Box<[u8]>,
-	size: usize,
+	exec: Executable,
+}
+
+impl DynamicLibrary {
+	/// Parses a library, returning an error if it is malformed or has no export table
+	pub fn parse(bytes: &[u8]) -> Result<DynamicLibrary, FormatError> {
+		DynamicLibrary::try_from(Executable::parse(bytes)?)
+	}
+	
+	/// Parses a library without copying it (see [`Executable::parse_shared`])
+	pub fn parse_shared(bytes: Bytes) -> Result<DynamicLibrary, FormatError> {
+		DynamicLibrary::try_from(Executable::parse_shared(bytes)?)
+	}
+	
+	pub fn executable(&self) -> &Executable {
+		&self.exec
+	}
+	
+	pub fn export_table(&self) -> &SymbolTable {
+		self.exec.export_table()
+	}
+	
+	pub fn into_executable(self) -> Executable {
+		self.exec
+	}
+}
+
+impl TryFrom<Executable> for DynamicLibrary {
+	type Error = FormatError;
+	
+	fn try_from(exec: Executable) -> Result<Self, Self::Error> {
+		if exec.offsets().export_table() == 0 {
+			return Err(FormatError::custom(ExecutableFormatError::MissingExportTable))
+		}
+		Ok(DynamicLibrary {
+			exec,
+		})
+	}
 }
 
 impl BinaryFile for DynamicLibrary {
-	fn buf(&mut self) -> &mut [u8] {
-		&mut *self.buf
+	fn buf(&self) -> &[u8] {
+		self.exec.buf()
 	}
 
 	fn size(&self) -> usize {
-		self.size
+		self.exec.size()
 	}
 }
 
-// TODO: aarch64, MIPS, RISC-V, etc. support
+// TODO: MIPS, etc. support
@@ -0,0 +1,411 @@
+use crate::vm::bin::builder::{Class, Constant, ExecutableBuilder, Field, Function, Symbol};
+use crate::vm::bin::def::symbol::SymbolKind;
+use crate::vm::error::asm::AsmError;
+use crate::vm::insn::Insn;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlag, TypeFlags, TypeId};
+
+/// Assembles E# assembly (`.esasm`) into an executable<br>
+/// A file is a list of definitions, one instruction or declaration per line, and `;` starts a comment:
+/// ```text
+/// class shapes.Square : shapes.Shape
+///     field side f64
+///     method shapes.Square.area(shapes.Square) -> f64
+///         push shapes.Square 0
+///         getfield shapes.Square side
+///         ...
+///         vret f64
+///     end
+/// end
+///
+/// fn main() -> i32
+///     ldc str "hello"
+///     call str.print
+///     ldc i32 0
+///     vret i32
+/// end
+///
+/// field counter i64
+/// export fn main
+/// import class shapes.Shape
+/// ```
+/// Types are written `i8` to `i64`, `u8` to `u64`, `f32`, `f64`, `str`, `dyn`, `void`, `[<type>]` for
+/// arrays and `fn <fn-id>` for functions, any other name is an object of the class of that name.
+/// Instructions are written as their mnemonic followed by their operands, with names in place of the
+/// constant indices of classes, functions and fields. `ldc` takes the type of its constant followed
+/// by a literal, which is a string (`"..."`) for `str` and `[i8]`.
+pub fn assemble(src: &str) -> Result<ExecutableBuilder, AsmError> {
+	let mut assembler = Assembler {
+		builder: ExecutableBuilder::new(),
+		lines: src.lines()
+			.enumerate()
+			.map(|(index, line)| Line::tokenize(index + 1, line))
+			.filter(|line| !matches!(line, Ok(line) if line.tokens.is_empty())),
+	};
+	while let Some(line) = assembler.lines.next() {
+		let mut line = line?;
+		match line.word("a definition")?.as_str() {
+			"fn" => {
+				let function = assembler.function(&mut line)?;
+				assembler.builder.functions.push(function);
+			},
+			"class" => {
+				let class = assembler.class(&mut line)?;
+				assembler.builder.classes.push(class);
+			},
+			"field" => {
+				let field = assembler.field(&mut line)?;
+				assembler.builder.fields.push(field);
+			},
+			"export" => {
+				let symbol = assembler.symbol(&mut line)?;
+				assembler.builder.exports.push(symbol);
+			},
+			"import" => {
+				let symbol = assembler.symbol(&mut line)?;
+				assembler.builder.imports.push(symbol);
+			},
+			word => return Err(AsmError::Expected(line.number, "a definition", word.to_string())),
+		}
+	}
+	Ok(assembler.builder)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+	Word(String),
+	Str(String),
+	/// One of `(`, `)`, `[`, `]`, `,` and `:`
+	Punct(char),
+}
+
+impl Token {
+	fn text(&self) -> String {
+		match self {
+			Token::Word(word) => word.clone(),
+			Token::Str(str) => format!("{:?}", str),
+			Token::Punct(punct) => punct.to_string(),
+		}
+	}
+}
+
+struct Line {
+	number: usize,
+	tokens: Vec<Token>,
+	head: usize,
+}
+
+impl Line {
+	fn tokenize(number: usize, line: &str) -> Result<Line, AsmError> {
+		let mut tokens = Vec::new();
+		let mut chars = line.chars().peekable();
+		while let Some(char) = chars.next() {
+			match char {
+				';' => break,
+				'(' | ')' | '[' | ']' | ',' | ':' => tokens.push(Token::Punct(char)),
+				'"' => {
+					let mut str = String::new();
+					loop {
+						match chars.next().ok_or(AsmError::UnterminatedString(number))? {
+							'"' => break,
+							'\\' => str.push(match chars.next().ok_or(AsmError::UnterminatedString(number))? {
+								'n' => '\n',
+								't' => '\t',
+								'0' => '\0',
+								escaped => escaped,
+							}),
+							char => str.push(char),
+						}
+					}
+					tokens.push(Token::Str(str));
+				},
+				_ if char.is_whitespace() => {},
+				_ => {
+					let mut word = String::from(char);
+					while let Some(char) = chars.next_if(|char| !char.is_whitespace() && !"()[],:;\"".contains(*char)) {
+						word.push(char);
+					}
+					tokens.push(Token::Word(word));
+				},
+			}
+		}
+		Ok(Line {
+			number,
+			tokens,
+			head: 0,
+		})
+	}
+
+	fn next(&mut self) -> Option<&Token> {
+		let token = self.tokens.get(self.head);
+		self.head += 1;
+		token
+	}
+
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.head)
+	}
+
+	fn expected(&self, expected: &'static str) -> AsmError {
+		let found = self.tokens.get(self.head.saturating_sub(1)).filter(|_| self.head <= self.tokens.len());
+		AsmError::Expected(self.number, expected, found.map_or_else(String::new, Token::text))
+	}
+
+	fn word(&mut self, expected: &'static str) -> Result<String, AsmError> {
+		match self.next() {
+			Some(Token::Word(word)) => Ok(word.clone()),
+			_ => Err(self.expected(expected)),
+		}
+	}
+
+	fn punct(&mut self, punct: char, expected: &'static str) -> Result<(), AsmError> {
+		match self.next() {
+			Some(Token::Punct(found)) if *found == punct => Ok(()),
+			_ => Err(self.expected(expected)),
+		}
+	}
+
+	/// Whether the next token is `punct`, consuming it if it is
+	fn eat(&mut self, punct: char) -> bool {
+		let found = self.peek() == Some(&Token::Punct(punct));
+		if found {
+			self.head += 1;
+		}
+		found
+	}
+
+	fn end(&mut self) -> Result<(), AsmError> {
+		match self.next() {
+			None => Ok(()),
+			Some(_) => Err(self.expected("end of line")),
+		}
+	}
+
+	/// Whether the line is `end`
+	fn is_end(&self) -> bool {
+		self.tokens == [Token::Word(String::from("end"))]
+	}
+}
+
+struct Assembler<I: Iterator<Item = Result<Line, AsmError>>> {
+	builder: ExecutableBuilder,
+	lines: I,
+}
+
+impl<I: Iterator<Item = Result<Line, AsmError>>> Assembler<I> {
+	fn constant(&mut self, constant: Constant) -> Result<ConstantIndex, AsmError> {
+		if self.builder.constants.len() > ConstantIndex::MAX as usize && !self.builder.constants.contains(&constant) {
+			return Err(AsmError::TooManyConstants)
+		}
+		Ok(self.builder.constant(constant))
+	}
+
+	fn name(&mut self, line: &mut Line, expected: &'static str) -> Result<ConstantIndex, AsmError> {
+		let name = line.word(expected)?;
+		self.constant(Constant::name(&name))
+	}
+
+	fn ty(&mut self, line: &mut Line) -> Result<TypeDesc, AsmError> {
+		if line.eat('[') {
+			let elem = self.ty(line)?;
+			if elem.operand.is_some() {
+				return Err(line.expected("a primitive element type"))
+			}
+			line.punct(']', "`]`")?;
+			return Ok(TypeDesc::new(TypeFlags::ARRAY, Some(elem.flags as u16)))
+		}
+		let unsigned = |id: u8| TypeDesc::new(TypeFlags::UNSIGNED << 4 | id, None);
+		Ok(match line.word("a type")?.as_str() {
+			"i8" => TypeDesc::new(TypeFlags::I8, None),
+			"i16" => TypeDesc::new(TypeFlags::I16, None),
+			"i32" => TypeDesc::new(TypeFlags::I32, None),
+			"i64" => TypeDesc::new(TypeFlags::I64, None),
+			"u8" => unsigned(TypeFlags::I8),
+			"u16" => unsigned(TypeFlags::I16),
+			"u32" => unsigned(TypeFlags::I32),
+			"u64" => unsigned(TypeFlags::I64),
+			"f32" => TypeDesc::new(TypeFlags::F32, None),
+			"f64" => TypeDesc::new(TypeFlags::F64, None),
+			"str" => TypeDesc::new(TypeFlags::STR, None),
+			"dyn" => TypeDesc::new(TypeFlags::DYN, None),
+			"void" => TypeDesc::new(TypeFlags::VOID, None),
+			"fn" => TypeDesc::new(TypeFlags::FUNCTION, Some(self.name(line, "a function")?)),
+			class => TypeDesc::new(TypeFlags::OBJECT, Some(self.constant(Constant::name(class))?)),
+		})
+	}
+
+	/// The constant of a literal of type `ty`
+	fn literal(&mut self, line: &mut Line, ty: TypeDesc) -> Result<Constant, AsmError> {
+		let is_str = ty.id() == TypeFlags::STR || ty.id() == TypeFlags::ARRAY && ty.operand == Some(TypeFlags::I8 as u16);
+		let token = line.next().cloned().ok_or_else(|| line.expected("a literal"))?;
+		let invalid = || AsmError::InvalidLiteral(line.number, token.text());
+		match &token {
+			Token::Str(str) if is_str => Ok(Constant {
+				ty,
+				data: str.as_bytes().to_vec(),
+			}),
+			Token::Word(word) if ty.is_int() => {
+				let (negative, digits) = match word.strip_prefix('-') {
+					Some(digits) => (true, digits),
+					None => (false, word.as_str()),
+				};
+				let magnitude = match digits.strip_prefix("0x") {
+					Some(hex) => i128::from_str_radix(hex, 16),
+					None => digits.parse::<i128>(),
+				}.map_err(|_| invalid())?;
+				let value = if negative { -magnitude } else { magnitude };
+				let bits = 8 * crate::vm::types::size_of(ty.flags) as u32;
+				let (min, max) = if ty.is_unsigned() {
+					(0, (1i128 << bits) - 1)
+				} else {
+					(-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+				};
+				if value < min || value > max {
+					return Err(invalid())
+				}
+				Ok(Constant::primitive(ty, value as u64))
+			},
+			Token::Word(word) if ty.id() == TypeFlags::F32 => {
+				let float = word.parse::<f32>().map_err(|_| invalid())?;
+				Ok(Constant::primitive(ty, float.to_bits() as u64))
+			},
+			Token::Word(word) if ty.id() == TypeFlags::F64 => {
+				let float = word.parse::<f64>().map_err(|_| invalid())?;
+				Ok(Constant::primitive(ty, float.to_bits()))
+			},
+			_ => Err(invalid()),
+		}
+	}
+
+	fn insn(&mut self, line: &mut Line) -> Result<Insn, AsmError> {
+		let mnemonic = line.word("an instruction")?;
+		let insn = match mnemonic.as_str() {
+			"nop" => Insn::Nop,
+			"add" => Insn::Add(self.ty(line)?),
+			"sub" => Insn::Sub(self.ty(line)?),
+			"mul" => Insn::Mul(self.ty(line)?),
+			"div" => Insn::Div(self.ty(line)?),
+			"inc" => Insn::Inc(self.ty(line)?),
+			"dec" => Insn::Dec(self.ty(line)?),
+			"push" => {
+				let ty = self.ty(line)?;
+				let local = line.word("a local")?.parse::<u8>().map_err(|_| line.expected("a local"))?;
+				Insn::Push(ty, local)
+			},
+			"pop" => Insn::Pop,
+			"cast" => Insn::Cast(self.ty(line)?, self.ty(line)?),
+			"call" => Insn::Call(self.name(line, "a function")?),
+			"callvirt" => Insn::CallVirt(self.name(line, "a class")?, self.name(line, "a method")?),
+			"calltrait" => Insn::CallTrait(self.name(line, "a trait")?, self.name(line, "a method")?),
+			"ret" => Insn::Ret,
+			"vret" => Insn::VRet(self.ty(line)?),
+			"ldc" => {
+				let ty = self.ty(line)?;
+				let constant = self.literal(line, ty)?;
+				Insn::Ldc(self.constant(constant)?)
+			},
+			"new" => Insn::New(self.name(line, "a class")?),
+			"getfield" => Insn::GetField(self.name(line, "a class")?, self.name(line, "a field")?),
+			"setfield" => Insn::SetField(self.name(line, "a class")?, self.name(line, "a field")?),
+			"todyn" => Insn::ToDyn(self.name(line, "a trait")?),
+			"newarray" => Insn::NewArray(self.ty(line)?),
+			"aload" => Insn::ALoad(self.ty(line)?),
+			"astore" => Insn::AStore(self.ty(line)?),
+			"alen" => Insn::ALen,
+			_ => return Err(AsmError::UnknownInstruction(line.number, mnemonic)),
+		};
+		line.end()?;
+		Ok(insn)
+	}
+
+	/// Assembles a function (or method) whose header is `line`, up to its `end`
+	fn function(&mut self, line: &mut Line) -> Result<Function, AsmError> {
+		let name = self.name(line, "a function name")?;
+		line.punct('(', "`(`")?;
+		let mut args = Vec::new();
+		if !line.eat(')') {
+			loop {
+				args.push(self.ty(line)?);
+				if line.eat(')') {
+					break
+				}
+				line.punct(',', "`,` or `)`")?;
+			}
+		}
+		let ret = match line.next() {
+			None => TypeDesc::new(TypeFlags::VOID, None),
+			Some(Token::Word(arrow)) if arrow == "->" => {
+				let ret = self.ty(line)?;
+				line.end()?;
+				ret
+			},
+			Some(_) => return Err(line.expected("`->` or end of line")),
+		};
+
+		let mut code = Vec::new();
+		loop {
+			let mut line = self.lines.next().ok_or(AsmError::Unterminated(line.number))??;
+			if line.is_end() {
+				break
+			}
+			self.insn(&mut line)?.encode(&mut code);
+		}
+		Ok(Function {
+			name,
+			ret,
+			args,
+			code,
+		})
+	}
+
+	/// Assembles a class whose header is `line`, up to its `end`
+	fn class(&mut self, line: &mut Line) -> Result<Class, AsmError> {
+		let name = self.name(line, "a class name")?;
+		let super_name = if line.eat(':') { self.name(line, "a supertype")? } else { name };
+		line.end()?;
+
+		let mut fields = Vec::new();
+		let mut methods = Vec::new();
+		loop {
+			let mut line = self.lines.next().ok_or(AsmError::Unterminated(line.number))??;
+			if line.is_end() {
+				break
+			}
+			match line.word("`field`, `method` or `end`")?.as_str() {
+				"field" => fields.push(self.field(&mut line)?),
+				"method" => methods.push(self.function(&mut line)?),
+				word => return Err(AsmError::Expected(line.number, "`field`, `method` or `end`", word.to_string())),
+			}
+		}
+		Ok(Class {
+			name,
+			super_name,
+			fields,
+			methods,
+		})
+	}
+
+	fn field(&mut self, line: &mut Line) -> Result<Field, AsmError> {
+		let name = self.name(line, "a field name")?;
+		let ty = self.ty(line)?;
+		line.end()?;
+		Ok(Field {
+			name,
+			ty,
+		})
+	}
+
+	/// An export or import, `fn <fn-id>` or `class <class-id>`
+	fn symbol(&mut self, line: &mut Line) -> Result<Symbol, AsmError> {
+		let kind = match line.word("`fn` or `class`")?.as_str() {
+			"fn" => SymbolKind::Function,
+			"class" => SymbolKind::Class,
+			_ => return Err(line.expected("`fn` or `class`")),
+		};
+		let name = self.name(line, "a name")?;
+		line.end()?;
+		Ok(Symbol {
+			kind,
+			name,
+		})
+	}
+}
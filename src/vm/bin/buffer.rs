@@ -0,0 +1,163 @@
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::{Bound, Deref, RangeBounds};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use serde::de::{DeserializeSeed, Error};
+use serde::de::value::BytesDeserializer;
+
+/// The storage behind [`Bytes`]
+enum Buffer {
+	Owned(Box<[u8]>),
+	/// A read-only mapping of a file
+	Mapped(Mmap),
+}
+
+impl Deref for Buffer {
+	type Target = [u8];
+
+	fn deref(&self) -> &Self::Target {
+		match self {
+			Buffer::Owned(buf) => buf,
+			Buffer::Mapped(map) => map,
+		}
+	}
+}
+
+/// A shared slice of the contents of an executable<br>
+/// Cloning and slicing don't copy, so the code and strings of definitions can borrow from the
+/// executable (or from the file it is mapped from, see [`Bytes::map`]).
+#[derive(Clone)]
+pub struct Bytes {
+	buf: Arc<Buffer>,
+	start: usize,
+	end: usize,
+}
+
+impl Bytes {
+	/// Maps `file` into memory, pages are only read once they're accessed
+	///
+	/// # Safety
+	/// The file must not be modified (or truncated) while it's mapped.
+	pub unsafe fn map(file: &File) -> io::Result<Bytes> {
+		let map = Mmap::map(file)?;
+		let end = map.len();
+		Ok(Bytes {
+			buf: Arc::new(Buffer::Mapped(map)),
+			start: 0,
+			end,
+		})
+	}
+
+	/// Whether the bytes are in a mapped file
+	pub fn is_mapped(&self) -> bool {
+		matches!(*self.buf, Buffer::Mapped(_))
+	}
+
+	/// The bytes in `range` (relative to the start of these bytes)
+	pub fn slice(&self, range: impl RangeBounds<usize>) -> Bytes {
+		let start = match range.start_bound() {
+			Bound::Included(start) => *start,
+			Bound::Excluded(start) => start + 1,
+			Bound::Unbounded => 0,
+		};
+		let end = match range.end_bound() {
+			Bound::Included(end) => end + 1,
+			Bound::Excluded(end) => *end,
+			Bound::Unbounded => self.len(),
+		};
+		assert!(start <= end && end <= self.len(), "range {}..{} out of bounds of {} bytes", start, end, self.len());
+		Bytes {
+			buf: self.buf.clone(),
+			start: self.start + start,
+			end: self.start + end,
+		}
+	}
+
+	/// The bytes of `subset`, which must be a slice of these bytes
+	pub fn slice_ref(&self, subset: &[u8]) -> Bytes {
+		let start = (subset.as_ptr() as usize).wrapping_sub(self.as_ptr() as usize);
+		assert!(start <= self.len() && subset.len() <= self.len() - start, "slice is not part of the bytes");
+		self.slice(start..start + subset.len())
+	}
+}
+
+impl Deref for Bytes {
+	type Target = [u8];
+
+	fn deref(&self) -> &Self::Target {
+		&self.buf[self.start..self.end]
+	}
+}
+
+impl AsRef<[u8]> for Bytes {
+	fn as_ref(&self) -> &[u8] {
+		self
+	}
+}
+
+impl Default for Bytes {
+	fn default() -> Self {
+		Bytes::from(Vec::new())
+	}
+}
+
+impl From<Vec<u8>> for Bytes {
+	fn from(buf: Vec<u8>) -> Self {
+		let end = buf.len();
+		Bytes {
+			buf: Arc::new(Buffer::Owned(buf.into_boxed_slice())),
+			start: 0,
+			end,
+		}
+	}
+}
+
+impl From<&[u8]> for Bytes {
+	fn from(bytes: &[u8]) -> Self {
+		Bytes::from(bytes.to_vec())
+	}
+}
+
+impl PartialEq for Bytes {
+	fn eq(&self, other: &Self) -> bool {
+		**self == **other
+	}
+}
+
+impl Eq for Bytes {}
+
+impl Debug for Bytes {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+
+/// A [`DeserializeSeed`] for a `T` that borrows from `bytes`<br>
+/// ***Note:** The deserializer must be given `bytes` itself, see [`Shared::parse`].*
+pub struct Shared<'a, T> {
+	bytes: &'a Bytes,
+	marker: PhantomData<T>,
+}
+
+impl<'a, T> Shared<'a, T> {
+	pub fn new(bytes: &'a Bytes) -> Self {
+		Shared {
+			bytes,
+			marker: PhantomData,
+		}
+	}
+
+	pub fn bytes(&self) -> &'a Bytes {
+		self.bytes
+	}
+
+	/// Deserializes a `T` from `bytes`
+	pub fn parse<'de, E>(self) -> Result<T, E> where Self: DeserializeSeed<'de, Value = T>, E: Error {
+		let bytes = self.bytes;
+		self.deserialize(BytesDeserializer::<E>::new(bytes))
+	}
+}
@@ -0,0 +1,335 @@
+use crate::vm::bin::Executable;
+use crate::vm::bin::def::EMPTY_TABLE;
+use crate::vm::bin::header;
+use crate::vm::bin::header::{Feature, Features, Version};
+use crate::vm::bin::def::class::{ClassDef, ClassTable};
+use crate::vm::bin::def::constant::{ConstantDef, ConstantTable};
+use crate::vm::bin::def::debug::{DebugInfoDef, DebugInfoTable, LineEntry, LocalEntry};
+use crate::vm::bin::def::field::{FieldDef, FieldTable};
+use crate::vm::bin::def::function::{FunctionDef, FunctionTable};
+use crate::vm::bin::def::symbol::{SymbolDef, SymbolKind, SymbolTable};
+use crate::vm::types;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlags, TypeId};
+
+/// The end of every definition but the last one of a table
+const END: u16 = 0xFFFF;
+
+/// A constant of an [`ExecutableBuilder`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Constant {
+	pub ty: TypeDesc,
+	/// The value as it is stored in an executable (primitives are big-endian)
+	pub data: Vec<u8>,
+}
+
+impl Constant {
+	/// A UTF-8 string (an `[i8]`), as used for the names of classes, functions and fields
+	pub fn name(name: &str) -> Self {
+		Constant {
+			ty: TypeDesc::new(TypeFlags::ARRAY, Some(TypeFlags::I8 as u16)),
+			data: name.as_bytes().to_vec(),
+		}
+	}
+
+	/// A primitive of type `ty`, from its 64-bit value slot (see [`ConstantDef::bits`])
+	pub fn primitive(ty: TypeDesc, bits: u64) -> Self {
+		let size = types::size_of(ty.flags);
+		Constant {
+			ty,
+			data: bits.to_be_bytes()[8 - size..].to_vec(),
+		}
+	}
+}
+
+impl From<&ConstantDef> for Constant {
+	fn from(def: &ConstantDef) -> Self {
+		let ty = TypeDesc::new(def.type_flags(), def.type_operand());
+		match def.bits() {
+			Some(bits) => Constant::primitive(ty, bits),
+			None => Constant {
+				ty,
+				data: def.bytes().to_vec(),
+			},
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+	pub name: ConstantIndex,
+	pub ty: TypeDesc,
+}
+
+impl From<&FieldDef> for Field {
+	fn from(def: &FieldDef) -> Self {
+		Field {
+			name: def.name(),
+			ty: TypeDesc::new(def.type_flags(), def.type_operand()),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+	pub name: ConstantIndex,
+	pub ret: TypeDesc,
+	pub args: Vec<TypeDesc>,
+	pub code: Vec<u8>,
+}
+
+impl From<&FunctionDef> for Function {
+	fn from(def: &FunctionDef) -> Self {
+		Function {
+			name: def.name(),
+			ret: def.return_type_desc(),
+			args: def.arg_types(),
+			code: def.code().to_vec(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Class {
+	pub name: ConstantIndex,
+	/// The name of the supertype (the class's own name if it extends nothing)
+	pub super_name: ConstantIndex,
+	pub fields: Vec<Field>,
+	pub methods: Vec<Function>,
+}
+
+impl From<&ClassDef> for Class {
+	fn from(def: &ClassDef) -> Self {
+		Class {
+			name: def.name(),
+			super_name: def.super_name(),
+			fields: def.field_table().map_or_else(Vec::new, |table| table.fields().iter().map(Field::from).collect()),
+			methods: def.function_table().map_or_else(Vec::new, |table| table.functions().iter().map(Function::from).collect()),
+		}
+	}
+}
+
+/// An export or an import
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Symbol {
+	pub kind: SymbolKind,
+	pub name: ConstantIndex,
+}
+
+impl From<&SymbolDef> for Symbol {
+	fn from(def: &SymbolDef) -> Self {
+		Symbol {
+			kind: def.kind(),
+			name: def.name(),
+		}
+	}
+}
+
+/// The debug information of a function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugInfo {
+	/// The fully-qualified name of the function
+	pub function: ConstantIndex,
+	/// The name of the source file
+	pub file: ConstantIndex,
+	/// The line table, ordered by bytecode offset
+	pub lines: Vec<LineEntry>,
+	pub locals: Vec<LocalEntry>,
+}
+
+impl From<&DebugInfoDef> for DebugInfo {
+	fn from(def: &DebugInfoDef) -> Self {
+		DebugInfo {
+			function: def.function(),
+			file: def.file(),
+			lines: def.lines().clone(),
+			locals: def.locals().clone(),
+		}
+	}
+}
+
+/// An executable under construction, which encodes to the format of the E# standard<br>
+/// ***Note:** Executables are stamped with [`Version::CURRENT`].*
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecutableBuilder {
+	pub constants: Vec<Constant>,
+	pub classes: Vec<Class>,
+	pub functions: Vec<Function>,
+	pub fields: Vec<Field>,
+	/// The exports of a library (the export table is left out if there are none)
+	pub exports: Vec<Symbol>,
+	/// The imports (the import table is left out if there are none)
+	pub imports: Vec<Symbol>,
+	/// Whether to emit a checksum, which the loader verifies before parsing the executable
+	pub checksum: bool,
+	/// The debug information of functions (the debug info table is left out if there is none)
+	pub debug_info: Vec<DebugInfo>,
+}
+
+impl ExecutableBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a constant, returning its index (or the index of an equal constant that was already added)
+	pub fn constant(&mut self, constant: Constant) -> ConstantIndex {
+		let index = match self.constants.iter().position(|existing| *existing == constant) {
+			Some(index) => index,
+			None => {
+				self.constants.push(constant);
+				self.constants.len() - 1
+			},
+		};
+		ConstantIndex::try_from(index).expect("An executable holds at most 65536 constants")
+	}
+
+	/// Adds a name constant (see [`Constant::name`]), returning its index
+	pub fn name(&mut self, name: &str) -> ConstantIndex {
+		self.constant(Constant::name(name))
+	}
+
+	/// Encodes the executable
+	pub fn build(&self) -> Vec<u8> {
+		let mut constants = Vec::new();
+		table(&mut constants, &self.constants, ConstantTable::TERMINATOR, |out, constant| {
+			type_desc(out, constant.ty);
+			out.extend_from_slice(&(constant.data.len() as u32).to_be_bytes());
+			out.extend_from_slice(&constant.data);
+		});
+		let mut classes = Vec::new();
+		table(&mut classes, &self.classes, ClassDef::TERMINATOR, |out, class| {
+			out.extend_from_slice(&class.name.to_be_bytes());
+			out.extend_from_slice(&class.super_name.to_be_bytes());
+			table(out, &class.fields, FieldTable::TERMINATOR, field);
+			table(out, &class.methods, FunctionTable::TERMINATOR, function);
+		});
+		let mut functions = Vec::new();
+		table(&mut functions, &self.functions, FunctionTable::TERMINATOR, function);
+		let mut fields = Vec::new();
+		table(&mut fields, &self.fields, FieldTable::TERMINATOR, field);
+		let mut exports = Vec::new();
+		if !self.exports.is_empty() {
+			table(&mut exports, &self.exports, SymbolTable::TERMINATOR, symbol);
+		}
+		let mut imports = Vec::new();
+		if !self.imports.is_empty() {
+			table(&mut imports, &self.imports, SymbolTable::TERMINATOR, symbol);
+		}
+		let mut debug_info = Vec::new();
+		if !self.debug_info.is_empty() {
+			table(&mut debug_info, &self.debug_info, DebugInfoTable::TERMINATOR, debug);
+		}
+
+		let mut out = Executable::MAGIC.to_be_bytes().to_vec();
+		// the offsets of optional tables follow the header
+		let mut offset = if debug_info.is_empty() { 36 } else { 40 };
+		for table in [&constants, &classes, &functions, &fields, &exports, &imports] {
+			// a table that's left out has offset 0
+			let table_offset = if table.is_empty() { 0 } else { offset as u32 };
+			out.extend_from_slice(&table_offset.to_be_bytes());
+			offset += table.len();
+		}
+		out.extend_from_slice(&Version::CURRENT.to_bits().to_be_bytes());
+		let mut features = 0;
+		if !self.exports.is_empty() {
+			features |= Features::LIBRARY;
+		}
+		if self.checksum {
+			features |= Features::CHECKSUM;
+		}
+		if !debug_info.is_empty() {
+			features |= Features::DEBUG_INFO;
+		}
+		out.extend_from_slice(&features.to_be_bytes());
+		// the checksum, which is filled in once everything else is written
+		out.extend_from_slice(&[0; 4]);
+		if !debug_info.is_empty() {
+			out.extend_from_slice(&(offset as u32).to_be_bytes());
+		}
+		for table in [constants, classes, functions, fields, exports, imports, debug_info] {
+			out.extend_from_slice(&table);
+		}
+		if self.checksum {
+			let checksum = header::checksum(&out);
+			out[32..36].copy_from_slice(&checksum.to_be_bytes());
+		}
+		out
+	}
+
+	/// Encodes and parses the executable
+	pub fn finish(&self) -> Executable {
+		Executable::parse(&self.build()).expect("Failed to parse a built executable")
+	}
+}
+
+impl From<&Executable> for ExecutableBuilder {
+	fn from(exec: &Executable) -> Self {
+		ExecutableBuilder {
+			constants: exec.constant_table().constants().iter().map(Constant::from).collect(),
+			classes: exec.class_table().classes().iter().map(Class::from).collect(),
+			functions: exec.function_table().functions().iter().map(Function::from).collect(),
+			fields: exec.field_table().fields().iter().map(Field::from).collect(),
+			exports: exec.export_table().symbols().iter().map(Symbol::from).collect(),
+			imports: exec.import_table().symbols().iter().map(Symbol::from).collect(),
+			checksum: exec.features() & Features::CHECKSUM != 0,
+			debug_info: exec.debug_info().functions().iter().map(DebugInfo::from).collect(),
+		}
+	}
+}
+
+/// Encodes a table of definitions, ending the last one with `terminator`
+fn table<T>(out: &mut Vec<u8>, defs: &[T], terminator: u16, mut def: impl FnMut(&mut Vec<u8>, &T)) {
+	if defs.is_empty() {
+		out.extend_from_slice(&EMPTY_TABLE.to_be_bytes());
+		return
+	}
+	for (i, item) in defs.iter().enumerate() {
+		def(out, item);
+		let end = if i == defs.len() - 1 { terminator } else { END };
+		out.extend_from_slice(&end.to_be_bytes());
+	}
+}
+
+fn type_desc(out: &mut Vec<u8>, ty: TypeDesc) {
+	out.push(ty.flags);
+	match (types::type_id(ty.flags), ty.operand) {
+		(TypeFlags::ARRAY, Some(operand)) => out.push(operand as u8),
+		(TypeFlags::OBJECT | TypeFlags::FUNCTION, Some(operand)) => out.extend_from_slice(&operand.to_be_bytes()),
+		_ => {},
+	}
+}
+
+fn field(out: &mut Vec<u8>, field: &Field) {
+	out.extend_from_slice(&field.name.to_be_bytes());
+	type_desc(out, field.ty);
+}
+
+fn symbol(out: &mut Vec<u8>, symbol: &Symbol) {
+	out.push(symbol.kind as u8);
+	out.extend_from_slice(&symbol.name.to_be_bytes());
+}
+
+fn debug(out: &mut Vec<u8>, info: &DebugInfo) {
+	out.extend_from_slice(&info.function.to_be_bytes());
+	out.extend_from_slice(&info.file.to_be_bytes());
+	out.extend_from_slice(&(info.lines.len() as u16).to_be_bytes());
+	for entry in &info.lines {
+		out.extend_from_slice(&entry.offset.to_be_bytes());
+		out.extend_from_slice(&entry.line.to_be_bytes());
+	}
+	out.extend_from_slice(&(info.locals.len() as u16).to_be_bytes());
+	for entry in &info.locals {
+		out.push(entry.local);
+		out.extend_from_slice(&entry.name.to_be_bytes());
+	}
+}
+
+fn function(out: &mut Vec<u8>, function: &Function) {
+	out.extend_from_slice(&function.name.to_be_bytes());
+	type_desc(out, function.ret);
+	out.extend_from_slice(&(function.args.len() as u16).to_be_bytes());
+	for arg in &function.args {
+		type_desc(out, *arg);
+	}
+	out.extend_from_slice(&(function.code.len() as u64).to_be_bytes());
+	out.extend_from_slice(&function.code);
+}
@@ -1,6 +1,42 @@
+use std::mem;
+use serde::Deserialize;
+use serde::de::Error;
+use crate::util;
+use crate::vm::error::jit::ExecutableFormatError;
+use crate::vm::types;
+use crate::vm::types::{TypeFlags, TypeId};
+
 pub mod constant;
 pub mod class;
 pub mod function;
 pub mod field;
+pub mod symbol;
+pub mod debug;
+
+/// The first 8 bytes of a table that has no definitions
+pub const EMPTY_TABLE: u64 = 0xDEADCAFEBABEFADE;
 
 pub trait Definition {}
+
+/// The `len` bytes at `head`, failing if `v` ends before them
+pub(crate) fn slice<E>(v: &[u8], head: usize, len: usize) -> Result<&[u8], E> where E: Error {
+	head.checked_add(len)
+		.and_then(|end| v.get(head..end))
+		.ok_or_else(|| Error::custom(ExecutableFormatError::UnexpectedEnd))
+}
+
+/// Reads a big-endian integer at `head`, failing if `v` ends before it
+pub(crate) fn read<'a, T, E>(v: &'a [u8], head: usize) -> Result<T, E> where T: Deserialize<'a>, E: Error {
+	util::deserialize::<T>(slice(v, head, mem::size_of::<T>())?).map_err(Error::custom)
+}
+
+/// Reads a `type-flags` and its operand at `head`, returning them and the offset after them
+pub(crate) fn type_desc<E>(v: &[u8], head: usize) -> Result<(TypeFlags, Option<u16>, usize), E> where E: Error {
+	let type_flags = read::<u8, E>(v, head)?;
+	let type_operand = match types::type_id(type_flags) {
+		TypeFlags::ARRAY => Some(read::<u8, E>(v, head + 1)? as u16),
+		TypeFlags::OBJECT | TypeFlags::FUNCTION => Some(read::<u16, E>(v, head + 1)?),
+		_ => None,
+	};
+	Ok((type_flags, type_operand, head + 1 + types::operand_len(type_flags)))
+}
@@ -1,12 +1,14 @@
 use std::fmt::Formatter;
 use serde::{Deserialize, Deserializer};
-use serde::de::{Error, Visitor};
+use serde::de::{DeserializeSeed, Error, Visitor};
 use serde::de::value::BytesDeserializer;
 use crate::util;
-use crate::vm::bin::def::Definition;
+use crate::vm::bin::buffer::{Bytes, Shared};
+use crate::vm::bin::def::{Definition, EMPTY_TABLE, read};
+use crate::vm::bin::def::constant::ConstantTable;
 use crate::vm::bin::def::field::FieldTable;
 use crate::vm::bin::def::function::FunctionTable;
-use crate::vm::error::jit::{ClassDefError, FormatError};
+use crate::vm::error::jit::{ClassDefError, ExecutableFormatError, FormatError};
 use crate::vm::types::ConstantIndex;
 
 #[derive(Debug)]
@@ -44,17 +46,25 @@ impl ClassDef {
 
 impl Definition for ClassDef {}
 
+impl From<&Bytes> for ClassDef {
+	fn from(bytes: &Bytes) -> Self {
+		Shared::<ClassDef>::new(bytes).parse::<FormatError>().unwrap()
+	}
+}
+
 impl From<&[u8]> for ClassDef {
 	fn from(bytes: &[u8]) -> Self {
-		ClassDef::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		ClassDef::from(&Bytes::from(bytes))
 	}
 }
 
-impl<'de> Deserialize<'de> for ClassDef {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct ClassDefVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, ClassDef> {
+	type Value = ClassDef;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct ClassDefVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for ClassDefVisitor {
+		impl<'de> Visitor<'de> for ClassDefVisitor<'_> {
 			type Value = ClassDef;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -62,45 +72,30 @@ impl<'de> Deserialize<'de> for ClassDef {
 			}
 			
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
-				let mut len: usize = 12;
+				let name = read::<u16, E>(v, 0)?;
+				let super_name = read::<u16, E>(v, 2)?;
 				
-				let name = util::deserialize::<u16>(&v[0..1]).unwrap();
-				let super_name = util::deserialize::<u16>(&v[2..3]).unwrap();
-				
-				let field_table = if util::deserialize::<u64>(&v[4..12]).unwrap() != 0xDEADCAFEBABEFADE {
-					Some(FieldTable::from(&v[4..]))
-				} else {
+				let fields = v.get(4..).ok_or_else(|| Error::custom(ExecutableFormatError::UnexpectedEnd))?;
+				let field_table = FieldTable::deserialize(BytesDeserializer::<E>::new(fields))?;
+				let mut head = 4 + field_table.len();
+				let field_table = if field_table.fields().is_empty() {
 					None
-				};
-				let mut field_len = 8;
-				let field_table = match field_table {
-					Some(field_table) => {
-						field_len = field_table.len();
-						Some(field_table)
-					}
-					_ => None,
+				} else {
+					Some(field_table)
 				};
 				
-				let function_table = if util::deserialize::<u64>(&v[field_len + 8..field_len + 16]).unwrap() != 0xDEADCAFEBABEFADE {
-					Some(FunctionTable::from(&v[field_len + 3..]))
-				} else {
+				let function_table = Shared::<FunctionTable>::new(&self.0.slice(head..)).parse::<E>()?;
+				head += function_table.len();
+				let function_table = if function_table.functions().is_empty() {
 					None
-				};
-				let mut function_len = 8;
-				let function_table = match function_table {
-					Some(function_table) => {
-						function_len = function_table.len();
-						Some(function_table)
-					},
-					_ => None,
+				} else {
+					Some(function_table)
 				};
 				
-				len += field_len + function_len;
+				let len = head + 2;
 				
-				let terminator = util::deserialize::<u16>(&v[field_len + function_len + 3..]).unwrap_or_else(|_| {
-					return 0xEEEE
-				});
-				return if terminator == ClassDef::TERMINATOR {
+				let terminator = read::<u16, E>(v, head).unwrap_or(0xEEEE);
+				return if terminator == ClassDef::TERMINATOR || terminator == 0xFFFF {
 					Ok(ClassDef {
 						name,
 						super_name,
@@ -116,7 +111,7 @@ impl<'de> Deserialize<'de> for ClassDef {
 			}
 		}
 		
-		deserializer.deserialize_struct("ClassDef", &["name", "super_name", "field_table", "function_table", "len"], ClassDefVisitor)
+		deserializer.deserialize_struct("ClassDef", &["name", "super_name", "field_table", "function_table", "len"], ClassDefVisitor(self.bytes()))
 	}
 }
 
@@ -131,22 +126,42 @@ impl ClassTable {
 		&self.classes
 	}
 	
+	/// Finds the class named `name`, resolving class names with `constants`
+	pub fn find(&self, constants: &ConstantTable, name: &str) -> Option<&ClassDef> {
+		self.classes.iter().find(|class| constants.str(class.name) == Some(name))
+	}
+	
 	pub fn len(&self) -> usize {
 		self.len
 	}
 }
 
+impl ClassTable {
+	/// Parses a class table, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<ClassTable, FormatError> {
+		Shared::<ClassTable>::new(&Bytes::from(bytes)).parse()
+	}
+}
+
+impl From<&Bytes> for ClassTable {
+	fn from(bytes: &Bytes) -> Self {
+		Shared::<ClassTable>::new(bytes).parse::<FormatError>().unwrap()
+	}
+}
+
 impl From<&[u8]> for ClassTable {
 	fn from(bytes: &[u8]) -> Self {
-		ClassTable::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		ClassTable::parse(bytes).unwrap()
 	}
 }
 
-impl<'de> Deserialize<'de> for ClassTable {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct ClassTableVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, ClassTable> {
+	type Value = ClassTable;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct ClassTableVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for ClassTableVisitor {
+		impl<'de> Visitor<'de> for ClassTableVisitor<'_> {
 			type Value = ClassTable;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -156,15 +171,22 @@ impl<'de> Deserialize<'de> for ClassTable {
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
 				let mut classes = Vec::new();
 				
+				if util::deserialize_trailing::<u64>(v).is_ok_and(|marker| marker == EMPTY_TABLE) {
+					return Ok(ClassTable {
+						classes,
+						len: 8,
+					})
+				}
+				
 				let mut head: usize = 0;
 				loop {
 					let v = v.split_at(head).1;
-					let class = ClassDef::from(v);
+					let class = Shared::<ClassDef>::new(&self.0.slice_ref(v)).parse::<E>()?;
 					let len = class.len;
 					classes.push(class);
 					
-					let terminator = util::deserialize::<u16>(&v[len - 2..len]).unwrap();
-					if terminator == 0xFADE {
+					let terminator = read::<u16, E>(v, len - 2)?;
+					if terminator == ClassDef::TERMINATOR {
 						return Ok(ClassTable {
 							classes,
 							len: head + len,
@@ -176,6 +198,6 @@ impl<'de> Deserialize<'de> for ClassTable {
 			}
 		}
 		
-		deserializer.deserialize_struct("ClassTable", &["classes", "len"], ClassTableVisitor)
+		deserializer.deserialize_struct("ClassTable", &["classes", "len"], ClassTableVisitor(self.bytes()))
 	}
 }
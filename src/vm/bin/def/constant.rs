@@ -5,15 +5,16 @@ use std::mem;
 use std::ops::AddAssign;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use serde::de::{Error, SeqAccess, Visitor};
+use serde::de::{DeserializeSeed, Error, SeqAccess, Visitor};
 use serde::de::value::BytesDeserializer;
 use serde::ser::SerializeStruct;
 
 use crate::util;
-use crate::vm::bin::def::Definition;
+use crate::vm::bin::buffer::{Bytes, Shared};
+use crate::vm::bin::def::{Definition, EMPTY_TABLE, read, slice, type_desc};
 use crate::vm::error::jit::{ExecutableFormatError, FormatError};
 use crate::vm::types;
-use crate::vm::types::TypeFlags;
+use crate::vm::types::{ConstantIndex, TypeFlags, TypeId, TypeFlag};
 
 /// A constant definition
 #[derive(Debug)]
@@ -21,7 +22,7 @@ pub struct ConstantDef {
 	type_flags: TypeFlags,
 	type_operand: Option<u16>,
 	data_len: u32,
-	val: types::Any,
+	data: Bytes,
 	len: usize,
 }
 
@@ -41,14 +42,45 @@ impl ConstantDef {
 		self.data_len
 	}
 	
-	/// The data
+	/// The data<br>
+	/// ***Note:** The data is not guaranteed to be aligned for `T`; use [`std::ptr::read_unaligned`].*
 	pub fn val<T>(&self) -> *mut T {
-		self.val as *mut T
+		self.data.as_ptr() as *mut T
 	}
 	
 	/// The data as a `*mut ()` (raw void pointer)
 	pub fn val_raw(&self) -> types::Any {
-		self.val
+		self.data.as_ptr() as types::Any
+	}
+	
+	/// The data as bytes (primitives are stored in native byte order)
+	pub fn bytes(&self) -> &[u8] {
+		&self.data
+	}
+	
+	/// The data as a UTF-8 string (for `class-id`, `fn-id` and other string constants)
+	pub fn str(&self) -> Option<&str> {
+		match types::type_id(self.type_flags) {
+			TypeFlags::ARRAY | TypeFlags::OBJECT | TypeFlags::STR => std::str::from_utf8(&self.data).ok(),
+			_ => None,
+		}
+	}
+	
+	/// The data as a 64-bit value slot (integers are sign or zero extended, floats are stored as their bits)
+	pub fn bits(&self) -> Option<u64> {
+		let unsigned = types::type_modifier(self.type_flags) & TypeFlags::UNSIGNED != 0;
+		let bits = match types::type_id(self.type_flags) {
+			TypeFlags::I8 if unsigned => self.data[0] as u64,
+			TypeFlags::I8 => self.data[0] as i8 as u64,
+			TypeFlags::I16 if unsigned => u16::from_ne_bytes(self.data[..2].try_into().unwrap()) as u64,
+			TypeFlags::I16 => i16::from_ne_bytes(self.data[..2].try_into().unwrap()) as u64,
+			TypeFlags::I32 if unsigned => u32::from_ne_bytes(self.data[..4].try_into().unwrap()) as u64,
+			TypeFlags::I32 => i32::from_ne_bytes(self.data[..4].try_into().unwrap()) as u64,
+			TypeFlags::F32 => u32::from_ne_bytes(self.data[..4].try_into().unwrap()) as u64,
+			TypeFlags::I64 | TypeFlags::F64 => u64::from_ne_bytes(self.data[..8].try_into().unwrap()),
+			_ => return None,
+		};
+		Some(bits)
 	}
 	
 	/// The length of the entire constant defintion
@@ -59,17 +91,25 @@ impl ConstantDef {
 
 impl Definition for ConstantDef {}
 
+impl From<&Bytes> for ConstantDef {
+	fn from(bytes: &Bytes) -> Self {
+		Shared::<ConstantDef>::new(bytes).parse::<FormatError>().unwrap()
+	}
+}
+
 impl From<&[u8]> for ConstantDef {
 	fn from(bytes: &[u8]) -> Self {
-		ConstantDef::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		ConstantDef::from(&Bytes::from(bytes))
 	}
 }
 
-impl<'de> Deserialize<'de> for ConstantDef {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct ConstantDefVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, ConstantDef> {
+	type Value = ConstantDef;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct ConstantDefVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for ConstantDefVisitor {
+		impl<'de> Visitor<'de> for ConstantDefVisitor<'_> {
 			type Value = ConstantDef;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -77,59 +117,32 @@ impl<'de> Deserialize<'de> for ConstantDef {
 			}
 			
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
-				let mut len: usize = 7;
-				let mut offset = 0;
-				
-				let type_flags = v[0];
-				let type_id = type_flags & 0x0F;
-				println!("type flags {:#X}", type_flags);
-				let type_operand = match type_id {
-					0x8 => {
-						offset += 1;
-						Some(v[1] as u16)
-					},
-					0x6 => {
-						offset += 2;
-						len += 1;
-						Some(util::deserialize::<u16>(&v[1..2]).unwrap())
-					}
-					_ => None,
-				};
+				let (type_flags, type_operand, offset) = type_desc::<E>(v, 0)?;
+				let type_id = types::type_id(type_flags);
 				
-				let data_len = util::deserialize::<u32>(&v[1..5]).unwrap();
-				len += data_len as usize;
-				println!("data len {:#X}", data_len);
-				println!("{:#X?}", &v[1..5]);
-				let val = {
-					match type_id {
-						0x0 => &mut v[8].clone() as *mut u8 as *mut (),
-						0x1 => &mut util::deserialize::<u16>(&v[5..7]).unwrap() as *mut u16 as *mut (),
-						0x2 | 0x4 => &mut util::deserialize::<u32>(&v[5..9]).unwrap() as *mut u32 as *mut (),
-						0x3 | 0x5 => &mut util::deserialize::<u64>(&v[5..13]).unwrap() as *mut u64 as *mut (),
-						0x6 | 0x8 => {
-							let mut vec = v[offset + 4..data_len as usize + offset + 4].to_vec();
-							println!("product object/array {:#X?}", vec);
-							[vec.as_mut_ptr() as usize, vec.len(), vec.capacity()].as_mut_ptr() as *mut ()
-						}
-						_ => return Err(Error::custom(ExecutableFormatError::IllegalTypeId(type_id))),
-					}
+				let data_len = read::<u32, E>(v, offset)?;
+				let v = slice::<E>(v, offset + 4, data_len as usize)?;
+				// primitives are converted to native byte order, everything else is borrowed
+				let data = match type_id {
+					TypeFlags::I8 => Bytes::from(vec![read::<u8, E>(v, 0)?]),
+					TypeFlags::I16 => Bytes::from(read::<u16, E>(v, 0)?.to_ne_bytes().to_vec()),
+					TypeFlags::I32 | TypeFlags::F32 => Bytes::from(read::<u32, E>(v, 0)?.to_ne_bytes().to_vec()),
+					TypeFlags::I64 | TypeFlags::F64 => Bytes::from(read::<u64, E>(v, 0)?.to_ne_bytes().to_vec()),
+					TypeFlags::OBJECT | TypeFlags::ARRAY | TypeFlags::STR => self.0.slice_ref(v),
+					_ => return Err(Error::custom(ExecutableFormatError::IllegalTypeId(type_id))),
 				};
 				
-				if type_id == 0x2 || type_id == 0x4 {
-					unsafe { println!("product constant: {:#X}", *(val as *mut u32)); }
-				}
-				
 				Ok(ConstantDef {
 					type_flags,
 					type_operand,
 					data_len,
-					val,
-					len,
+					data,
+					len: offset + 4 + data_len as usize + 2,
 				})
 			}
 		}
 		
-		deserializer.deserialize_struct("ConstantDef", &["type_flags", "type_operand", "data_len", "val", "len"], ConstantDefVisitor)
+		deserializer.deserialize_struct("ConstantDef", &["type_flags", "type_operand", "data_len", "data", "len"], ConstantDefVisitor(self.bytes()))
 	}
 }
 
@@ -140,23 +153,50 @@ pub struct ConstantTable {
 }
 
 impl ConstantTable {
+	pub const TERMINATOR: u16 = 0xF00F;
+	
 	/// An immutable reference to the `Vec<ConstantDef>` containing all of the constants
 	pub fn constants(&self) -> &Vec<ConstantDef> {
 		&self.constants
 	}
+	
+	/// The constant at `index`
+	pub fn get(&self, index: ConstantIndex) -> Option<&ConstantDef> {
+		self.constants.get(index as usize)
+	}
+	
+	/// The constant at `index` as a UTF-8 string
+	pub fn str(&self, index: ConstantIndex) -> Option<&str> {
+		self.get(index).and_then(ConstantDef::str)
+	}
+}
+
+impl ConstantTable {
+	/// Parses a constant table, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<ConstantTable, FormatError> {
+		Shared::<ConstantTable>::new(&Bytes::from(bytes)).parse()
+	}
+}
+
+impl From<&Bytes> for ConstantTable {
+	fn from(bytes: &Bytes) -> Self {
+		Shared::<ConstantTable>::new(bytes).parse::<FormatError>().unwrap()
+	}
 }
 
 impl From<&[u8]> for ConstantTable {
 	fn from(bytes: &[u8]) -> Self {
-		ConstantTable::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		ConstantTable::parse(bytes).unwrap()
 	}
 }
 
-impl<'de> Deserialize<'de> for ConstantTable {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct ConstantTableVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, ConstantTable> {
+	type Value = ConstantTable;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct ConstantTableVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for ConstantTableVisitor {
+		impl<'de> Visitor<'de> for ConstantTableVisitor<'_> {
 			type Value = ConstantTable;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -166,17 +206,21 @@ impl<'de> Deserialize<'de> for ConstantTable {
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
 				let mut constants = Vec::new();
 				
+				if util::deserialize_trailing::<u64>(v).is_ok_and(|marker| marker == EMPTY_TABLE) {
+					return Ok(ConstantTable {
+						constants,
+					})
+				}
+				
 				let mut head: usize = 0;
 				loop {
 					let v = v.split_at(head).1;
-					let constant = ConstantDef::from(v);
+					let constant = Shared::<ConstantDef>::new(&self.0.slice_ref(v)).parse::<E>()?;
 					let len = constant.len;
 					constants.push(constant);
 					
-					println!("end {:#X?}", &v[len - 2..len]);
-					
-					let terminator = util::deserialize::<u16>(&v[len - 2..len]).unwrap();
-					if terminator == 0xF00F {
+					let terminator = read::<u16, E>(v, len - 2)?;
+					if terminator == ConstantTable::TERMINATOR {
 						return Ok(ConstantTable {
 							constants,
 						})
@@ -187,6 +231,6 @@ impl<'de> Deserialize<'de> for ConstantTable {
 			}
 		}
 		
-		deserializer.deserialize_struct("ConstantTable", &["constants"], ConstantTableVisitor)
+		deserializer.deserialize_struct("ConstantTable", &["constants"], ConstantTableVisitor(self.bytes()))
 	}
 }
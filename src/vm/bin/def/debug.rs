@@ -0,0 +1,225 @@
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{Error, Visitor};
+use serde::de::value::BytesDeserializer;
+use crate::util;
+use crate::vm::bin::def::{Definition, EMPTY_TABLE, read};
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::error::jit::FormatError;
+use crate::vm::types::ConstantIndex;
+
+/// The source line of the instructions starting at a bytecode offset
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LineEntry {
+	pub offset: u32,
+	pub line: u32,
+}
+
+/// The name of a local variable
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LocalEntry {
+	pub local: u8,
+	pub name: ConstantIndex,
+}
+
+/// Where an instruction is in the source
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation<'a> {
+	pub file: &'a str,
+	pub line: u32,
+}
+
+impl Display for SourceLocation<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}", self.file, self.line)
+	}
+}
+
+/// The debug information of a function
+#[derive(Debug)]
+pub struct DebugInfoDef {
+	function: ConstantIndex,
+	file: ConstantIndex,
+	lines: Vec<LineEntry>,
+	locals: Vec<LocalEntry>,
+	len: usize,
+}
+
+impl DebugInfoDef {
+	/// The fully-qualified name of the function
+	pub fn function(&self) -> ConstantIndex {
+		self.function
+	}
+
+	/// The name of the source file
+	pub fn file(&self) -> ConstantIndex {
+		self.file
+	}
+
+	/// The line table, ordered by bytecode offset
+	pub fn lines(&self) -> &Vec<LineEntry> {
+		&self.lines
+	}
+
+	pub fn locals(&self) -> &Vec<LocalEntry> {
+		&self.locals
+	}
+
+	/// The source line of the instruction at `offset`
+	pub fn line(&self, offset: usize) -> Option<u32> {
+		self.lines.iter().take_while(|entry| entry.offset as usize <= offset).last().map(|entry| entry.line)
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+impl Definition for DebugInfoDef {}
+
+impl From<&[u8]> for DebugInfoDef {
+	fn from(bytes: &[u8]) -> Self {
+		DebugInfoDef::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+	}
+}
+
+impl<'de> Deserialize<'de> for DebugInfoDef {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+		struct DebugInfoDefVisitor;
+
+		impl<'de> Visitor<'de> for DebugInfoDefVisitor {
+			type Value = DebugInfoDef;
+
+			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+				formatter.write_str("a &[u8] comprising a Debug Info Definition (as-per E# standard)")
+			}
+
+			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
+				let function = read::<u16, E>(v, 0)?;
+				let file = read::<u16, E>(v, 2)?;
+
+				let lines_len = read::<u16, E>(v, 4)?;
+				let mut head = 6;
+				let mut lines = Vec::new();
+				for _ in 0..lines_len {
+					lines.push(LineEntry {
+						offset: read::<u32, E>(v, head)?,
+						line: read::<u32, E>(v, head + 4)?,
+					});
+					head += 8;
+				}
+
+				let locals_len = read::<u16, E>(v, head)?;
+				head += 2;
+				let mut locals = Vec::new();
+				for _ in 0..locals_len {
+					locals.push(LocalEntry {
+						local: read::<u8, E>(v, head)?,
+						name: read::<u16, E>(v, head + 1)?,
+					});
+					head += 3;
+				}
+
+				Ok(DebugInfoDef {
+					function,
+					file,
+					lines,
+					locals,
+					len: head + 2,
+				})
+			}
+		}
+
+		deserializer.deserialize_struct("DebugInfoDef", &["function", "file", "lines", "locals", "len"], DebugInfoDefVisitor)
+	}
+}
+
+/// The debug information of the functions (and methods) of an executable
+#[derive(Debug, Default)]
+pub struct DebugInfoTable {
+	functions: Vec<DebugInfoDef>,
+	len: usize,
+}
+
+impl DebugInfoTable {
+	pub const TERMINATOR: u16 = 0xDEB6;
+
+	pub fn functions(&self) -> &Vec<DebugInfoDef> {
+		&self.functions
+	}
+
+	/// Finds the debug information of the function named `name`, resolving function names with `constants`
+	pub fn find(&self, constants: &ConstantTable, name: &str) -> Option<&DebugInfoDef> {
+		self.functions.iter().find(|function| constants.str(function.function) == Some(name))
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+impl DebugInfoTable {
+	/// Parses a debug info table, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<DebugInfoTable, FormatError> {
+		DebugInfoTable::deserialize(BytesDeserializer::<FormatError>::new(bytes))
+	}
+}
+
+impl From<&[u8]> for DebugInfoTable {
+	fn from(bytes: &[u8]) -> Self {
+		DebugInfoTable::parse(bytes).unwrap()
+	}
+}
+
+impl<'de> Deserialize<'de> for DebugInfoTable {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+		struct DebugInfoTableVisitor;
+
+		impl<'de> Visitor<'de> for DebugInfoTableVisitor {
+			type Value = DebugInfoTable;
+
+			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+				formatter.write_str("a &[u8] comprising a Debug Info Table (as-per E# standard)")
+			}
+
+			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
+				let mut functions = Vec::new();
+
+				if util::deserialize_trailing::<u64>(v).is_ok_and(|marker| marker == EMPTY_TABLE) {
+					return Ok(DebugInfoTable {
+						functions,
+						len: 8,
+					})
+				}
+
+				let mut head: usize = 0;
+				loop {
+					let v = v.split_at(head).1;
+					let function = DebugInfoDef::deserialize(BytesDeserializer::<E>::new(v))?;
+					let len = function.len;
+					functions.push(function);
+
+					let terminator = read::<u16, E>(v, len - 2)?;
+					if terminator == DebugInfoTable::TERMINATOR {
+						return Ok(DebugInfoTable {
+							functions,
+							len: head + len,
+						})
+					}
+
+					head += len;
+				}
+			}
+		}
+
+		deserializer.deserialize_struct("DebugInfoTable", &["functions", "len"], DebugInfoTableVisitor)
+	}
+}
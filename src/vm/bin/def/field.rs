@@ -3,7 +3,7 @@ use serde::{Deserialize, Deserializer};
 use serde::de::{Error, Visitor};
 use serde::de::value::BytesDeserializer;
 use crate::util;
-use crate::vm::bin::def::Definition;
+use crate::vm::bin::def::{Definition, EMPTY_TABLE, read, type_desc};
 use crate::vm::error::jit::FormatError;
 use crate::vm::types::{ConstantIndex, TypeFlags};
 
@@ -60,20 +60,9 @@ impl<'de> Deserialize<'de> for FieldDef {
 			}
 			
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
-				let mut len: usize = 3;
-				let name = util::deserialize::<u16>(&v[0..1]).unwrap();
-				let type_flags = v[2];
-				let type_operand = match type_flags & 0x0F {
-					0x8 => {
-						len += 1;
-						Some(v[3] as u16)
-					},
-					0x6 | 0x7 => {
-						len += 2;
-						Some(util::deserialize::<u16>(&v[3..4]).unwrap())
-					},
-					_ => None,
-				};
+				let name = read::<u16, E>(v, 0)?;
+				let (type_flags, type_operand, head) = type_desc::<E>(v, 2)?;
+				let len = head + 2;
 				
 				Ok(FieldDef {
 					name,
@@ -95,6 +84,8 @@ pub struct FieldTable {
 }
 
 impl FieldTable {
+	pub const TERMINATOR: u16 = 0xBABA;
+	
 	pub fn fields(&self) -> &Vec<FieldDef> {
 		&self.fields
 	}
@@ -104,9 +95,16 @@ impl FieldTable {
 	}
 }
 
+impl FieldTable {
+	/// Parses a field table, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<FieldTable, FormatError> {
+		FieldTable::deserialize(BytesDeserializer::<FormatError>::new(bytes))
+	}
+}
+
 impl From<&[u8]> for FieldTable {
 	fn from(bytes: &[u8]) -> Self {
-		FieldTable::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		FieldTable::parse(bytes).unwrap()
 	}
 }
 
@@ -124,15 +122,22 @@ impl<'de> Deserialize<'de> for FieldTable {
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
 				let mut fields = Vec::new();
 				
+				if util::deserialize_trailing::<u64>(v).is_ok_and(|marker| marker == EMPTY_TABLE) {
+					return Ok(FieldTable {
+						fields,
+						len: 8,
+					})
+				}
+				
 				let mut head: usize = 0;
 				loop {
 					let v = v.split_at(head).1;
-					let field = FieldDef::from(v);
+					let field = FieldDef::deserialize(BytesDeserializer::<E>::new(v))?;
 					let len = field.len;
 					fields.push(field);
 					
-					let terminator = util::deserialize::<u16>(&v[len - 2..len]).unwrap();
-					if terminator == 0xBABA {
+					let terminator = read::<u16, E>(v, len - 2)?;
+					if terminator == FieldTable::TERMINATOR {
 						return Ok(FieldTable {
 							fields,
 							len: head + len,
@@ -1,11 +1,12 @@
 use std::fmt::Formatter;
-use serde::{Deserialize, Deserializer};
-use serde::de::{Error, Visitor};
-use serde::de::value::BytesDeserializer;
+use serde::Deserializer;
+use serde::de::{DeserializeSeed, Error, Visitor};
 use crate::util;
-use crate::vm::bin::def::Definition;
-use crate::vm::error::jit::FormatError;
-use crate::vm::types::{ConstantIndex, TypeFlags};
+use crate::vm::bin::buffer::{Bytes, Shared};
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::bin::def::{Definition, EMPTY_TABLE, read, slice, type_desc};
+use crate::vm::error::jit::{ExecutableFormatError, FormatError};
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlags};
 
 #[derive(Debug)]
 pub struct FunctionDef {
@@ -14,8 +15,9 @@ pub struct FunctionDef {
 	return_type_operand: Option<u16>,
 	args_len: u16,
 	args: Vec<u8>,
+	arg_operands: Vec<Option<u16>>,
 	code_len: u64,
-	code: Vec<u8>,
+	code: Bytes,
 	len: usize,
 }
 
@@ -40,11 +42,25 @@ impl FunctionDef {
 		&self.args
 	}
 	
+	pub fn arg_operands(&self) -> &Vec<Option<u16>> {
+		&self.arg_operands
+	}
+	
+	/// The argument types together with their operands
+	pub fn arg_types(&self) -> Vec<TypeDesc> {
+		self.args.iter().zip(&self.arg_operands).map(|(flags, operand)| TypeDesc::new(*flags, *operand)).collect()
+	}
+	
+	/// The return type together with its operand
+	pub fn return_type_desc(&self) -> TypeDesc {
+		TypeDesc::new(self.return_type, self.return_type_operand)
+	}
+	
 	pub fn code_len(&self) -> u64 {
 		self.code_len
 	}
 	
-	pub fn code(&self) -> &Vec<u8> {
+	pub fn code(&self) -> &Bytes {
 		&self.code
 	}
 	
@@ -55,17 +71,25 @@ impl FunctionDef {
 
 impl Definition for FunctionDef {}
 
+impl From<&Bytes> for FunctionDef {
+	fn from(bytes: &Bytes) -> Self {
+		Shared::<FunctionDef>::new(bytes).parse::<FormatError>().unwrap()
+	}
+}
+
 impl From<&[u8]> for FunctionDef {
 	fn from(bytes: &[u8]) -> Self {
-		FunctionDef::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		FunctionDef::from(&Bytes::from(bytes))
 	}
 }
 
-impl<'de> Deserialize<'de> for FunctionDef {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct FunctionDefVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, FunctionDef> {
+	type Value = FunctionDef;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct FunctionDefVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for FunctionDefVisitor {
+		impl<'de> Visitor<'de> for FunctionDefVisitor<'_> {
 			type Value = FunctionDef;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -73,39 +97,25 @@ impl<'de> Deserialize<'de> for FunctionDef {
 			}
 			
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
-				let mut len: usize = 12;
-				let mut offset: usize = 0;
+				let name = read::<u16, E>(v, 0)?;
+				let (return_type, return_type_operand, mut head) = type_desc::<E>(v, 2)?;
 				
-				let name = util::deserialize::<u16>(&v[0..1]).unwrap();
-				let return_type = v[2];
-				let return_type_operand = match return_type & 0x0F {
-					0x8 => {
-						offset += 1;
-						len += 1;
-						Some(v[3] as u16)
-					},
-					0x6 | 0x7 => {
-						offset += 2;
-						len += 2;
-						Some(util::deserialize::<u16>(&v[3..4]).unwrap())
-					},
-					_ => None
-				};
-				
-				let v = v.split_at(len + offset - 1).1;
-				let args_len = util::deserialize::<u16>(&v[0..1]).unwrap();
+				let args_len = read::<u16, E>(v, head)?;
+				head += 2;
 				let mut args = Vec::new();
-				for i in 0..args_len {
-					args.push(v[i as usize + 2])
+				let mut arg_operands = Vec::new();
+				for _ in 0..args_len {
+					let (arg, operand, next) = type_desc::<E>(v, head)?;
+					args.push(arg);
+					arg_operands.push(operand);
+					head = next;
 				}
 				
-				let v = v.split_at(args_len as usize - 1).1;
-				let code_len = util::deserialize::<u64>(&v[0..8]).unwrap();
-				len += code_len as usize;
-				let mut code = Vec::new();
-				for i in 0..code_len {
-					args.push(v[i as usize + 9])
-				}
+				let code_len = read::<u64, E>(v, head)?;
+				head += 8;
+				let code_len_usize = usize::try_from(code_len).map_err(|_| Error::custom(ExecutableFormatError::UnexpectedEnd))?;
+				let code = self.0.slice_ref(slice::<E>(v, head, code_len_usize)?);
+				let len = head + code_len_usize + 2;
 				
 				Ok(FunctionDef {
 					name,
@@ -113,6 +123,7 @@ impl<'de> Deserialize<'de> for FunctionDef {
 					return_type_operand,
 					args_len,
 					args,
+					arg_operands,
 					code_len,
 					code,
 					len,
@@ -120,7 +131,7 @@ impl<'de> Deserialize<'de> for FunctionDef {
 			}
 		}
 		
-		deserializer.deserialize_struct("FunctionDef", &["name", "return_type", "return_type_operand", "args_len", "args", "code_len", "code", "len"], FunctionDefVisitor)
+		deserializer.deserialize_struct("FunctionDef", &["name", "return_type", "return_type_operand", "args_len", "args", "arg_operands", "code_len", "code", "len"], FunctionDefVisitor(self.bytes()))
 	}
 }
 
@@ -131,44 +142,73 @@ pub struct FunctionTable {
 }
 
 impl FunctionTable {
+	pub const TERMINATOR: u16 = 0xFADE;
+	
 	pub fn functions(&self) -> &Vec<FunctionDef> {
 		&self.functions
 	}
 	
+	/// Finds the function named `name`, resolving function names with `constants`
+	pub fn find(&self, constants: &ConstantTable, name: &str) -> Option<&FunctionDef> {
+		self.functions.iter().find(|function| constants.str(function.name) == Some(name))
+	}
+	
 	pub fn len(&self) -> usize {
 		self.len
 	}
 }
 
+impl FunctionTable {
+	/// Parses a function table, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<FunctionTable, FormatError> {
+		Shared::<FunctionTable>::new(&Bytes::from(bytes)).parse()
+	}
+}
+
+impl From<&Bytes> for FunctionTable {
+	fn from(bytes: &Bytes) -> Self {
+		Shared::<FunctionTable>::new(bytes).parse::<FormatError>().unwrap()
+	}
+}
+
 impl From<&[u8]> for FunctionTable {
 	fn from(bytes: &[u8]) -> Self {
-		FunctionTable::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+		FunctionTable::parse(bytes).unwrap()
 	}
 }
 
-impl<'de> Deserialize<'de> for FunctionTable {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-		struct FunctionTableVisitor;
+impl<'de> DeserializeSeed<'de> for Shared<'_, FunctionTable> {
+	type Value = FunctionTable;
+	
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error> where D: Deserializer<'de> {
+		struct FunctionTableVisitor<'a>(&'a Bytes);
 		
-		impl<'de> Visitor<'de> for FunctionTableVisitor {
+		impl<'de> Visitor<'de> for FunctionTableVisitor<'_> {
 			type Value = FunctionTable;
 			
 			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-				formatter.write_str("a &[u8] comprising a Field Table (as-per E# standard)")
+				formatter.write_str("a &[u8] comprising a Function Table (as-per E# standard)")
 			}
 			
 			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
 				let mut functions = Vec::new();
 				
+				if util::deserialize_trailing::<u64>(v).is_ok_and(|marker| marker == EMPTY_TABLE) {
+					return Ok(FunctionTable {
+						functions,
+						len: 8,
+					})
+				}
+				
 				let mut head: usize = 0;
 				loop {
 					let v = v.split_at(head).1;
-					let function = FunctionDef::from(v);
+					let function = Shared::<FunctionDef>::new(&self.0.slice_ref(v)).parse::<E>()?;
 					let len = function.len;
 					functions.push(function);
 					
-					let terminator = util::deserialize::<u16>(&v[len - 2..len]).unwrap();
-					if terminator == 0xFADE {
+					let terminator = read::<u16, E>(v, len - 2)?;
+					if terminator == FunctionTable::TERMINATOR {
 						return Ok(FunctionTable {
 							functions,
 							len: head + len,
@@ -180,6 +220,6 @@ impl<'de> Deserialize<'de> for FunctionTable {
 			}
 		}
 		
-		deserializer.deserialize_struct("FunctionTable", &["functions", "len"], FunctionTableVisitor)
+		deserializer.deserialize_struct("FunctionTable", &["functions", "len"], FunctionTableVisitor(self.bytes()))
 	}
 }
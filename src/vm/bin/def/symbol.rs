@@ -0,0 +1,177 @@
+use std::fmt::Formatter;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{Error, Visitor};
+use serde::de::value::BytesDeserializer;
+use crate::util;
+use crate::vm::bin::def::{Definition, EMPTY_TABLE, read};
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::error::jit::{ExecutableFormatError, FormatError};
+use crate::vm::types::ConstantIndex;
+
+/// What a symbol names
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+	Class = 0,
+	Function = 1,
+}
+
+impl TryFrom<u8> for SymbolKind {
+	type Error = ExecutableFormatError;
+
+	fn try_from(kind: u8) -> Result<Self, Self::Error> {
+		match kind {
+			0 => Ok(SymbolKind::Class),
+			1 => Ok(SymbolKind::Function),
+			_ => Err(ExecutableFormatError::IllegalSymbolKind(kind)),
+		}
+	}
+}
+
+/// A class or function that a library exports, or that an executable imports
+#[derive(Debug)]
+pub struct SymbolDef {
+	kind: SymbolKind,
+	name: ConstantIndex,
+	len: usize,
+}
+
+impl SymbolDef {
+	pub fn kind(&self) -> SymbolKind {
+		self.kind
+	}
+
+	/// The fully-qualified name of the class or function
+	pub fn name(&self) -> ConstantIndex {
+		self.name
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+impl Definition for SymbolDef {}
+
+impl From<&[u8]> for SymbolDef {
+	fn from(bytes: &[u8]) -> Self {
+		SymbolDef::deserialize(BytesDeserializer::<FormatError>::new(bytes)).unwrap()
+	}
+}
+
+impl<'de> Deserialize<'de> for SymbolDef {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+		struct SymbolDefVisitor;
+
+		impl<'de> Visitor<'de> for SymbolDefVisitor {
+			type Value = SymbolDef;
+
+			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+				formatter.write_str("a &[u8] comprising a Symbol Definition (as-per E# standard)")
+			}
+
+			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
+				let kind = SymbolKind::try_from(read::<u8, E>(v, 0)?).map_err(Error::custom)?;
+				let name = read::<u16, E>(v, 1)?;
+
+				Ok(SymbolDef {
+					kind,
+					name,
+					len: 5,
+				})
+			}
+		}
+
+		deserializer.deserialize_struct("SymbolDef", &["kind", "name", "len"], SymbolDefVisitor)
+	}
+}
+
+/// An export table or an import table
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+	symbols: Vec<SymbolDef>,
+	len: usize,
+}
+
+impl SymbolTable {
+	pub const TERMINATOR: u16 = 0xD1D1;
+
+	pub fn symbols(&self) -> &Vec<SymbolDef> {
+		&self.symbols
+	}
+
+	/// Finds the symbol named `name`, resolving symbol names with `constants`
+	pub fn find(&self, constants: &ConstantTable, name: &str) -> Option<&SymbolDef> {
+		self.symbols.iter().find(|symbol| constants.str(symbol.name) == Some(name))
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+impl SymbolTable {
+	/// Parses an export or import table, returning an error if it is malformed
+	pub fn parse(bytes: &[u8]) -> Result<SymbolTable, FormatError> {
+		SymbolTable::deserialize(BytesDeserializer::<FormatError>::new(bytes))
+	}
+}
+
+impl From<&[u8]> for SymbolTable {
+	fn from(bytes: &[u8]) -> Self {
+		SymbolTable::parse(bytes).unwrap()
+	}
+}
+
+impl<'de> Deserialize<'de> for SymbolTable {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+		struct SymbolTableVisitor;
+
+		impl<'de> Visitor<'de> for SymbolTableVisitor {
+			type Value = SymbolTable;
+
+			fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+				formatter.write_str("a &[u8] comprising a Symbol Table (as-per E# standard)")
+			}
+
+			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: Error {
+				let mut symbols = Vec::new();
+
+				if util::deserialize_trailing::<u64>(v).is_ok_and(|marker| marker == EMPTY_TABLE) {
+					return Ok(SymbolTable {
+						symbols,
+						len: 8,
+					})
+				}
+
+				let mut head: usize = 0;
+				loop {
+					let v = v.split_at(head).1;
+					let symbol = SymbolDef::deserialize(BytesDeserializer::<E>::new(v))?;
+					let len = symbol.len;
+					symbols.push(symbol);
+
+					let terminator = read::<u16, E>(v, len - 2)?;
+					if terminator == SymbolTable::TERMINATOR {
+						return Ok(SymbolTable {
+							symbols,
+							len: head + len,
+						})
+					}
+
+					head += len;
+				}
+			}
+		}
+
+		deserializer.deserialize_struct("SymbolTable", &["symbols", "len"], SymbolTableVisitor)
+	}
+}
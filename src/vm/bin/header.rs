@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use serde::Serialize;
+
+/// The revision of the E# standard an executable was built for (the `<major>.<minor>` of the standard's version)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Version {
+	pub major: u8,
+	pub minor: u8,
+}
+
+impl Version {
+	/// The revision this VM implements
+	pub const CURRENT: Version = Version::new(0, 10);
+	/// The revision of executables that carry no version (which predate the version field)
+	pub const UNVERSIONED: Version = Version::new(0, 9);
+
+	pub const fn new(major: u8, minor: u8) -> Self {
+		Version {
+			major,
+			minor,
+		}
+	}
+
+	/// Whether this VM can load executables built for this revision<br>
+	/// ***Note:** Revisions only add to the format until `1.0`, so older revisions of the same major version are supported.*
+	pub fn is_supported(&self) -> bool {
+		self.major == Self::CURRENT.major && self.minor <= Self::CURRENT.minor
+	}
+
+	/// Reads a version field (`0` is [`Version::UNVERSIONED`])
+	pub fn from_bits(bits: u16) -> Self {
+		match bits {
+			0 => Self::UNVERSIONED,
+			bits => Version::new((bits >> 8) as u8, bits as u8),
+		}
+	}
+
+	pub fn to_bits(&self) -> u16 {
+		(self.major as u16) << 8 | self.minor as u16
+	}
+}
+
+impl Display for Version {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}.{}", self.major, self.minor)
+	}
+}
+
+pub type Features = u16;
+
+/// Feature flags of an executable
+pub trait Feature {
+	/// The executable is a dynamic library (it has an export table)
+	const LIBRARY: u16 = 0x0001;
+	/// The last reserved offset holds a checksum of the executable (see [`checksum`])
+	const CHECKSUM: u16 = 0x0002;
+	/// The offset of a debug info table follows the header
+	const DEBUG_INFO: u16 = 0x0004;
+	/// Every feature this VM knows about
+	const KNOWN: u16 = 0x0007;
+}
+
+impl Feature for Features {}
+
+/// The CRC-32 of an executable, which covers every byte but the checksum itself (the last offset of the header)
+pub fn checksum(bytes: &[u8]) -> u32 {
+	let mut hasher = crc32fast::Hasher::new();
+	hasher.update(&bytes[..32]);
+	hasher.update(&bytes[36..]);
+	hasher.finalize()
+}
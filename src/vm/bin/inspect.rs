@@ -0,0 +1,217 @@
+use serde::Serialize;
+
+use crate::vm::bin::Executable;
+use crate::vm::bin::def::constant::{ConstantDef, ConstantTable};
+use crate::vm::bin::def::debug::LineEntry;
+use crate::vm::bin::def::field::{FieldDef, FieldTable};
+use crate::vm::bin::def::function::{FunctionDef, FunctionTable};
+use crate::vm::bin::def::symbol::{SymbolKind, SymbolTable};
+use crate::vm::bin::header::Features;
+use crate::vm::bin::offset::Offsets;
+use crate::vm::types;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlag, TypeFlags, TypeId};
+
+/// The structure of an executable, with every constant index resolved<br>
+/// Serializes to a stable JSON document (see [`to_json`]), for scripts that assert on the contents
+/// of a module.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExecutableInfo {
+	/// The revision of the standard, such as `0.10`
+	pub version: String,
+	pub features: Features,
+	pub offsets: Offsets,
+	pub constants: Vec<ConstantInfo>,
+	pub classes: Vec<ClassInfo>,
+	pub functions: Vec<FunctionInfo>,
+	pub fields: Vec<FieldInfo>,
+	pub exports: Vec<SymbolInfo>,
+	pub imports: Vec<SymbolInfo>,
+	pub debug_info: Vec<DebugInfo>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConstantInfo {
+	pub index: ConstantIndex,
+	/// The type, such as `i32`, `str` or `[u8]`
+	#[serde(rename = "type")]
+	pub ty: String,
+	pub value: ConstantValue,
+}
+
+/// The decoded data of a constant
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum ConstantValue {
+	Int(i64),
+	UInt(u64),
+	Float(f64),
+	Str(String),
+	/// Data that isn't a primitive or a string
+	Bytes(Vec<u8>),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ClassInfo {
+	pub name: String,
+	/// The name of the supertype, `None` if the class extends nothing
+	#[serde(rename = "super")]
+	pub super_name: Option<String>,
+	pub fields: Vec<FieldInfo>,
+	pub methods: Vec<FunctionInfo>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldInfo {
+	pub name: String,
+	#[serde(rename = "type")]
+	pub ty: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SymbolInfo {
+	pub kind: SymbolKind,
+	pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DebugInfo {
+	pub function: String,
+	pub file: String,
+	pub lines: Vec<LineEntry>,
+	pub locals: Vec<LocalInfo>,
+}
+
+/// The name of a local variable
+#[derive(Clone, Debug, Serialize)]
+pub struct LocalInfo {
+	pub local: u8,
+	pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FunctionInfo {
+	pub name: String,
+	pub args: Vec<String>,
+	pub ret: String,
+	/// The size of the bytecode in bytes
+	pub code_size: u64,
+}
+
+/// Describes the structure of `exec`
+pub fn inspect(exec: &Executable) -> ExecutableInfo {
+	let constants = exec.constant_table();
+	ExecutableInfo {
+		version: exec.version().to_string(),
+		features: exec.features(),
+		offsets: exec.offsets(),
+		constants: constants.constants().iter().enumerate()
+			.map(|(index, constant)| constant_info(index as ConstantIndex, constant, constants))
+			.collect(),
+		classes: exec.class_table().classes().iter()
+			.map(|class| ClassInfo {
+				name: name(class.name(), constants),
+				super_name: (class.super_name() != class.name()).then(|| name(class.super_name(), constants)),
+				fields: class.field_table().map_or_else(Vec::new, |table| fields(table, constants)),
+				methods: class.function_table().map_or_else(Vec::new, |table| functions(table, constants)),
+			})
+			.collect(),
+		functions: functions(exec.function_table(), constants),
+		fields: fields(exec.field_table(), constants),
+		exports: symbols(exec.export_table(), constants),
+		imports: symbols(exec.import_table(), constants),
+		debug_info: exec.debug_info().functions().iter()
+			.map(|info| DebugInfo {
+				function: name(info.function(), constants),
+				file: name(info.file(), constants),
+				lines: info.lines().clone(),
+				locals: info.locals().iter().map(|local| LocalInfo {
+					local: local.local,
+					name: name(local.name, constants),
+				}).collect(),
+			})
+			.collect(),
+	}
+}
+
+/// Describes the structure of `exec` as pretty-printed JSON
+pub fn to_json(exec: &Executable) -> String {
+	serde_json::to_string_pretty(&inspect(exec)).expect("Failed to serialize executable info")
+}
+
+/// The name of a type, resolving the names of classes and functions in its operand
+pub fn type_name(ty: TypeDesc, constants: &ConstantTable) -> String {
+	let unsigned = ty.modifier() & TypeFlags::UNSIGNED != 0;
+	let name = match ty.id() {
+		TypeFlags::I8 if unsigned => "u8",
+		TypeFlags::I8 => "i8",
+		TypeFlags::I16 if unsigned => "u16",
+		TypeFlags::I16 => "i16",
+		TypeFlags::I32 if unsigned => "u32",
+		TypeFlags::I32 => "i32",
+		TypeFlags::I64 if unsigned => "u64",
+		TypeFlags::I64 => "i64",
+		TypeFlags::F32 => "f32",
+		TypeFlags::F64 => "f64",
+		TypeFlags::DYN => "dyn",
+		TypeFlags::STR => "str",
+		TypeFlags::VOID => "void",
+		TypeFlags::OBJECT => return ty.operand.map_or_else(|| String::from("object"), |class| name(class, constants)),
+		TypeFlags::FUNCTION => return ty.operand.map_or_else(|| String::from("fn"), |function| format!("fn {}", name(function, constants))),
+		TypeFlags::ARRAY => return match ty.operand {
+			Some(elem) => format!("[{}]", type_name(TypeDesc::new(elem as TypeFlags, None), constants)),
+			None => String::from("[]"),
+		},
+		id => return format!("{:#X}", id),
+	};
+	String::from(name)
+}
+
+/// The string constant at `index`, or the index itself if it doesn't resolve
+fn name(index: ConstantIndex, constants: &ConstantTable) -> String {
+	constants.str(index).map_or_else(|| format!("#{}", index), String::from)
+}
+
+fn constant_info(index: ConstantIndex, constant: &ConstantDef, constants: &ConstantTable) -> ConstantInfo {
+	let ty = TypeDesc::new(constant.type_flags(), constant.type_operand());
+	let is_text = match ty.id() {
+		TypeFlags::STR | TypeFlags::OBJECT => true,
+		TypeFlags::ARRAY => ty.operand.is_some_and(|elem| types::type_id(elem as TypeFlags) == TypeFlags::I8),
+		_ => false,
+	};
+	let value = match (constant.str(), constant.bits()) {
+		(Some(str), _) if is_text => ConstantValue::Str(String::from(str)),
+		(_, Some(bits)) if ty.id() == TypeFlags::F32 => ConstantValue::Float(f32::from_bits(bits as u32) as f64),
+		(_, Some(bits)) if ty.id() == TypeFlags::F64 => ConstantValue::Float(f64::from_bits(bits)),
+		(_, Some(bits)) if ty.is_unsigned() => ConstantValue::UInt(bits),
+		(_, Some(bits)) => ConstantValue::Int(bits as i64),
+		_ => ConstantValue::Bytes(constant.bytes().to_vec()),
+	};
+	ConstantInfo {
+		index,
+		ty: type_name(ty, constants),
+		value,
+	}
+}
+
+fn fields(table: &FieldTable, constants: &ConstantTable) -> Vec<FieldInfo> {
+	table.fields().iter().map(|field: &FieldDef| FieldInfo {
+		name: name(field.name(), constants),
+		ty: type_name(TypeDesc::new(field.type_flags(), field.type_operand()), constants),
+	}).collect()
+}
+
+fn symbols(table: &SymbolTable, constants: &ConstantTable) -> Vec<SymbolInfo> {
+	table.symbols().iter().map(|symbol| SymbolInfo {
+		kind: symbol.kind(),
+		name: name(symbol.name(), constants),
+	}).collect()
+}
+
+fn functions(table: &FunctionTable, constants: &ConstantTable) -> Vec<FunctionInfo> {
+	table.functions().iter().map(|function: &FunctionDef| FunctionInfo {
+		name: name(function.name(), constants),
+		args: function.arg_types().into_iter().map(|arg| type_name(arg, constants)).collect(),
+		ret: type_name(function.return_type_desc(), constants),
+		code_size: function.code_len(),
+	}).collect()
+}
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::vm::bin::Executable;
+use crate::vm::bin::builder::{Class, Constant, DebugInfo, ExecutableBuilder, Field, Function, Symbol};
+use crate::vm::bin::def::debug::LocalEntry;
+use crate::vm::bin::def::symbol::SymbolKind;
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::bin::def::symbol::SymbolDef;
+use crate::vm::error::link::LinkError;
+use crate::vm::insn;
+use crate::vm::types::ConstantIndex;
+
+/// Merges executables into one<br>
+/// The constant tables are merged (equal constants are only kept once), and every constant index in
+/// the definitions and bytecode of each executable is renumbered to refer to the merged table.
+/// The exports and debug information of every executable are kept, as are the imports that none of
+/// them defines. Bytecode offsets don't change, as renumbered instructions keep their length.
+#[derive(Debug, Default)]
+pub struct Linker {
+	builder: ExecutableBuilder,
+	/// The index of every constant in the merged table
+	constants: HashMap<Constant, ConstantIndex>,
+	classes: HashSet<String>,
+	functions: HashSet<String>,
+	fields: HashSet<String>,
+	/// The number of executables linked so far
+	linked: usize,
+}
+
+impl Linker {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds the definitions of `exec`<br>
+	/// ***Note:** Nothing is added if an error is returned.*
+	pub fn link(&mut self, exec: &Executable) -> Result<(), LinkError> {
+		// the constants are merged in place, and removed again if the executable fails to link
+		let len = self.builder.constants.len();
+		let result = self.link_definitions(exec);
+		if result.is_err() {
+			for constant in self.builder.constants.drain(len..) {
+				self.constants.remove(&constant);
+			}
+		}
+		result
+	}
+
+	/// Adds the definitions of `exec`, leaving only its constants in the merged table on failure
+	fn link_definitions(&mut self, exec: &Executable) -> Result<(), LinkError> {
+		let exec_index = self.linked;
+		let table = exec.constant_table();
+		let map = merge_constants(table, &mut self.constants, &mut self.builder.constants).map_err(|index| LinkError::UnresolvedConstant(exec_index, index))?;
+		if self.builder.constants.len() > ConstantIndex::MAX as usize + 1 {
+			return Err(LinkError::TooManyConstants)
+		}
+		let remap = |index: ConstantIndex| map.get(index as usize).copied().ok_or(LinkError::UnresolvedConstant(exec_index, index));
+		let name = |index: ConstantIndex| table.str(index).map(String::from).ok_or(LinkError::InvalidName(exec_index, index));
+
+		// the names this executable defines, which are only added to the linked ones once it links
+		let mut classes = HashSet::new();
+		let mut functions = HashSet::new();
+		let mut fields = HashSet::new();
+		let mut new_classes = Vec::new();
+		for class in exec.class_table().classes() {
+			let class_name = name(class.name())?;
+			if self.classes.contains(&class_name) || !classes.insert(class_name.clone()) {
+				return Err(LinkError::DuplicateClass(class_name))
+			}
+			let class = Class::from(class);
+			let mut methods = Vec::new();
+			for method in &class.methods {
+				methods.push(link_function(method, &self.functions, &mut functions, &name, &remap)?);
+			}
+			new_classes.push(Class {
+				name: remap(class.name)?,
+				super_name: remap(class.super_name)?,
+				fields: class.fields.iter().map(|field| link_field(field, &remap)).collect::<Result<_, _>>()?,
+				methods,
+			});
+		}
+		let mut new_functions = Vec::new();
+		for function in exec.function_table().functions() {
+			new_functions.push(link_function(&Function::from(function), &self.functions, &mut functions, &name, &remap)?);
+		}
+		let mut new_fields = Vec::new();
+		for field in exec.field_table().fields() {
+			let field_name = name(field.name())?;
+			if self.fields.contains(&field_name) || !fields.insert(field_name.clone()) {
+				return Err(LinkError::DuplicateField(field_name))
+			}
+			new_fields.push(link_field(&Field::from(field), &remap)?);
+		}
+		let symbol = |symbol: &SymbolDef| Ok(Symbol {
+			kind: symbol.kind(),
+			name: remap(symbol.name())?,
+		});
+		let mut debug_info = Vec::new();
+		for info in exec.debug_info().functions() {
+			let info = DebugInfo::from(info);
+			debug_info.push(DebugInfo {
+				function: remap(info.function)?,
+				file: remap(info.file)?,
+				lines: info.lines,
+				locals: info.locals.iter()
+					.map(|entry| Ok(LocalEntry { local: entry.local, name: remap(entry.name)? }))
+					.collect::<Result<_, LinkError>>()?,
+			});
+		}
+		let exports = exec.export_table().symbols().iter().map(symbol).collect::<Result<Vec<_>, LinkError>>()?;
+		let imports = exec.import_table().symbols().iter().map(symbol).collect::<Result<Vec<_>, LinkError>>()?;
+
+		self.builder.classes.extend(new_classes);
+		self.builder.functions.extend(new_functions);
+		self.builder.fields.extend(new_fields);
+		self.builder.debug_info.extend(debug_info);
+		for export in exports {
+			if !self.builder.exports.contains(&export) {
+				self.builder.exports.push(export);
+			}
+		}
+		for import in imports {
+			if !self.builder.imports.contains(&import) {
+				self.builder.imports.push(import);
+			}
+		}
+		self.classes.extend(classes);
+		self.functions.extend(functions);
+		self.fields.extend(fields);
+		self.linked += 1;
+		Ok(())
+	}
+
+	/// Returns the merged executable
+	pub fn finish(mut self) -> ExecutableBuilder {
+		let constants = &self.builder.constants;
+		let name = |symbol: &Symbol| std::str::from_utf8(&constants[symbol.name as usize].data).unwrap_or_default();
+		let (classes, functions) = (&self.classes, &self.functions);
+		self.builder.imports.retain(|import| match import.kind {
+			SymbolKind::Class => !classes.contains(name(import)),
+			SymbolKind::Function => !functions.contains(name(import)),
+		});
+		self.builder
+	}
+}
+
+/// Links `execs` into one executable
+pub fn link(execs: &[Executable]) -> Result<ExecutableBuilder, LinkError> {
+	let mut linker = Linker::new();
+	for exec in execs {
+		linker.link(exec)?;
+	}
+	Ok(linker.finish())
+}
+
+/// Adds the constants of `table` to `merged`, returning the merged index of each of them<br>
+/// Returns the index of a constant whose type operand doesn't resolve on failure.
+fn merge_constants(table: &ConstantTable, indices: &mut HashMap<Constant, ConstantIndex>, merged: &mut Vec<Constant>) -> Result<Vec<ConstantIndex>, ConstantIndex> {
+	let len = table.constants().len();
+	let mut map: Vec<Option<ConstantIndex>> = vec![None; len];
+	// the type operand of a constant may name a class by a later constant, so merge until nothing changes
+	loop {
+		let mut progress = false;
+		for (i, def) in table.constants().iter().enumerate() {
+			if map[i].is_some() {
+				continue
+			}
+			let constant = Constant::from(def);
+			let ty = match constant.ty.constant() {
+				Some(operand) if operand as usize >= len => return Err(i as ConstantIndex),
+				Some(operand) => match map[operand as usize] {
+					Some(merged) => constant.ty.map_constant::<()>(|_| Ok(merged)).unwrap(),
+					None => continue,
+				},
+				None => constant.ty,
+			};
+			let constant = Constant {
+				ty,
+				data: constant.data,
+			};
+			let index = *indices.entry(constant).or_insert_with_key(|constant| {
+				merged.push(constant.clone());
+				(merged.len() - 1) as ConstantIndex
+			});
+			map[i] = Some(index);
+			progress = true;
+		}
+		if !progress {
+			break
+		}
+	}
+	map.iter().enumerate()
+		.map(|(i, index)| index.ok_or(i as ConstantIndex))
+		.collect()
+}
+
+fn link_field(field: &Field, remap: &impl Fn(ConstantIndex) -> Result<ConstantIndex, LinkError>) -> Result<Field, LinkError> {
+	Ok(Field {
+		name: remap(field.name)?,
+		ty: field.ty.map_constant(remap)?,
+	})
+}
+
+fn link_function(
+	function: &Function,
+	linked: &HashSet<String>,
+	functions: &mut HashSet<String>,
+	name: &impl Fn(ConstantIndex) -> Result<String, LinkError>,
+	remap: &impl Fn(ConstantIndex) -> Result<ConstantIndex, LinkError>,
+) -> Result<Function, LinkError> {
+	let function_name = name(function.name)?;
+	if linked.contains(&function_name) || !functions.insert(function_name.clone()) {
+		return Err(LinkError::DuplicateFunction(function_name))
+	}
+	let insns = insn::decode_all(&function.code).map_err(|err| LinkError::Decode(function_name.clone(), err))?;
+	let mut code = Vec::with_capacity(function.code.len());
+	for (_, insn) in insns {
+		insn.map_constants(remap)?.encode(&mut code);
+	}
+	Ok(Function {
+		name: remap(function.name)?,
+		ret: function.ret.map_constant(remap)?,
+		args: function.args.iter().map(|arg| arg.map_constant(remap)).collect::<Result<_, _>>()?,
+		code,
+	})
+}
@@ -1,12 +1,14 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// The offsets of all the relevant data in the executable
-#[derive(Copy, Clone, Deserialize, Debug)]
+#[derive(Copy, Clone, Deserialize, Serialize, Debug)]
 pub struct Offsets {
 	constant_table: u32,
 	class_table: u32,
 	function_table: u32,
 	field_table: u32,
+	export_table: u32,
+	import_table: u32,
 }
 
 impl Offsets {
@@ -25,4 +27,14 @@ impl Offsets {
 	pub fn field_table(&self) -> u32 {
 		self.field_table
 	}
+	
+	/// The offset of the export table (`0` if there is none)
+	pub fn export_table(&self) -> u32 {
+		self.export_table
+	}
+	
+	/// The offset of the import table (`0` if there is none)
+	pub fn import_table(&self) -> u32 {
+		self.import_table
+	}
 }
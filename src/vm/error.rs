@@ -1 +1,8 @@
 pub mod jit;
+pub mod insn;
+pub mod object;
+pub mod vm;
+pub mod verify;
+pub mod interp;
+pub mod link;
+pub mod asm;
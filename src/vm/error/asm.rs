@@ -0,0 +1,38 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+/// A failure to assemble E# assembly, at a line (starting at `1`)
+pub enum AsmError {
+	/// What was expected, and what was found instead (empty at the end of the line)
+	Expected(usize, &'static str, String),
+	UnknownInstruction(usize, String),
+	/// A literal that isn't a value of the type of its constant
+	InvalidLiteral(usize, String),
+	UnterminatedString(usize),
+	/// A block (a function, class or method) that isn't closed by `end`
+	Unterminated(usize),
+	/// The executable needs more than 65536 constants
+	TooManyConstants,
+}
+
+impl Debug for AsmError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Expected(line, expected, found) if found.is_empty() => f.write_fmt(format_args!("line {}: expected {}, found end of line", line, expected)),
+			Self::Expected(line, expected, found) => f.write_fmt(format_args!("line {}: expected {}, found `{}`", line, expected, found)),
+			Self::UnknownInstruction(line, mnemonic) => f.write_fmt(format_args!("line {}: unknown instruction `{}`", line, mnemonic)),
+			Self::InvalidLiteral(line, literal) => f.write_fmt(format_args!("line {}: invalid literal `{}`", line, literal)),
+			Self::UnterminatedString(line) => f.write_fmt(format_args!("line {}: unterminated string", line)),
+			Self::Unterminated(line) => f.write_fmt(format_args!("line {}: missing `end`", line)),
+			Self::TooManyConstants => f.write_str("the executable has more than 65536 constants"),
+		}
+	}
+}
+
+impl Display for AsmError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for AsmError {}
@@ -0,0 +1,24 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+pub enum DecodeError {
+	IllegalOpcode(u8),
+	UnexpectedEnd,
+}
+
+impl Debug for DecodeError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::IllegalOpcode(opcode) => f.write_fmt(format_args!("illegal opcode {:#X}", opcode)),
+			Self::UnexpectedEnd => f.write_str("unexpected end of bytecode"),
+		}
+	}
+}
+
+impl Display for DecodeError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for DecodeError {}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::vm::error::vm::VmError;
+
+/// A runtime failure of interpreted or compiled code, which [`Vm::call`](crate::vm::Vm::call) returns<br>
+/// ***Note:** Compiled code called other than through `Vm::call` prints the message and aborts the process instead.*
+pub enum Trap {
+	IndexOutOfBounds(i64, usize),
+	NegativeLength(i64),
+	DivisionByZero,
+	/// A field or method of a null object, or an element or the length of a null array, is accessed
+	NullReference,
+	/// The class (the first name) doesn't implement the trait (the second name)
+	NotImplemented(String, String),
+	/// A function is declared (such as a trait method) but has no code<br>
+	/// Its name is `None` if compiled code called it, which calls through the function's code pointer.
+	UndefinedFunction(Option<String>),
+	UnknownFunction(String),
+	/// A function of a [lazy](crate::vm::Vm::set_lazy) VM failed to materialize when it was first
+	/// called, which `Vm::call` returns as the error it failed with
+	Materialize(Box<VmError>),
+}
+
+impl Debug for Trap {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::IndexOutOfBounds(index, len) => f.write_fmt(format_args!("index {} out of bounds for length {}", index, len)),
+			Self::NegativeLength(len) => f.write_fmt(format_args!("negative array length {}", len)),
+			Self::DivisionByZero => f.write_str("division by zero"),
+			Self::NullReference => f.write_str("null reference"),
+			Self::NotImplemented(class, trait_name) => f.write_fmt(format_args!("class {} does not implement trait {}", class, trait_name)),
+			Self::UndefinedFunction(Some(function)) => f.write_fmt(format_args!("called function {} which has no definition", function)),
+			Self::UndefinedFunction(None) => f.write_str("called a function which has no definition"),
+			Self::UnknownFunction(function) => f.write_fmt(format_args!("unknown function {}", function)),
+			Self::Materialize(err) => Debug::fmt(err, f),
+		}
+	}
+}
+
+impl Display for Trap {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for Trap {}
@@ -6,15 +6,57 @@ use serde::de;
 use serde::de::{Expected, Unexpected};
 use crate::vm::bin::def::class::ClassDef;
 use crate::vm::bin::Executable;
+use crate::vm::bin::header::Version;
+use crate::vm::error::insn::DecodeError;
+use crate::vm::insn::Insn;
 use crate::vm::meta::platform::PlatformKind;
+use crate::vm::types::ConstantIndex;
 
 #[derive(Debug)]
 pub enum TranspileError {
 	Unknown,
 	IllegalInsn(*mut u8),
 	UnsupportedPlatform(PlatformKind),
+	Decode(DecodeError),
+	/// An instruction the JIT cannot compile, and its bytecode offset
+	UnsupportedInsn(usize, Insn),
+	UnresolvedConstant(ConstantIndex),
+	UnsupportedConstant(ConstantIndex),
+	UnknownFunction(String),
+	UnknownClass(String),
+	UnknownField(String, String),
+	UnknownMethod(String, String),
+	/// An instruction at the bytecode offset popped a value of the wrong type
+	TypeMismatch(usize),
+	/// An instruction at the bytecode offset popped from an empty stack
+	StackUnderflow(usize),
+	/// An instruction at the bytecode offset pushed a local variable that isn't defined
+	UndefinedLocal(usize, u8),
+	TooManyArgs(usize),
 }
 
+impl Display for TranspileError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Decode(err) => Display::fmt(err, f),
+			Self::UnsupportedInsn(offset, insn) => f.write_fmt(format_args!("unsupported instruction `{}` at {:#X}", insn, offset)),
+			Self::UnresolvedConstant(index) => f.write_fmt(format_args!("constant #{} does not exist", index)),
+			Self::UnsupportedConstant(index) => f.write_fmt(format_args!("constant #{} cannot be loaded", index)),
+			Self::UnknownFunction(function) => f.write_fmt(format_args!("unknown function {}", function)),
+			Self::UnknownClass(class) => f.write_fmt(format_args!("unknown class {}", class)),
+			Self::UnknownField(class, field) => f.write_fmt(format_args!("class {} has no field {}", class, field)),
+			Self::UnknownMethod(class, method) => f.write_fmt(format_args!("class {} has no method {}", class, method)),
+			Self::TypeMismatch(offset) => f.write_fmt(format_args!("type mismatch at {:#X}", offset)),
+			Self::StackUnderflow(offset) => f.write_fmt(format_args!("stack underflow at {:#X}", offset)),
+			Self::UndefinedLocal(offset, local) => f.write_fmt(format_args!("undefined local variable {} at {:#X}", local, offset)),
+			Self::TooManyArgs(args) => f.write_fmt(format_args!("too many arguments ({})", args)),
+			_ => Debug::fmt(self, f),
+		}
+	}
+}
+
+impl Error for TranspileError {}
+
 #[derive(Debug)]
 pub struct FormatError(String);
 
@@ -38,6 +80,18 @@ pub enum ExecutableFormatError {
 	InvalidMagic(u32),
 	IllegalTypeModifier(u8),
 	IllegalTypeId(u8),
+	IllegalSymbolKind(u8),
+	/// A dynamic library has no export table
+	MissingExportTable,
+	UnsupportedVersion(Version),
+	/// The feature flags this VM doesn't know about
+	UnsupportedFeatures(u16),
+	/// The checksum in the header, and the checksum of the contents
+	ChecksumMismatch(u32, u32),
+	/// A definition or table runs past the end of the executable
+	UnexpectedEnd,
+	/// An offset in the header points past the end of the executable
+	InvalidOffset(u32),
 }
 
 impl Debug for ExecutableFormatError {
@@ -46,6 +100,13 @@ impl Debug for ExecutableFormatError {
 			Self::InvalidMagic(magic) => f.write_fmt(format_args!("invalid magic {:#X}, expected magic {:#X}", magic, Executable::MAGIC)),
 			Self::IllegalTypeModifier(type_modifier) => f.write_fmt(format_args!("illegal type modifier {:#X}", type_modifier)),
 			Self::IllegalTypeId(type_id) => f.write_fmt(format_args!("illegal type ID {:#X}", type_id)),
+			Self::IllegalSymbolKind(kind) => f.write_fmt(format_args!("illegal symbol kind {:#X}", kind)),
+			Self::MissingExportTable => f.write_str("missing export table, dynamic libraries must export their symbols"),
+			Self::UnsupportedVersion(version) => f.write_fmt(format_args!("unsupported version {}, expected version {} or older", version, Version::CURRENT)),
+			Self::UnsupportedFeatures(features) => f.write_fmt(format_args!("unsupported feature flags {:#06X}", features)),
+			Self::ChecksumMismatch(expected, found) => f.write_fmt(format_args!("checksum mismatch, expected checksum {:#010X} but the contents hash to {:#010X} (the executable is corrupt)", expected, found)),
+			Self::UnexpectedEnd => f.write_str("unexpected end of executable"),
+			Self::InvalidOffset(offset) => f.write_fmt(format_args!("invalid offset {:#X}, past the end of the executable", offset)),
 		}
 	}
 }
@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::vm::error::insn::DecodeError;
+use crate::vm::types::ConstantIndex;
+
+pub enum LinkError {
+	/// An operand of the executable at the index refers to a constant that does not exist
+	UnresolvedConstant(usize, ConstantIndex),
+	/// A name of the executable at the index is not a string constant
+	InvalidName(usize, ConstantIndex),
+	DuplicateClass(String),
+	DuplicateFunction(String),
+	DuplicateField(String),
+	/// The bytecode of the named function failed to decode
+	Decode(String, DecodeError),
+	TooManyConstants,
+}
+
+impl Debug for LinkError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnresolvedConstant(exec, index) => f.write_fmt(format_args!("constant #{} of executable {} does not exist", index, exec)),
+			Self::InvalidName(exec, index) => f.write_fmt(format_args!("constant #{} of executable {} is not a valid name", index, exec)),
+			Self::DuplicateClass(class) => f.write_fmt(format_args!("class {} is defined more than once", class)),
+			Self::DuplicateFunction(function) => f.write_fmt(format_args!("function {} is defined more than once", function)),
+			Self::DuplicateField(field) => f.write_fmt(format_args!("field {} is defined more than once", field)),
+			Self::Decode(function, err) => f.write_fmt(format_args!("failed to decode {}: {}", function, err)),
+			Self::TooManyConstants => f.write_str("the linked executable has more than 65536 constants"),
+		}
+	}
+}
+
+impl Display for LinkError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for LinkError {}
@@ -0,0 +1,31 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::vm::types::ConstantIndex;
+
+pub enum LayoutError {
+	InvalidName(ConstantIndex),
+	UnknownSuper(String, String),
+	CyclicInheritance(String),
+	DuplicateClass(String),
+	DuplicateField(String, String),
+}
+
+impl Debug for LayoutError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InvalidName(index) => f.write_fmt(format_args!("constant #{} is not a valid class or field name", index)),
+			Self::UnknownSuper(class, super_name) => f.write_fmt(format_args!("class {} extends unknown class {}", class, super_name)),
+			Self::CyclicInheritance(class) => f.write_fmt(format_args!("class {} inherits from itself", class)),
+			Self::DuplicateClass(class) => f.write_fmt(format_args!("class {} is defined more than once", class)),
+			Self::DuplicateField(class, field) => f.write_fmt(format_args!("field {} is defined more than once in class {}", field, class)),
+		}
+	}
+}
+
+impl Display for LayoutError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for LayoutError {}
@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::vm::error::insn::DecodeError;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::function::Signature;
+
+/// An error in the bytecode of a function<br>
+/// The `usize` of each variant is the bytecode offset of the offending instruction.
+pub enum VerifyError {
+	Decode(DecodeError),
+	StackUnderflow(usize),
+	/// An instruction expected a value of the first type, but found the second
+	TypeMismatch(usize, TypeDesc, TypeDesc),
+	/// An instruction expected an object of the first class (or of one of its subclasses), or an
+	/// array of objects of the first class, but found the second (`None` if the class isn't known)
+	ClassMismatch(usize, String, Option<String>),
+	/// An instruction operates on a type it doesn't support
+	InvalidType(usize, TypeDesc),
+	UndefinedLocal(usize, u8),
+	UnresolvedConstant(usize, ConstantIndex),
+	UnsupportedConstant(usize, ConstantIndex),
+	UnknownFunction(usize, String),
+	UnknownClass(usize, String),
+	UnknownField(usize, String, String),
+	UnknownMethod(usize, String, String),
+	/// A virtual or trait call calls a method (the function) that takes no receiver
+	MissingReceiver(usize, String),
+	/// A return doesn't return the function's return type (the second type)
+	ReturnMismatch(usize, TypeDesc),
+	/// The bytecode ends without returning
+	MissingReturn,
+	/// The function takes more argument slots than [`Signature::MAX_ARG_SLOTS`], and how many
+	TooManyArgs(usize),
+}
+
+impl Debug for VerifyError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Decode(err) => Debug::fmt(err, f),
+			Self::StackUnderflow(offset) => f.write_fmt(format_args!("stack underflow at {:#X}", offset)),
+			Self::TypeMismatch(offset, expected, found) => f.write_fmt(format_args!("expected a value of type {} at {:#X}, found {}", type_name(expected), offset, type_name(found))),
+			Self::ClassMismatch(offset, expected, found) => f.write_fmt(format_args!("expected class {} at {:#X}, found {}", expected, offset, found.as_deref().unwrap_or("an unknown class"))),
+			Self::InvalidType(offset, ty) => f.write_fmt(format_args!("invalid type {:#04X} at {:#X}", ty.flags, offset)),
+			Self::UndefinedLocal(offset, local) => f.write_fmt(format_args!("undefined local variable {} at {:#X}", local, offset)),
+			Self::UnresolvedConstant(offset, index) => f.write_fmt(format_args!("unresolved constant #{} at {:#X}", index, offset)),
+			Self::UnsupportedConstant(offset, index) => f.write_fmt(format_args!("constant #{} can't be loaded at {:#X}", index, offset)),
+			Self::UnknownFunction(offset, function) => f.write_fmt(format_args!("unknown function {} at {:#X}", function, offset)),
+			Self::UnknownClass(offset, class) => f.write_fmt(format_args!("unknown class {} at {:#X}", class, offset)),
+			Self::UnknownField(offset, class, field) => f.write_fmt(format_args!("class {} has no field {} at {:#X}", class, field, offset)),
+			Self::UnknownMethod(offset, class, method) => f.write_fmt(format_args!("class {} has no method {} at {:#X}", class, method, offset)),
+			Self::MissingReceiver(offset, method) => f.write_fmt(format_args!("method {} called at {:#X} takes no receiver", method, offset)),
+			Self::ReturnMismatch(offset, ret) => f.write_fmt(format_args!("return at {:#X} doesn't return the return type {:#04X}", offset, ret.flags)),
+			Self::MissingReturn => f.write_str("bytecode ends without returning"),
+			Self::TooManyArgs(slots) => f.write_fmt(format_args!("function takes {} argument slots, at most {} are supported", slots, Signature::MAX_ARG_SLOTS)),
+		}
+	}
+}
+
+/// The flags of a type, followed by the flags of the elements of an array type
+fn type_name(ty: &TypeDesc) -> String {
+	match (ty.id(), ty.operand) {
+		(TypeFlags::ARRAY, Some(elem)) => format!("{:#04X}[{:#04X}]", ty.flags, elem),
+		_ => format!("{:#04X}", ty.flags),
+	}
+}
+
+impl Display for VerifyError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for VerifyError {}
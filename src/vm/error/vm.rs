@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::vm::error::interp::Trap;
+use crate::vm::error::jit::TranspileError;
+use crate::vm::error::object::LayoutError;
+use crate::vm::error::verify::VerifyError;
+use crate::vm::types::ConstantIndex;
+
+pub enum VmError {
+	Layout(LayoutError),
+	/// The named function failed to compile
+	Transpile(String, TranspileError),
+	/// The named function failed verification
+	Verify(String, VerifyError),
+	InvalidName(ConstantIndex),
+	DuplicateFunction(String),
+	UnknownFunction(String),
+	/// The named function takes the first number of argument words, but was called with the second
+	ArgumentCount(String, usize, usize),
+	Trap(Trap),
+	/// No loaded library exports the imported class or function
+	UnresolvedImport(String),
+	/// A library exports a class or function it doesn't define
+	UnresolvedExport(String),
+}
+
+impl Debug for VmError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Layout(err) => Debug::fmt(err, f),
+			Self::Transpile(function, err) => f.write_fmt(format_args!("failed to compile {}: {}", function, err)),
+			Self::Verify(function, err) => f.write_fmt(format_args!("failed to verify {}: {}", function, err)),
+			Self::InvalidName(index) => f.write_fmt(format_args!("constant #{} is not a valid function name", index)),
+			Self::DuplicateFunction(function) => f.write_fmt(format_args!("function {} is defined more than once", function)),
+			Self::UnknownFunction(function) => f.write_fmt(format_args!("unknown function {}", function)),
+			Self::ArgumentCount(function, expected, found) => f.write_fmt(format_args!("function {} takes {} argument words, but {} were passed", function, expected, found)),
+			Self::Trap(trap) => Debug::fmt(trap, f),
+			Self::UnresolvedImport(name) => f.write_fmt(format_args!("unresolved import {}, no loaded library exports it", name)),
+			Self::UnresolvedExport(name) => f.write_fmt(format_args!("exported symbol {} is not defined", name)),
+		}
+	}
+}
+
+impl Display for VmError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self, f)
+	}
+}
+
+impl Error for VmError {}
+
+impl From<LayoutError> for VmError {
+	fn from(err: LayoutError) -> Self {
+		Self::Layout(err)
+	}
+}
@@ -0,0 +1,320 @@
+use std::alloc::Layout;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::mem;
+use std::ptr::null_mut;
+use crate::vm::types::{self, TypeDesc};
+use crate::vm::types::array::Array;
+use crate::vm::types::object::{ClassLayout, ObjectHeader};
+use crate::vm::types::str::Str;
+
+/// The default number of bytes that may be allocated between two collections
+const DEFAULT_THRESHOLD: usize = 1 << 20;
+
+/// What a heap allocation holds
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+	Object,
+	Array,
+	Str,
+}
+
+/// The header in front of every heap allocation<br>
+/// ***Note:** Values point past this header, to their [`ObjectHeader`], [`Array`] or [`Str`] header.*
+#[repr(C)]
+struct GcHeader {
+	next: *mut GcHeader,
+	layout: Layout,
+	kind: Kind,
+	marked: bool,
+}
+
+impl GcHeader {
+	/// The offset of a value from its header
+	const SIZE: usize = (mem::size_of::<GcHeader>() + 7) & !7;
+
+	unsafe fn of(value: *mut u8) -> *mut GcHeader {
+		value.sub(Self::SIZE) as *mut GcHeader
+	}
+
+	unsafe fn value(this: *mut GcHeader) -> *mut u8 {
+		(this as *mut u8).add(Self::SIZE)
+	}
+}
+
+/// The frame offsets (from `rbp`) of the references that are live at a call site of compiled code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackMap {
+	/// The offset of the call's return address in the function's code
+	pub offset: usize,
+	/// The frame offsets of the words that hold references (a `dyn` is recorded by its object word)
+	pub slots: Vec<i32>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+	/// The number of live allocations
+	pub allocations: usize,
+	/// The number of bytes held by live allocations (including their headers)
+	pub bytes: usize,
+	/// The number of collections so far
+	pub collections: usize,
+	/// The number of allocations freed so far
+	pub freed: usize,
+}
+
+/// The garbage-collected heap of objects, arrays and strings<br>
+/// Unreachable values are reclaimed by a precise mark-sweep collector. A value is reachable if it is
+/// referenced by a root (see [`Heap::root`]), by a live frame of compiled code, or by another
+/// reachable value.
+#[derive(Debug)]
+pub struct Heap {
+	/// The most recent allocation, every allocation links to the one before it
+	head: Cell<*mut GcHeader>,
+	/// Host roots and the number of times each was rooted
+	roots: RefCell<HashMap<usize, usize>>,
+	/// Stack maps, keyed by the absolute return address of their call site
+	stack_maps: RefCell<HashMap<usize, Box<[i32]>>>,
+	/// Rooted strings for string constants, keyed by their contents
+	interned: RefCell<HashMap<String, *mut Str>>,
+	stats: Cell<HeapStats>,
+	/// The number of bytes allocated since the last collection
+	allocated: Cell<usize>,
+	/// The number of bytes that survived the last collection
+	live: Cell<usize>,
+	threshold: Cell<usize>,
+}
+
+impl Heap {
+	pub fn new() -> Self {
+		Heap {
+			head: Cell::new(null_mut()),
+			roots: RefCell::new(HashMap::new()),
+			stack_maps: RefCell::new(HashMap::new()),
+			interned: RefCell::new(HashMap::new()),
+			stats: Cell::new(HeapStats::default()),
+			allocated: Cell::new(0),
+			live: Cell::new(0),
+			threshold: Cell::new(DEFAULT_THRESHOLD),
+		}
+	}
+
+	pub fn stats(&self) -> HeapStats {
+		self.stats.get()
+	}
+
+	/// Sets the number of bytes that may be allocated before compiled code triggers a collection<br>
+	/// If more bytes are live than that, compiled code waits until as many bytes were allocated.
+	pub fn set_threshold(&self, bytes: usize) {
+		self.threshold.set(bytes);
+	}
+
+	/// Allocates a zero-initialized object of `class`<br>
+	/// ***Note:** The object is only kept alive while it is reachable, so the host must [root](Heap::root) it.*
+	///
+	/// # Safety
+	/// `class` must outlive the object, since the object's header points to it.
+	pub unsafe fn alloc_object(&self, class: &ClassLayout) -> *mut ObjectHeader {
+		let obj = self.alloc(class.alloc_layout(), Kind::Object) as *mut ObjectHeader;
+		ObjectHeader::init(obj, class);
+		obj
+	}
+
+	/// Allocates an array of `len` zero-initialized elements of type `elem`<br>
+	/// ***Note:** The array is only kept alive while it is reachable, so the host must [root](Heap::root) it.*
+	///
+	/// # Safety
+	/// `elem` must be a type that values can have (not `void`), since the collector walks the elements by it.
+	pub unsafe fn alloc_array(&self, elem: TypeDesc, len: usize) -> *mut Array {
+		let array = self.alloc(Array::layout(elem, len), Kind::Array) as *mut Array;
+		Array::init(array, elem, len);
+		array
+	}
+
+	/// Allocates a string of the concatenation of `parts`<br>
+	/// ***Note:** The string is only kept alive while it is reachable, so the host must [root](Heap::root) it.*
+	pub fn alloc_str(&self, parts: &[&str]) -> *mut Str {
+		unsafe {
+			let len = parts.iter().map(|part| part.len()).sum();
+			let str = self.alloc(Str::layout(len), Kind::Str) as *mut Str;
+			Str::init(str, parts);
+			str
+		}
+	}
+
+	/// Returns the string of `value`, which is allocated the first time and rooted forever
+	pub fn intern(&self, value: &str) -> *mut Str {
+		if let Some(str) = self.interned.borrow().get(value) {
+			return *str
+		}
+		let str = self.alloc_str(&[value]);
+		self.root(str as *const u8);
+		self.interned.borrow_mut().insert(value.to_string(), str);
+		str
+	}
+
+	unsafe fn alloc(&self, layout: Layout, kind: Kind) -> *mut u8 {
+		let (full, offset) = Layout::new::<GcHeader>().extend(layout).unwrap();
+		debug_assert_eq!(offset, GcHeader::SIZE);
+		let header = std::alloc::alloc_zeroed(full) as *mut GcHeader;
+		if header.is_null() {
+			std::alloc::handle_alloc_error(full)
+		}
+		header.write(GcHeader {
+			next: self.head.get(),
+			layout: full,
+			kind,
+			marked: false,
+		});
+		self.head.set(header);
+
+		let mut stats = self.stats.get();
+		stats.allocations += 1;
+		stats.bytes += full.size();
+		self.stats.set(stats);
+		self.allocated.set(self.allocated.get() + full.size());
+		GcHeader::value(header)
+	}
+
+	/// Keeps the value at `value` (and everything it references) alive until it is unrooted<br>
+	/// A value may be rooted more than once, and stays rooted until it is unrooted as many times.
+	pub fn root(&self, value: *const u8) {
+		*self.roots.borrow_mut().entry(value as usize).or_insert(0) += 1;
+	}
+
+	pub fn unroot(&self, value: *const u8) {
+		let mut roots = self.roots.borrow_mut();
+		if let Some(count) = roots.get_mut(&(value as usize)) {
+			*count -= 1;
+			if *count == 0 {
+				roots.remove(&(value as usize));
+			}
+		}
+	}
+
+	/// Registers the stack maps of a function whose code starts at `base`
+	pub fn register_stack_maps(&self, base: usize, maps: &[StackMap]) {
+		let mut stack_maps = self.stack_maps.borrow_mut();
+		for map in maps {
+			stack_maps.insert(base + map.offset, map.slots.clone().into_boxed_slice());
+		}
+	}
+
+	/// Collects garbage if enough has been allocated since the last collection<br>
+	/// `rbp` and `pc` are the frame pointer and the return address of the compiled code that called
+	/// into the runtime, the frames of compiled code are walked from there to find their roots.
+	///
+	/// # Safety
+	/// `rbp` and `pc` must be those of a live frame of compiled code whose stack maps are registered,
+	/// and every reference that isn't in a stack map or rooted must not be used after the call.
+	pub unsafe fn safepoint(&self, rbp: usize, pc: usize) {
+		// let the heap grow with the live set, so collections stay proportional to allocation
+		if self.allocated.get() >= self.threshold.get().max(self.live.get()) {
+			self.collect_from(Some((rbp, pc)));
+		}
+	}
+
+	/// Collects garbage, only considering host roots<br>
+	/// ***Note:** This must not be called while compiled code is running.*
+	pub fn collect(&self) {
+		unsafe {
+			self.collect_from(None);
+		}
+	}
+
+	unsafe fn collect_from(&self, frame: Option<(usize, usize)>) {
+		let mut pending = self.roots.borrow().keys().map(|root| *root as *mut u8).collect::<Vec<_>>();
+
+		if let Some((mut rbp, mut pc)) = frame {
+			let stack_maps = self.stack_maps.borrow();
+			// the caller of the outermost frame of compiled code has no stack map
+			while let Some(slots) = stack_maps.get(&pc) {
+				pending.extend(slots.iter().map(|slot| *((rbp as isize + *slot as isize) as *const *mut u8)));
+				pc = *((rbp + 8) as *const usize);
+				rbp = *(rbp as *const usize);
+			}
+		}
+
+		self.mark(pending);
+		self.sweep();
+	}
+
+	unsafe fn mark(&self, mut pending: Vec<*mut u8>) {
+		while let Some(value) = pending.pop() {
+			if value.is_null() {
+				continue
+			}
+			let header = GcHeader::of(value);
+			if (*header).marked {
+				continue
+			}
+			(*header).marked = true;
+
+			match (*header).kind {
+				Kind::Object => {
+					let obj = value as *mut ObjectHeader;
+					for field in (*obj).class().fields().iter().filter(|field| field.type_desc().is_ref()) {
+						pending.push(*(ObjectHeader::field_ptr(obj, field) as *const *mut u8));
+					}
+				},
+				Kind::Array => {
+					let array = value as *mut Array;
+					if (*array).elem().is_ref() {
+						let data = Array::data_ptr(array);
+						for i in 0..(*array).len() {
+							pending.push(*(data.add(i * types::size_of((*array).elem().flags)) as *const *mut u8));
+						}
+					}
+				},
+				// strings don't reference other values
+				Kind::Str => {},
+			}
+		}
+	}
+
+	unsafe fn sweep(&self) {
+		let mut stats = self.stats.get();
+		let mut link = self.head.as_ptr();
+		while !(*link).is_null() {
+			let header = *link;
+			if (*header).marked {
+				(*header).marked = false;
+				link = &mut (*header).next;
+			} else {
+				*link = (*header).next;
+				stats.allocations -= 1;
+				stats.bytes -= (*header).layout.size();
+				stats.freed += 1;
+				// poison freed values, so reading one shows up in debug builds
+				if cfg!(debug_assertions) {
+					std::ptr::write_bytes(GcHeader::value(header), 0xDD, (*header).layout.size() - GcHeader::SIZE);
+				}
+				std::alloc::dealloc(header as *mut u8, (*header).layout);
+			}
+		}
+
+		stats.collections += 1;
+		self.stats.set(stats);
+		self.allocated.set(0);
+		self.live.set(stats.bytes);
+	}
+}
+
+impl Default for Heap {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Drop for Heap {
+	fn drop(&mut self) {
+		let mut header = self.head.get();
+		while !header.is_null() {
+			unsafe {
+				let next = (*header).next;
+				std::alloc::dealloc(header as *mut u8, (*header).layout);
+				header = next;
+			}
+		}
+	}
+}
@@ -0,0 +1,321 @@
+use std::fmt::{Display, Formatter};
+use crate::vm::error::insn::DecodeError;
+use crate::vm::types;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlags, TypeId};
+
+/// An instruction opcode (as-per E# standard)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+	Nop = 0x00,
+	Add = 0x01,
+	Sub = 0x02,
+	Mul = 0x03,
+	Div = 0x04,
+	Inc = 0x05,
+	Dec = 0x06,
+	Push = 0x10,
+	Pop = 0x11,
+	Cast = 0x14,
+	Call = 0x18,
+	CallVirt = 0x19,
+	Ret = 0x1A,
+	VRet = 0x1B,
+	Ldc = 0x1C,
+	CallTrait = 0x1D,
+	New = 0x20,
+	GetField = 0x21,
+	SetField = 0x22,
+	ToDyn = 0x23,
+	NewArray = 0x24,
+	ALoad = 0x25,
+	AStore = 0x26,
+	ALen = 0x27,
+}
+
+impl Opcode {
+	/// The mnemonic used by the standard
+	pub fn mnemonic(&self) -> &'static str {
+		match self {
+			Self::Nop => "nop",
+			Self::Add => "add",
+			Self::Sub => "sub",
+			Self::Mul => "mul",
+			Self::Div => "div",
+			Self::Inc => "inc",
+			Self::Dec => "dec",
+			Self::Push => "push",
+			Self::Pop => "pop",
+			Self::Cast => "cast",
+			Self::Call => "call",
+			Self::CallVirt => "callvirt",
+			Self::Ret => "ret",
+			Self::VRet => "vret",
+			Self::Ldc => "ldc",
+			Self::CallTrait => "calltrait",
+			Self::New => "new",
+			Self::GetField => "getfield",
+			Self::SetField => "setfield",
+			Self::ToDyn => "todyn",
+			Self::NewArray => "newarray",
+			Self::ALoad => "aload",
+			Self::AStore => "astore",
+			Self::ALen => "alen",
+		}
+	}
+}
+
+impl TryFrom<u8> for Opcode {
+	type Error = DecodeError;
+
+	fn try_from(opcode: u8) -> Result<Self, Self::Error> {
+		Ok(match opcode {
+			0x00 => Self::Nop,
+			0x01 => Self::Add,
+			0x02 => Self::Sub,
+			0x03 => Self::Mul,
+			0x04 => Self::Div,
+			0x05 => Self::Inc,
+			0x06 => Self::Dec,
+			0x10 => Self::Push,
+			0x11 => Self::Pop,
+			0x14 => Self::Cast,
+			0x18 => Self::Call,
+			0x19 => Self::CallVirt,
+			0x1A => Self::Ret,
+			0x1B => Self::VRet,
+			0x1C => Self::Ldc,
+			0x1D => Self::CallTrait,
+			0x20 => Self::New,
+			0x21 => Self::GetField,
+			0x22 => Self::SetField,
+			0x23 => Self::ToDyn,
+			0x24 => Self::NewArray,
+			0x25 => Self::ALoad,
+			0x26 => Self::AStore,
+			0x27 => Self::ALen,
+			_ => return Err(DecodeError::IllegalOpcode(opcode)),
+		})
+	}
+}
+
+/// A decoded instruction and its operands
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Insn {
+	Nop,
+	Add(TypeDesc),
+	Sub(TypeDesc),
+	Mul(TypeDesc),
+	Div(TypeDesc),
+	Inc(TypeDesc),
+	Dec(TypeDesc),
+	Push(TypeDesc, u8),
+	Pop,
+	Cast(TypeDesc, TypeDesc),
+	Call(ConstantIndex),
+	/// `callvirt` [`class-id`] [`fn-id`]
+	CallVirt(ConstantIndex, ConstantIndex),
+	Ret,
+	VRet(TypeDesc),
+	Ldc(ConstantIndex),
+	/// `calltrait` [`class-id`] [`fn-id`]
+	CallTrait(ConstantIndex, ConstantIndex),
+	/// `new` [`class-id`]
+	New(ConstantIndex),
+	/// `getfield` [`class-id`] *field name*
+	GetField(ConstantIndex, ConstantIndex),
+	/// `setfield` [`class-id`] *field name*
+	SetField(ConstantIndex, ConstantIndex),
+	/// `todyn` [`class-id`]
+	ToDyn(ConstantIndex),
+	/// `newarray` *element type*
+	NewArray(TypeDesc),
+	/// `aload` *element type*
+	ALoad(TypeDesc),
+	/// `astore` *element type*
+	AStore(TypeDesc),
+	ALen,
+}
+
+impl Insn {
+	/// Decodes the instruction at the start of `code`, returning it and its encoded length
+	pub fn decode(code: &[u8]) -> Result<(Insn, usize), DecodeError> {
+		let mut reader = Reader {
+			code,
+			head: 1,
+		};
+		let opcode = Opcode::try_from(*code.first().ok_or(DecodeError::UnexpectedEnd)?)?;
+		let insn = match opcode {
+			Opcode::Nop => Insn::Nop,
+			Opcode::Add => Insn::Add(reader.type_desc()?),
+			Opcode::Sub => Insn::Sub(reader.type_desc()?),
+			Opcode::Mul => Insn::Mul(reader.type_desc()?),
+			Opcode::Div => Insn::Div(reader.type_desc()?),
+			Opcode::Inc => Insn::Inc(reader.type_desc()?),
+			Opcode::Dec => Insn::Dec(reader.type_desc()?),
+			Opcode::Push => Insn::Push(reader.type_desc()?, reader.u8()?),
+			Opcode::Pop => Insn::Pop,
+			Opcode::Cast => Insn::Cast(reader.type_desc()?, reader.type_desc()?),
+			Opcode::Call => Insn::Call(reader.u16()?),
+			Opcode::CallVirt => Insn::CallVirt(reader.u16()?, reader.u16()?),
+			Opcode::Ret => Insn::Ret,
+			Opcode::VRet => Insn::VRet(reader.type_desc()?),
+			Opcode::Ldc => Insn::Ldc(reader.u16()?),
+			Opcode::CallTrait => Insn::CallTrait(reader.u16()?, reader.u16()?),
+			Opcode::New => Insn::New(reader.u16()?),
+			Opcode::GetField => Insn::GetField(reader.u16()?, reader.u16()?),
+			Opcode::SetField => Insn::SetField(reader.u16()?, reader.u16()?),
+			Opcode::ToDyn => Insn::ToDyn(reader.u16()?),
+			Opcode::NewArray => Insn::NewArray(reader.type_desc()?),
+			Opcode::ALoad => Insn::ALoad(reader.type_desc()?),
+			Opcode::AStore => Insn::AStore(reader.type_desc()?),
+			Opcode::ALen => Insn::ALen,
+		};
+		Ok((insn, reader.head))
+	}
+
+	/// Encodes the instruction, appending it to `out`
+	pub fn encode(&self, out: &mut Vec<u8>) {
+		out.push(self.opcode() as u8);
+		match *self {
+			Insn::Nop | Insn::Pop | Insn::Ret | Insn::ALen => {},
+			Insn::Add(ty) | Insn::Sub(ty) | Insn::Mul(ty) | Insn::Div(ty) | Insn::Inc(ty) | Insn::Dec(ty) | Insn::VRet(ty)
+			| Insn::NewArray(ty) | Insn::ALoad(ty) | Insn::AStore(ty) => encode_type_desc(ty, out),
+			Insn::Push(ty, local) => {
+				encode_type_desc(ty, out);
+				out.push(local);
+			},
+			Insn::Cast(from, to) => {
+				encode_type_desc(from, out);
+				encode_type_desc(to, out);
+			},
+			Insn::Call(index) | Insn::Ldc(index) | Insn::New(index) | Insn::ToDyn(index) => out.extend_from_slice(&index.to_be_bytes()),
+			Insn::GetField(class, field) | Insn::SetField(class, field) | Insn::CallVirt(class, field) | Insn::CallTrait(class, field) => {
+				out.extend_from_slice(&class.to_be_bytes());
+				out.extend_from_slice(&field.to_be_bytes());
+			},
+		}
+	}
+
+	/// Replaces every constant index in the operands (including those of type operands) with `f(index)`
+	pub fn map_constants<E>(self, mut f: impl FnMut(ConstantIndex) -> Result<ConstantIndex, E>) -> Result<Insn, E> {
+		Ok(match self {
+			Insn::Nop => Insn::Nop,
+			Insn::Add(ty) => Insn::Add(ty.map_constant(&mut f)?),
+			Insn::Sub(ty) => Insn::Sub(ty.map_constant(&mut f)?),
+			Insn::Mul(ty) => Insn::Mul(ty.map_constant(&mut f)?),
+			Insn::Div(ty) => Insn::Div(ty.map_constant(&mut f)?),
+			Insn::Inc(ty) => Insn::Inc(ty.map_constant(&mut f)?),
+			Insn::Dec(ty) => Insn::Dec(ty.map_constant(&mut f)?),
+			Insn::Push(ty, local) => Insn::Push(ty.map_constant(&mut f)?, local),
+			Insn::Pop => Insn::Pop,
+			Insn::Cast(from, to) => Insn::Cast(from.map_constant(&mut f)?, to.map_constant(&mut f)?),
+			Insn::Call(function) => Insn::Call(f(function)?),
+			Insn::CallVirt(class, method) => Insn::CallVirt(f(class)?, f(method)?),
+			Insn::Ret => Insn::Ret,
+			Insn::VRet(ty) => Insn::VRet(ty.map_constant(&mut f)?),
+			Insn::Ldc(index) => Insn::Ldc(f(index)?),
+			Insn::CallTrait(class, method) => Insn::CallTrait(f(class)?, f(method)?),
+			Insn::New(class) => Insn::New(f(class)?),
+			Insn::GetField(class, field) => Insn::GetField(f(class)?, f(field)?),
+			Insn::SetField(class, field) => Insn::SetField(f(class)?, f(field)?),
+			Insn::ToDyn(class) => Insn::ToDyn(f(class)?),
+			Insn::NewArray(ty) => Insn::NewArray(ty.map_constant(&mut f)?),
+			Insn::ALoad(ty) => Insn::ALoad(ty.map_constant(&mut f)?),
+			Insn::AStore(ty) => Insn::AStore(ty.map_constant(&mut f)?),
+			Insn::ALen => Insn::ALen,
+		})
+	}
+
+	pub fn opcode(&self) -> Opcode {
+		match self {
+			Insn::Nop => Opcode::Nop,
+			Insn::Add(_) => Opcode::Add,
+			Insn::Sub(_) => Opcode::Sub,
+			Insn::Mul(_) => Opcode::Mul,
+			Insn::Div(_) => Opcode::Div,
+			Insn::Inc(_) => Opcode::Inc,
+			Insn::Dec(_) => Opcode::Dec,
+			Insn::Push(..) => Opcode::Push,
+			Insn::Pop => Opcode::Pop,
+			Insn::Cast(..) => Opcode::Cast,
+			Insn::Call(_) => Opcode::Call,
+			Insn::CallVirt(..) => Opcode::CallVirt,
+			Insn::Ret => Opcode::Ret,
+			Insn::VRet(_) => Opcode::VRet,
+			Insn::Ldc(_) => Opcode::Ldc,
+			Insn::CallTrait(..) => Opcode::CallTrait,
+			Insn::New(_) => Opcode::New,
+			Insn::GetField(..) => Opcode::GetField,
+			Insn::SetField(..) => Opcode::SetField,
+			Insn::ToDyn(_) => Opcode::ToDyn,
+			Insn::NewArray(_) => Opcode::NewArray,
+			Insn::ALoad(_) => Opcode::ALoad,
+			Insn::AStore(_) => Opcode::AStore,
+			Insn::ALen => Opcode::ALen,
+		}
+	}
+}
+
+impl Display for Insn {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.opcode().mnemonic())?;
+		match *self {
+			Insn::Nop | Insn::Pop | Insn::Ret | Insn::ALen => Ok(()),
+			Insn::Add(ty) | Insn::Sub(ty) | Insn::Mul(ty) | Insn::Div(ty) | Insn::Inc(ty) | Insn::Dec(ty) | Insn::VRet(ty)
+			| Insn::NewArray(ty) | Insn::ALoad(ty) | Insn::AStore(ty) => write!(f, " {:#04X}", ty.flags),
+			Insn::Push(ty, local) => write!(f, " {:#04X} {}", ty.flags, local),
+			Insn::Cast(from, to) => write!(f, " {:#04X} {:#04X}", from.flags, to.flags),
+			Insn::Call(index) | Insn::Ldc(index) | Insn::New(index) | Insn::ToDyn(index) => write!(f, " #{}", index),
+			Insn::GetField(class, field) | Insn::SetField(class, field) | Insn::CallVirt(class, field) | Insn::CallTrait(class, field) => write!(f, " #{} #{}", class, field),
+		}
+	}
+}
+
+/// Decodes every instruction in `code`, returning each with its bytecode offset
+pub fn decode_all(code: &[u8]) -> Result<Vec<(usize, Insn)>, DecodeError> {
+	let mut insns = Vec::new();
+	let mut head = 0;
+	while head < code.len() {
+		let (insn, len) = Insn::decode(&code[head..])?;
+		insns.push((head, insn));
+		head += len;
+	}
+	Ok(insns)
+}
+
+fn encode_type_desc(ty: TypeDesc, out: &mut Vec<u8>) {
+	out.push(ty.flags);
+	match (types::type_id(ty.flags), ty.operand) {
+		(TypeFlags::ARRAY, Some(operand)) => out.push(operand as u8),
+		(TypeFlags::OBJECT | TypeFlags::FUNCTION, Some(operand)) => out.extend_from_slice(&operand.to_be_bytes()),
+		_ => {},
+	}
+}
+
+struct Reader<'a> {
+	code: &'a [u8],
+	head: usize,
+}
+
+impl Reader<'_> {
+	fn u8(&mut self) -> Result<u8, DecodeError> {
+		let byte = *self.code.get(self.head).ok_or(DecodeError::UnexpectedEnd)?;
+		self.head += 1;
+		Ok(byte)
+	}
+
+	fn u16(&mut self) -> Result<u16, DecodeError> {
+		Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+	}
+
+	fn type_desc(&mut self) -> Result<TypeDesc, DecodeError> {
+		let flags = self.u8()?;
+		let operand = match types::type_id(flags) {
+			TypeFlags::ARRAY => Some(self.u8()? as u16),
+			TypeFlags::OBJECT | TypeFlags::FUNCTION => Some(self.u16()?),
+			_ => None,
+		};
+		Ok(TypeDesc::new(flags, operand))
+	}
+}
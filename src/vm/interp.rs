@@ -0,0 +1,347 @@
+use crate::vm::Vm;
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::bin::def::function::FunctionDef;
+use crate::vm::error::interp::Trap;
+use crate::vm::insn;
+use crate::vm::insn::Insn;
+use crate::vm::intrinsics;
+use crate::vm::types;
+use crate::vm::types::{TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::array::Array;
+use crate::vm::types::object::{ClassLayout, ObjectHeader};
+use crate::vm::types::str::Str;
+
+/// The interpreter relies on the verifier, so it panics with this if the bytecode is malformed
+const UNVERIFIED: &str = "Interpreted bytecode must be verified";
+
+/// A value on the operand stack or in a local variable<br>
+/// Values are kept like compiled code keeps them: integers are extended to 64 bits, floats are
+/// stored as their bits, and a `dyn` takes both words (its object, then its trait vtable).
+#[derive(Copy, Clone, Debug)]
+struct Value {
+	words: [u64; 2],
+}
+
+impl Value {
+	fn new(word: u64) -> Self {
+		Value {
+			words: [word, 0],
+		}
+	}
+
+	fn word(&self) -> u64 {
+		self.words[0]
+	}
+}
+
+/// A reference interpreter of verified bytecode<br>
+/// It produces the same results (and [`Trap`]s) as compiled code.<br>
+/// ***Note:** Garbage isn't collected while interpreted code runs, as its frames have no stack maps.*
+pub struct Interpreter<'a> {
+	vm: &'a Vm,
+}
+
+impl<'a> Interpreter<'a> {
+	pub fn new(vm: &'a Vm) -> Self {
+		Interpreter {
+			vm,
+		}
+	}
+
+	/// Calls the function named `name` with arguments passed like compiled code takes them (a
+	/// `dyn` takes two), returning the first word of its return value (`0` if it returns nothing)<br>
+	/// With a [lazy](Vm::set_lazy) VM, each function is [materialized](Vm::materialize) when it's first called.
+	pub fn call(&self, name: &str, args: &[u64]) -> Result<u64, Trap> {
+		let slot = self.vm.functions().get(name).ok_or_else(|| Trap::UnknownFunction(name.to_string()))?;
+		let mut words = args.iter().copied();
+		let args = slot.signature().args.iter()
+			.map(|ty| {
+				let mut value = Value {
+					words: [0; 2],
+				};
+				for word in value.words.iter_mut().take(ty.slots()) {
+					*word = words.next().unwrap_or(0);
+				}
+				value
+			})
+			.collect();
+		Ok(self.invoke(name, args)?.map_or(0, |value| value.word()))
+	}
+
+	fn invoke(&self, name: &str, args: Vec<Value>) -> Result<Option<Value>, Trap> {
+		if intrinsics::lookup(name).is_some() {
+			return self.intrinsic(name, &args)
+		}
+		let (constants, def) = self.vm.definition(name).ok_or_else(|| Trap::UnknownFunction(name.to_string()))?;
+		if def.code().is_empty() {
+			return Err(Trap::UndefinedFunction(Some(name.to_string())))
+		}
+		self.vm.materialize(name).map_err(|err| Trap::Materialize(Box::new(err)))?;
+		self.run(constants, def, args)
+	}
+
+	fn intrinsic(&self, name: &str, args: &[Value]) -> Result<Option<Value>, Trap> {
+		let intrinsic = intrinsics::lookup(name).ok_or_else(|| Trap::UnknownFunction(name.to_string()))?;
+		// the only references intrinsics take are strings
+		if intrinsic.args.iter().zip(args).any(|(ty, arg)| ty.is_ref() && arg.word() == 0) {
+			return Err(Trap::NullReference)
+		}
+		let str = |i: usize| unsafe { &*(args[i].word() as *const Str) };
+		let word = |word: u64| Some(Value::new(word));
+		Ok(match name {
+			"str.len" => word(str(0).len() as u64),
+			"str.concat" => Some(Value::new(self.vm.heap().alloc_str(&[str(0).as_str(), str(1).as_str()]) as u64)),
+			"str.byte" => {
+				let index = args[1].word() as i64;
+				let byte = usize::try_from(index).ok().and_then(|index| str(0).as_bytes().get(index));
+				word(*byte.ok_or(Trap::IndexOutOfBounds(index, str(0).len()))? as u64)
+			},
+			"str.eq" => word((str(0) == str(1)) as u64),
+			"str.print" => {
+				println!("{}", str(0));
+				None
+			},
+			_ => return Err(Trap::UnknownFunction(name.to_string())),
+		})
+	}
+
+	fn run(&self, constants: &ConstantTable, def: &FunctionDef, args: Vec<Value>) -> Result<Option<Value>, Trap> {
+		let insns = insn::decode_all(def.code()).expect(UNVERIFIED);
+		let mut locals = args;
+		let mut stack: Vec<Value> = Vec::new();
+		let class = |index| -> &ClassLayout {
+			let name = constants.str(index).expect(UNVERIFIED);
+			self.vm.classes().get(name).expect(UNVERIFIED)
+		};
+		macro_rules! pop {
+			() => { stack.pop().expect(UNVERIFIED) };
+		}
+
+		for (_, insn) in insns {
+			match insn {
+				Insn::Nop => {},
+				Insn::Add(ty) | Insn::Sub(ty) | Insn::Mul(ty) | Insn::Div(ty) => {
+					let b = pop!().word();
+					let a = pop!().word();
+					stack.push(Value::new(arith(insn, ty, a, b)?));
+				},
+				Insn::Inc(ty) | Insn::Dec(ty) => {
+					let one = match ty.id() {
+						TypeFlags::F32 => 1f32.to_bits() as u64,
+						TypeFlags::F64 => 1f64.to_bits(),
+						_ => 1,
+					};
+					let op = if let Insn::Inc(_) = insn { Insn::Add(ty) } else { Insn::Sub(ty) };
+					let a = pop!().word();
+					stack.push(Value::new(arith(op, ty, a, one)?));
+				},
+				Insn::Push(_, local) => stack.push(locals[local as usize]),
+				Insn::Pop => locals.push(pop!()),
+				Insn::Cast(from, to) => {
+					let a = pop!().word();
+					stack.push(Value::new(cast(from, to, a)));
+				},
+				Insn::Call(function) => {
+					let name = constants.str(function).expect(UNVERIFIED);
+					let argc = match self.vm.functions().get(name) {
+						Some(function) => function.signature().args.len(),
+						None => intrinsics::lookup(name).ok_or_else(|| Trap::UnknownFunction(name.to_string()))?.args.len(),
+					};
+					let args = stack.split_off(stack.len() - argc);
+					stack.extend(self.invoke(name, args)?);
+				},
+				Insn::CallVirt(class_index, method) => {
+					let name = constants.str(method).expect(UNVERIFIED);
+					let (slot, method) = class(class_index).method(name).expect(UNVERIFIED);
+					let args = stack.split_off(stack.len() - method.signature().args.len());
+					let receiver = unsafe { (*reference::<ObjectHeader>(args[0])?).class() };
+					let function = receiver.methods()[slot].function().to_string();
+					stack.extend(self.invoke(&function, args)?);
+				},
+				Insn::CallTrait(class_index, method) => {
+					let name = constants.str(method).expect(UNVERIFIED);
+					let (_, method) = class(class_index).method(name).expect(UNVERIFIED);
+					let mut args = stack.split_off(stack.len() - method.signature().args.len());
+					// pass only the object of the `dyn`
+					args[0] = Value::new(args[0].word());
+					let receiver = unsafe { (*reference::<ObjectHeader>(args[0])?).class() };
+					let function = receiver.method(method.name())
+						.ok_or_else(|| Trap::NotImplemented(receiver.name().to_string(), class(class_index).name().to_string()))?
+						.1.function().to_string();
+					stack.extend(self.invoke(&function, args)?);
+				},
+				Insn::Ret => return Ok(None),
+				Insn::VRet(_) => return Ok(Some(pop!())),
+				Insn::Ldc(index) => {
+					let constant = constants.get(index).expect(UNVERIFIED);
+					let value = match constant.bits() {
+						Some(bits) => Value::new(bits),
+						None => {
+							let str = self.vm.heap().intern(constant.str().expect(UNVERIFIED));
+							Value::new(str as u64)
+						},
+					};
+					stack.push(value);
+				},
+				Insn::New(index) => {
+					let obj = unsafe { self.vm.heap().alloc_object(class(index)) };
+					stack.push(Value::new(obj as u64));
+				},
+				Insn::GetField(class_index, field) => {
+					let field = class(class_index).field(constants.str(field).expect(UNVERIFIED)).expect(UNVERIFIED);
+					let obj = reference(pop!())?;
+					let ptr = unsafe { ObjectHeader::field_ptr(obj, field) };
+					stack.push(unsafe { load(ptr, field.type_desc()) });
+				},
+				Insn::SetField(class_index, field) => {
+					let field = class(class_index).field(constants.str(field).expect(UNVERIFIED)).expect(UNVERIFIED);
+					let value = pop!();
+					let obj = reference(pop!())?;
+					unsafe {
+						store(ObjectHeader::field_ptr(obj, field), field.type_desc(), value);
+					}
+				},
+				Insn::ToDyn(index) => {
+					let trait_class = class(index);
+					let obj = pop!();
+					let receiver = unsafe { (*reference::<ObjectHeader>(obj)?).class() };
+					let vtable = receiver.trait_vtable(trait_class)
+						.ok_or_else(|| Trap::NotImplemented(receiver.name().to_string(), trait_class.name().to_string()))?;
+					stack.push(Value {
+						words: [obj.word(), vtable as u64],
+					});
+				},
+				Insn::NewArray(elem) => {
+					let len = pop!().word() as i64;
+					if len < 0 {
+						return Err(Trap::NegativeLength(len))
+					}
+					let array = unsafe { self.vm.heap().alloc_array(elem, len as usize) };
+					stack.push(Value::new(array as u64));
+				},
+				Insn::ALoad(elem) => {
+					let index = pop!().word() as i64;
+					let array = reference(pop!())?;
+					let ptr = unsafe { element(array, elem, index)? };
+					stack.push(unsafe { load(ptr, elem) });
+				},
+				Insn::AStore(elem) => {
+					let value = pop!();
+					let index = pop!().word() as i64;
+					let array = reference(pop!())?;
+					unsafe {
+						store(element(array, elem, index)?, elem, value);
+					}
+				},
+				Insn::ALen => {
+					let array = reference::<Array>(pop!())?;
+					stack.push(Value::new(unsafe { (*array).len() } as u64));
+				},
+			}
+		}
+		Ok(None)
+	}
+}
+
+/// Sign or zero extends an integer from the width of `ty`
+fn normalize(ty: TypeDesc, value: u64) -> u64 {
+	match (ty.id(), ty.is_unsigned()) {
+		(TypeFlags::I8, false) => value as i8 as u64,
+		(TypeFlags::I8, true) => value as u8 as u64,
+		(TypeFlags::I16, false) => value as i16 as u64,
+		(TypeFlags::I16, true) => value as u16 as u64,
+		(TypeFlags::I32, false) => value as i32 as u64,
+		(TypeFlags::I32, true) => value as u32 as u64,
+		_ => value,
+	}
+}
+
+fn arith(insn: Insn, ty: TypeDesc, a: u64, b: u64) -> Result<u64, Trap> {
+	macro_rules! float {
+		( $t:ty, $from:expr, $to:expr ) => {{
+			let (a, b) = ($from(a), $from(b));
+			$to(match insn {
+				Insn::Add(_) => a + b,
+				Insn::Sub(_) => a - b,
+				Insn::Mul(_) => a * b,
+				_ => a / b,
+			})
+		}};
+	}
+
+	Ok(match ty.id() {
+		TypeFlags::F32 => float!(f32, |v: u64| f32::from_bits(v as u32), |v: f32| v.to_bits() as u64),
+		TypeFlags::F64 => float!(f64, f64::from_bits, f64::to_bits),
+		_ => normalize(ty, match insn {
+			Insn::Add(_) => a.wrapping_add(b),
+			Insn::Sub(_) => a.wrapping_sub(b),
+			Insn::Mul(_) => a.wrapping_mul(b),
+			_ if b == 0 => return Err(Trap::DivisionByZero),
+			_ if ty.is_unsigned() => a / b,
+			_ => (a as i64).wrapping_div(b as i64) as u64,
+		}),
+	})
+}
+
+fn cast(from: TypeDesc, to: TypeDesc, value: u64) -> u64 {
+	// float to integer conversions truncate, and produce `i64::MIN` if out of range or NaN (like `cvttsd2si`)
+	let range = i64::MIN as f64..-(i64::MIN as f64);
+	let truncate = |value: f64| if !range.contains(&value) {
+		i64::MIN as u64
+	} else {
+		value as i64 as u64
+	};
+	match (from.id(), to.id()) {
+		(TypeFlags::F32, TypeFlags::F64) => (f32::from_bits(value as u32) as f64).to_bits(),
+		(TypeFlags::F64, TypeFlags::F32) => (f64::from_bits(value) as f32).to_bits() as u64,
+		(TypeFlags::F32, TypeFlags::F32) | (TypeFlags::F64, TypeFlags::F64) => value,
+		(TypeFlags::F32, _) => normalize(to, truncate(f32::from_bits(value as u32) as f64)),
+		(TypeFlags::F64, _) => normalize(to, truncate(f64::from_bits(value))),
+		(_, TypeFlags::F32) => (value as i64 as f32).to_bits() as u64,
+		(_, TypeFlags::F64) => (value as i64 as f64).to_bits(),
+		_ => normalize(to, value),
+	}
+}
+
+/// Returns the object or array that `value` refers to, or traps if it's null
+fn reference<T>(value: Value) -> Result<*mut T, Trap> {
+	match value.word() {
+		0 => Err(Trap::NullReference),
+		word => Ok(word as *mut T),
+	}
+}
+
+/// Returns a pointer to the element at `index`, or traps if it's out of bounds
+unsafe fn element(array: *mut Array, elem: TypeDesc, index: i64) -> Result<*mut u8, Trap> {
+	let len = (*array).len();
+	match usize::try_from(index) {
+		Ok(i) if i < len => Ok(Array::data_ptr(array).add(i * types::size_of(elem.flags))),
+		_ => Err(Trap::IndexOutOfBounds(index, len)),
+	}
+}
+
+unsafe fn load(ptr: *const u8, ty: TypeDesc) -> Value {
+	let word = match types::size_of(ty.flags) {
+		1 => ptr.read() as u64,
+		2 => (ptr as *const u16).read_unaligned() as u64,
+		4 => (ptr as *const u32).read_unaligned() as u64,
+		_ => (ptr as *const u64).read_unaligned(),
+	};
+	let word = if ty.is_int() { normalize(ty, word) } else { word };
+	let high = if ty.slots() == 2 { (ptr as *const u64).add(1).read_unaligned() } else { 0 };
+	Value {
+		words: [word, high],
+	}
+}
+
+unsafe fn store(ptr: *mut u8, ty: TypeDesc, value: Value) {
+	match types::size_of(ty.flags) {
+		1 => ptr.write(value.word() as u8),
+		2 => (ptr as *mut u16).write_unaligned(value.word() as u16),
+		4 => (ptr as *mut u32).write_unaligned(value.word() as u32),
+		_ => (ptr as *mut u64).write_unaligned(value.word()),
+	}
+	if ty.slots() == 2 {
+		(ptr as *mut u64).add(1).write_unaligned(value.words[1]);
+	}
+}
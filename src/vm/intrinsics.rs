@@ -0,0 +1,115 @@
+use std::ptr::null_mut;
+use crate::vm::error::interp::Trap;
+use crate::vm::heap::Heap;
+use crate::vm::jit;
+use crate::vm::jit::native;
+use crate::vm::types::{TypeDesc, TypeFlag, TypeFlags, TypeId};
+use crate::vm::types::str::Str;
+
+/// A function implemented by the VM, which `call` names like any other function
+pub struct Intrinsic {
+	pub args: &'static [TypeDesc],
+	pub ret: TypeDesc,
+	pub addr: usize,
+	/// Whether the intrinsic allocates (it takes the heap and the caller's frame after its arguments)
+	pub allocates: bool,
+}
+
+const STR: TypeDesc = TypeDesc::new(TypeFlags::STR, None);
+const I64: TypeDesc = TypeDesc::new(TypeFlags::I64, None);
+
+/// Returns the intrinsic named `name`
+pub fn lookup(name: &str) -> Option<Intrinsic> {
+	let (args, ret, addr, allocates): (&'static [TypeDesc], _, *const (), _) = match name {
+		"str.len" => (&[STR], I64, str_len as _, false),
+		"str.concat" => (&[STR, STR], STR, str_concat as _, true),
+		"str.byte" => (&[STR, I64], TypeDesc::new(TypeFlags::UNSIGNED << 4 | TypeFlags::I8, None), str_byte as _, false),
+		"str.eq" => (&[STR, STR], TypeDesc::new(TypeFlags::I8, None), str_eq as _, false),
+		"str.print" => (&[STR], TypeDesc::new(TypeFlags::VOID, None), str_print as _, false),
+		_ => return None,
+	};
+	Some(Intrinsic {
+		args,
+		ret,
+		addr: addr as usize,
+		allocates,
+	})
+}
+
+/// Whether any of the strings is null, in which case it raises a trap
+fn any_null(strs: &[*const Str]) -> bool {
+	let null = strs.iter().any(|str| str.is_null());
+	if null {
+		jit::raise(Trap::NullReference);
+	}
+	null
+}
+
+native! {
+	/// Intrinsic `str.len`
+	fn str_len(str: *const Str) -> i64 {
+		if any_null(&[str]) {
+			return 0
+		}
+		unsafe {
+			(*str).len() as i64
+		}
+	}
+}
+
+native! {
+	/// Intrinsic `str.concat`
+	fn str_concat(a: *const Str, b: *const Str, heap: *const Heap, rbp: usize, pc: usize) -> *mut Str {
+		if any_null(&[a, b]) {
+			return null_mut()
+		}
+		unsafe {
+			// the operands are popped, so they may be collected at the safepoint
+			let value = [(*a).as_str(), (*b).as_str()].concat();
+			(*heap).safepoint(rbp, pc);
+			(*heap).alloc_str(&[&value])
+		}
+	}
+}
+
+native! {
+	/// Intrinsic `str.byte`
+	fn str_byte(str: *const Str, index: i64) -> u64 {
+		if any_null(&[str]) {
+			return 0
+		}
+		unsafe {
+			match usize::try_from(index).ok().and_then(|index| (*str).as_bytes().get(index)) {
+				Some(byte) => *byte as u64,
+				None => {
+					jit::index_out_of_bounds(index, (*str).len());
+					0
+				},
+			}
+		}
+	}
+}
+
+native! {
+	/// Intrinsic `str.eq`
+	fn str_eq(a: *const Str, b: *const Str) -> u64 {
+		if any_null(&[a, b]) {
+			return 0
+		}
+		unsafe {
+			(*a == *b) as u64
+		}
+	}
+}
+
+native! {
+	/// Intrinsic `str.print`
+	fn str_print(str: *const Str) {
+		if any_null(&[str]) {
+			return
+		}
+		unsafe {
+			println!("{}", *str);
+		}
+	}
+}
@@ -1,84 +1,660 @@
-#[cfg(target_os = "linux")]
-use libc::{MAP_FAILED, MREMAP_FIXED, MREMAP_MAYMOVE, SYS_mremap};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ptr::{null, null_mut};
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::error::interp::Trap;
 use crate::vm::error::jit::TranspileError;
-use crate::vm::types::function::{Function, NativeFn, page_size, RawFn};
-
-#[repr(C)]
-pub struct CodeData {
-	pub addr: *mut u8,
-	pub size: usize,
-}
-
-/// Flags that define what registers are in use.
-trait RegisterData {
-	const R0: u16  = 0b1000000000000000;
-	const R1: u16  = 0b0100000000000000;
-	const R2: u16  = 0b0010000000000000;
-	const R3: u16  = 0b0001000000000000;
-	const R4: u16  = 0b0000100000000000;
-	const R5: u16  = 0b0000010000000000;
-	const R6: u16  = 0b0000001000000000;
-	const R7: u16  = 0b0000000100000000;
-	const R8: u16  = 0b0000000010000000;
-	const R9: u16  = 0b0000000001000000;
-	const R10: u16 = 0b0000000000100000;
-	const R11: u16 = 0b0000000000010000;
-	const R12: u16 = 0b0000000000001000;
-	const R13: u16 = 0b0000000000000100;
-	const R14: u16 = 0b0000000000000010;
-	const R15: u16 = 0b0000000000000001;
-	
-	const RAX: u16  = 0b1000000000000000;
-	const RBX: u16  = 0b0100000000000000;
-	const RCX: u16  = 0b0010000000000000;
-	const RDX: u16  = 0b0001000000000000;
-	const RSP: u16  = 0b0000100000000000;
-	const RBP: u16  = 0b0000010000000000;
-	const RSI: u16  = 0b0000001000000000;
-	const RDI: u16  = 0b0000000100000000;
-}
-
-pub unsafe fn transpile(mut raw: RawFn, code_dest: *mut u8, mut alloc_size: usize) -> Result<CodeData, TranspileError> {
-	// size of function code
-	let mut code_size = 0;
-	// new code dest
-	let mut new_dest = code_dest;
-	// registers in use
-	let mut registers: u16 = 0;
-
-	#[cfg(target_arch = "x86_64")]
-	loop {
-		// remap if necessary
-		if code_size == alloc_size {
-			alloc_size = code_size + page_size();
-			#[cfg(target_os = "linux")]
-			{
-				new_dest = libc::mremap(new_dest as *mut libc::c_void, code_size, alloc_size, MREMAP_MAYMOVE) as *mut u8;
-				if new_dest == MAP_FAILED as *mut u8 {
-					panic!("Failed to remap memory");
-				}
+use crate::vm::heap::{Heap, StackMap};
+use crate::vm::insn;
+use crate::vm::insn::Insn;
+use crate::vm::intrinsics;
+use crate::vm::intrinsics::Intrinsic;
+use crate::vm::jit::aarch64::Aarch64;
+use crate::vm::jit::riscv64::RiscV64;
+use crate::vm::jit::x86_64::X86_64;
+use crate::vm::meta::platform::{CpuArch, Platform, PlatformKind};
+use crate::vm::types;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::array::Array;
+use crate::vm::types::function::{Functions, Signature};
+use crate::vm::types::object::{Classes, ClassLayout, FieldLayout, MethodLayout, ObjectHeader};
+
+pub mod aarch64;
+pub mod riscv64;
+pub mod x86_64;
+
+/// Everything the JIT needs to resolve the operands of the function it compiles
+pub struct Context<'a> {
+	pub constants: &'a ConstantTable,
+	pub classes: &'a Classes,
+	pub functions: &'a Functions,
+	pub heap: &'a Heap,
+	/// Where the compiled code reports traps
+	pub traps: &'a Traps,
+	/// The classes and functions of loaded libraries that aren't exported, keyed by the index of their
+	/// library, which the verifier treats as undefined outside of their library
+	pub hidden: &'a HashMap<String, usize>,
+	/// The index of the executable the code is from
+	pub exec: usize,
+	/// The platform the code is compiled for, whose CPU features decide which instructions are used
+	pub platform: Platform,
+}
+
+impl Context<'_> {
+	/// Whether the class or function named `name` can't be used by the code
+	pub fn is_hidden(&self, name: &str) -> bool {
+		self.hidden.get(name).is_some_and(|lib| *lib != self.exec)
+	}
+
+	fn str(&self, index: ConstantIndex) -> Result<&str, TranspileError> {
+		self.constants.str(index).ok_or(TranspileError::UnresolvedConstant(index))
+	}
+
+	fn class(&self, index: ConstantIndex) -> Result<&ClassLayout, TranspileError> {
+		let name = self.str(index)?;
+		self.classes.get(name).ok_or_else(|| TranspileError::UnknownClass(name.to_string()))
+	}
+
+	fn field(&self, class: ConstantIndex, field: ConstantIndex) -> Result<&FieldLayout, TranspileError> {
+		let class = self.class(class)?;
+		let name = self.str(field)?;
+		class.field(name).ok_or_else(|| TranspileError::UnknownField(class.name().to_string(), name.to_string()))
+	}
+
+	fn method(&self, class: ConstantIndex, method: ConstantIndex) -> Result<(usize, &MethodLayout), TranspileError> {
+		let class = self.class(class)?;
+		let name = self.str(method)?;
+		class.method(name).ok_or_else(|| TranspileError::UnknownMethod(class.name().to_string(), name.to_string()))
+	}
+}
+
+/// Declares functions (or function pointer types) with the calling convention of compiled code,
+/// which is System V on x86-64 (even on Windows) and the C calling convention elsewhere
+macro_rules! native {
+	($(#[$attr:meta])* $vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block) => {
+		#[cfg(target_arch = "x86_64")]
+		$(#[$attr])* $vis extern "sysv64" fn $name($($arg: $ty),*) $(-> $ret)? $body
+		#[cfg(not(target_arch = "x86_64"))]
+		$(#[$attr])* $vis extern "C" fn $name($($arg: $ty),*) $(-> $ret)? $body
+	};
+	($vis:vis type $name:ident = fn($($ty:ty),* $(,)?) $(-> $ret:ty)?;) => {
+		#[cfg(target_arch = "x86_64")]
+		$vis type $name = extern "sysv64" fn($($ty),*) $(-> $ret)?;
+		#[cfg(not(target_arch = "x86_64"))]
+		$vis type $name = extern "C" fn($($ty),*) $(-> $ret)?;
+	};
+}
+
+pub(crate) use native;
+
+/// An arithmetic instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Arith {
+	Add,
+	Sub,
+	Mul,
+	/// Traps on integer division by zero, and wraps the signed division of the lowest value by `-1`
+	Div,
+}
+
+/// How a call instruction finds the code of its callee
+#[derive(Copy, Clone, Debug)]
+pub enum Dispatch {
+	/// Through the code pointer of a function
+	Static(*const usize),
+	/// Through a slot of the receiver's class vtable
+	Virtual(usize),
+	/// Through a slot of the receiver's trait vtable (the receiver is a `dyn`, but the method only
+	/// takes its object)
+	Trait(usize),
+}
+
+/// A code generator for one architecture, which the transpiler drives instruction by instruction<br>
+/// The operand stack lives in the frame, below the local variables: the slot at depth `n` is at
+/// `fp - frame - 8 * (n + 1)`, so stack maps don't depend on the architecture. Operations get the
+/// depth of the stack once their operands are popped, which is where their result is pushed. Local
+/// variables are addressed by their frame offset (`fp - 8` for the first slot), the low slot of a
+/// two-slot value first.<br>
+/// A runtime helper that traps records it (see [`Traps`]) and returns, and the code that called it
+/// returns right away, as does each caller in turn until the trap reaches [`Traps::catch`].<br>
+/// ***Note:** Every argument and the return value is passed as 64-bit integers (floats are passed
+/// as their bits, and a `dyn` is passed as two integers).*
+pub trait Backend {
+	/// The position in the code, which is the return address of a call that was just emitted
+	fn offset(&self) -> usize;
+
+	/// Sets up a frame with `frame` bytes of local variables, and spills the arguments into their
+	/// local variables (at the frame offsets in `args`)
+	fn prologue(&mut self, frame: i32, args: &[(i32, TypeDesc)]) -> Result<(), TranspileError>;
+
+	/// Returns, with the value at `depth` if `ret` isn't void
+	fn ret(&mut self, ret: TypeDesc, depth: usize);
+
+	/// The machine code, once every instruction has been emitted
+	fn finish(self) -> Vec<u8>;
+
+	fn nop(&mut self);
+
+	/// Pushes the bits of a constant
+	fn constant(&mut self, bits: u64, depth: usize);
+
+	/// Pushes the value at the top of the stack again
+	fn dup(&mut self, depth: usize);
+
+	/// Pushes the local variable at `disp`
+	fn load_local(&mut self, disp: i32, ty: TypeDesc, depth: usize);
+
+	/// Pops a value into the local variable at `disp`
+	fn store_local(&mut self, disp: i32, ty: TypeDesc, depth: usize);
+
+	fn arith(&mut self, op: Arith, ty: TypeDesc, depth: usize);
+
+	fn cast(&mut self, from: TypeDesc, to: TypeDesc, depth: usize);
+
+	/// Pops the arguments of a function taking `args`, calls it and pushes its return value,
+	/// returning the offset of the return address<br>
+	/// Virtual and trait calls trap if the receiver is null.
+	fn call(&mut self, dispatch: Dispatch, args: &[TypeDesc], ret: TypeDesc, depth: usize) -> Result<usize, TranspileError>;
+
+	/// Returns (with an unspecified value) if the word at `pending` is nonzero, which it is once the
+	/// function or runtime helper that was just called trapped
+	fn unwind_if_trapped(&mut self, pending: usize);
+
+	/// Calls the runtime helper at `addr` with the popped `operands` (one slot each), then `consts`,
+	/// then if `frame` is set, the frame pointer and the return address so the helper may collect
+	/// garbage, and pushes the one-slot result unless `ret` is void, returning the offset of the
+	/// return address
+	fn helper(&mut self, addr: usize, operands: usize, consts: &[u64], frame: bool, ret: TypeDesc, depth: usize) -> usize;
+
+	/// Pops an object, and pushes its field of type `ty` at `offset` (trapping if the object is null)
+	fn get_field(&mut self, offset: i32, ty: TypeDesc, depth: usize);
+
+	/// Pops an object and a value, and stores it in its field of type `ty` at `offset` (trapping if
+	/// the object is null)
+	fn set_field(&mut self, offset: i32, ty: TypeDesc, depth: usize);
+
+	/// Pops an array and an index, and pushes the element at the index (trapping if the array is null
+	/// or the index is out of bounds)
+	fn load_elem(&mut self, elem: TypeDesc, depth: usize);
+
+	/// Pops an array, an index and a value, and stores it at the index (trapping if the array is null
+	/// or the index is out of bounds)
+	fn store_elem(&mut self, elem: TypeDesc, depth: usize);
+
+	/// Pops an array, and pushes its length (trapping if the array is null)
+	fn array_len(&mut self, depth: usize);
+}
+
+/// Compilation state of the function being transpiled
+struct Transpiler<'a, 'b, B> {
+	backend: B,
+	ctx: &'b Context<'a>,
+	/// The types of the values on the operand stack
+	stack: Vec<TypeDesc>,
+	/// The frame offsets and types of the local variables
+	locals: Vec<(i32, TypeDesc)>,
+	/// The number of frame slots used by local variables
+	frame_slots: usize,
+	/// The size of the frame (in bytes), the operand stack starts below it
+	frame: i32,
+	stack_maps: Vec<StackMap>,
+}
+
+impl<B: Backend> Transpiler<'_, '_, B> {
+	/// The number of slots currently on the operand stack
+	fn depth(&self) -> usize {
+		self.stack.iter().map(TypeDesc::slots).sum()
+	}
+
+	fn pop(&mut self, offset: usize) -> Result<TypeDesc, TranspileError> {
+		self.stack.pop().ok_or(TranspileError::StackUnderflow(offset))
+	}
+
+	/// Pops a value that must take as many slots as `ty`
+	fn pop_as(&mut self, ty: TypeDesc, offset: usize) -> Result<(), TranspileError> {
+		match self.pop(offset)?.slots() == ty.slots() {
+			true => Ok(()),
+			false => Err(TranspileError::TypeMismatch(offset)),
+		}
+	}
+
+	/// Records the references that are live at the call whose return address is at `pc`<br>
+	/// Arguments have been popped and the result hasn't been pushed yet, so these are the
+	/// references in local variables and on the operand stack.
+	fn safepoint(&mut self, pc: usize) {
+		let mut slots = self.locals.iter()
+			.filter(|(_, ty)| ty.is_ref())
+			.map(|(disp, _)| *disp)
+			.collect::<Vec<_>>();
+		let mut depth = 0;
+		for ty in &self.stack {
+			if ty.is_ref() {
+				slots.push(-self.frame - 8 * (depth as i32 + 1));
 			}
+			depth += ty.slots();
 		}
-		// transpile bytecode
-		match *raw.head {
-			0x00 => {
-				*new_dest = 0x90;
+		self.stack_maps.push(StackMap {
+			offset: pc,
+			slots,
+		});
+	}
+
+	/// Pops the arguments of `signature`, calls the function and pushes its return value
+	fn invoke(&mut self, signature: &Signature, dispatch: Dispatch, offset: usize) -> Result<(), TranspileError> {
+		let skip = matches!(dispatch, Dispatch::Trait(_)) as usize;
+		for arg in signature.args.iter().skip(skip).rev() {
+			self.pop_as(*arg, offset)?;
+		}
+		if skip == 1 && self.pop(offset)?.id() != TypeFlags::DYN {
+			return Err(TranspileError::TypeMismatch(offset))
+		}
+		let pc = self.backend.call(dispatch, &signature.args, signature.ret, self.depth())?;
+		self.safepoint(pc);
+		self.stack.push(signature.ret);
+		self.backend.unwind_if_trapped(self.ctx.traps.pending());
+		Ok(())
+	}
+
+	/// Pops the operands of a runtime helper taking one slot each, calls it and pushes its result
+	fn helper(&mut self, addr: usize, operands: usize, consts: &[u64], frame: bool, ret: TypeDesc, offset: usize) -> Result<(), TranspileError> {
+		for _ in 0..operands {
+			if self.pop(offset)?.slots() != 1 {
+				return Err(TranspileError::TypeMismatch(offset))
+			}
+		}
+		let pc = self.backend.helper(addr, operands, consts, frame, ret, self.depth());
+		if frame {
+			self.safepoint(pc);
+		}
+		self.stack.push(ret);
+		self.backend.unwind_if_trapped(self.ctx.traps.pending());
+		Ok(())
+	}
+
+	/// Pops the arguments of an intrinsic, calls it and pushes its return value
+	fn intrinsic(&mut self, intrinsic: Intrinsic, offset: usize) -> Result<(), TranspileError> {
+		let args = &self.stack[self.stack.len().saturating_sub(intrinsic.args.len())..];
+		if args.len() < intrinsic.args.len() {
+			return Err(TranspileError::StackUnderflow(offset))
+		}
+		if args.iter().zip(intrinsic.args).any(|(ty, arg)| ty.id() != arg.id()) {
+			return Err(TranspileError::TypeMismatch(offset))
+		}
+		// the intrinsics that allocate take the heap after their arguments
+		let heap = [self.ctx.heap as *const Heap as u64];
+		let consts: &[u64] = if intrinsic.allocates { &heap } else { &[] };
+		self.helper(intrinsic.addr, intrinsic.args.len(), consts, intrinsic.allocates, intrinsic.ret, offset)
+	}
+
+	/// Pops two operands and pushes the result of `op`
+	fn arith(&mut self, op: Arith, ty: TypeDesc, offset: usize) -> Result<(), TranspileError> {
+		self.pop_as(ty, offset)?;
+		self.pop_as(ty, offset)?;
+		self.backend.arith(op, ty, self.depth());
+		self.stack.push(ty);
+		Ok(())
+	}
+
+	/// Adds one to (or subtracts it from) the value at the top of the stack
+	fn step(&mut self, op: Arith, ty: TypeDesc, offset: usize) -> Result<(), TranspileError> {
+		let one = match ty.id() {
+			TypeFlags::F32 => 1f32.to_bits() as u64,
+			TypeFlags::F64 => 1f64.to_bits(),
+			_ => 1,
+		};
+		self.backend.constant(one, self.depth());
+		self.stack.push(ty);
+		self.arith(op, ty, offset)
+	}
+
+	fn transpile(&mut self, insn: Insn, offset: usize) -> Result<(), TranspileError> {
+		match insn {
+			Insn::Nop => self.backend.nop(),
+			Insn::Add(ty) => self.arith(Arith::Add, ty, offset)?,
+			Insn::Sub(ty) => self.arith(Arith::Sub, ty, offset)?,
+			Insn::Mul(ty) => self.arith(Arith::Mul, ty, offset)?,
+			Insn::Div(ty) => self.arith(Arith::Div, ty, offset)?,
+			Insn::Inc(ty) => self.step(Arith::Add, ty, offset)?,
+			Insn::Dec(ty) => self.step(Arith::Sub, ty, offset)?,
+			Insn::Push(_, local) => {
+				let (disp, ty) = *self.locals.get(local as usize).ok_or(TranspileError::UndefinedLocal(offset, local))?;
+				self.backend.load_local(disp, ty, self.depth());
+				self.stack.push(ty);
+			},
+			Insn::Pop => {
+				let ty = self.pop(offset)?;
+				let disp = local(self.frame_slots);
+				self.backend.store_local(disp, ty, self.depth());
+				self.frame_slots += ty.slots();
+				self.locals.push((disp, ty));
+			},
+			Insn::Cast(from, to) => {
+				self.pop_as(from, offset)?;
+				self.backend.cast(from, to, self.depth());
+				self.stack.push(to);
+			},
+			Insn::Call(function) => {
+				let name = self.ctx.str(function)?;
+				match (self.ctx.functions.get(name), intrinsics::lookup(name)) {
+					(Some(function), _) => self.invoke(function.signature(), Dispatch::Static(function.code_ptr()), offset)?,
+					(None, Some(intrinsic)) => self.intrinsic(intrinsic, offset)?,
+					(None, None) => return Err(TranspileError::UnknownFunction(name.to_string())),
+				}
 			},
-			0x01 => {
+			Insn::CallVirt(class, method) => {
+				let (slot, method) = self.ctx.method(class, method)?;
+				self.invoke(method.signature(), Dispatch::Virtual(slot), offset)?;
 			},
-			0x1A => {
-				*new_dest = 0xC3;
-				return Ok(CodeData {
-					addr: new_dest,
-					size: code_size,
-				})
+			Insn::CallTrait(class, method) => {
+				let (slot, method) = self.ctx.method(class, method)?;
+				self.invoke(method.signature(), Dispatch::Trait(slot), offset)?;
 			},
-			_ => {
-				return Err(TranspileError::IllegalInsn(raw.head))
+			Insn::Ret => self.backend.ret(TypeDesc::new(TypeFlags::VOID, None), self.depth()),
+			Insn::VRet(_) => {
+				let ty = self.pop(offset)?;
+				self.backend.ret(ty, self.depth());
+			},
+			Insn::Ldc(index) => {
+				let constant = self.ctx.constants.get(index).ok_or(TranspileError::UnresolvedConstant(index))?;
+				// string constants are `str`s (or arrays of `i8`, like names)
+				let is_str = match types::type_id(constant.type_flags()) {
+					TypeFlags::STR => true,
+					TypeFlags::ARRAY => constant.type_operand().is_some_and(|elem| types::type_id(elem as u8) == TypeFlags::I8),
+					_ => false,
+				};
+				if is_str {
+					let value = constant.str().ok_or(TranspileError::UnsupportedConstant(index))?;
+					self.backend.constant(self.ctx.heap.intern(value) as u64, self.depth());
+					self.stack.push(TypeDesc::new(TypeFlags::STR, None));
+					return Ok(())
+				}
+				let bits = constant.bits().ok_or(TranspileError::UnsupportedConstant(index))?;
+				self.backend.constant(bits, self.depth());
+				self.stack.push(TypeDesc::new(constant.type_flags(), constant.type_operand()));
+			},
+			Insn::New(index) => {
+				let class = self.ctx.class(index)? as *const ClassLayout;
+				let heap = self.ctx.heap as *const Heap;
+				self.helper(new_object as *const () as usize, 0, &[class as u64, heap as u64], true, TypeDesc::new(TypeFlags::OBJECT, Some(index)), offset)?;
+			},
+			Insn::GetField(class, field) => {
+				let field = self.ctx.field(class, field)?;
+				self.pop(offset)?;
+				self.backend.get_field(field.offset() as i32, field.type_desc(), self.depth());
+				self.stack.push(field.type_desc());
+			},
+			Insn::SetField(class, field) => {
+				let field = self.ctx.field(class, field)?;
+				self.pop_as(field.type_desc(), offset)?;
+				self.pop(offset)?;
+				self.backend.set_field(field.offset() as i32, field.type_desc(), self.depth());
+			},
+			Insn::NewArray(elem) => {
+				let heap = self.ctx.heap as *const Heap;
+				let ty = TypeDesc::new(TypeFlags::ARRAY, Some(elem.flags as u16));
+				self.helper(new_array as *const () as usize, 1, &[heap as u64, elem.to_bits() as u64], true, ty, offset)?;
+			},
+			Insn::ALoad(elem) => {
+				self.pop(offset)?;
+				self.pop(offset)?;
+				self.backend.load_elem(elem, self.depth());
+				self.stack.push(elem);
+			},
+			Insn::AStore(elem) => {
+				self.pop_as(elem, offset)?;
+				self.pop(offset)?;
+				self.pop(offset)?;
+				self.backend.store_elem(elem, self.depth());
+			},
+			Insn::ALen => {
+				self.pop(offset)?;
+				self.backend.array_len(self.depth());
+				self.stack.push(TypeDesc::new(TypeFlags::I64, None));
+			},
+			Insn::ToDyn(index) => {
+				let trait_class = self.ctx.class(index)? as *const ClassLayout;
+				let object = match self.stack.last() {
+					Some(ty) if ty.id() == TypeFlags::OBJECT => *ty,
+					Some(_) => return Err(TranspileError::TypeMismatch(offset)),
+					None => return Err(TranspileError::StackUnderflow(offset)),
+				};
+				// keep the object on the stack, and push its trait vtable after it
+				self.backend.dup(self.depth());
+				self.stack.push(object);
+				self.helper(trait_vtable as *const () as usize, 1, &[trait_class as u64], false, TypeDesc::new(TypeFlags::I64, None), offset)?;
+				self.stack.truncate(self.stack.len() - 2);
+				self.stack.push(TypeDesc::new(TypeFlags::DYN, None));
 			},
 		}
-		raw.head = raw.head.add(1);
-		code_size += 1;
-		new_dest = new_dest.add(1);
+		Ok(())
+	}
+}
+
+/// The frame offset of a local variable slot
+fn local(slot: usize) -> i32 {
+	-8 * (slot as i32 + 1)
+}
+
+/// The frame offset of the operand stack slot at `depth`, in a frame with `frame` bytes of local
+/// variables
+fn operand(frame: i32, depth: usize) -> i32 {
+	-frame - 8 * (depth as i32 + 1)
+}
+
+/// The output of the JIT for a function
+#[derive(Debug)]
+pub struct Compiled {
+	pub code: Vec<u8>,
+	/// The stack maps of every call site in `code`
+	pub stack_maps: Vec<StackMap>,
+}
+
+/// Transpiles bytecode into machine code for the platform of `ctx`, using the instructions its CPU
+/// features allow
+pub fn transpile(code: &[u8], args: &[TypeDesc], ctx: &Context) -> Result<Compiled, TranspileError> {
+	match ctx.platform.arch {
+		CpuArch::X86_64 => transpile_with(X86_64::new(ctx.platform), code, args, ctx),
+		CpuArch::Aarch64 => transpile_with(Aarch64::new(), code, args, ctx),
+		CpuArch::RiscV64 => transpile_with(RiscV64::new(), code, args, ctx),
+		arch => Err(TranspileError::UnsupportedPlatform(PlatformKind::Arch(arch))),
+	}
+}
+
+/// Transpiles bytecode with a backend
+pub fn transpile_with(backend: impl Backend, code: &[u8], args: &[TypeDesc], ctx: &Context) -> Result<Compiled, TranspileError> {
+	let insns = insn::decode_all(code).map_err(TranspileError::Decode)?;
+	// every local variable takes at most two slots
+	let slots = 2 * (args.len() + insns.iter().filter(|(_, insn)| *insn == Insn::Pop).count());
+	let frame = (slots * 8 + 15) & !15;
+
+	let mut transpiler = Transpiler {
+		backend,
+		ctx,
+		stack: Vec::new(),
+		locals: Vec::new(),
+		frame_slots: 0,
+		frame: frame as i32,
+		stack_maps: Vec::new(),
+	};
+	for arg in args {
+		transpiler.locals.push((local(transpiler.frame_slots), *arg));
+		transpiler.frame_slots += arg.slots();
+	}
+	transpiler.backend.prologue(frame as i32, &transpiler.locals)?;
+
+	for (offset, insn) in insns {
+		transpiler.transpile(insn, offset)?;
+	}
+	// fall back to returning if the bytecode doesn't
+	transpiler.backend.ret(TypeDesc::new(TypeFlags::VOID, None), 0);
+
+	Ok(Compiled {
+		code: transpiler.backend.finish(),
+		stack_maps: transpiler.stack_maps,
+	})
+}
+
+/// Compiles the stub of a function with `signature` that isn't materialized yet, which calls the
+/// runtime helper at `materialize` with `consts` (which materializes the function) and then calls the
+/// function through its code pointer `code`, passing on its arguments and return value
+pub fn stub(signature: &Signature, code: *const usize, materialize: usize, consts: &[u64], ctx: &Context) -> Result<Compiled, TranspileError> {
+	match ctx.platform.arch {
+		CpuArch::X86_64 => stub_with(X86_64::new(ctx.platform), signature, code, materialize, consts, ctx),
+		CpuArch::Aarch64 => stub_with(Aarch64::new(), signature, code, materialize, consts, ctx),
+		CpuArch::RiscV64 => stub_with(RiscV64::new(), signature, code, materialize, consts, ctx),
+		arch => Err(TranspileError::UnsupportedPlatform(PlatformKind::Arch(arch))),
+	}
+}
+
+/// Compiles the stub of a function with a backend (see [`stub`])
+pub fn stub_with(backend: impl Backend, signature: &Signature, code: *const usize, materialize: usize, consts: &[u64], ctx: &Context) -> Result<Compiled, TranspileError> {
+	let frame = (signature.arg_slots() * 8 + 15) & !15;
+	let mut transpiler = Transpiler {
+		backend,
+		ctx,
+		stack: Vec::new(),
+		locals: Vec::new(),
+		frame_slots: 0,
+		frame: frame as i32,
+		stack_maps: Vec::new(),
+	};
+	for arg in &signature.args {
+		transpiler.locals.push((local(transpiler.frame_slots), *arg));
+		transpiler.frame_slots += arg.slots();
+	}
+	transpiler.backend.prologue(frame as i32, &transpiler.locals)?;
+
+	// the helper doesn't collect garbage, so its call site needs no stack map
+	transpiler.helper(materialize, 0, consts, false, TypeDesc::new(TypeFlags::VOID, None), 0)?;
+	transpiler.stack.clear();
+	for (disp, ty) in transpiler.locals.clone() {
+		transpiler.backend.load_local(disp, ty, transpiler.depth());
+		transpiler.stack.push(ty);
+	}
+	transpiler.invoke(signature, Dispatch::Static(code), 0)?;
+	let ret = transpiler.pop(0)?;
+	transpiler.backend.ret(ret, transpiler.depth());
+
+	Ok(Compiled {
+		code: transpiler.backend.finish(),
+		stack_maps: transpiler.stack_maps,
+	})
+}
+
+native! {
+	/// Runtime helper for `new`
+	fn new_object(class: *const ClassLayout, heap: *const Heap, rbp: usize, pc: usize) -> *mut ObjectHeader {
+		unsafe {
+			(*heap).safepoint(rbp, pc);
+			(*heap).alloc_object(&*class)
+		}
+	}
+}
+
+native! {
+	/// Runtime helper for `newarray`
+	fn new_array(len: i64, heap: *const Heap, elem: u32, rbp: usize, pc: usize) -> *mut Array {
+		if len < 0 {
+			raise(Trap::NegativeLength(len));
+			return null_mut()
+		}
+		unsafe {
+			(*heap).safepoint(rbp, pc);
+			(*heap).alloc_array(TypeDesc::from_bits(elem), len as usize)
+		}
+	}
+}
+
+native! {
+	/// Runtime helper for failed bounds checks
+	pub(crate) fn index_out_of_bounds(index: i64, len: usize) {
+		raise(Trap::IndexOutOfBounds(index, len));
+	}
+}
+
+native! {
+	/// Runtime helper for integer division by zero
+	fn division_by_zero() {
+		raise(Trap::DivisionByZero);
+	}
+}
+
+native! {
+	/// Runtime helper for null references to objects and arrays
+	pub(crate) fn null_reference() {
+		raise(Trap::NullReference);
+	}
+}
+
+native! {
+	/// Runtime helper for `todyn`
+	fn trait_vtable(obj: *mut ObjectHeader, trait_class: *const ClassLayout) -> *const *const usize {
+		if obj.is_null() {
+			raise(Trap::NullReference);
+			return null()
+		}
+		unsafe {
+			let class = (*obj).class();
+			match class.trait_vtable(&*trait_class) {
+				Some(vtable) => vtable,
+				None => {
+					raise(Trap::NotImplemented(class.name().to_string(), (*trait_class).name().to_string()));
+					null()
+				},
+			}
+		}
+	}
+}
+
+thread_local! {
+	/// The traps of the VM whose compiled code runs on this thread
+	static CURRENT: Cell<*const Traps> = const { Cell::new(null()) };
+}
+
+/// Where compiled code reports a trap, so it unwinds to the VM rather than aborting the process<br>
+/// Compiled code checks whether a trap is pending after each call, and returns right away if one is.
+#[derive(Debug, Default)]
+pub struct Traps {
+	/// Nonzero while a trap is pending
+	pending: Cell<u64>,
+	trap: RefCell<Option<Trap>>,
+}
+
+impl Traps {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The address of the word that compiled code checks
+	pub fn pending(&self) -> usize {
+		self.pending.as_ptr() as usize
+	}
+
+	/// Runs `f`, which calls compiled code, with the traps raised on this thread reported here, and
+	/// returns the trap that unwound it if one did
+	pub fn catch<R>(&self, f: impl FnOnce() -> R) -> Result<R, Trap> {
+		let outer = CURRENT.with(|current| current.replace(self));
+		let ret = f();
+		CURRENT.with(|current| current.set(outer));
+		self.pending.set(0);
+		match self.trap.borrow_mut().take() {
+			Some(trap) => Err(trap),
+			None => Ok(ret),
+		}
+	}
+}
+
+/// Records a trap of the compiled code running on this thread, which unwinds once the runtime
+/// helper returns<br>
+/// ***Note:** Compiled code that isn't run by [`Traps::catch`] can't unwind, so this aborts the process.*
+pub(crate) fn raise(trap: Trap) {
+	let traps = CURRENT.with(Cell::get);
+	if traps.is_null() {
+		eprintln!("{}", trap);
+		std::process::abort()
+	}
+	unsafe {
+		(*traps).trap.replace(Some(trap));
+		(*traps).pending.set(1);
 	}
 }
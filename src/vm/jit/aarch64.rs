@@ -0,0 +1,669 @@
+use crate::vm::error::jit::TranspileError;
+use crate::vm::jit::{division_by_zero, index_out_of_bounds, null_reference, operand, Arith, Backend, Dispatch};
+use crate::vm::types;
+use crate::vm::types::{TypeDesc, TypeFlag, TypeFlags, TypeId};
+use crate::vm::types::array::Array;
+use crate::vm::types::function::Signature;
+
+/// AArch64 register numbers
+pub mod reg {
+	pub const X0: u8 = 0;
+	pub const X1: u8 = 1;
+	pub const X2: u8 = 2;
+	pub const X3: u8 = 3;
+	/// The first scratch register, which holds the targets of calls
+	pub const X16: u8 = 16;
+	/// The second scratch register, which holds addresses whose offset doesn't fit an instruction
+	pub const X17: u8 = 17;
+	/// The frame pointer
+	pub const FP: u8 = 29;
+	/// The link register
+	pub const LR: u8 = 30;
+	/// The stack pointer, or the zero register (depending on the instruction)
+	pub const SP: u8 = 31;
+	pub const XZR: u8 = 31;
+
+	/// Integer argument registers (AAPCS64)
+	pub const ARGS: [u8; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+}
+
+/// Condition codes of `b.cond`
+pub mod cond {
+	/// Unsigned lower
+	pub const LO: u8 = 0x3;
+	/// Signed less than, which is false if a float comparison is unordered
+	pub const LT: u8 = 0xB;
+}
+
+/// A minimal AArch64 machine code emitter<br>
+/// Every instruction is a little-endian 32-bit word. Integer registers are 64-bit, and floats are
+/// held in `d`/`s` registers, whose number is the same as an integer register's.
+#[derive(Default)]
+pub struct Assembler {
+	pub code: Vec<u8>,
+}
+
+impl Assembler {
+	pub fn insn(&mut self, word: u32) {
+		self.code.extend_from_slice(&word.to_le_bytes());
+	}
+
+	fn word(&self, pos: usize) -> u32 {
+		u32::from_le_bytes(self.code[pos..pos + 4].try_into().unwrap())
+	}
+
+	fn set_word(&mut self, pos: usize, word: u32) {
+		self.code[pos..pos + 4].copy_from_slice(&word.to_le_bytes());
+	}
+
+	/// `<op> rd, rn, rm` (in the data-processing register encodings)
+	fn rrr(&mut self, op: u32, rd: u8, rn: u8, rm: u8) {
+		self.insn(op | (rm as u32) << 16 | (rn as u32) << 5 | rd as u32);
+	}
+
+	/// `<op> rd, rn` (in the bitfield and conversion encodings)
+	fn rr(&mut self, op: u32, rd: u8, rn: u8) {
+		self.insn(op | (rn as u32) << 5 | rd as u32);
+	}
+
+	pub fn nop(&mut self) {
+		self.insn(0xD503201F);
+	}
+
+	pub fn add(&mut self, rd: u8, rn: u8, rm: u8) {
+		self.rrr(0x8B000000, rd, rn, rm);
+	}
+
+	/// `add rd, rn, rm, lsl #shift`
+	pub fn add_shifted(&mut self, rd: u8, rn: u8, rm: u8, shift: u8) {
+		self.rrr(0x8B000000 | (shift as u32) << 10, rd, rn, rm);
+	}
+
+	pub fn sub(&mut self, rd: u8, rn: u8, rm: u8) {
+		self.rrr(0xCB000000, rd, rn, rm);
+	}
+
+	/// `sub sp, sp, rm`
+	pub fn sub_sp(&mut self, rm: u8) {
+		// the extended register form, in which register 31 is `sp`
+		self.rrr(0xCB206000, reg::SP, reg::SP, rm);
+	}
+
+	pub fn mul(&mut self, rd: u8, rn: u8, rm: u8) {
+		// madd rd, rn, rm, xzr
+		self.rrr(0x9B007C00, rd, rn, rm);
+	}
+
+	pub fn sdiv(&mut self, rd: u8, rn: u8, rm: u8) {
+		self.rrr(0x9AC00C00, rd, rn, rm);
+	}
+
+	pub fn udiv(&mut self, rd: u8, rn: u8, rm: u8) {
+		self.rrr(0x9AC00800, rd, rn, rm);
+	}
+
+	/// `cmp rn, rm`
+	pub fn cmp(&mut self, rn: u8, rm: u8) {
+		// subs xzr, rn, rm
+		self.rrr(0xEB000000, reg::XZR, rn, rm);
+	}
+
+	/// `mov rd, rm`, where neither is `sp`
+	pub fn mov(&mut self, rd: u8, rm: u8) {
+		// orr rd, xzr, rm
+		self.rrr(0xAA000000, rd, reg::XZR, rm);
+	}
+
+	/// `mov rd, rn`, where either may be `sp`
+	pub fn mov_sp(&mut self, rd: u8, rn: u8) {
+		// add rd, rn, #0
+		self.rr(0x91000000, rd, rn);
+	}
+
+	pub fn movz(&mut self, rd: u8, imm: u16, shift: u8) {
+		self.insn(0xD2800000 | (shift as u32 / 16) << 21 | (imm as u32) << 5 | rd as u32);
+	}
+
+	pub fn movn(&mut self, rd: u8, imm: u16, shift: u8) {
+		self.insn(0x92800000 | (shift as u32 / 16) << 21 | (imm as u32) << 5 | rd as u32);
+	}
+
+	pub fn movk(&mut self, rd: u8, imm: u16, shift: u8) {
+		self.insn(0xF2800000 | (shift as u32 / 16) << 21 | (imm as u32) << 5 | rd as u32);
+	}
+
+	/// Moves a 64-bit immediate into `rd` with as few instructions as possible, starting from `movn`
+	/// if most of its halfwords are `0xFFFF`
+	pub fn mov_imm(&mut self, rd: u8, imm: u64) {
+		let halves = [0, 16, 32, 48].map(|shift| ((imm >> shift) as u16, shift));
+		let inverted = halves.iter().filter(|(half, _)| *half == 0xFFFF).count() > halves.iter().filter(|(half, _)| *half == 0).count();
+		let fill = if inverted { 0xFFFF } else { 0 };
+		let mut first = true;
+		for (half, shift) in halves.into_iter().filter(|(half, _)| *half != fill) {
+			match (first, inverted) {
+				(true, true) => self.movn(rd, !half, shift),
+				(true, false) => self.movz(rd, half, shift),
+				(false, _) => self.movk(rd, half, shift),
+			}
+			first = false;
+		}
+		if first {
+			match inverted {
+				true => self.movn(rd, 0, 0),
+				false => self.movz(rd, 0, 0),
+			}
+		}
+	}
+
+	/// Sign or zero extends the integer in `rd` from the width of `ty`
+	pub fn normalize(&mut self, rd: u8, ty: TypeDesc) {
+		match (ty.id(), ty.is_unsigned()) {
+			// sxtb/uxtb
+			(TypeFlags::I8, false) => self.rr(0x93401C00, rd, rd),
+			(TypeFlags::I8, true) => self.rr(0x53001C00, rd, rd),
+			// sxth/uxth
+			(TypeFlags::I16, false) => self.rr(0x93403C00, rd, rd),
+			(TypeFlags::I16, true) => self.rr(0x53003C00, rd, rd),
+			// sxtw/mov wd, wd
+			(TypeFlags::I32, false) => self.rr(0x93407C00, rd, rd),
+			(TypeFlags::I32, true) => self.rrr(0x2A000000, rd, reg::XZR, rd),
+			_ => {},
+		}
+	}
+
+	/// A load or store at `[rn + disp]`, in the unscaled immediate encoding, or through `x17` if
+	/// `disp` doesn't fit in 9 bits
+	fn mem(&mut self, op: u32, rt: u8, rn: u8, disp: i32) {
+		if (-256..256).contains(&disp) {
+			self.insn(op | (disp as u32 & 0x1FF) << 12 | (rn as u32) << 5 | rt as u32);
+		} else {
+			self.mov_imm(reg::X17, disp as i64 as u64);
+			self.add(reg::X17, rn, reg::X17);
+			self.insn(op | (reg::X17 as u32) << 5 | rt as u32);
+		}
+	}
+
+	/// Stores the lowest `size` bytes of `rt` at `[rn + disp]`
+	pub fn store(&mut self, rt: u8, rn: u8, disp: i32, size: usize) {
+		// sturb/sturh/stur wt/stur xt
+		let op = match size {
+			1 => 0x38000000,
+			2 => 0x78000000,
+			4 => 0xB8000000,
+			_ => 0xF8000000,
+		};
+		self.mem(op, rt, rn, disp);
+	}
+
+	/// Loads a value of type `ty` from `[rn + disp]` into `rt`, extending it to 64 bits
+	pub fn load(&mut self, rt: u8, rn: u8, disp: i32, ty: TypeDesc) {
+		let op = match (ty.id(), ty.is_unsigned()) {
+			// ldursb/ldurb
+			(TypeFlags::I8, false) => 0x38800000,
+			(TypeFlags::I8, true) => 0x38400000,
+			// ldursh/ldurh
+			(TypeFlags::I16, false) => 0x78800000,
+			(TypeFlags::I16, true) => 0x78400000,
+			// ldursw/ldur wt
+			(TypeFlags::I32, false) => 0xB8800000,
+			(TypeFlags::I32, true) | (TypeFlags::F32, _) => 0xB8400000,
+			// ldur xt
+			_ => 0xF8400000,
+		};
+		self.mem(op, rt, rn, disp);
+	}
+
+	/// `fmov dd, xn`/`fmov sd, wn`
+	pub fn to_float(&mut self, rd: u8, rn: u8, ty: TypeDesc) {
+		self.rr(if is_double(ty) { 0x9E670000 } else { 0x1E270000 }, rd, rn);
+	}
+
+	/// `fmov xd, dn`/`fmov wd, sn`
+	pub fn from_float(&mut self, rd: u8, rn: u8, ty: TypeDesc) {
+		self.rr(if is_double(ty) { 0x9E660000 } else { 0x1E260000 }, rd, rn);
+	}
+
+	/// `fadd`/`fsub`/`fmul`/`fdiv` of `d` or `s` registers
+	pub fn float_op(&mut self, op: Arith, rd: u8, rn: u8, rm: u8, ty: TypeDesc) {
+		let op = match op {
+			Arith::Add => 0x2800,
+			Arith::Sub => 0x3800,
+			Arith::Mul => 0x0800,
+			Arith::Div => 0x1800,
+		};
+		self.rrr(if is_double(ty) { 0x1E600000 } else { 0x1E200000 } | op, rd, rn, rm);
+	}
+
+	/// `fcmp dn, dm`
+	pub fn fcmp(&mut self, rn: u8, rm: u8) {
+		self.rrr(0x1E602000, 0, rn, rm);
+	}
+
+	/// `scvtf dd, xn`/`scvtf sd, xn`
+	pub fn scvtf(&mut self, rd: u8, rn: u8, ty: TypeDesc) {
+		self.rr(if is_double(ty) { 0x9E620000 } else { 0x9E220000 }, rd, rn);
+	}
+
+	/// `fcvtzs xd, dn`
+	pub fn fcvtzs(&mut self, rd: u8, rn: u8) {
+		self.rr(0x9E780000, rd, rn);
+	}
+
+	/// `fcvt dd, sn` if `to` is `f64`, or `fcvt sd, dn`
+	pub fn fcvt(&mut self, rd: u8, rn: u8, to: TypeDesc) {
+		self.rr(if is_double(to) { 0x1E22C000 } else { 0x1E624000 }, rd, rn);
+	}
+
+	/// `blr rn`
+	pub fn blr(&mut self, rn: u8) {
+		self.rr(0xD63F0000, 0, rn);
+	}
+
+	pub fn ret(&mut self) {
+		self.insn(0xD65F03C0);
+	}
+
+	/// `b.cond`, returning its position so it can be patched
+	pub fn b_cond(&mut self, cond: u8) -> usize {
+		self.insn(0x54000000 | cond as u32);
+		self.code.len() - 4
+	}
+
+	/// `cbnz rt`, returning its position so it can be patched
+	pub fn cbnz(&mut self, rt: u8) -> usize {
+		self.insn(0xB5000000 | rt as u32);
+		self.code.len() - 4
+	}
+
+	/// `cbz rt`, returning its position so it can be patched
+	pub fn cbz(&mut self, rt: u8) -> usize {
+		self.insn(0xB4000000 | rt as u32);
+		self.code.len() - 4
+	}
+
+	/// `adr rd`, returning its position so it can be patched
+	pub fn adr(&mut self, rd: u8) -> usize {
+		self.insn(0x10000000 | rd as u32);
+		self.code.len() - 4
+	}
+
+	/// Patches the `b.cond`, `cbz`, `cbnz` or `adr` at `pos` to refer to `target`
+	pub fn patch(&mut self, pos: usize, target: usize) {
+		let disp = target as i32 - pos as i32;
+		let word = self.word(pos);
+		let word = if word & 0x9F000000 == 0x10000000 {
+			word | (disp as u32 & 0x3) << 29 | (disp as u32 >> 2 & 0x7FFFF) << 5
+		} else {
+			word | (disp as u32 >> 2 & 0x7FFFF) << 5
+		};
+		self.set_word(pos, word);
+	}
+
+	/// Patches the immediate of the `movz`/`movk` at `pos`
+	pub fn patch_imm(&mut self, pos: usize, imm: u16) {
+		let word = self.word(pos) & !(0xFFFF << 5);
+		self.set_word(pos, word | (imm as u32) << 5);
+	}
+
+	/// `stp fp, lr, [sp, #-16]!; mov fp, sp`
+	pub fn enter(&mut self) {
+		self.insn(0xA9BF7BFD);
+		self.mov_sp(reg::FP, reg::SP);
+	}
+
+	/// `mov sp, fp; ldp fp, lr, [sp], #16; ret`
+	pub fn leave(&mut self) {
+		self.mov_sp(reg::SP, reg::FP);
+		self.insn(0xA8C17BFD);
+		self.ret();
+	}
+}
+
+fn is_double(ty: TypeDesc) -> bool {
+	ty.id() == TypeFlags::F64
+}
+
+/// The AArch64 backend, following AAPCS64 (with up to [`Signature::MAX_ARG_SLOTS`] argument slots)<br>
+/// The stack pointer doesn't move after the prologue, which allocates the local variables and the
+/// deepest the operand stack gets, so operand stack slots are loaded and stored relative to the
+/// frame pointer.
+pub struct Aarch64 {
+	asm: Assembler,
+	/// The size of the frame's local variables
+	frame: i32,
+	/// The number of operand stack slots the frame needs
+	slots: usize,
+	/// The position of the `movz`/`movk` pair that sets the size of the frame
+	frame_size: usize,
+}
+
+impl Aarch64 {
+	pub fn new() -> Self {
+		Aarch64 {
+			asm: Assembler::default(),
+			frame: 0,
+			slots: 0,
+			frame_size: 0,
+		}
+	}
+
+	/// The frame offset of the operand stack slot at `depth`
+	fn slot(&mut self, depth: usize) -> i32 {
+		self.slots = self.slots.max(depth + 1);
+		operand(self.frame, depth)
+	}
+
+	fn load_slot(&mut self, rt: u8, depth: usize) {
+		let disp = self.slot(depth);
+		self.asm.load(rt, reg::FP, disp, TypeDesc::new(TypeFlags::I64, None));
+	}
+
+	fn store_slot(&mut self, rt: u8, depth: usize) {
+		let disp = self.slot(depth);
+		self.asm.store(rt, reg::FP, disp, 8);
+	}
+
+	/// Calls the code at the address in `x16`, returning the offset of the return address
+	fn call_x16(&mut self) -> usize {
+		self.asm.blr(reg::X16);
+		self.asm.code.len()
+	}
+
+	/// Traps (returning from the function) if the reference in `rt` is null
+	fn null_check(&mut self, rt: u8) {
+		let nonnull = self.asm.cbnz(rt);
+		self.asm.mov_imm(reg::X16, null_reference as *const () as u64);
+		self.call_x16();
+		self.asm.leave();
+		self.asm.patch(nonnull, self.asm.code.len());
+	}
+
+	/// Computes the address of the element at index `x1` of the array in `x0` into `x0`, relative to
+	/// [`Array::DATA_OFFSET`], trapping (and returning from the function) unless the index is in
+	/// bounds (clobbers `x2`)
+	fn element(&mut self, elem: TypeDesc) {
+		self.asm.load(reg::X2, reg::X0, 0, TypeDesc::new(TypeFlags::I64, None));
+		self.asm.cmp(reg::X1, reg::X2);
+		// an unsigned comparison, so negative indices are out of bounds too
+		let in_bounds = self.asm.b_cond(cond::LO);
+		self.asm.mov(reg::X0, reg::X1);
+		self.asm.mov(reg::X1, reg::X2);
+		self.asm.mov_imm(reg::X16, index_out_of_bounds as *const () as u64);
+		self.call_x16();
+		self.asm.leave();
+		self.asm.patch(in_bounds, self.asm.code.len());
+		// element sizes are powers of two
+		self.asm.add_shifted(reg::X0, reg::X0, reg::X1, types::size_of(elem.flags).trailing_zeros() as u8);
+	}
+}
+
+impl Default for Aarch64 {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Backend for Aarch64 {
+	fn offset(&self) -> usize {
+		self.asm.code.len()
+	}
+
+	fn prologue(&mut self, frame: i32, args: &[(i32, TypeDesc)]) -> Result<(), TranspileError> {
+		if args.iter().map(|(_, ty)| ty.slots()).sum::<usize>() > Signature::MAX_ARG_SLOTS {
+			return Err(TranspileError::TooManyArgs(args.len()))
+		}
+		self.frame = frame;
+		self.asm.enter();
+		// the size of the frame is only known once every instruction has been emitted
+		self.frame_size = self.asm.code.len();
+		self.asm.movz(reg::X16, 0, 0);
+		self.asm.movk(reg::X16, 0, 16);
+		self.asm.sub_sp(reg::X16);
+		// spill the arguments into their local variables
+		let mut registers = reg::ARGS.iter();
+		for (disp, ty) in args {
+			for (i, register) in registers.by_ref().take(ty.slots()).enumerate() {
+				self.asm.store(*register, reg::FP, disp - 8 * i as i32, 8);
+			}
+		}
+		Ok(())
+	}
+
+	fn ret(&mut self, ret: TypeDesc, depth: usize) {
+		for i in 0..ret.slots() {
+			self.load_slot(reg::ARGS[i], depth + i);
+		}
+		self.asm.leave();
+	}
+
+	fn finish(mut self) -> Vec<u8> {
+		let size = (self.frame as usize + 8 * self.slots + 15) & !15;
+		let size = u32::try_from(size).expect("Frames must be smaller than 4 GiB");
+		self.asm.patch_imm(self.frame_size, size as u16);
+		self.asm.patch_imm(self.frame_size + 4, (size >> 16) as u16);
+		self.asm.code
+	}
+
+	fn nop(&mut self) {
+		self.asm.nop();
+	}
+
+	fn constant(&mut self, bits: u64, depth: usize) {
+		self.asm.mov_imm(reg::X0, bits);
+		self.store_slot(reg::X0, depth);
+	}
+
+	fn dup(&mut self, depth: usize) {
+		self.load_slot(reg::X0, depth - 1);
+		self.store_slot(reg::X0, depth);
+	}
+
+	fn load_local(&mut self, disp: i32, ty: TypeDesc, depth: usize) {
+		for i in 0..ty.slots() {
+			self.asm.load(reg::X0, reg::FP, disp - 8 * i as i32, TypeDesc::new(TypeFlags::I64, None));
+			self.store_slot(reg::X0, depth + i);
+		}
+	}
+
+	fn store_local(&mut self, disp: i32, ty: TypeDesc, depth: usize) {
+		for i in 0..ty.slots() {
+			self.load_slot(reg::X0, depth + i);
+			self.asm.store(reg::X0, reg::FP, disp - 8 * i as i32, 8);
+		}
+	}
+
+	fn arith(&mut self, op: Arith, ty: TypeDesc, depth: usize) {
+		self.load_slot(reg::X0, depth);
+		self.load_slot(reg::X1, depth + 1);
+		if ty.is_float() {
+			self.asm.to_float(0, reg::X0, ty);
+			self.asm.to_float(1, reg::X1, ty);
+			self.asm.float_op(op, 0, 0, 1, ty);
+			self.asm.from_float(reg::X0, 0, ty);
+		} else {
+			match op {
+				Arith::Add => self.asm.add(reg::X0, reg::X0, reg::X1),
+				Arith::Sub => self.asm.sub(reg::X0, reg::X0, reg::X1),
+				Arith::Mul => self.asm.mul(reg::X0, reg::X0, reg::X1),
+				Arith::Div => {
+					let nonzero = self.asm.cbnz(reg::X1);
+					self.asm.mov_imm(reg::X16, division_by_zero as *const () as u64);
+					self.call_x16();
+					self.asm.leave();
+					self.asm.patch(nonzero, self.asm.code.len());
+					// dividing the lowest value by -1 wraps, and doesn't fault like on x86
+					match ty.is_unsigned() {
+						true => self.asm.udiv(reg::X0, reg::X0, reg::X1),
+						false => self.asm.sdiv(reg::X0, reg::X0, reg::X1),
+					}
+				},
+			}
+			self.asm.normalize(reg::X0, ty);
+		}
+		self.store_slot(reg::X0, depth);
+	}
+
+	fn cast(&mut self, from: TypeDesc, to: TypeDesc, depth: usize) {
+		let f64 = TypeDesc::new(TypeFlags::F64, None);
+		self.load_slot(reg::X0, depth);
+		match (from.is_float(), to.is_float()) {
+			(false, false) => self.asm.normalize(reg::X0, to),
+			(false, true) => {
+				self.asm.scvtf(0, reg::X0, to);
+				self.asm.from_float(reg::X0, 0, to);
+			},
+			(true, false) => {
+				self.asm.to_float(0, reg::X0, from);
+				if !is_double(from) {
+					self.asm.fcvt(0, 0, f64);
+				}
+				self.asm.fcvtzs(reg::X0, 0);
+				// `fcvtzs` saturates, and converts NaN to 0, but out of range values convert to the
+				// lowest value (like on x86), so compare with 2^63 (which also fails for NaN)
+				self.asm.mov_imm(reg::X16, 2f64.powi(63).to_bits());
+				self.asm.to_float(1, reg::X16, f64);
+				self.asm.fcmp(0, 1);
+				let in_range = self.asm.b_cond(cond::LT);
+				self.asm.mov_imm(reg::X0, i64::MIN as u64);
+				self.asm.patch(in_range, self.asm.code.len());
+				self.asm.normalize(reg::X0, to);
+			},
+			(true, true) if from.id() != to.id() => {
+				self.asm.to_float(0, reg::X0, from);
+				self.asm.fcvt(0, 0, to);
+				self.asm.from_float(reg::X0, 0, to);
+			},
+			_ => {},
+		}
+		self.store_slot(reg::X0, depth);
+	}
+
+	fn call(&mut self, dispatch: Dispatch, args: &[TypeDesc], ret: TypeDesc, depth: usize) -> Result<usize, TranspileError> {
+		if args.iter().map(TypeDesc::slots).sum::<usize>() > Signature::MAX_ARG_SLOTS {
+			return Err(TranspileError::TooManyArgs(args.len()))
+		}
+		let mut slot = depth;
+		let mut registers = reg::ARGS.iter();
+		for (i, arg) in args.iter().enumerate() {
+			// the receiver of a trait call is a `dyn`, whose vtable is kept for the dispatch
+			if i == 0 && matches!(dispatch, Dispatch::Trait(_)) {
+				self.load_slot(*registers.next().unwrap(), slot);
+				self.load_slot(reg::X16, slot + 1);
+				slot += 2;
+				continue
+			}
+			for register in registers.by_ref().take(arg.slots()) {
+				self.load_slot(*register, slot);
+				slot += 1;
+			}
+		}
+
+		let word = TypeDesc::new(TypeFlags::I64, None);
+		match dispatch {
+			Dispatch::Static(code) => {
+				self.asm.mov_imm(reg::X16, code as u64);
+				self.asm.load(reg::X16, reg::X16, 0, word);
+			},
+			Dispatch::Virtual(slot) => {
+				self.null_check(reg::X0);
+				// load the class, then its vtable, then the method's code pointer
+				self.asm.load(reg::X16, reg::X0, 0, word);
+				self.asm.load(reg::X16, reg::X16, 0, word);
+				self.asm.load(reg::X16, reg::X16, 8 * slot as i32, word);
+			},
+			Dispatch::Trait(slot) => {
+				// a null `dyn` has a null vtable too
+				self.null_check(reg::X0);
+				self.asm.load(reg::X16, reg::X16, 8 * slot as i32, word);
+			},
+		}
+		let pc = self.call_x16();
+		for i in 0..ret.slots() {
+			self.store_slot(reg::ARGS[i], depth + i);
+		}
+		Ok(pc)
+	}
+
+	fn unwind_if_trapped(&mut self, pending: usize) {
+		self.asm.mov_imm(reg::X16, pending as u64);
+		self.asm.load(reg::X16, reg::X16, 0, TypeDesc::new(TypeFlags::I64, None));
+		let resume = self.asm.cbz(reg::X16);
+		self.asm.leave();
+		self.asm.patch(resume, self.asm.code.len());
+	}
+
+	fn helper(&mut self, addr: usize, operands: usize, consts: &[u64], frame: bool, ret: TypeDesc, depth: usize) -> usize {
+		for i in 0..operands {
+			self.load_slot(reg::ARGS[i], depth + i);
+		}
+		for (register, value) in reg::ARGS[operands..].iter().zip(consts) {
+			self.asm.mov_imm(*register, *value);
+		}
+		let n = operands + consts.len();
+		let mut ret_addr = None;
+		if frame {
+			self.asm.mov(reg::ARGS[n], reg::FP);
+			ret_addr = Some(self.asm.adr(reg::ARGS[n + 1]));
+		}
+		self.asm.mov_imm(reg::X16, addr as u64);
+		let pc = self.call_x16();
+		if let Some(pos) = ret_addr {
+			self.asm.patch(pos, pc);
+		}
+		if !ret.is_void() {
+			self.store_slot(reg::X0, depth);
+		}
+		pc
+	}
+
+	fn get_field(&mut self, offset: i32, ty: TypeDesc, depth: usize) {
+		self.load_slot(reg::X1, depth);
+		self.null_check(reg::X1);
+		self.asm.load(reg::X0, reg::X1, offset, ty);
+		self.store_slot(reg::X0, depth);
+		if ty.slots() == 2 {
+			self.asm.load(reg::X0, reg::X1, offset + 8, TypeDesc::new(TypeFlags::I64, None));
+			self.store_slot(reg::X0, depth + 1);
+		}
+	}
+
+	fn set_field(&mut self, offset: i32, ty: TypeDesc, depth: usize) {
+		self.load_slot(reg::X1, depth);
+		self.null_check(reg::X1);
+		for i in 0..ty.slots() {
+			self.load_slot(reg::X0, depth + 1 + i);
+			self.asm.store(reg::X0, reg::X1, offset + 8 * i as i32, types::size_of(ty.flags).min(8));
+		}
+	}
+
+	fn load_elem(&mut self, elem: TypeDesc, depth: usize) {
+		self.load_slot(reg::X0, depth);
+		self.load_slot(reg::X1, depth + 1);
+		self.null_check(reg::X0);
+		self.element(elem);
+		if elem.slots() == 2 {
+			self.asm.load(reg::X1, reg::X0, Array::DATA_OFFSET as i32 + 8, TypeDesc::new(TypeFlags::I64, None));
+			self.store_slot(reg::X1, depth + 1);
+		}
+		self.asm.load(reg::X0, reg::X0, Array::DATA_OFFSET as i32, elem);
+		self.store_slot(reg::X0, depth);
+	}
+
+	fn store_elem(&mut self, elem: TypeDesc, depth: usize) {
+		self.load_slot(reg::X0, depth);
+		self.load_slot(reg::X1, depth + 1);
+		self.null_check(reg::X0);
+		self.element(elem);
+		for i in 0..elem.slots() {
+			self.load_slot(reg::X1, depth + 2 + i);
+			self.asm.store(reg::X1, reg::X0, Array::DATA_OFFSET as i32 + 8 * i as i32, types::size_of(elem.flags).min(8));
+		}
+	}
+
+	fn array_len(&mut self, depth: usize) {
+		self.load_slot(reg::X0, depth);
+		self.null_check(reg::X0);
+		self.asm.load(reg::X0, reg::X0, 0, TypeDesc::new(TypeFlags::I64, None));
+		self.store_slot(reg::X0, depth);
+	}
+}
@@ -0,0 +1,691 @@
+use crate::vm::error::jit::TranspileError;
+use crate::vm::jit::{division_by_zero, index_out_of_bounds, null_reference, operand, Arith, Backend, Dispatch};
+use crate::vm::types;
+use crate::vm::types::{TypeDesc, TypeFlag, TypeFlags, TypeId};
+use crate::vm::types::array::Array;
+use crate::vm::types::function::Signature;
+
+/// RISC-V integer register numbers (by their ABI names)
+pub mod reg {
+	pub const ZERO: u8 = 0;
+	/// The return address
+	pub const RA: u8 = 1;
+	/// The stack pointer
+	pub const SP: u8 = 2;
+	/// The first scratch register, which holds the targets of calls
+	pub const T0: u8 = 5;
+	/// The second scratch register, which holds trait vtables and comparison results
+	pub const T1: u8 = 6;
+	/// The frame pointer
+	pub const FP: u8 = 8;
+	pub const A0: u8 = 10;
+	pub const A1: u8 = 11;
+	pub const A2: u8 = 12;
+	/// The scratch register that holds addresses whose offset doesn't fit an instruction
+	pub const T6: u8 = 31;
+
+	/// Integer argument registers (psABI)
+	pub const ARGS: [u8; 8] = [10, 11, 12, 13, 14, 15, 16, 17];
+
+	/// The floating-point scratch registers
+	pub const FT0: u8 = 0;
+	pub const FT1: u8 = 1;
+}
+
+/// A minimal RV64IMFD machine code emitter<br>
+/// Every instruction is a little-endian 32-bit word (compressed instructions aren't used, but run on
+/// RV64GC all the same).
+#[derive(Default)]
+pub struct Assembler {
+	pub code: Vec<u8>,
+}
+
+impl Assembler {
+	pub fn insn(&mut self, word: u32) {
+		self.code.extend_from_slice(&word.to_le_bytes());
+	}
+
+	fn word(&self, pos: usize) -> u32 {
+		u32::from_le_bytes(self.code[pos..pos + 4].try_into().unwrap())
+	}
+
+	fn set_word(&mut self, pos: usize, word: u32) {
+		self.code[pos..pos + 4].copy_from_slice(&word.to_le_bytes());
+	}
+
+	/// An R-type instruction
+	fn r(&mut self, opcode: u32, funct3: u32, funct7: u32, rd: u8, rs1: u8, rs2: u8) {
+		self.insn(funct7 << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | funct3 << 12 | (rd as u32) << 7 | opcode);
+	}
+
+	/// An I-type instruction, with a 12-bit immediate
+	fn i(&mut self, opcode: u32, funct3: u32, rd: u8, rs1: u8, imm: i32) {
+		self.insn((imm as u32 & 0xFFF) << 20 | (rs1 as u32) << 15 | funct3 << 12 | (rd as u32) << 7 | opcode);
+	}
+
+	/// An S-type instruction, with a 12-bit immediate
+	fn s(&mut self, funct3: u32, rs1: u8, rs2: u8, imm: i32) {
+		let imm = imm as u32;
+		self.insn((imm >> 5 & 0x7F) << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | funct3 << 12 | (imm & 0x1F) << 7 | 0x23);
+	}
+
+	pub fn add(&mut self, rd: u8, rs1: u8, rs2: u8) {
+		self.r(0x33, 0, 0x00, rd, rs1, rs2);
+	}
+
+	pub fn sub(&mut self, rd: u8, rs1: u8, rs2: u8) {
+		self.r(0x33, 0, 0x20, rd, rs1, rs2);
+	}
+
+	pub fn mul(&mut self, rd: u8, rs1: u8, rs2: u8) {
+		self.r(0x33, 0, 0x01, rd, rs1, rs2);
+	}
+
+	pub fn div(&mut self, rd: u8, rs1: u8, rs2: u8) {
+		self.r(0x33, 4, 0x01, rd, rs1, rs2);
+	}
+
+	pub fn divu(&mut self, rd: u8, rs1: u8, rs2: u8) {
+		self.r(0x33, 5, 0x01, rd, rs1, rs2);
+	}
+
+	pub fn addi(&mut self, rd: u8, rs1: u8, imm: i32) {
+		self.i(0x13, 0, rd, rs1, imm);
+	}
+
+	pub fn addiw(&mut self, rd: u8, rs1: u8, imm: i32) {
+		self.i(0x1B, 0, rd, rs1, imm);
+	}
+
+	pub fn andi(&mut self, rd: u8, rs1: u8, imm: i32) {
+		self.i(0x13, 7, rd, rs1, imm);
+	}
+
+	pub fn slli(&mut self, rd: u8, rs1: u8, shamt: u8) {
+		self.i(0x13, 1, rd, rs1, shamt as i32);
+	}
+
+	pub fn srli(&mut self, rd: u8, rs1: u8, shamt: u8) {
+		self.i(0x13, 5, rd, rs1, shamt as i32);
+	}
+
+	pub fn srai(&mut self, rd: u8, rs1: u8, shamt: u8) {
+		self.i(0x13, 5, rd, rs1, 0x400 | shamt as i32);
+	}
+
+	pub fn lui(&mut self, rd: u8, imm: u32) {
+		self.insn((imm & 0xFFFFF) << 12 | (rd as u32) << 7 | 0x37);
+	}
+
+	/// `mv rd, rs`
+	pub fn mv(&mut self, rd: u8, rs: u8) {
+		self.addi(rd, rs, 0);
+	}
+
+	/// Moves a 64-bit immediate into `rd`, with `lui`/`addiw` for the lowest 32 bits, and shifts and
+	/// `addi`s for the rest
+	pub fn li(&mut self, rd: u8, imm: i64) {
+		if imm as i32 as i64 == imm {
+			// `addiw` sign extends the low 12 bits, which the upper 20 make up for
+			let hi = ((imm + 0x800) >> 12) as u32 & 0xFFFFF;
+			let lo = (imm << 52 >> 52) as i32;
+			if hi == 0 {
+				self.addi(rd, reg::ZERO, lo);
+			} else {
+				self.lui(rd, hi);
+				if lo != 0 {
+					self.addiw(rd, rd, lo);
+				}
+			}
+		} else {
+			let lo = imm << 52 >> 52;
+			// the upper 52 bits, rounded up if the low 12 are negative (and wrapped like `slli` would)
+			let hi = (imm as u64).wrapping_add(0x800) as i64 >> 12;
+			let shift = 12 + hi.trailing_zeros();
+			self.li(rd, hi >> (shift - 12));
+			self.slli(rd, rd, shift as u8);
+			if lo != 0 {
+				self.addi(rd, rd, lo as i32);
+			}
+		}
+	}
+
+	/// Sign or zero extends the integer in `rd` from the width of `ty`
+	pub fn normalize(&mut self, rd: u8, ty: TypeDesc) {
+		let bits = match ty.id() {
+			TypeFlags::I8 => 8,
+			TypeFlags::I16 => 16,
+			TypeFlags::I32 => 32,
+			_ => return,
+		};
+		match (bits, ty.is_unsigned()) {
+			(8, true) => self.andi(rd, rd, 0xFF),
+			// sext.w
+			(32, false) => self.addiw(rd, rd, 0),
+			(_, unsigned) => {
+				self.slli(rd, rd, 64 - bits);
+				match unsigned {
+					true => self.srli(rd, rd, 64 - bits),
+					false => self.srai(rd, rd, 64 - bits),
+				}
+			},
+		}
+	}
+
+	/// The base and offset of `[rs1 + disp]`, through `t6` if `disp` doesn't fit in 12 bits
+	fn address(&mut self, rs1: u8, disp: i32) -> (u8, i32) {
+		if (-2048..2048).contains(&disp) {
+			(rs1, disp)
+		} else {
+			self.li(reg::T6, disp as i64);
+			self.add(reg::T6, rs1, reg::T6);
+			(reg::T6, 0)
+		}
+	}
+
+	/// Stores the lowest `size` bytes of `rs2` at `[rs1 + disp]`
+	pub fn store(&mut self, rs2: u8, rs1: u8, disp: i32, size: usize) {
+		// sb/sh/sw/sd
+		let funct3 = match size {
+			1 => 0,
+			2 => 1,
+			4 => 2,
+			_ => 3,
+		};
+		let (base, disp) = self.address(rs1, disp);
+		self.s(funct3, base, rs2, disp);
+	}
+
+	/// Loads a value of type `ty` from `[rs1 + disp]` into `rd`, extending it to 64 bits
+	pub fn load(&mut self, rd: u8, rs1: u8, disp: i32, ty: TypeDesc) {
+		// lb/lbu/lh/lhu/lw/lwu/ld
+		let funct3 = match (ty.id(), ty.is_unsigned()) {
+			(TypeFlags::I8, false) => 0,
+			(TypeFlags::I8, true) => 4,
+			(TypeFlags::I16, false) => 1,
+			(TypeFlags::I16, true) => 5,
+			(TypeFlags::I32, false) => 2,
+			(TypeFlags::I32, true) | (TypeFlags::F32, _) => 6,
+			_ => 3,
+		};
+		let (base, disp) = self.address(rs1, disp);
+		self.i(0x03, funct3, rd, base, disp);
+	}
+
+	/// `fmv.d.x fd, rs`/`fmv.w.x fd, rs`
+	pub fn to_float(&mut self, fd: u8, rs: u8, ty: TypeDesc) {
+		self.r(0x53, 0, if is_double(ty) { 0x79 } else { 0x78 }, fd, rs, 0);
+	}
+
+	/// `fmv.x.d rd, fs`/`fmv.x.w rd, fs`, zero extending the bits of an `f32` like other backends
+	pub fn from_float(&mut self, rd: u8, fs: u8, ty: TypeDesc) {
+		self.r(0x53, 0, if is_double(ty) { 0x71 } else { 0x70 }, rd, fs, 0);
+		if !is_double(ty) {
+			self.slli(rd, rd, 32);
+			self.srli(rd, rd, 32);
+		}
+	}
+
+	/// `fadd`/`fsub`/`fmul`/`fdiv` with the dynamic rounding mode
+	pub fn float_op(&mut self, op: Arith, fd: u8, fs1: u8, fs2: u8, ty: TypeDesc) {
+		let funct7 = match op {
+			Arith::Add => 0x00,
+			Arith::Sub => 0x04,
+			Arith::Mul => 0x08,
+			Arith::Div => 0x0C,
+		};
+		self.r(0x53, 7, funct7 | is_double(ty) as u32, fd, fs1, fs2);
+	}
+
+	/// `flt.d rd, fs1, fs2`, which is false if either is NaN
+	pub fn flt(&mut self, rd: u8, fs1: u8, fs2: u8) {
+		self.r(0x53, 1, 0x51, rd, fs1, fs2);
+	}
+
+	/// `fcvt.d.l fd, rs`/`fcvt.s.l fd, rs`
+	pub fn fcvt_from_int(&mut self, fd: u8, rs: u8, ty: TypeDesc) {
+		self.r(0x53, 7, if is_double(ty) { 0x69 } else { 0x68 }, fd, rs, 2);
+	}
+
+	/// `fcvt.l.d rd, fs, rtz`
+	pub fn fcvt_to_int(&mut self, rd: u8, fs: u8) {
+		self.r(0x53, 1, 0x61, rd, fs, 2);
+	}
+
+	/// `fcvt.d.s fd, fs` if `to` is `f64`, or `fcvt.s.d fd, fs`
+	pub fn fcvt(&mut self, fd: u8, fs: u8, to: TypeDesc) {
+		match is_double(to) {
+			true => self.r(0x53, 0, 0x21, fd, fs, 0),
+			false => self.r(0x53, 7, 0x20, fd, fs, 1),
+		}
+	}
+
+	/// `jalr ra, 0(rs)`
+	pub fn call(&mut self, rs: u8) {
+		self.i(0x67, 0, reg::RA, rs, 0);
+	}
+
+	/// `ret`
+	pub fn ret(&mut self) {
+		self.i(0x67, 0, reg::ZERO, reg::RA, 0);
+	}
+
+	/// A conditional branch (`beq`, `bne`, `blt`, `bge`, `bltu` or `bgeu` by `funct3`), returning its
+	/// position so it can be patched
+	fn branch(&mut self, funct3: u32, rs1: u8, rs2: u8) -> usize {
+		self.r(0x63, funct3, 0, 0, rs1, rs2);
+		self.code.len() - 4
+	}
+
+	pub fn beqz(&mut self, rs: u8) -> usize {
+		self.branch(0, rs, reg::ZERO)
+	}
+
+	pub fn bnez(&mut self, rs: u8) -> usize {
+		self.branch(1, rs, reg::ZERO)
+	}
+
+	pub fn bltu(&mut self, rs1: u8, rs2: u8) -> usize {
+		self.branch(6, rs1, rs2)
+	}
+
+	/// `auipc rd, 0; addi rd, rd, 0`, returning its position so it can be patched into the address
+	/// of a position in the code
+	pub fn la(&mut self, rd: u8) -> usize {
+		self.insn(0x17 | (rd as u32) << 7);
+		self.addi(rd, rd, 0);
+		self.code.len() - 8
+	}
+
+	/// Patches the branch or `la` at `pos` to refer to `target`, which must be within 2 KiB
+	pub fn patch(&mut self, pos: usize, target: usize) {
+		let disp = target as i32 - pos as i32;
+		let word = self.word(pos);
+		if word & 0x7F == 0x17 {
+			let addi = self.word(pos + 4);
+			self.set_word(pos + 4, addi | (disp as u32 & 0xFFF) << 20);
+		} else {
+			let disp = disp as u32;
+			let imm = (disp >> 12 & 1) << 31 | (disp >> 5 & 0x3F) << 25 | (disp >> 1 & 0xF) << 8 | (disp >> 11 & 1) << 7;
+			self.set_word(pos, word | imm);
+		}
+	}
+
+	/// Patches the `lui`/`addiw` pair at `pos` to load `imm`
+	pub fn patch_li(&mut self, pos: usize, imm: i32) {
+		let hi = ((imm as i64 + 0x800) >> 12) as u32 & 0xFFFFF;
+		let lo = (imm as i64) << 52 >> 52;
+		let lui = self.word(pos);
+		let addiw = self.word(pos + 4);
+		self.set_word(pos, lui | hi << 12);
+		self.set_word(pos + 4, addiw | (lo as u32 & 0xFFF) << 20);
+	}
+
+	/// `addi sp, sp, -16; sd ra, 8(sp); sd fp, 0(sp); mv fp, sp`
+	pub fn enter(&mut self) {
+		self.addi(reg::SP, reg::SP, -16);
+		self.store(reg::RA, reg::SP, 8, 8);
+		self.store(reg::FP, reg::SP, 0, 8);
+		self.mv(reg::FP, reg::SP);
+	}
+
+	/// `mv sp, fp; ld ra, 8(sp); ld fp, 0(sp); addi sp, sp, 16; ret`
+	pub fn leave(&mut self) {
+		let word = TypeDesc::new(TypeFlags::I64, None);
+		self.mv(reg::SP, reg::FP);
+		self.load(reg::RA, reg::SP, 8, word);
+		self.load(reg::FP, reg::SP, 0, word);
+		self.addi(reg::SP, reg::SP, 16);
+		self.ret();
+	}
+}
+
+fn is_double(ty: TypeDesc) -> bool {
+	ty.id() == TypeFlags::F64
+}
+
+/// The RISC-V backend for RV64GC, following the LP64D psABI (with up to [`Signature::MAX_ARG_SLOTS`]
+/// argument slots, all in integer registers since every value is passed as its bits)<br>
+/// Like on AArch64, the frame (below the saved frame pointer and return address) is allocated in
+/// the prologue, and operand stack slots are addressed relative to the frame pointer. The frame
+/// pointer points at the saved frame pointer rather than above it, which is the layout the heap
+/// walks frames with.
+pub struct RiscV64 {
+	asm: Assembler,
+	/// The size of the frame's local variables
+	frame: i32,
+	/// The number of operand stack slots the frame needs
+	slots: usize,
+	/// The position of the `lui`/`addiw` pair that sets the size of the frame
+	frame_size: usize,
+}
+
+impl RiscV64 {
+	pub fn new() -> Self {
+		RiscV64 {
+			asm: Assembler::default(),
+			frame: 0,
+			slots: 0,
+			frame_size: 0,
+		}
+	}
+
+	/// The frame offset of the operand stack slot at `depth`
+	fn slot(&mut self, depth: usize) -> i32 {
+		self.slots = self.slots.max(depth + 1);
+		operand(self.frame, depth)
+	}
+
+	fn load_slot(&mut self, rd: u8, depth: usize) {
+		let disp = self.slot(depth);
+		self.asm.load(rd, reg::FP, disp, TypeDesc::new(TypeFlags::I64, None));
+	}
+
+	fn store_slot(&mut self, rs: u8, depth: usize) {
+		let disp = self.slot(depth);
+		self.asm.store(rs, reg::FP, disp, 8);
+	}
+
+	/// Calls the code at `addr`, returning the offset of the return address
+	fn call(&mut self, addr: usize) -> usize {
+		self.asm.li(reg::T0, addr as i64);
+		self.asm.call(reg::T0);
+		self.asm.code.len()
+	}
+
+	/// Traps (returning from the function) if the reference in `rs` is null
+	fn null_check(&mut self, rs: u8) {
+		let nonnull = self.asm.bnez(rs);
+		self.call(null_reference as *const () as usize);
+		self.asm.leave();
+		self.asm.patch(nonnull, self.asm.code.len());
+	}
+
+	/// Computes the address of the element at index `a1` of the array in `a0` into `a0`, relative to
+	/// [`Array::DATA_OFFSET`], trapping (and returning from the function) unless the index is in
+	/// bounds (clobbers `a1` and `a2`)
+	fn element(&mut self, elem: TypeDesc) {
+		self.asm.load(reg::A2, reg::A0, 0, TypeDesc::new(TypeFlags::I64, None));
+		// an unsigned comparison, so negative indices are out of bounds too
+		let in_bounds = self.asm.bltu(reg::A1, reg::A2);
+		self.asm.mv(reg::A0, reg::A1);
+		self.asm.mv(reg::A1, reg::A2);
+		self.call(index_out_of_bounds as *const () as usize);
+		self.asm.leave();
+		self.asm.patch(in_bounds, self.asm.code.len());
+		// element sizes are powers of two
+		self.asm.slli(reg::A1, reg::A1, types::size_of(elem.flags).trailing_zeros() as u8);
+		self.asm.add(reg::A0, reg::A0, reg::A1);
+	}
+}
+
+impl Default for RiscV64 {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Backend for RiscV64 {
+	fn offset(&self) -> usize {
+		self.asm.code.len()
+	}
+
+	fn prologue(&mut self, frame: i32, args: &[(i32, TypeDesc)]) -> Result<(), TranspileError> {
+		if args.iter().map(|(_, ty)| ty.slots()).sum::<usize>() > Signature::MAX_ARG_SLOTS {
+			return Err(TranspileError::TooManyArgs(args.len()))
+		}
+		self.frame = frame;
+		self.asm.enter();
+		// the size of the frame is only known once every instruction has been emitted
+		self.frame_size = self.asm.code.len();
+		self.asm.lui(reg::T0, 0);
+		self.asm.addiw(reg::T0, reg::T0, 0);
+		self.asm.sub(reg::SP, reg::SP, reg::T0);
+		// spill the arguments into their local variables
+		let mut registers = reg::ARGS.iter();
+		for (disp, ty) in args {
+			for (i, register) in registers.by_ref().take(ty.slots()).enumerate() {
+				self.asm.store(*register, reg::FP, disp - 8 * i as i32, 8);
+			}
+		}
+		Ok(())
+	}
+
+	fn ret(&mut self, ret: TypeDesc, depth: usize) {
+		for i in 0..ret.slots() {
+			self.load_slot(reg::ARGS[i], depth + i);
+		}
+		self.asm.leave();
+	}
+
+	fn finish(mut self) -> Vec<u8> {
+		let size = (self.frame as usize + 8 * self.slots + 15) & !15;
+		let size = i32::try_from(size).expect("Frames must be smaller than 2 GiB");
+		self.asm.patch_li(self.frame_size, size);
+		self.asm.code
+	}
+
+	fn nop(&mut self) {
+		// addi zero, zero, 0
+		self.asm.addi(reg::ZERO, reg::ZERO, 0);
+	}
+
+	fn constant(&mut self, bits: u64, depth: usize) {
+		self.asm.li(reg::A0, bits as i64);
+		self.store_slot(reg::A0, depth);
+	}
+
+	fn dup(&mut self, depth: usize) {
+		self.load_slot(reg::A0, depth - 1);
+		self.store_slot(reg::A0, depth);
+	}
+
+	fn load_local(&mut self, disp: i32, ty: TypeDesc, depth: usize) {
+		for i in 0..ty.slots() {
+			self.asm.load(reg::A0, reg::FP, disp - 8 * i as i32, TypeDesc::new(TypeFlags::I64, None));
+			self.store_slot(reg::A0, depth + i);
+		}
+	}
+
+	fn store_local(&mut self, disp: i32, ty: TypeDesc, depth: usize) {
+		for i in 0..ty.slots() {
+			self.load_slot(reg::A0, depth + i);
+			self.asm.store(reg::A0, reg::FP, disp - 8 * i as i32, 8);
+		}
+	}
+
+	fn arith(&mut self, op: Arith, ty: TypeDesc, depth: usize) {
+		self.load_slot(reg::A0, depth);
+		self.load_slot(reg::A1, depth + 1);
+		if ty.is_float() {
+			self.asm.to_float(reg::FT0, reg::A0, ty);
+			self.asm.to_float(reg::FT1, reg::A1, ty);
+			self.asm.float_op(op, reg::FT0, reg::FT0, reg::FT1, ty);
+			self.asm.from_float(reg::A0, reg::FT0, ty);
+		} else {
+			match op {
+				Arith::Add => self.asm.add(reg::A0, reg::A0, reg::A1),
+				Arith::Sub => self.asm.sub(reg::A0, reg::A0, reg::A1),
+				Arith::Mul => self.asm.mul(reg::A0, reg::A0, reg::A1),
+				Arith::Div => {
+					// dividing by zero doesn't fault, it returns -1
+					let nonzero = self.asm.bnez(reg::A1);
+					self.call(division_by_zero as *const () as usize);
+					self.asm.leave();
+					self.asm.patch(nonzero, self.asm.code.len());
+					match ty.is_unsigned() {
+						true => self.asm.divu(reg::A0, reg::A0, reg::A1),
+						false => self.asm.div(reg::A0, reg::A0, reg::A1),
+					}
+				},
+			}
+			self.asm.normalize(reg::A0, ty);
+		}
+		self.store_slot(reg::A0, depth);
+	}
+
+	fn cast(&mut self, from: TypeDesc, to: TypeDesc, depth: usize) {
+		let f64 = TypeDesc::new(TypeFlags::F64, None);
+		self.load_slot(reg::A0, depth);
+		match (from.is_float(), to.is_float()) {
+			(false, false) => self.asm.normalize(reg::A0, to),
+			(false, true) => {
+				self.asm.fcvt_from_int(reg::FT0, reg::A0, to);
+				self.asm.from_float(reg::A0, reg::FT0, to);
+			},
+			(true, false) => {
+				self.asm.to_float(reg::FT0, reg::A0, from);
+				if !is_double(from) {
+					self.asm.fcvt(reg::FT0, reg::FT0, f64);
+				}
+				self.asm.fcvt_to_int(reg::A0, reg::FT0);
+				// `fcvt` saturates, and converts NaN to the highest value, but out of range values
+				// convert to the lowest value (like on x86), so compare with 2^63 (which also fails
+				// for NaN)
+				self.asm.li(reg::T0, 2f64.powi(63).to_bits() as i64);
+				self.asm.to_float(reg::FT1, reg::T0, f64);
+				self.asm.flt(reg::T1, reg::FT0, reg::FT1);
+				let in_range = self.asm.bnez(reg::T1);
+				self.asm.li(reg::A0, i64::MIN);
+				self.asm.patch(in_range, self.asm.code.len());
+				self.asm.normalize(reg::A0, to);
+			},
+			(true, true) if from.id() != to.id() => {
+				self.asm.to_float(reg::FT0, reg::A0, from);
+				self.asm.fcvt(reg::FT0, reg::FT0, to);
+				self.asm.from_float(reg::A0, reg::FT0, to);
+			},
+			_ => {},
+		}
+		self.store_slot(reg::A0, depth);
+	}
+
+	fn call(&mut self, dispatch: Dispatch, args: &[TypeDesc], ret: TypeDesc, depth: usize) -> Result<usize, TranspileError> {
+		if args.iter().map(TypeDesc::slots).sum::<usize>() > Signature::MAX_ARG_SLOTS {
+			return Err(TranspileError::TooManyArgs(args.len()))
+		}
+		let mut slot = depth;
+		let mut registers = reg::ARGS.iter();
+		for (i, arg) in args.iter().enumerate() {
+			// the receiver of a trait call is a `dyn`, whose vtable is kept for the dispatch
+			if i == 0 && matches!(dispatch, Dispatch::Trait(_)) {
+				self.load_slot(*registers.next().unwrap(), slot);
+				self.load_slot(reg::T1, slot + 1);
+				slot += 2;
+				continue
+			}
+			for register in registers.by_ref().take(arg.slots()) {
+				self.load_slot(*register, slot);
+				slot += 1;
+			}
+		}
+
+		let word = TypeDesc::new(TypeFlags::I64, None);
+		match dispatch {
+			Dispatch::Static(code) => {
+				self.asm.li(reg::T0, code as i64);
+				self.asm.load(reg::T0, reg::T0, 0, word);
+			},
+			Dispatch::Virtual(slot) => {
+				self.null_check(reg::A0);
+				// load the class, then its vtable, then the method's code pointer
+				self.asm.load(reg::T0, reg::A0, 0, word);
+				self.asm.load(reg::T0, reg::T0, 0, word);
+				self.asm.load(reg::T0, reg::T0, 8 * slot as i32, word);
+			},
+			Dispatch::Trait(slot) => {
+				// a null `dyn` has a null vtable too
+				self.null_check(reg::A0);
+				self.asm.load(reg::T0, reg::T1, 8 * slot as i32, word);
+			},
+		}
+		self.asm.call(reg::T0);
+		let pc = self.asm.code.len();
+		for i in 0..ret.slots() {
+			self.store_slot(reg::ARGS[i], depth + i);
+		}
+		Ok(pc)
+	}
+
+	fn unwind_if_trapped(&mut self, pending: usize) {
+		self.asm.li(reg::T0, pending as i64);
+		self.asm.load(reg::T0, reg::T0, 0, TypeDesc::new(TypeFlags::I64, None));
+		let resume = self.asm.beqz(reg::T0);
+		self.asm.leave();
+		self.asm.patch(resume, self.asm.code.len());
+	}
+
+	fn helper(&mut self, addr: usize, operands: usize, consts: &[u64], frame: bool, ret: TypeDesc, depth: usize) -> usize {
+		for i in 0..operands {
+			self.load_slot(reg::ARGS[i], depth + i);
+		}
+		for (register, value) in reg::ARGS[operands..].iter().zip(consts) {
+			self.asm.li(*register, *value as i64);
+		}
+		let n = operands + consts.len();
+		let mut ret_addr = None;
+		if frame {
+			self.asm.mv(reg::ARGS[n], reg::FP);
+			ret_addr = Some(self.asm.la(reg::ARGS[n + 1]));
+		}
+		let pc = self.call(addr);
+		if let Some(pos) = ret_addr {
+			self.asm.patch(pos, pc);
+		}
+		if !ret.is_void() {
+			self.store_slot(reg::A0, depth);
+		}
+		pc
+	}
+
+	fn get_field(&mut self, offset: i32, ty: TypeDesc, depth: usize) {
+		self.load_slot(reg::A1, depth);
+		self.null_check(reg::A1);
+		self.asm.load(reg::A0, reg::A1, offset, ty);
+		self.store_slot(reg::A0, depth);
+		if ty.slots() == 2 {
+			self.asm.load(reg::A0, reg::A1, offset + 8, TypeDesc::new(TypeFlags::I64, None));
+			self.store_slot(reg::A0, depth + 1);
+		}
+	}
+
+	fn set_field(&mut self, offset: i32, ty: TypeDesc, depth: usize) {
+		self.load_slot(reg::A1, depth);
+		self.null_check(reg::A1);
+		for i in 0..ty.slots() {
+			self.load_slot(reg::A0, depth + 1 + i);
+			self.asm.store(reg::A0, reg::A1, offset + 8 * i as i32, types::size_of(ty.flags).min(8));
+		}
+	}
+
+	fn load_elem(&mut self, elem: TypeDesc, depth: usize) {
+		self.load_slot(reg::A0, depth);
+		self.load_slot(reg::A1, depth + 1);
+		self.null_check(reg::A0);
+		self.element(elem);
+		if elem.slots() == 2 {
+			self.asm.load(reg::A1, reg::A0, Array::DATA_OFFSET as i32 + 8, TypeDesc::new(TypeFlags::I64, None));
+			self.store_slot(reg::A1, depth + 1);
+		}
+		self.asm.load(reg::A0, reg::A0, Array::DATA_OFFSET as i32, elem);
+		self.store_slot(reg::A0, depth);
+	}
+
+	fn store_elem(&mut self, elem: TypeDesc, depth: usize) {
+		self.load_slot(reg::A0, depth);
+		self.load_slot(reg::A1, depth + 1);
+		self.null_check(reg::A0);
+		self.element(elem);
+		for i in 0..elem.slots() {
+			self.load_slot(reg::A1, depth + 2 + i);
+			self.asm.store(reg::A1, reg::A0, Array::DATA_OFFSET as i32 + 8 * i as i32, types::size_of(elem.flags).min(8));
+		}
+	}
+
+	fn array_len(&mut self, depth: usize) {
+		self.load_slot(reg::A0, depth);
+		self.null_check(reg::A0);
+		self.asm.load(reg::A0, reg::A0, 0, TypeDesc::new(TypeFlags::I64, None));
+		self.store_slot(reg::A0, depth);
+	}
+}
@@ -0,0 +1,609 @@
+use crate::vm::error::jit::TranspileError;
+use crate::vm::jit::{index_out_of_bounds, division_by_zero, null_reference, Arith, Backend, Dispatch};
+use crate::vm::meta::platform::{CpuFeature, CpuFeatures, Platform};
+use crate::vm::types;
+use crate::vm::types::{TypeDesc, TypeFlag, TypeFlags, TypeId};
+use crate::vm::types::array::Array;
+use crate::vm::types::function::Signature;
+
+/// x86-64 register numbers (as encoded in ModR/M and REX)
+mod reg {
+	pub const RAX: u8 = 0;
+	pub const RCX: u8 = 1;
+	pub const RDX: u8 = 2;
+	pub const RSP: u8 = 4;
+	pub const RBP: u8 = 5;
+	pub const RSI: u8 = 6;
+	pub const RDI: u8 = 7;
+	pub const R8: u8  = 8;
+	pub const R9: u8  = 9;
+	pub const R11: u8 = 11;
+
+	/// Integer argument registers (System V)
+	pub const ARGS: [u8; 6] = [RDI, RSI, RDX, RCX, R8, R9];
+}
+
+/// A minimal x86-64 machine code emitter
+struct Assembler {
+	code: Vec<u8>,
+	/// Whether AVX instructions may be used
+	avx: bool,
+}
+
+impl Assembler {
+	fn emit(&mut self, bytes: &[u8]) {
+		self.code.extend_from_slice(bytes);
+	}
+
+	fn rex(&mut self, w: bool, reg: u8, base: u8, force: bool) {
+		let rex = 0x40 | (w as u8) << 3 | (reg >> 3) << 2 | base >> 3;
+		if rex != 0x40 || force {
+			self.code.push(rex);
+		}
+	}
+
+	/// ModR/M (and SIB) for `[base + disp32]`
+	fn mem(&mut self, reg: u8, base: u8, disp: i32) {
+		self.code.push(0x80 | (reg & 7) << 3 | base & 7);
+		if base & 7 == reg::RSP {
+			self.code.push(0x24);
+		}
+		self.emit(&disp.to_le_bytes());
+	}
+
+	fn push(&mut self, reg: u8) {
+		self.rex(false, 0, reg, false);
+		self.code.push(0x50 + (reg & 7));
+	}
+
+	fn pop(&mut self, reg: u8) {
+		self.rex(false, 0, reg, false);
+		self.code.push(0x58 + (reg & 7));
+	}
+
+	fn mov_imm(&mut self, reg: u8, imm: u64) {
+		self.rex(true, 0, reg, false);
+		self.code.push(0xB8 + (reg & 7));
+		self.emit(&imm.to_le_bytes());
+	}
+
+	/// Stores the lowest `size` bytes of `src` at `[base + disp]`
+	fn store(&mut self, base: u8, disp: i32, src: u8, size: usize) {
+		match size {
+			1 => {
+				self.rex(false, src, base, src >= reg::RSP);
+				self.code.push(0x88);
+			},
+			2 => {
+				self.code.push(0x66);
+				self.rex(false, src, base, false);
+				self.code.push(0x89);
+			},
+			4 => {
+				self.rex(false, src, base, false);
+				self.code.push(0x89);
+			},
+			_ => {
+				self.rex(true, src, base, false);
+				self.code.push(0x89);
+			},
+		}
+		self.mem(src, base, disp);
+	}
+
+	/// Loads a value of type `ty` from `[base + disp]` into `dst`, extending it to 64 bits
+	fn load(&mut self, dst: u8, base: u8, disp: i32, ty: TypeDesc) {
+		match (ty.id(), ty.is_unsigned()) {
+			(TypeFlags::I8, false) => {
+				self.rex(true, dst, base, false);
+				self.emit(&[0x0F, 0xBE]);
+			},
+			(TypeFlags::I8, true) => {
+				self.rex(false, dst, base, false);
+				self.emit(&[0x0F, 0xB6]);
+			},
+			(TypeFlags::I16, false) => {
+				self.rex(true, dst, base, false);
+				self.emit(&[0x0F, 0xBF]);
+			},
+			(TypeFlags::I16, true) => {
+				self.rex(false, dst, base, false);
+				self.emit(&[0x0F, 0xB7]);
+			},
+			(TypeFlags::I32, false) => {
+				self.rex(true, dst, base, false);
+				self.code.push(0x63);
+			},
+			(TypeFlags::I32, true) | (TypeFlags::F32, _) => {
+				self.rex(false, dst, base, false);
+				self.code.push(0x8B);
+			},
+			_ => {
+				self.rex(true, dst, base, false);
+				self.code.push(0x8B);
+			},
+		}
+		self.mem(dst, base, disp);
+	}
+
+	/// Sign or zero extends the integer in `rax` from the width of `ty`
+	fn normalize(&mut self, ty: TypeDesc) {
+		match (ty.id(), ty.is_unsigned()) {
+			(TypeFlags::I8, false) => self.emit(&[0x48, 0x0F, 0xBE, 0xC0]),
+			(TypeFlags::I8, true) => self.emit(&[0x0F, 0xB6, 0xC0]),
+			(TypeFlags::I16, false) => self.emit(&[0x48, 0x0F, 0xBF, 0xC0]),
+			(TypeFlags::I16, true) => self.emit(&[0x0F, 0xB7, 0xC0]),
+			(TypeFlags::I32, false) => self.emit(&[0x48, 0x63, 0xC0]),
+			(TypeFlags::I32, true) => self.emit(&[0x89, 0xC0]),
+			_ => {},
+		}
+	}
+
+	/// Emits `prefix [REX.W] 0F opcode modrm`, a scalar SSE instruction, or its VEX encoding (with
+	/// `xmm0` as the extra source) if AVX may be used, which doesn't leave the upper halves of the
+	/// vector registers dirty<br>
+	/// ***Note:** The operands must be below `r8`/`xmm8`.*
+	fn sse(&mut self, prefix: u8, w: bool, opcode: u8, modrm: u8) {
+		if self.avx {
+			let pp = match prefix {
+				0x66 => 0b01,
+				0xF3 => 0b10,
+				0xF2 => 0b11,
+				_ => 0b00,
+			};
+			if w {
+				self.emit(&[0xC4, 0xE1, 0xF8 | pp]);
+			} else {
+				self.emit(&[0xC5, 0xF8 | pp]);
+			}
+		} else {
+			self.code.push(prefix);
+			self.rex(w, 0, 0, false);
+			self.code.push(0x0F);
+		}
+		self.emit(&[opcode, modrm]);
+	}
+
+	/// Moves `rax` into `xmm0` and `rcx` into `xmm1`
+	fn mov_to_xmm(&mut self, ty: TypeDesc) {
+		// movd/movq
+		let w = ty.id() != TypeFlags::F32;
+		self.sse(0x66, w, 0x6E, 0xC0);
+		self.sse(0x66, w, 0x6E, 0xC9);
+	}
+
+	/// Moves `xmm0` into `rax`
+	fn mov_from_xmm(&mut self, ty: TypeDesc) {
+		// movd/movq
+		self.sse(0x66, ty.id() != TypeFlags::F32, 0x7E, 0xC0);
+	}
+
+	/// `<op>ss`/`<op>sd xmm0, xmm1`
+	fn float_op(&mut self, ty: TypeDesc, op: u8) {
+		self.sse(float_prefix(ty), false, op, 0xC1);
+	}
+
+	fn sub_rsp(&mut self, imm: i32) {
+		self.emit(&[0x48, 0x81, 0xEC]);
+		self.emit(&imm.to_le_bytes());
+	}
+
+	fn add_rsp(&mut self, imm: i32) {
+		self.emit(&[0x48, 0x81, 0xC4]);
+		self.emit(&imm.to_le_bytes());
+	}
+
+	/// `mov dst, src`
+	fn mov(&mut self, dst: u8, src: u8) {
+		self.rex(true, src, dst, false);
+		self.emit(&[0x89, 0xC0 | (src & 7) << 3 | dst & 7]);
+	}
+
+	/// `jcc rel32` with condition code `cc`, returning the position of the displacement so it can be patched
+	fn jcc(&mut self, cc: u8) -> usize {
+		self.emit(&[0x0F, 0x80 | cc]);
+		self.emit(&0i32.to_le_bytes());
+		self.code.len() - 4
+	}
+
+	/// `jmp rel32`, returning the position of the displacement so it can be patched
+	fn jmp(&mut self) -> usize {
+		self.code.push(0xE9);
+		self.emit(&0i32.to_le_bytes());
+		self.code.len() - 4
+	}
+
+	/// `lea dst, [rip + disp32]`, returning the position of the displacement so it can be patched
+	fn lea_rip(&mut self, dst: u8) -> usize {
+		self.rex(true, dst, 0, false);
+		self.emit(&[0x8D, 0x05 | (dst & 7) << 3]);
+		self.emit(&0i32.to_le_bytes());
+		self.code.len() - 4
+	}
+
+	/// Patches the displacement at `pos` (of a RIP-relative instruction) to refer to `target`
+	fn patch_rel(&mut self, pos: usize, target: usize) {
+		let disp = target as i32 - (pos + 4) as i32;
+		self.code[pos..pos + 4].copy_from_slice(&disp.to_le_bytes());
+	}
+
+	fn epilogue(&mut self) {
+		// leave; ret
+		self.emit(&[0xC9, 0xC3]);
+	}
+}
+
+/// The x86-64 backend, whose operand stack is the machine stack, which values are pushed onto and
+/// popped from a slot at a time
+pub struct X86_64 {
+	asm: Assembler,
+}
+
+impl X86_64 {
+	pub fn new(platform: Platform) -> Self {
+		X86_64 {
+			asm: Assembler {
+				code: Vec::new(),
+				avx: platform.has(CpuFeatures::AVX2),
+			},
+		}
+	}
+
+	/// Pushes a value held in `regs` (its low slot first)
+	fn push(&mut self, ty: TypeDesc, regs: &[u8]) {
+		for reg in regs.iter().take(ty.slots()) {
+			self.asm.push(*reg);
+		}
+	}
+
+	/// Pops a value into `regs` (its low slot first)
+	fn pop(&mut self, ty: TypeDesc, regs: &[u8]) {
+		for reg in regs.iter().take(ty.slots()).rev() {
+			self.asm.pop(*reg);
+		}
+	}
+
+	/// Calls the code at the address held by `[target + disp]`, keeping the machine stack
+	/// 16-byte aligned, and returns the offset of the return address
+	fn call_indirect(&mut self, target: u8, disp: i32, depth: usize) -> usize {
+		let pad = depth % 2 == 1;
+		if pad {
+			self.asm.sub_rsp(8);
+		}
+		self.asm.load(reg::RAX, target, disp, TypeDesc::new(TypeFlags::I64, None));
+		// call rax
+		self.asm.emit(&[0xFF, 0xD0]);
+		let pc = self.asm.code.len();
+		if pad {
+			self.asm.add_rsp(8);
+		}
+		pc
+	}
+
+	/// Calls a runtime helper, and returns the offset of the return address<br>
+	/// If `frame` is set, the frame pointer and the return address are passed in its registers so
+	/// the helper may collect garbage.
+	fn call(&mut self, addr: usize, frame: Option<(u8, u8)>, depth: usize) -> usize {
+		let pad = depth % 2 == 1;
+		let mut ret = None;
+		if let Some((rbp, pc)) = frame {
+			self.asm.mov(rbp, reg::RBP);
+			ret = Some(self.asm.lea_rip(pc));
+		}
+		if pad {
+			self.asm.sub_rsp(8);
+		}
+		self.asm.mov_imm(reg::RAX, addr as u64);
+		// call rax
+		self.asm.emit(&[0xFF, 0xD0]);
+		let pc = self.asm.code.len();
+		if let Some(pos) = ret {
+			self.asm.patch_rel(pos, pc);
+		}
+		if pad {
+			self.asm.add_rsp(8);
+		}
+		pc
+	}
+
+	/// Traps (returning from the function) unless the index in `rcx` is in bounds of the array in
+	/// `rax` (clobbers `rdi` and `rsi`)
+	fn bounds_check(&mut self, depth: usize) {
+		self.asm.load(reg::RSI, reg::RAX, 0, TypeDesc::new(TypeFlags::I64, None));
+		// cmp rcx, rsi
+		self.asm.emit(&[0x48, 0x39, 0xF1]);
+		// jb (an unsigned comparison, so negative indices are out of bounds too)
+		let pos = self.asm.jcc(0x2);
+		self.asm.mov(reg::RDI, reg::RCX);
+		self.call(index_out_of_bounds as *const () as usize, None, depth);
+		self.asm.epilogue();
+		self.asm.patch_rel(pos, self.asm.code.len());
+	}
+
+	/// Traps (returning from the function) if the reference in `register` is null
+	fn null_check(&mut self, register: u8, depth: usize) {
+		// test reg, reg; jnz
+		self.asm.rex(true, register, register, false);
+		self.asm.emit(&[0x85, 0xC0 | (register & 7) << 3 | register & 7]);
+		let nonnull = self.asm.jcc(0x5);
+		self.call(null_reference as *const () as usize, None, depth);
+		self.asm.epilogue();
+		self.asm.patch_rel(nonnull, self.asm.code.len());
+	}
+
+	/// Computes the address of the element at index `rcx` of the array in `rax` into `rax`, relative
+	/// to [`Array::DATA_OFFSET`]
+	fn element(&mut self, ty: TypeDesc, depth: usize) {
+		self.bounds_check(depth);
+		// imul rcx, rcx, size; add rax, rcx
+		self.asm.emit(&[0x48, 0x69, 0xC9]);
+		self.asm.emit(&(types::size_of(ty.flags) as i32).to_le_bytes());
+		self.asm.emit(&[0x48, 0x01, 0xC8]);
+	}
+
+	/// Divides `rax` by `rcx`, trapping on integer division by zero, and wrapping the signed
+	/// division of the lowest value by `-1` (which `idiv` faults on)
+	fn divide(&mut self, ty: TypeDesc, depth: usize) {
+		// test rcx, rcx; jnz
+		self.asm.emit(&[0x48, 0x85, 0xC9]);
+		let nonzero = self.asm.jcc(0x5);
+		self.call(division_by_zero as *const () as usize, None, depth);
+		self.asm.epilogue();
+		self.asm.patch_rel(nonzero, self.asm.code.len());
+		if ty.is_unsigned() {
+			// xor edx, edx; div rcx
+			self.asm.emit(&[0x31, 0xD2, 0x48, 0xF7, 0xF1]);
+		} else {
+			// cmp rcx, -1; jne
+			self.asm.emit(&[0x48, 0x83, 0xF9, 0xFF]);
+			let divide = self.asm.jcc(0x5);
+			// neg rax
+			self.asm.emit(&[0x48, 0xF7, 0xD8]);
+			let done = self.asm.jmp();
+			self.asm.patch_rel(divide, self.asm.code.len());
+			// cqo; idiv rcx
+			self.asm.emit(&[0x48, 0x99, 0x48, 0xF7, 0xF9]);
+			self.asm.patch_rel(done, self.asm.code.len());
+		}
+	}
+}
+
+impl Backend for X86_64 {
+	fn offset(&self) -> usize {
+		self.asm.code.len()
+	}
+
+	fn prologue(&mut self, frame: i32, args: &[(i32, TypeDesc)]) -> Result<(), TranspileError> {
+		let registers = assign_registers(&args.iter().map(|(_, ty)| *ty).collect::<Vec<_>>())?;
+		// push rbp; mov rbp, rsp
+		self.asm.emit(&[0x55, 0x48, 0x89, 0xE5]);
+		self.asm.sub_rsp(frame);
+		// spill the arguments into their local variables
+		for ((disp, _), regs) in args.iter().zip(registers) {
+			for (i, register) in regs.iter().enumerate() {
+				self.asm.store(reg::RBP, disp - 8 * i as i32, *register, 8);
+			}
+		}
+		Ok(())
+	}
+
+	fn ret(&mut self, ret: TypeDesc, _: usize) {
+		self.pop(ret, &[reg::RAX, reg::RDX]);
+		self.asm.epilogue();
+	}
+
+	fn finish(self) -> Vec<u8> {
+		self.asm.code
+	}
+
+	fn nop(&mut self) {
+		self.asm.emit(&[0x90]);
+	}
+
+	fn constant(&mut self, bits: u64, _: usize) {
+		self.asm.mov_imm(reg::RAX, bits);
+		self.asm.push(reg::RAX);
+	}
+
+	fn dup(&mut self, _: usize) {
+		self.asm.load(reg::RAX, reg::RSP, 0, TypeDesc::new(TypeFlags::I64, None));
+		self.asm.push(reg::RAX);
+	}
+
+	fn load_local(&mut self, disp: i32, ty: TypeDesc, _: usize) {
+		let word = TypeDesc::new(TypeFlags::I64, None);
+		self.asm.load(reg::RAX, reg::RBP, disp, word);
+		if ty.slots() == 2 {
+			self.asm.load(reg::RDX, reg::RBP, disp - 8, word);
+		}
+		self.push(ty, &[reg::RAX, reg::RDX]);
+	}
+
+	fn store_local(&mut self, disp: i32, ty: TypeDesc, _: usize) {
+		self.pop(ty, &[reg::RAX, reg::RDX]);
+		self.asm.store(reg::RBP, disp, reg::RAX, 8);
+		if ty.slots() == 2 {
+			self.asm.store(reg::RBP, disp - 8, reg::RDX, 8);
+		}
+	}
+
+	fn arith(&mut self, op: Arith, ty: TypeDesc, depth: usize) {
+		self.asm.pop(reg::RCX);
+		self.asm.pop(reg::RAX);
+		// add/sub/imul rax, rcx, or addss/addsd (and so on) xmm0, xmm1
+		let (int, float): (&[u8], u8) = match op {
+			Arith::Add => (&[0x48, 0x01, 0xC8], 0x58),
+			Arith::Sub => (&[0x48, 0x29, 0xC8], 0x5C),
+			Arith::Mul => (&[0x48, 0x0F, 0xAF, 0xC1], 0x59),
+			Arith::Div => (&[], 0x5E),
+		};
+		if ty.is_float() {
+			self.asm.mov_to_xmm(ty);
+			self.asm.float_op(ty, float);
+			self.asm.mov_from_xmm(ty);
+		} else {
+			match op {
+				Arith::Div => self.divide(ty, depth),
+				_ => self.asm.emit(int),
+			}
+			self.asm.normalize(ty);
+		}
+		self.asm.push(reg::RAX);
+	}
+
+	fn cast(&mut self, from: TypeDesc, to: TypeDesc, _: usize) {
+		self.asm.pop(reg::RAX);
+		match (from.is_float(), to.is_float()) {
+			(false, false) => self.asm.normalize(to),
+			(false, true) => {
+				// cvtsi2ss/cvtsi2sd xmm0, rax
+				self.asm.sse(float_prefix(to), true, 0x2A, 0xC0);
+				self.asm.mov_from_xmm(to);
+			},
+			(true, false) => {
+				self.asm.mov_to_xmm(from);
+				// cvttss2si/cvttsd2si rax, xmm0
+				self.asm.sse(float_prefix(from), true, 0x2C, 0xC0);
+				self.asm.normalize(to);
+			},
+			(true, true) if from.id() != to.id() => {
+				self.asm.mov_to_xmm(from);
+				// cvtss2sd/cvtsd2ss xmm0, xmm0
+				self.asm.sse(float_prefix(from), false, 0x5A, 0xC0);
+				self.asm.mov_from_xmm(to);
+			},
+			_ => {},
+		}
+		self.asm.push(reg::RAX);
+	}
+
+	fn call(&mut self, dispatch: Dispatch, args: &[TypeDesc], ret: TypeDesc, depth: usize) -> Result<usize, TranspileError> {
+		let registers = assign_registers(args)?;
+		let skip = matches!(dispatch, Dispatch::Trait(_)) as usize;
+		for (arg, regs) in args.iter().zip(&registers).skip(skip).rev() {
+			self.pop(*arg, regs);
+		}
+		if skip == 1 {
+			self.pop(TypeDesc::new(TypeFlags::DYN, None), &[reg::RDI, reg::R11]);
+		}
+
+		let word = TypeDesc::new(TypeFlags::I64, None);
+		let pc = match dispatch {
+			Dispatch::Static(code) => {
+				self.asm.mov_imm(reg::RAX, code as u64);
+				self.call_indirect(reg::RAX, 0, depth)
+			},
+			Dispatch::Virtual(slot) => {
+				self.null_check(reg::RDI, depth);
+				// load the class, then its vtable, then the method's code pointer
+				self.asm.load(reg::RAX, reg::RDI, 0, word);
+				self.asm.load(reg::RAX, reg::RAX, 0, word);
+				self.asm.load(reg::RAX, reg::RAX, 8 * slot as i32, word);
+				self.call_indirect(reg::RAX, 0, depth)
+			},
+			Dispatch::Trait(slot) => {
+				// a null `dyn` has a null vtable too
+				self.null_check(reg::RDI, depth);
+				self.asm.load(reg::RAX, reg::R11, 8 * slot as i32, word);
+				self.call_indirect(reg::RAX, 0, depth)
+			},
+		};
+		self.push(ret, &[reg::RAX, reg::RDX]);
+		Ok(pc)
+	}
+
+	fn unwind_if_trapped(&mut self, pending: usize) {
+		self.asm.mov_imm(reg::R11, pending as u64);
+		// cmp qword [r11], 0; je +2
+		self.asm.emit(&[0x49, 0x83, 0x3B, 0x00, 0x74, 0x02]);
+		self.asm.epilogue();
+	}
+
+	fn helper(&mut self, addr: usize, operands: usize, consts: &[u64], frame: bool, ret: TypeDesc, depth: usize) -> usize {
+		for register in reg::ARGS[..operands].iter().rev() {
+			self.asm.pop(*register);
+		}
+		for (register, value) in reg::ARGS[operands..].iter().zip(consts) {
+			self.asm.mov_imm(*register, *value);
+		}
+		let n = operands + consts.len();
+		let pc = self.call(addr, frame.then(|| (reg::ARGS[n], reg::ARGS[n + 1])), depth);
+		self.push(ret, &[reg::RAX]);
+		pc
+	}
+
+	fn get_field(&mut self, offset: i32, ty: TypeDesc, depth: usize) {
+		self.asm.pop(reg::RCX);
+		self.null_check(reg::RCX, depth);
+		self.asm.load(reg::RAX, reg::RCX, offset, ty);
+		if ty.slots() == 2 {
+			self.asm.load(reg::RDX, reg::RCX, offset + 8, TypeDesc::new(TypeFlags::I64, None));
+		}
+		self.push(ty, &[reg::RAX, reg::RDX]);
+	}
+
+	fn set_field(&mut self, offset: i32, ty: TypeDesc, depth: usize) {
+		self.pop(ty, &[reg::RCX, reg::RDX]);
+		self.asm.pop(reg::RAX);
+		self.null_check(reg::RAX, depth);
+		if ty.slots() == 2 {
+			self.asm.store(reg::RAX, offset, reg::RCX, 8);
+			self.asm.store(reg::RAX, offset + 8, reg::RDX, 8);
+		} else {
+			self.asm.store(reg::RAX, offset, reg::RCX, types::size_of(ty.flags));
+		}
+	}
+
+	fn load_elem(&mut self, elem: TypeDesc, depth: usize) {
+		self.asm.pop(reg::RCX);
+		self.asm.pop(reg::RAX);
+		self.null_check(reg::RAX, depth);
+		self.element(elem, depth);
+		if elem.slots() == 2 {
+			self.asm.load(reg::RDX, reg::RAX, Array::DATA_OFFSET as i32 + 8, TypeDesc::new(TypeFlags::I64, None));
+		}
+		self.asm.load(reg::RAX, reg::RAX, Array::DATA_OFFSET as i32, elem);
+		self.push(elem, &[reg::RAX, reg::RDX]);
+	}
+
+	fn store_elem(&mut self, elem: TypeDesc, depth: usize) {
+		self.pop(elem, &[reg::RDX, reg::R8]);
+		self.asm.pop(reg::RCX);
+		self.asm.pop(reg::RAX);
+		self.null_check(reg::RAX, depth);
+		self.element(elem, depth);
+		if elem.slots() == 2 {
+			self.asm.store(reg::RAX, Array::DATA_OFFSET as i32, reg::RDX, 8);
+			self.asm.store(reg::RAX, Array::DATA_OFFSET as i32 + 8, reg::R8, 8);
+		} else {
+			self.asm.store(reg::RAX, Array::DATA_OFFSET as i32, reg::RDX, types::size_of(elem.flags));
+		}
+	}
+
+	fn array_len(&mut self, depth: usize) {
+		self.asm.pop(reg::RAX);
+		self.null_check(reg::RAX, depth);
+		self.asm.load(reg::RAX, reg::RAX, 0, TypeDesc::new(TypeFlags::I64, None));
+		self.asm.push(reg::RAX);
+	}
+}
+
+/// The legacy prefix of scalar float instructions of type `ty`
+fn float_prefix(ty: TypeDesc) -> u8 {
+	if ty.id() == TypeFlags::F32 { 0xF3 } else { 0xF2 }
+}
+
+/// Assigns argument registers (System V) to each argument
+fn assign_registers(args: &[TypeDesc]) -> Result<Vec<Vec<u8>>, TranspileError> {
+	let slots = args.iter().map(TypeDesc::slots).sum::<usize>();
+	if slots > Signature::MAX_ARG_SLOTS {
+		return Err(TranspileError::TooManyArgs(args.len()))
+	}
+
+	let mut next = 0;
+	Ok(args.iter().map(|arg| {
+		let regs = reg::ARGS[next..next + arg.slots()].to_vec();
+		next += arg.slots();
+		regs
+	}).collect())
+}
@@ -1,19 +1,22 @@
-#[derive(Debug)]
+use std::sync::OnceLock;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PlatformKind {
 	Arch(CpuArch),
 	Os(OperatingSystem),
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CpuArch {
 	Unknown,
 	X86,
 	X86_64,
 	ARMv8,
 	Aarch64,
+	RiscV64,
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum OperatingSystem {
 	Unknown,
 	Unix,
@@ -22,3 +25,105 @@ pub enum OperatingSystem {
 	Windows,
 	BSD,
 }
+
+pub type CpuFeatures = u32;
+
+/// Optional instruction set extensions of the CPU, which the JIT may use
+pub trait CpuFeature {
+	/// AVX2, and with it the VEX encodings of scalar float instructions
+	const AVX2: u32 = 0x0001;
+	/// Every feature this VM knows about
+	const ALL: u32  = 0x0001;
+}
+
+impl CpuFeature for CpuFeatures {}
+
+/// The host a VM runs on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Platform {
+	pub arch: CpuArch,
+	pub os: OperatingSystem,
+	/// The [`CpuFeature`]s of the CPU
+	pub features: CpuFeatures,
+}
+
+impl Platform {
+	/// The platform this process runs on, whose CPU features are only probed once
+	pub fn current() -> Self {
+		static CURRENT: OnceLock<Platform> = OnceLock::new();
+		*CURRENT.get_or_init(|| Platform {
+			arch: current_arch(),
+			os: current_os(),
+			features: detect_features(),
+		})
+	}
+
+	/// The platform without the features that aren't in `features`, so code generated for it runs
+	/// on CPUs that only have those<br>
+	/// ***Note:** Features can only be removed, a CPU doesn't gain any.*
+	pub fn with_features(self, features: CpuFeatures) -> Self {
+		Platform {
+			features: self.features & features,
+			..self
+		}
+	}
+
+	pub fn has(&self, feature: CpuFeatures) -> bool {
+		self.features & feature == feature
+	}
+}
+
+fn current_arch() -> CpuArch {
+	if cfg!(target_arch = "x86_64") {
+		CpuArch::X86_64
+	} else if cfg!(target_arch = "x86") {
+		CpuArch::X86
+	} else if cfg!(target_arch = "aarch64") {
+		CpuArch::Aarch64
+	} else if cfg!(target_arch = "arm") {
+		CpuArch::ARMv8
+	} else if cfg!(target_arch = "riscv64") {
+		CpuArch::RiscV64
+	} else {
+		CpuArch::Unknown
+	}
+}
+
+fn current_os() -> OperatingSystem {
+	if cfg!(target_os = "linux") {
+		OperatingSystem::Linux
+	} else if cfg!(target_os = "macos") {
+		OperatingSystem::MacOS
+	} else if cfg!(windows) {
+		OperatingSystem::Windows
+	} else if cfg!(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly")) {
+		OperatingSystem::BSD
+	} else if cfg!(unix) {
+		OperatingSystem::Unix
+	} else {
+		OperatingSystem::Unknown
+	}
+}
+
+/// Probes the CPU features with `cpuid`<br>
+/// AVX2 also needs the OS to save the upper halves of the vector registers, which `xgetbv` reports.
+#[cfg(target_arch = "x86_64")]
+fn detect_features() -> CpuFeatures {
+	use std::arch::x86_64::{__cpuid, __cpuid_count, _xgetbv};
+
+	// every x86-64 CPU has `cpuid`
+	let max = __cpuid(0).eax;
+	let mut features = 0;
+	// OSXSAVE and AVX, then the XMM and YMM state in XCR0
+	let basic = __cpuid(1);
+	let avx = basic.ecx & (1 << 27 | 1 << 28) == 1 << 27 | 1 << 28 && unsafe { _xgetbv(0) } & 0b110 == 0b110;
+	if avx && max >= 7 && __cpuid_count(7, 0).ebx & 1 << 5 != 0 {
+		features |= CpuFeatures::AVX2;
+	}
+	features
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn detect_features() -> CpuFeatures {
+	0
+}
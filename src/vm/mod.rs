@@ -1,6 +1,486 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ptr::null;
+use crate::vm::bin::{DynamicLibrary, Executable};
+use crate::vm::bin::def::symbol::SymbolKind;
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::bin::def::function::FunctionDef;
+use crate::vm::error::interp::Trap;
+use crate::vm::error::vm::VmError;
+use crate::vm::heap::{Heap, HeapStats};
+use crate::vm::insn::Insn;
+use crate::vm::interp::Interpreter;
+use crate::vm::jit::{native, raise, Context, Traps};
+use crate::vm::meta::platform::{CpuFeatures, Platform};
+use crate::vm::types::TypeDesc;
+use crate::vm::types::array::Array;
+use crate::vm::types::function;
+use crate::vm::types::str::Str;
+use crate::vm::types::function::{FunctionSlot, Functions, NativeFn, Signature};
+use crate::vm::types::object::{Classes, ObjectHeader};
+
 pub mod bin;
 pub mod error;
 pub mod meta;
 pub mod alloc;
 pub mod types;
 pub mod jit;
+pub mod insn;
+pub mod heap;
+pub mod intrinsics;
+pub mod verify;
+pub mod interp;
+
+/// How a VM executes functions
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+	/// Compile every function to native code when it's loaded
+	#[default]
+	Jit,
+	/// Interpret bytecode (see [`Interpreter`])
+	Interpreter,
+}
+
+/// Where the definition of a function is in the executables of a VM
+#[derive(Copy, Clone, Debug)]
+struct DefLocation {
+	exec: usize,
+	/// The index of the class in the class table, if the function is a method
+	class: Option<usize>,
+	/// The index of the function in its function table
+	index: usize,
+}
+
+/// How many of the functions (and methods) of the loaded executables have been materialized, that
+/// is verified (and with the JIT engine, compiled)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadStats {
+	/// The number of functions with a body
+	pub functions: usize,
+	/// The number of functions that have been materialized
+	pub materialized: usize,
+}
+
+/// The E# virtual machine
+#[derive(Debug)]
+pub struct Vm {
+	engine: Engine,
+	/// The host, whose CPU features the JIT may use
+	platform: Platform,
+	executables: Vec<Executable>,
+	definitions: HashMap<String, DefLocation>,
+	classes: Classes,
+	functions: Functions,
+	/// Boxed so compiled code can refer to it while the VM moves
+	heap: Box<Heap>,
+	/// Boxed for the same reason as the heap
+	traps: Box<Traps>,
+	/// The classes and functions exported by loaded libraries
+	exports: HashMap<String, SymbolKind>,
+	/// The classes and functions of loaded libraries that aren't exported, keyed by the index of their library
+	hidden: HashMap<String, usize>,
+	/// Whether functions are materialized when they're first requested instead of when they're loaded
+	lazy: bool,
+	/// The functions with a body that haven't been materialized yet
+	pending: RefCell<HashSet<String>>,
+	load_stats: Cell<LoadStats>,
+}
+
+impl Vm {
+	pub fn new() -> Self {
+		Self::with_engine(Engine::default())
+	}
+
+	pub fn with_engine(engine: Engine) -> Self {
+		function::init_page_size();
+		Vm {
+			engine,
+			platform: Platform::current(),
+			executables: Vec::new(),
+			definitions: HashMap::new(),
+			classes: Classes::new(),
+			functions: Functions::new(),
+			heap: Box::new(Heap::new()),
+			traps: Box::new(Traps::new()),
+			exports: HashMap::new(),
+			hidden: HashMap::new(),
+			lazy: false,
+			pending: RefCell::new(HashSet::new()),
+			load_stats: Cell::new(LoadStats::default()),
+		}
+	}
+
+	pub fn engine(&self) -> Engine {
+		self.engine
+	}
+
+	pub fn platform(&self) -> Platform {
+		self.platform
+	}
+
+	/// Restricts the CPU features the JIT may use to `features` (see [`Platform::with_features`]),
+	/// so the code compiled from then on also runs on CPUs with fewer features
+	pub fn set_cpu_features(&mut self, features: CpuFeatures) {
+		self.platform = Platform::current().with_features(features);
+	}
+
+	/// Makes the VM materialize (verify and compile) functions the first time they're called (see
+	/// [`Vm::materialize`]) rather than when they're loaded, so loading a large executable only costs
+	/// parsing its tables, which borrow its code rather than copying it<br>
+	/// Until a function is materialized, compiled code calls a stub in its place, which materializes it.<br>
+	/// ***Note:** Functions that fail to verify are only reported once they're called, while
+	/// malformed tables are still reported by [`Executable::parse`].*
+	pub fn set_lazy(&mut self, lazy: bool) {
+		self.lazy = lazy;
+	}
+
+	pub fn is_lazy(&self) -> bool {
+		self.lazy
+	}
+
+	pub fn load_stats(&self) -> LoadStats {
+		self.load_stats.get()
+	}
+
+	/// Loads an executable, computing the layouts of its classes, and verifying (and with the JIT
+	/// engine, compiling) its functions and methods (unless the VM [is lazy](Vm::set_lazy))<br>
+	/// Functions may call functions and methods of previously loaded executables, and the classes and
+	/// functions exported by loaded libraries. Every import must be exported by a loaded library.
+	pub fn load(&mut self, exec: Executable) -> Result<(), VmError> {
+		self.resolve_imports(&exec)?;
+		self.load_definitions(exec)
+	}
+
+	/// Loads a library like an executable (see [`Vm::load`]), after which only the classes and
+	/// functions it exports can be used by other executables<br>
+	/// ***Note:** The methods of an exported class are exported with it.*
+	pub fn load_library(&mut self, lib: DynamicLibrary) -> Result<(), VmError> {
+		let exec = lib.into_executable();
+		self.resolve_imports(&exec)?;
+		let constants = exec.constant_table();
+		let mut exports = HashMap::new();
+		for export in exec.export_table().symbols() {
+			let name = constants.str(export.name()).ok_or(VmError::InvalidName(export.name()))?;
+			let defined = match export.kind() {
+				SymbolKind::Class => exec.class_table().find(constants, name).is_some(),
+				SymbolKind::Function => exec.function_table().find(constants, name).is_some(),
+			};
+			if !defined {
+				return Err(VmError::UnresolvedExport(name.to_string()))
+			}
+			exports.insert(name.to_string(), export.kind());
+		}
+
+		let lib_index = self.executables.len();
+		let mut hidden = HashSet::new();
+		for class in exec.class_table().classes() {
+			let name = constants.str(class.name()).ok_or(VmError::InvalidName(class.name()))?;
+			if exports.get(name) == Some(&SymbolKind::Class) {
+				continue
+			}
+			hidden.insert(name.to_string());
+			let methods = class.function_table().map_or(&[][..], |table| table.functions());
+			hidden.extend(methods.iter().filter_map(|method| constants.str(method.name())).map(String::from));
+		}
+		for function in exec.function_table().functions() {
+			let name = constants.str(function.name()).ok_or(VmError::InvalidName(function.name()))?;
+			if exports.get(name) != Some(&SymbolKind::Function) {
+				hidden.insert(name.to_string());
+			}
+		}
+
+		self.load_definitions(exec)?;
+		self.exports.extend(exports);
+		self.hidden.extend(hidden.into_iter().map(|name| (name, lib_index)));
+		Ok(())
+	}
+
+	fn resolve_imports(&self, exec: &Executable) -> Result<(), VmError> {
+		let constants = exec.constant_table();
+		for import in exec.import_table().symbols() {
+			let name = constants.str(import.name()).ok_or(VmError::InvalidName(import.name()))?;
+			if self.exports.get(name) != Some(&import.kind()) {
+				return Err(VmError::UnresolvedImport(name.to_string()))
+			}
+		}
+		Ok(())
+	}
+
+	/// Defines the classes and functions of an executable, and materializes them unless the VM is
+	/// lazy<br>
+	/// Nothing of the executable stays defined if any of it fails to load.
+	fn load_definitions(&mut self, exec: Executable) -> Result<(), VmError> {
+		let constants = exec.constant_table();
+		// methods are declared under their fully-qualified fn-id, like any other function
+		let exec_index = self.executables.len();
+		let functions = exec.function_table().functions().iter().enumerate()
+			.map(|(index, def)| (DefLocation { exec: exec_index, class: None, index }, def));
+		let methods = exec.class_table().classes().iter().enumerate()
+			.filter_map(|(class, def)| def.function_table().map(|table| (class, table)))
+			.flat_map(|(class, table)| table.functions().iter().enumerate()
+				.map(move |(index, def)| (DefLocation { exec: exec_index, class: Some(class), index }, def)));
+		let defs = functions.chain(methods)
+			.map(|(location, def)| constants.str(def.name()).map(|name| (name, location, def)).ok_or(VmError::InvalidName(def.name())))
+			.collect::<Result<Vec<(&str, DefLocation, &FunctionDef)>, _>>()?;
+		let mut declared = HashSet::new();
+		if let Some((name, _, _)) = defs.iter().find(|(name, _, _)| self.functions.get(name).is_some() || !declared.insert(*name)) {
+			return Err(VmError::DuplicateFunction(name.to_string()))
+		}
+
+		self.classes.define(exec.class_table(), constants)?;
+		for (name, location, def) in &defs {
+			self.functions.declare(name, Signature::of(def, constants));
+			self.definitions.insert(name.to_string(), *location);
+		}
+		self.classes.link(&self.functions);
+		// functions without a body (such as trait methods) stay undefined
+		let names = defs.into_iter()
+			.filter(|(_, _, def)| !def.code().is_empty())
+			.map(|(name, _, _)| name.to_string())
+			.collect::<Vec<_>>();
+		self.executables.push(exec);
+
+		let mut stats = self.load_stats.get();
+		stats.functions += names.len();
+		self.load_stats.set(stats);
+		self.pending.get_mut().extend(names.iter().cloned());
+		let result = match self.lazy {
+			true => self.stub_all(&names, exec_index),
+			false => self.materialize_all(names),
+		};
+		if result.is_err() {
+			self.unload_last();
+		}
+		result
+	}
+
+	/// Points the code of the functions named `names`, of the executable at `exec`, to stubs that
+	/// materialize them when compiled code first calls them
+	fn stub_all(&self, names: &[String], exec: usize) -> Result<(), VmError> {
+		if self.engine != Engine::Jit {
+			return Ok(())
+		}
+		let ctx = self.context(exec);
+		for name in names {
+			let slot = self.functions.get(name).expect("Pending functions must be declared");
+			let consts = [slot as *const FunctionSlot as u64];
+			let compiled = jit::stub(slot.signature(), slot.code_ptr(), materialize_stub as *const () as usize, &consts, &ctx)
+				.map_err(|err| VmError::Transpile(name.clone(), err))?;
+			let args = slot.signature().args.iter().map(|ty| ty.flags).collect::<Vec<_>>();
+			let stub = NativeFn::new(name, &args, slot.signature().ret.flags, &compiled.code).expect("Failed to map and allocate function pages");
+			self.heap.register_stack_maps(unsafe { stub.code_raw() } as usize, &compiled.stack_maps);
+			self.functions.set_stub(name, stub);
+		}
+		Ok(())
+	}
+
+	/// Removes the classes and functions of the last loaded executable, none of which may be
+	/// materialized yet
+	fn unload_last(&mut self) {
+		let exec = self.executables.pop().expect("An executable must be loaded");
+		let constants = exec.constant_table();
+		for class in exec.class_table().classes() {
+			if let Some(name) = constants.str(class.name()) {
+				self.classes.remove(name);
+			}
+		}
+		let pending = self.pending.get_mut();
+		let mut stats = self.load_stats.get();
+		let exec_index = self.executables.len();
+		self.definitions.retain(|name, location| {
+			if location.exec != exec_index {
+				return true
+			}
+			self.functions.remove(name);
+			if pending.remove(name) {
+				stats.functions -= 1;
+			}
+			false
+		});
+		self.load_stats.set(stats);
+	}
+
+	/// Verifies (and with the JIT engine, compiles) the function (or method) named `name`, unless it
+	/// already is<br>
+	/// The functions it calls are materialized when they're first called. [`Vm::call`] and
+	/// [`Vm::function`] materialize the function they're given.
+	pub fn materialize(&self, name: &str) -> Result<(), VmError> {
+		self.materialize_all(vec![name.to_string()])
+	}
+
+	/// Materializes the functions named `names` like [`Vm::materialize`], all of them or none
+	fn materialize_all(&self, names: Vec<String>) -> Result<(), VmError> {
+		let pending = self.pending.borrow();
+		let mut functions = Vec::new();
+		for name in names {
+			if !pending.contains(&name) {
+				continue
+			}
+			let location = self.definitions[&name];
+			let (constants, def) = self.definition(&name).expect("Pending functions must be defined");
+			let ctx = self.context(location.exec);
+			verify::verify(def.code(), &Signature::of(def, constants), &ctx).map_err(|err| VmError::Verify(name.clone(), err))?;
+			functions.push((name, location, def));
+		}
+		drop(pending);
+
+		if self.engine == Engine::Jit {
+			// every function is compiled before any is defined, so none is if one fails to compile
+			let compiled = functions.iter()
+				.map(|(name, location, def)| jit::transpile(def.code(), &def.arg_types(), &self.context(location.exec)).map_err(|err| VmError::Transpile(name.to_string(), err)))
+				.collect::<Result<Vec<_>, _>>()?;
+			for ((name, _, def), compiled) in functions.iter().zip(compiled) {
+				let native = NativeFn::new(name, def.args(), def.return_type(), &compiled.code).expect("Failed to map and allocate function pages");
+				self.heap.register_stack_maps(unsafe { native.code_raw() } as usize, &compiled.stack_maps);
+				self.functions.define(name, native);
+			}
+		}
+		let mut pending = self.pending.borrow_mut();
+		for (name, _, _) in &functions {
+			pending.remove(name);
+		}
+		let mut stats = self.load_stats.get();
+		stats.materialized += functions.len();
+		self.load_stats.set(stats);
+		Ok(())
+	}
+
+	/// The context that the code of the executable at `exec` is verified and compiled in
+	pub(crate) fn context(&self, exec: usize) -> Context<'_> {
+		Context {
+			constants: self.executables[exec].constant_table(),
+			classes: &self.classes,
+			functions: &self.functions,
+			heap: &self.heap,
+			traps: &self.traps,
+			hidden: &self.hidden,
+			exec,
+			platform: self.platform,
+		}
+	}
+
+	pub fn executables(&self) -> &Vec<Executable> {
+		&self.executables
+	}
+
+	pub fn classes(&self) -> &Classes {
+		&self.classes
+	}
+
+	pub fn functions(&self) -> &Functions {
+		&self.functions
+	}
+
+	/// Returns the bytecode definition of the function (or method) named `name`, and the constant
+	/// table its operands refer to
+	pub fn definition(&self, name: &str) -> Option<(&ConstantTable, &FunctionDef)> {
+		let location = self.definitions.get(name)?;
+		let exec = &self.executables[location.exec];
+		let table = match location.class {
+			Some(class) => exec.class_table().classes()[class].function_table()?,
+			None => exec.function_table(),
+		};
+		Some((exec.constant_table(), &table.functions()[location.index]))
+	}
+
+	/// Calls the function named `name` with the engine of the VM<br>
+	/// Arguments are passed as 64-bit words (see [`NativeFn::call`]), and the first word of the
+	/// return value is returned (`0` if the function returns nothing).<br>
+	/// A function that fails to materialize when it's first called returns the error it failed with.
+	pub fn call(&self, name: &str, args: &[u64]) -> Result<u64, VmError> {
+		let outer = CURRENT.with(|current| current.replace(self));
+		let result = self.call_current(name, args);
+		CURRENT.with(|current| current.set(outer));
+		result.map_err(|err| match err {
+			VmError::Trap(Trap::Materialize(err)) => *err,
+			err => err,
+		})
+	}
+
+	/// Calls a function like [`Vm::call`], while the VM is the one running on this thread
+	fn call_current(&self, name: &str, args: &[u64]) -> Result<u64, VmError> {
+		let slot = self.functions.get(name).ok_or_else(|| VmError::UnknownFunction(name.to_string()))?;
+		self.materialize(name)?;
+		let expected = slot.signature().args.iter().map(TypeDesc::slots).sum();
+		if args.len() != expected {
+			return Err(VmError::ArgumentCount(name.to_string(), expected, args.len()))
+		}
+		match self.engine {
+			Engine::Jit => {
+				let native = slot.native().ok_or_else(|| VmError::Trap(Trap::UndefinedFunction(Some(name.to_string()))))?;
+				let ret = self.traps.catch(|| unsafe { native.call(args) }).map_err(VmError::Trap)?;
+				// compiled code leaves the return register as it is when it returns nothing
+				Ok(if slot.signature().ret.slots() == 0 { 0 } else { ret })
+			},
+			Engine::Interpreter => Interpreter::new(self).call(name, args).map_err(VmError::Trap),
+		}
+	}
+
+	/// Returns the compiled function (or method) named `name`, `None` if it fails to materialize<br>
+	/// ***Note:** Calling it directly aborts the process if it traps, or if it calls a function of a
+	/// [lazy](Vm::set_lazy) VM that isn't materialized yet. [`Vm::call`] reports the trap instead.*
+	pub fn function(&self, name: &str) -> Option<&NativeFn> {
+		self.materialize(name).ok()?;
+		self.functions.get(name).and_then(|slot| slot.native())
+	}
+
+	pub fn heap(&self) -> &Heap {
+		&self.heap
+	}
+
+	/// Collects garbage, keeping only values reachable from roots (see [`Heap::root`])
+	pub fn gc(&self) {
+		self.heap.collect();
+	}
+
+	pub fn heap_stats(&self) -> HeapStats {
+		self.heap.stats()
+	}
+
+	/// Allocates an array of `len` zero-initialized elements of type `elem` on the heap<br>
+	/// ***Note:** The array must be [rooted](Heap::root) to survive a collection.*
+	pub fn new_array(&self, elem: TypeDesc, len: usize) -> *mut Array {
+		unsafe {
+			self.heap.alloc_array(elem, len)
+		}
+	}
+
+	/// Allocates a string with the contents of `value` on the heap<br>
+	/// ***Note:** The string must be [rooted](Heap::root) to survive a collection.*
+	pub fn new_str(&self, value: &str) -> *mut Str {
+		self.heap.alloc_str(&[value])
+	}
+
+	/// Allocates a zero-initialized object of the class named `name` on the heap<br>
+	/// ***Note:** The object must be [rooted](Heap::root) to survive a collection.*
+	pub fn instantiate(&self, name: &str) -> Option<*mut ObjectHeader> {
+		self.classes.get(name).map(|class| unsafe { self.heap.alloc_object(class) })
+	}
+}
+
+thread_local! {
+	/// The VM whose functions are called on this thread (see [`Vm::call`])
+	static CURRENT: Cell<*const Vm> = const { Cell::new(null()) };
+}
+
+native! {
+	/// Runtime helper for the stubs of functions that aren't materialized yet, which materializes the
+	/// function of `slot` with the VM running on this thread
+	fn materialize_stub(slot: *const FunctionSlot) {
+		let vm = CURRENT.with(Cell::get);
+		let name = unsafe { (*slot).name() };
+		if vm.is_null() {
+			raise(Trap::UndefinedFunction(Some(name.to_string())));
+			return
+		}
+		if let Err(err) = unsafe { (*vm).materialize(name) } {
+			raise(Trap::Materialize(Box::new(err)));
+		}
+	}
+}
+
+impl Default for Vm {
+	fn default() -> Self {
+		Self::new()
+	}
+}
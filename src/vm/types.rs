@@ -1,16 +1,41 @@
 pub mod array;
 pub mod function;
+pub mod object;
+pub mod str;
 
 use crate::vm::types;
 use crate::vm::types::array::Array;
 use crate::vm::types::function::NativeFn;
 
 pub type TypeFlags = u8;
-trait TypeFlag {
+
+/// Type modifier flags (the higher 4 bits of a [`TypeFlags`])<br>
+/// ***Note:** Flag index `0` is the most significant bit of the modifier.*
+pub trait TypeFlag {
 	const DATA_TYPE: u8 = 0b1000;
 	const UNSIGNED: u8  = 0b0100;
 }
 
+impl TypeFlag for TypeFlags {}
+
+/// Type IDs (the lower 4 bits of a [`TypeFlags`])
+pub trait TypeId {
+	const I8: u8       = 0x0;
+	const I16: u8      = 0x1;
+	const I32: u8      = 0x2;
+	const I64: u8      = 0x3;
+	const F32: u8      = 0x4;
+	const F64: u8      = 0x5;
+	const OBJECT: u8   = 0x6;
+	const FUNCTION: u8 = 0x7;
+	const ARRAY: u8    = 0x8;
+	const DYN: u8      = 0x9;
+	const STR: u8      = 0xA;
+	const VOID: u8     = 0xF;
+}
+
+impl TypeId for TypeFlags {}
+
 pub type Any = *mut ();
 pub type ConstantIndex = u16;
 
@@ -29,3 +54,121 @@ pub enum Type {
 	Dyn,
 	Void
 }
+
+/// A [`TypeFlags`] together with its operand (if the type ID has one)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeDesc {
+	pub flags: TypeFlags,
+	pub operand: Option<u16>,
+}
+
+impl TypeDesc {
+	pub const fn new(flags: TypeFlags, operand: Option<u16>) -> Self {
+		TypeDesc {
+			flags,
+			operand,
+		}
+	}
+
+	/// The type ID (lower 4 bits)
+	pub fn id(&self) -> u8 {
+		type_id(self.flags)
+	}
+
+	/// The type modifier (higher 4 bits)
+	pub fn modifier(&self) -> u8 {
+		type_modifier(self.flags)
+	}
+
+	pub fn is_unsigned(&self) -> bool {
+		self.modifier() & TypeFlags::UNSIGNED != 0
+	}
+
+	pub fn is_int(&self) -> bool {
+		self.id() <= TypeFlags::I64
+	}
+
+	pub fn is_float(&self) -> bool {
+		self.id() == TypeFlags::F32 || self.id() == TypeFlags::F64
+	}
+
+	/// Whether values of this type are references to heap data
+	pub fn is_ref(&self) -> bool {
+		matches!(self.id(), TypeFlags::OBJECT | TypeFlags::ARRAY | TypeFlags::DYN | TypeFlags::STR)
+	}
+
+	pub fn is_void(&self) -> bool {
+		self.id() == TypeFlags::VOID
+	}
+
+	/// Packs the type into an integer (as passed to runtime helpers)
+	pub fn to_bits(&self) -> u32 {
+		match self.operand {
+			Some(operand) => 1 << 24 | (operand as u32) << 8 | self.flags as u32,
+			None => self.flags as u32,
+		}
+	}
+
+	/// Unpacks a type packed by [`TypeDesc::to_bits`]
+	pub fn from_bits(bits: u32) -> Self {
+		let operand = if bits >> 24 != 0 { Some((bits >> 8) as u16) } else { None };
+		TypeDesc::new(bits as u8, operand)
+	}
+
+	/// The constant index of the name in the operand (of an object's class or a function), if there is one
+	pub fn constant(&self) -> Option<ConstantIndex> {
+		match self.id() {
+			TypeFlags::OBJECT | TypeFlags::FUNCTION => self.operand,
+			_ => None,
+		}
+	}
+
+	/// Replaces the constant index in the operand (see [`TypeDesc::constant`]) with `f(index)`
+	pub fn map_constant<E>(self, f: impl FnOnce(ConstantIndex) -> Result<ConstantIndex, E>) -> Result<TypeDesc, E> {
+		Ok(match self.constant() {
+			Some(index) => TypeDesc::new(self.flags, Some(f(index)?)),
+			None => self,
+		})
+	}
+
+	/// The number of 64-bit slots a value of this type occupies on the stack (a `dyn` is an
+	/// object pointer followed by a vtable pointer)
+	pub fn slots(&self) -> usize {
+		match self.id() {
+			TypeFlags::VOID => 0,
+			TypeFlags::DYN => 2,
+			_ => 1,
+		}
+	}
+}
+
+/// Returns the type ID of a [`TypeFlags`]
+pub fn type_id(type_flags: TypeFlags) -> u8 {
+	type_flags & 0x0F
+}
+
+/// Returns the type modifier of a [`TypeFlags`]
+pub fn type_modifier(type_flags: TypeFlags) -> u8 {
+	type_flags >> 4
+}
+
+/// Returns the length of the operand that follows a [`TypeFlags`] in a binary
+pub fn operand_len(type_flags: TypeFlags) -> usize {
+	match type_id(type_flags) {
+		TypeFlags::OBJECT | TypeFlags::FUNCTION => 2,
+		TypeFlags::ARRAY => 1,
+		_ => 0,
+	}
+}
+
+/// Returns the size (in bytes) of a value of the type when stored in an object
+pub fn size_of(type_flags: TypeFlags) -> usize {
+	match type_id(type_flags) {
+		TypeFlags::I8 => 1,
+		TypeFlags::I16 => 2,
+		TypeFlags::I32 | TypeFlags::F32 => 4,
+		TypeFlags::DYN => 16,
+		TypeFlags::VOID => 0,
+		_ => 8,
+	}
+}
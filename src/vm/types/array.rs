@@ -1,6 +1,98 @@
+use std::alloc::Layout;
+use std::mem;
+use std::slice;
+use crate::vm::types;
+use crate::vm::types::{TypeDesc, TypeFlags, TypeId};
+
+/// A primitive that can be viewed as an element of an [`Array`]
+pub trait Element: Copy {
+	/// The type ID of arrays whose elements can be viewed as `Self`
+	const TYPE_ID: u8;
+}
+
+impl Element for i8 { const TYPE_ID: u8 = TypeFlags::I8; }
+impl Element for u8 { const TYPE_ID: u8 = TypeFlags::I8; }
+impl Element for i16 { const TYPE_ID: u8 = TypeFlags::I16; }
+impl Element for u16 { const TYPE_ID: u8 = TypeFlags::I16; }
+impl Element for i32 { const TYPE_ID: u8 = TypeFlags::I32; }
+impl Element for u32 { const TYPE_ID: u8 = TypeFlags::I32; }
+impl Element for i64 { const TYPE_ID: u8 = TypeFlags::I64; }
+impl Element for u64 { const TYPE_ID: u8 = TypeFlags::I64; }
+impl Element for f32 { const TYPE_ID: u8 = TypeFlags::F32; }
+impl Element for f64 { const TYPE_ID: u8 = TypeFlags::F64; }
+
+/// The header at the start of every array<br>
+/// The elements of the array follow the header (at [`Array::DATA_OFFSET`]), each taking the size
+/// of its type in an object.
 #[derive(Debug)]
 #[repr(C)]
 pub struct Array {
-	start: *mut (),
 	len: usize,
+	elem: TypeDesc,
+}
+
+impl Array {
+	/// The offset of the first element from the start of the array
+	pub const DATA_OFFSET: usize = (mem::size_of::<Array>() + 7) & !7;
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// The type of the elements
+	pub fn elem(&self) -> TypeDesc {
+		self.elem
+	}
+
+	/// The size (in bytes) of an element
+	pub fn elem_size(&self) -> usize {
+		types::size_of(self.elem.flags)
+	}
+
+	/// Returns a pointer to the first element of the array
+	///
+	/// # Safety
+	/// `this` must point to a live array.
+	pub unsafe fn data_ptr(this: *mut Array) -> *mut u8 {
+		(this as *mut u8).add(Self::DATA_OFFSET)
+	}
+
+	/// Views the elements as `&[T]`, or returns `None` if they aren't of type `T`<br>
+	/// Arrays of primitives are stored unboxed, so this doesn't copy.
+	pub fn as_slice<T: Element>(&self) -> Option<&[T]> {
+		if self.elem.id() != T::TYPE_ID {
+			return None
+		}
+		unsafe {
+			Some(slice::from_raw_parts(Array::data_ptr(self as *const Array as *mut Array) as *const T, self.len))
+		}
+	}
+
+	/// Views the elements as `&mut [T]`, or returns `None` if they aren't of type `T`
+	pub fn as_mut_slice<T: Element>(&mut self) -> Option<&mut [T]> {
+		if self.elem.id() != T::TYPE_ID {
+			return None
+		}
+		unsafe {
+			Some(slice::from_raw_parts_mut(Array::data_ptr(self) as *mut T, self.len))
+		}
+	}
+
+	/// The memory layout of an array of `len` elements of type `elem`
+	pub fn layout(elem: TypeDesc, len: usize) -> Layout {
+		let size = Self::DATA_OFFSET + len * types::size_of(elem.flags);
+		Layout::from_size_align(size, mem::align_of::<Array>()).unwrap()
+	}
+
+	/// Writes the header of an array into zero-initialized memory
+	pub(crate) unsafe fn init(this: *mut Array, elem: TypeDesc, len: usize) {
+		this.write(Array {
+			len,
+			elem,
+		});
+	}
 }
@@ -1,3 +1,5 @@
+use std::cell::{Cell, OnceCell};
+use std::collections::HashMap;
 use std::ffi::CStr;
 use std::io::Error;
 use std::mem::transmute;
@@ -21,8 +23,12 @@ use winapi::{
 use std::ptr::{null, slice_from_raw_parts, slice_from_raw_parts_mut};
 use std::ptr::null_mut;
 use crate::page_align;
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::bin::def::function::FunctionDef;
 use crate::vm::error::*;
-use crate::vm::jit::transpile;
+use crate::vm::error::interp::Trap;
+use crate::vm::jit::{native, raise, Context, transpile};
+use crate::vm::types::{TypeDesc, TypeFlags};
 
 #[no_mangle]
 static mut PAGE_SIZE: usize = 0;
@@ -91,11 +97,8 @@ impl RawFn {
 		}
 	}
 
-	pub unsafe fn compile(mut self) -> Result<NativeFn, jit::TranspileError> {
+	pub unsafe fn compile(mut self, ctx: &Context) -> Result<NativeFn, jit::TranspileError> {
 		// convert to NativeFn and transpile into machine code
-		// allocate page
-		let mut page = NativeFn::alloc().expect("Failed to map and allocate function page");
-		
 		// prepare to iterate over bytes
 		let mut tail = self.tail;
 		let mut head = self.head;
@@ -109,7 +112,7 @@ impl RawFn {
 		// pull out identifier, parameters, and return type
 		let name: &str;
 		let args: Vec<u8>;
-		let ret: *mut u8;
+		let ret: u8;
 		
 		// iterate over bytes while moving head until we hit ";"
 		while *head as char != ';' {
@@ -136,7 +139,7 @@ impl RawFn {
 		// skip 0xFF
 		skip!(head);
 		// get ret (last byte)
-		ret = head;
+		ret = *head;
 		// skip over ret
 		skip!(head);
 		// reset tail
@@ -145,30 +148,16 @@ impl RawFn {
 		self.head = head;
 		self.tail = tail;
 		
-		// convert vectors to fixed size boxes
-		//  leak the boxes so we can manually drop them later
-		let name = Box::leak(Box::from(name));
-		let args = Box::leak(args.into_boxed_slice());
+		// the rest of the buffer is the bytecode
+		let code = slice_from_raw_parts(head, self.size - head.offset_from(self.addr) as usize).as_ref().unwrap();
+		let arg_types = args.iter().map(|arg| TypeDesc::new(*arg, None)).collect::<Vec<_>>();
 		
 		// transpile into machine code
-		let (code_size, size) = {
-			let x = transpile(self, page, page_size()).unwrap();
-			page = x.addr;
-			(x.size, page_align!(x.size))
-		};
+		let compiled = transpile(code, &arg_types, ctx)?;
 		
-		// construct native function from new page address and size
-		let native = NativeFn {
-			addr: page,
-			size,
-			code_size,
-			name,
-			// decompose args box
-			args: args.as_mut_ptr(),
-			args_size: args.len(),
-			// move return type
-			ret,
-		};
+		// construct native function from the machine code
+		let native = NativeFn::new(name, &args, ret, &compiled.code).expect("Failed to map and allocate function pages");
+		ctx.heap.register_stack_maps(native.addr as usize, &compiled.stack_maps);
 		Ok(native)
 	}
 }
@@ -184,7 +173,7 @@ pub struct NativeFn {
 	name: *mut str,
 	args: *mut u8,
 	args_size: usize,
-	ret: *mut u8,
+	ret: TypeFlags,
 }
 
 impl Function for NativeFn {
@@ -212,6 +201,63 @@ impl Drop for NativeFn {
 }
 
 impl NativeFn {
+	/// Maps executable pages for `code` and constructs a function from them
+	pub fn new(name: &str, args: &[TypeFlags], ret: TypeFlags, code: &[u8]) -> Result<NativeFn, Error> {
+		unsafe {
+			let size = page_align!(code.len().max(1));
+			let addr = NativeFn::alloc(size)?;
+			addr.copy_from_nonoverlapping(code.as_ptr(), code.len());
+			// the instruction cache isn't coherent with writes on AArch64 and RISC-V
+			#[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+			__clear_cache(addr as *mut c_char, addr.add(code.len()) as *mut c_char);
+			
+			// leak the boxes so we can manually drop them later
+			let name = Box::leak(Box::from(name));
+			let args: &mut [u8] = Box::leak(Box::from(args));
+			
+			let mut native = NativeFn {
+				addr,
+				size,
+				code_size: code.len(),
+				name,
+				// decompose args box
+				args: args.as_mut_ptr(),
+				args_size: args.len(),
+				ret,
+			};
+			native.exec()?;
+			Ok(native)
+		}
+	}
+	
+	/// Calls the function, passing every argument as a 64-bit integer (floats are passed as their bits)<br>
+	/// Traps abort the process unless the call is made within [`Traps::catch`](crate::vm::jit::Traps::catch).
+	///
+	/// # Safety
+	/// The arguments must match the function's argument types, and references must point to live values
+	/// of the VM that compiled the function.
+	pub unsafe fn call(&self, args: &[u64]) -> u64 {
+		native! { type F0 = fn() -> u64; }
+		native! { type F1 = fn(u64) -> u64; }
+		native! { type F2 = fn(u64, u64) -> u64; }
+		native! { type F3 = fn(u64, u64, u64) -> u64; }
+		native! { type F4 = fn(u64, u64, u64, u64) -> u64; }
+		native! { type F5 = fn(u64, u64, u64, u64, u64) -> u64; }
+		native! { type F6 = fn(u64, u64, u64, u64, u64, u64) -> u64; }
+		
+		let addr = self.addr as *const ();
+		match *args {
+			[] => transmute::<*const (), F0>(addr)(),
+			[a] => transmute::<*const (), F1>(addr)(a),
+			[a, b] => transmute::<*const (), F2>(addr)(a, b),
+			[a, b, c] => transmute::<*const (), F3>(addr)(a, b, c),
+			[a, b, c, d] => transmute::<*const (), F4>(addr)(a, b, c, d),
+			[a, b, c, d, e] => transmute::<*const (), F5>(addr)(a, b, c, d, e),
+			[a, b, c, d, e, f] => transmute::<*const (), F6>(addr)(a, b, c, d, e, f),
+			_ => panic!("Native functions take at most 6 arguments"),
+		}
+	}
+	
 	pub fn name(&self) -> &str {
 		unsafe {
 			// Get function name as reference.
@@ -263,10 +309,7 @@ impl NativeFn {
 	}
 
 	pub fn ret(&self) -> u8 {
-		unsafe {
-			// dereference and return the return type byte
-			*self.ret
-		}
+		self.ret
 	}
 
 	/// Marks the function as read-only and executable
@@ -285,9 +328,9 @@ impl NativeFn {
 	/// Maps and allocates read/write access memory (per-page)<br>
 	/// **Warning**: This function must be called before reading from or writing to the function's code or marking it as executable!
 	#[cfg(target_os = "linux")]
-	pub unsafe fn alloc() -> Result<*mut u8, Error> {
-		// request a page of memory (only self.size is zero-initialized and usable)
-		let ptr = libc::mmap64(null_mut(), page_size(), libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, 0, 0);
+	pub unsafe fn alloc(size: usize) -> Result<*mut u8, Error> {
+		// request pages of memory (size must be page aligned)
+		let ptr = libc::mmap64(null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, 0, 0);
 
 		if ptr == libc::MAP_FAILED {
 			Err(Error::last_os_error())
@@ -345,3 +388,152 @@ impl NativeFn {
 		}
 	}
 }
+
+/// The argument and return types of a function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+	pub args: Vec<TypeDesc>,
+	pub ret: TypeDesc,
+	/// The class names of the object arguments (`None` for arguments of other types)<br>
+	/// The operands of the types refer to the constant table of the function's executable, so the
+	/// names are resolved along with the signature.
+	pub arg_classes: Vec<Option<String>>,
+	/// The class name of an object return value
+	pub ret_class: Option<String>,
+}
+
+impl Signature {
+	/// The number of slots the arguments of a function may take at most (see [`TypeDesc::slots`]),
+	/// as many as every JIT backend passes in registers
+	pub const MAX_ARG_SLOTS: usize = 6;
+	
+	/// The signature of a function, whose operands refer to `constants`
+	pub fn of(def: &FunctionDef, constants: &ConstantTable) -> Self {
+		let class = |ty: &TypeDesc| ty.constant().and_then(|index| constants.str(index)).map(String::from);
+		let args = def.arg_types();
+		let ret = def.return_type_desc();
+		Signature {
+			arg_classes: args.iter().map(class).collect(),
+			ret_class: class(&ret),
+			args,
+			ret,
+		}
+	}
+	
+	/// The number of slots the arguments take
+	pub fn arg_slots(&self) -> usize {
+		self.args.iter().map(TypeDesc::slots).sum()
+	}
+}
+
+/// A function known to the VM, and the address of its code
+#[derive(Debug)]
+pub struct FunctionSlot {
+	name: String,
+	/// The address of the function's code<br>
+	/// Compiled code calls through a pointer to this cell, so it can be set after the caller is compiled.
+	code: Cell<usize>,
+	signature: Signature,
+	native: OnceCell<NativeFn>,
+	/// The code that materializes the function when it's first called, until it's defined
+	stub: OnceCell<NativeFn>,
+}
+
+impl FunctionSlot {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn signature(&self) -> &Signature {
+		&self.signature
+	}
+
+	pub fn native(&self) -> Option<&NativeFn> {
+		self.native.get()
+	}
+
+	/// A pointer to the address of the function's code
+	pub fn code_ptr(&self) -> *const usize {
+		// Cell<usize> has the same memory layout as usize
+		&self.code as *const Cell<usize> as *const usize
+	}
+}
+
+/// The registry of functions (and methods), keyed by fully-qualified name
+#[derive(Debug, Default)]
+pub struct Functions {
+	slots: HashMap<String, Box<FunctionSlot>>,
+}
+
+impl Functions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn get(&self, name: &str) -> Option<&FunctionSlot> {
+		self.slots.get(name).map(Box::as_ref)
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.slots.keys().map(String::as_str)
+	}
+
+	/// Declares a function, returning `false` if a function with the same name was already declared<br>
+	/// Until [`Functions::define`] is called, calling the function traps.
+	pub fn declare(&mut self, name: &str, signature: Signature) -> bool {
+		if self.slots.contains_key(name) {
+			return false
+		}
+		self.slots.insert(name.to_string(), Box::new(FunctionSlot {
+			name: name.to_string(),
+			code: Cell::new(undefined_function as *const () as usize),
+			signature,
+			native: OnceCell::new(),
+			stub: OnceCell::new(),
+		}));
+		true
+	}
+
+	/// Removes a declared function, which no compiled code may call
+	pub(crate) fn remove(&mut self, name: &str) {
+		self.slots.remove(name);
+	}
+
+	/// Sets the code of a declared function that isn't defined yet to `stub`, which is called in its
+	/// place until it's defined
+	pub fn set_stub(&self, name: &str, stub: NativeFn) {
+		let slot = self.slots.get(name).expect("Function must be declared before it is stubbed");
+		if slot.native.get().is_none() {
+			slot.code.set(stub.addr as usize);
+		}
+		if slot.stub.set(stub).is_err() {
+			panic!("Function {} is already stubbed", name);
+		}
+	}
+
+	/// Sets the code of a declared function<br>
+	/// ***Note:** Compiled code reads the address on every call, so a function can be defined after its
+	/// callers are compiled.*
+	pub fn define(&self, name: &str, native: NativeFn) {
+		let slot = self.slots.get(name).expect("Function must be declared before it is defined");
+		slot.code.set(native.addr as usize);
+		if slot.native.set(native).is_err() {
+			panic!("Function {} is already defined", name);
+		}
+	}
+}
+
+#[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+extern "C" {
+	/// Synchronizes the instruction cache with the data cache (from compiler-rt/libgcc)
+	fn __clear_cache(start: *mut c_char, end: *mut c_char);
+}
+
+native! {
+	/// The code of functions that are declared but not defined (such as trait methods without a body),
+	/// which traps
+	fn undefined_function() -> u64 {
+		raise(Trap::UndefinedFunction(None));
+		0
+	}
+}
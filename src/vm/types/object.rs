@@ -0,0 +1,382 @@
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::ptr::null;
+use crate::vm::bin::def::class::{ClassDef, ClassTable};
+use crate::vm::bin::def::constant::ConstantTable;
+use crate::vm::error::object::LayoutError;
+use crate::vm::types;
+use crate::vm::types::TypeDesc;
+use crate::vm::types::function::{Functions, FunctionSlot, Signature};
+
+/// The header at the start of every object<br>
+/// The fields of the object follow the header, at the offsets described by its [`ClassLayout`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct ObjectHeader {
+	class: *const ClassLayout,
+}
+
+impl ObjectHeader {
+	pub const SIZE: usize = mem::size_of::<ObjectHeader>();
+
+	/// The layout of the object's class
+	pub fn class(&self) -> &ClassLayout {
+		unsafe {
+			// Class layouts are boxed and never removed from their registry, so they outlive
+			//  every object that points to them.
+			self.class.as_ref().unwrap()
+		}
+	}
+
+	/// Writes the header of an object of `class` into zero-initialized memory
+	pub(crate) unsafe fn init(this: *mut ObjectHeader, class: &ClassLayout) {
+		this.write(ObjectHeader {
+			class,
+		});
+	}
+
+	/// Returns a pointer to `field` in the object
+	///
+	/// # Safety
+	/// `this` must point to a live object of the class of `field` or of one of its subclasses.
+	pub unsafe fn field_ptr(this: *mut ObjectHeader, field: &FieldLayout) -> *mut u8 {
+		(this as *mut u8).add(field.offset)
+	}
+}
+
+/// The location and type of a field in an object
+#[derive(Debug)]
+pub struct FieldLayout {
+	name: String,
+	type_desc: TypeDesc,
+	class: Option<String>,
+	offset: usize,
+	size: usize,
+}
+
+impl FieldLayout {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn type_desc(&self) -> TypeDesc {
+		self.type_desc
+	}
+
+	/// The name of the class of an object field
+	pub fn class(&self) -> Option<&str> {
+		self.class.as_deref()
+	}
+
+	/// The offset of the field from the start of the object (including the [`ObjectHeader`])
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	pub fn size(&self) -> usize {
+		self.size
+	}
+}
+
+/// A method in a class's virtual method table
+#[derive(Clone, Debug)]
+pub struct MethodLayout {
+	name: String,
+	function: String,
+	signature: Signature,
+}
+
+impl MethodLayout {
+	/// The method's simple name (the part of its `fn-id` after the last `.`)
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// The fully-qualified name of the function that implements the method
+	pub fn function(&self) -> &str {
+		&self.function
+	}
+
+	pub fn signature(&self) -> &Signature {
+		&self.signature
+	}
+}
+
+/// The memory layout of a class's objects (including inherited fields) and its methods
+#[repr(C)]
+#[derive(Debug)]
+pub struct ClassLayout {
+	/// The virtual method table; each entry points to the code pointer of a method's function<br>
+	/// ***Note:** This must stay the first field, compiled code loads it through an object's class pointer.*
+	vtable: *const *const usize,
+	slots: Box<[*const usize]>,
+	methods: Vec<MethodLayout>,
+	/// Trait vtables, keyed by trait name
+	traits: RefCell<HashMap<String, Box<[*const usize]>>>,
+	name: String,
+	super_name: Option<String>,
+	fields: Vec<FieldLayout>,
+	size: usize,
+	align: usize,
+}
+
+impl ClassLayout {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// The name of the supertype, or `None` if the class extends nothing
+	pub fn super_name(&self) -> Option<&str> {
+		self.super_name.as_deref()
+	}
+
+	/// All fields, inherited fields first
+	pub fn fields(&self) -> &Vec<FieldLayout> {
+		&self.fields
+	}
+
+	pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+		self.fields.iter().find(|field| field.name == name)
+	}
+
+	/// All methods in vtable order, inherited methods first
+	pub fn methods(&self) -> &Vec<MethodLayout> {
+		&self.methods
+	}
+
+	/// Returns the vtable slot and layout of the method with the simple name `name`
+	pub fn method(&self, name: &str) -> Option<(usize, &MethodLayout)> {
+		let name = simple_name(name);
+		self.methods.iter().enumerate().find(|(_, method)| method.name == name)
+	}
+
+	/// The virtual method table, or null if the class hasn't been linked
+	pub fn vtable(&self) -> *const *const usize {
+		self.vtable
+	}
+
+	/// Returns the vtable that dispatches the methods of `trait_class` to this class's methods,
+	/// or `None` if this class doesn't implement all of them<br>
+	/// The slots of a trait vtable are in the order of the trait's own vtable.
+	pub fn trait_vtable(&self, trait_class: &ClassLayout) -> Option<*const *const usize> {
+		if let Some(vtable) = self.traits.borrow().get(&trait_class.name) {
+			return Some(vtable.as_ptr())
+		}
+
+		let vtable = trait_class.methods.iter()
+			.map(|method| self.method(&method.name).map(|(slot, _)| self.slots[slot]))
+			.collect::<Option<Box<[_]>>>()?;
+		let ptr = vtable.as_ptr();
+		self.traits.borrow_mut().insert(trait_class.name.clone(), vtable);
+		Some(ptr)
+	}
+
+	/// Resolves the vtable entries to the code pointers of the implementing functions
+	fn link(&mut self, functions: &Functions) {
+		self.slots = self.methods.iter()
+			.map(|method| functions.get(&method.function).map_or(null(), FunctionSlot::code_ptr))
+			.collect();
+		self.vtable = self.slots.as_ptr();
+	}
+
+	/// The size of an object (including the [`ObjectHeader`])
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	pub fn align(&self) -> usize {
+		self.align
+	}
+
+	pub fn alloc_layout(&self) -> Layout {
+		Layout::from_size_align(self.size, self.align).unwrap()
+	}
+
+	/// Allocates a zero-initialized object of this class<br>
+	/// ***Note:** The object is owned by the caller and must be freed with [`ClassLayout::free`].*
+	///
+	/// # Safety
+	/// The layout must outlive the object, since the object's header points to it.
+	pub unsafe fn instantiate(&self) -> *mut ObjectHeader {
+		let obj = std::alloc::alloc_zeroed(self.alloc_layout()) as *mut ObjectHeader;
+		if obj.is_null() {
+			std::alloc::handle_alloc_error(self.alloc_layout())
+		}
+		ObjectHeader::init(obj, self);
+		obj
+	}
+
+	/// Frees an object created by [`ClassLayout::instantiate`]
+	///
+	/// # Safety
+	/// `obj` must have been created by [`ClassLayout::instantiate`], and must not be used afterwards.
+	pub unsafe fn free(obj: *mut ObjectHeader) {
+		let layout = (*obj).class().alloc_layout();
+		std::alloc::dealloc(obj as *mut u8, layout);
+	}
+
+	fn compute(name: String, super_layout: Option<&ClassLayout>, class: &ClassDef, constants: &ConstantTable) -> Result<ClassLayout, LayoutError> {
+		let (mut fields, mut methods, mut end, mut align) = match super_layout {
+			Some(super_layout) => {
+				let fields = super_layout.fields.iter().map(|field| FieldLayout {
+					name: field.name.clone(),
+					type_desc: field.type_desc,
+					class: field.class.clone(),
+					offset: field.offset,
+					size: field.size,
+				}).collect();
+				(fields, super_layout.methods.clone(), super_layout.size, super_layout.align)
+			},
+			None => (Vec::new(), Vec::new(), ObjectHeader::SIZE, mem::align_of::<ObjectHeader>()),
+		};
+
+		for field in class.field_table().map_or(&[][..], |table| table.fields()) {
+			let field_name = constants.str(field.name()).ok_or(LayoutError::InvalidName(field.name()))?;
+			if fields.iter().any(|other: &FieldLayout| other.name == field_name) {
+				return Err(LayoutError::DuplicateField(name, field_name.to_string()))
+			}
+
+			let size = types::size_of(field.type_flags());
+			// fields are aligned to their size, up to the word size
+			let field_align = size.clamp(1, mem::size_of::<usize>());
+			let offset = (end + field_align - 1) & !(field_align - 1);
+			let type_desc = TypeDesc::new(field.type_flags(), field.type_operand());
+			fields.push(FieldLayout {
+				name: field_name.to_string(),
+				type_desc,
+				class: type_desc.constant().and_then(|index| constants.str(index)).map(String::from),
+				offset,
+				size,
+			});
+			end = offset + size;
+			align = align.max(field_align);
+		}
+
+		for method in class.function_table().map_or(&[][..], |table| table.functions()) {
+			let function = constants.str(method.name()).ok_or(LayoutError::InvalidName(method.name()))?;
+			let layout = MethodLayout {
+				name: simple_name(function).to_string(),
+				function: function.to_string(),
+				signature: Signature::of(method, constants),
+			};
+			// a method with the same simple name as an inherited method overrides it
+			match methods.iter_mut().find(|inherited| inherited.name == layout.name) {
+				Some(inherited) => *inherited = layout,
+				None => methods.push(layout),
+			}
+		}
+
+		Ok(ClassLayout {
+			vtable: null(),
+			slots: Box::new([]),
+			methods,
+			traits: RefCell::new(HashMap::new()),
+			super_name: super_layout.map(|super_layout| super_layout.name.clone()),
+			name,
+			fields,
+			size: (end + align - 1) & !(align - 1),
+			align,
+		})
+	}
+}
+
+/// The registry of class layouts, keyed by fully-qualified class name
+#[derive(Debug, Default)]
+pub struct Classes {
+	layouts: HashMap<String, Box<ClassLayout>>,
+}
+
+impl Classes {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn get(&self, name: &str) -> Option<&ClassLayout> {
+		self.layouts.get(name).map(Box::as_ref)
+	}
+
+	pub fn layouts(&self) -> impl Iterator<Item = &ClassLayout> {
+		self.layouts.values().map(Box::as_ref)
+	}
+
+	/// Whether the class named `class` is `ancestor` or extends it (directly or not)
+	pub fn is_subclass(&self, class: &str, ancestor: &str) -> bool {
+		let mut class = self.get(class);
+		while let Some(layout) = class {
+			if layout.name == ancestor {
+				return true
+			}
+			class = layout.super_name().and_then(|super_name| self.get(super_name));
+		}
+		false
+	}
+
+	/// Links the vtables of every class that hasn't been linked yet
+	pub fn link(&mut self, functions: &Functions) {
+		for layout in self.layouts.values_mut() {
+			if layout.vtable.is_null() {
+				layout.link(functions);
+			}
+		}
+	}
+
+	/// Computes the layouts of every class in `classes`, or of none of them if one fails<br>
+	/// Supertypes may be defined in `classes` or by a previous call.
+	pub fn define(&mut self, classes: &ClassTable, constants: &ConstantTable) -> Result<(), LayoutError> {
+		let mut pending = HashMap::new();
+		for class in classes.classes() {
+			let name = constants.str(class.name()).ok_or(LayoutError::InvalidName(class.name()))?;
+			let super_name = constants.str(class.super_name()).ok_or(LayoutError::InvalidName(class.super_name()))?;
+			if self.layouts.contains_key(name) || pending.contains_key(name) {
+				return Err(LayoutError::DuplicateClass(name.to_string()))
+			}
+			// a class that extends nothing names itself as its supertype
+			let super_name = if super_name == name { None } else { Some(super_name) };
+			pending.insert(name, (super_name, class));
+		}
+
+		let names = pending.keys().copied().collect::<Vec<_>>();
+		let result = names.iter().try_for_each(|name| self.resolve(name, &pending, constants, &mut HashSet::new()));
+		if result.is_err() {
+			// none of the classes stays defined
+			for name in names {
+				self.layouts.remove(name);
+			}
+		}
+		result
+	}
+
+	/// Removes the layout of the class named `name`, which no other class may extend
+	pub(crate) fn remove(&mut self, name: &str) {
+		self.layouts.remove(name);
+	}
+
+	fn resolve(&mut self, name: &str, pending: &HashMap<&str, (Option<&str>, &ClassDef)>, constants: &ConstantTable, visiting: &mut HashSet<String>) -> Result<(), LayoutError> {
+		if self.layouts.contains_key(name) {
+			return Ok(())
+		}
+		if !visiting.insert(name.to_string()) {
+			return Err(LayoutError::CyclicInheritance(name.to_string()))
+		}
+
+		let (super_name, class) = pending[name];
+		if let Some(super_name) = super_name {
+			if pending.contains_key(super_name) {
+				self.resolve(super_name, pending, constants, visiting)?;
+			} else if !self.layouts.contains_key(super_name) {
+				return Err(LayoutError::UnknownSuper(name.to_string(), super_name.to_string()))
+			}
+		}
+
+		let layout = ClassLayout::compute(name.to_string(), super_name.and_then(|super_name| self.get(super_name)), class, constants)?;
+		self.layouts.insert(name.to_string(), Box::new(layout));
+		Ok(())
+	}
+}
+
+/// Returns the part of a fully-qualified name after the last `.`
+pub fn simple_name(name: &str) -> &str {
+	name.rsplit('.').next().unwrap_or(name)
+}
@@ -0,0 +1,76 @@
+use std::alloc::Layout;
+use std::fmt::{Debug, Display, Formatter};
+use std::mem;
+use std::slice;
+
+/// The header at the start of every string, followed by its UTF-8 bytes<br>
+/// ***Note:** Strings are immutable, every operation on them creates a new string.*
+#[repr(C)]
+pub struct Str {
+	len: usize,
+}
+
+impl Str {
+	/// The offset of the first byte from the start of the string
+	pub const DATA_OFFSET: usize = mem::size_of::<Str>();
+
+	/// The length of the string in bytes
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		unsafe {
+			slice::from_raw_parts((self as *const Str as *const u8).add(Self::DATA_OFFSET), self.len)
+		}
+	}
+
+	pub fn as_str(&self) -> &str {
+		unsafe {
+			// strings are only created from `&str`s, or by concatenating strings
+			std::str::from_utf8_unchecked(self.as_bytes())
+		}
+	}
+
+	/// The memory layout of a string of `len` bytes
+	pub fn layout(len: usize) -> Layout {
+		Layout::from_size_align(Self::DATA_OFFSET + len, mem::align_of::<Str>()).unwrap()
+	}
+
+	/// Writes a string of `parts` (in order) into memory laid out by [`Str::layout`]
+	pub(crate) unsafe fn init(this: *mut Str, parts: &[&str]) {
+		let len = parts.iter().map(|part| part.len()).sum();
+		this.write(Str {
+			len,
+		});
+		let mut data = (this as *mut u8).add(Self::DATA_OFFSET);
+		for part in parts {
+			data.copy_from_nonoverlapping(part.as_ptr(), part.len());
+			data = data.add(part.len());
+		}
+	}
+}
+
+impl PartialEq for Str {
+	fn eq(&self, other: &Self) -> bool {
+		self.as_bytes() == other.as_bytes()
+	}
+}
+
+impl Eq for Str {}
+
+impl Debug for Str {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		Debug::fmt(self.as_str(), f)
+	}
+}
+
+impl Display for Str {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
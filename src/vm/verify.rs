@@ -0,0 +1,326 @@
+use crate::vm::error::verify::VerifyError;
+use crate::vm::insn;
+use crate::vm::insn::Insn;
+use crate::vm::intrinsics;
+use crate::vm::jit::Context;
+use crate::vm::types;
+use crate::vm::types::{ConstantIndex, TypeDesc, TypeFlags, TypeId};
+use crate::vm::types::function::Signature;
+use crate::vm::types::object::{ClassLayout, FieldLayout, MethodLayout};
+
+/// Checks that the bytecode of a function is well-typed<br>
+/// Every instruction must find values of the types it operates on, every operand must resolve,
+/// and the bytecode must end by returning the function's return type. Integers of any width are
+/// interchangeable (all engines keep them extended to 64 bits), and an object can be used as an
+/// object of any of its superclasses. The element types of arrays must match exactly.<br>
+/// ***Note:** Executables record only the type id of the elements of an array type, so the class of
+/// the objects in an array that crosses a signature or a field is trusted.*
+pub fn verify(code: &[u8], signature: &Signature, ctx: &Context) -> Result<(), VerifyError> {
+	check(code, signature, ctx, |_, _| {})
+}
+
+/// Verifies bytecode like [`verify`], returning the types on the operand stack after each
+/// instruction (with the offset of the instruction)
+pub fn stack_types(code: &[u8], signature: &Signature, ctx: &Context) -> Result<Vec<(usize, Vec<TypeDesc>)>, VerifyError> {
+	let mut types = Vec::new();
+	check(code, signature, ctx, |offset, stack| types.push((offset, stack.iter().map(|value| value.ty).collect())))?;
+	Ok(types)
+}
+
+fn check<'a>(code: &[u8], signature: &'a Signature, ctx: &'a Context<'a>, mut verified: impl FnMut(usize, &[Value])) -> Result<(), VerifyError> {
+	if signature.arg_slots() > Signature::MAX_ARG_SLOTS {
+		return Err(VerifyError::TooManyArgs(signature.arg_slots()))
+	}
+	let insns = insn::decode_all(code).map_err(VerifyError::Decode)?;
+	let mut verifier = Verifier {
+		ctx,
+		stack: Vec::new(),
+		locals: Value::args(signature),
+		ret: Value::new(signature.ret, signature.ret_class.as_deref()),
+	};
+	for (offset, insn) in &insns {
+		verifier.verify(*insn, *offset)?;
+		verified(*offset, &verifier.stack);
+	}
+	match insns.last() {
+		Some((_, Insn::Ret | Insn::VRet(_))) => Ok(()),
+		_ => Err(VerifyError::MissingReturn),
+	}
+}
+
+/// Whether a value of type `found` can be used as a value of type `expected`, regardless of
+/// classes<br>
+/// An array type without an element type stands for arrays of any element type.
+pub fn is_assignable(expected: TypeDesc, found: TypeDesc) -> bool {
+	if expected.is_int() {
+		found.is_int()
+	} else if expected.id() == TypeFlags::ARRAY && expected.operand.is_some() {
+		found.id() == TypeFlags::ARRAY && found.operand == expected.operand
+	} else {
+		expected.id() == found.id()
+	}
+}
+
+/// The type of a value on the operand stack or in a local variable
+#[derive(Copy, Clone, Debug)]
+struct Value<'a> {
+	ty: TypeDesc,
+	/// The name of the class of an object, or of the objects in an array, if it's known
+	class: Option<&'a str>,
+}
+
+impl<'a> Value<'a> {
+	fn new(ty: TypeDesc, class: Option<&'a str>) -> Self {
+		Value { ty, class }
+	}
+
+	fn args(signature: &'a Signature) -> Vec<Self> {
+		signature.args.iter().zip(&signature.arg_classes)
+			.map(|(ty, class)| Value::new(*ty, class.as_deref()))
+			.collect()
+	}
+}
+
+struct Verifier<'a> {
+	ctx: &'a Context<'a>,
+	stack: Vec<Value<'a>>,
+	locals: Vec<Value<'a>>,
+	ret: Value<'a>,
+}
+
+impl<'a> Verifier<'a> {
+	fn pop(&mut self, expected: Value, offset: usize) -> Result<Value<'a>, VerifyError> {
+		let found = self.stack.pop().ok_or(VerifyError::StackUnderflow(offset))?;
+		self.check_assignable(expected, found, offset)?;
+		Ok(found)
+	}
+
+	/// Checks that `found` can be used as a value of type `expected`: an object must be of the
+	/// expected class or of one of its subclasses, and an array must hold objects of the same class
+	fn check_assignable(&self, expected: Value, found: Value, offset: usize) -> Result<(), VerifyError> {
+		if !is_assignable(expected.ty, found.ty) {
+			return Err(VerifyError::TypeMismatch(offset, expected.ty, found.ty))
+		}
+		let matches = match (expected.ty.id(), expected.class, found.class) {
+			(_, None, _) => true,
+			(TypeFlags::OBJECT, Some(expected), Some(found)) => self.ctx.classes.is_subclass(found, expected),
+			(TypeFlags::OBJECT, Some(_), None) => false,
+			// the elements are trusted to be of the expected class if the array's class isn't known
+			(_, Some(expected), found) => found.is_none_or(|found| found == expected),
+		};
+		if !matches {
+			let expected = expected.class.unwrap_or_default().to_string();
+			return Err(VerifyError::ClassMismatch(offset, expected, found.class.map(String::from)))
+		}
+		Ok(())
+	}
+
+	fn push(&mut self, value: Value<'a>) {
+		if !value.ty.is_void() {
+			self.stack.push(value);
+		}
+	}
+
+	fn pop_args(&mut self, args: &[TypeDesc], classes: &[Option<String>], offset: usize) -> Result<(), VerifyError> {
+		for (arg, class) in args.iter().zip(classes).rev() {
+			self.pop(Value::new(*arg, class.as_deref()), offset)?;
+		}
+		Ok(())
+	}
+
+	fn str(&self, index: ConstantIndex, offset: usize) -> Result<&'a str, VerifyError> {
+		self.ctx.constants.str(index).ok_or(VerifyError::UnresolvedConstant(offset, index))
+	}
+
+	fn class(&self, index: ConstantIndex, offset: usize) -> Result<&'a ClassLayout, VerifyError> {
+		let ctx = self.ctx;
+		let name = self.str(index, offset)?;
+		ctx.classes.get(name)
+			.filter(|_| !ctx.is_hidden(name))
+			.ok_or_else(|| VerifyError::UnknownClass(offset, name.to_string()))
+	}
+
+	fn field(&self, class: ConstantIndex, field: ConstantIndex, offset: usize) -> Result<(&'a ClassLayout, &'a FieldLayout), VerifyError> {
+		let class = self.class(class, offset)?;
+		let name = self.str(field, offset)?;
+		let field = class.field(name).ok_or_else(|| VerifyError::UnknownField(offset, class.name().to_string(), name.to_string()))?;
+		Ok((class, field))
+	}
+
+	fn method(&self, class: ConstantIndex, method: ConstantIndex, offset: usize) -> Result<(&'a ClassLayout, &'a MethodLayout), VerifyError> {
+		let class = self.class(class, offset)?;
+		let name = self.str(method, offset)?;
+		let method = class.method(name)
+			.map(|(_, method)| method)
+			.ok_or_else(|| VerifyError::UnknownMethod(offset, class.name().to_string(), name.to_string()))?;
+		Ok((class, method))
+	}
+
+	/// The type of values of the type `ty` (an operand of an instruction), whose class must be known
+	fn value(&self, ty: TypeDesc, offset: usize) -> Result<Value<'a>, VerifyError> {
+		let class = match ty.constant() {
+			Some(index) if ty.id() == TypeFlags::OBJECT => Some(self.class(index, offset)?.name()),
+			_ => None,
+		};
+		Ok(Value::new(ty, class))
+	}
+
+	/// An array of elements of the type `elem`
+	fn array(&self, elem: TypeDesc, offset: usize) -> Result<Value<'a>, VerifyError> {
+		let elem = self.value(elem, offset)?;
+		Ok(Value::new(TypeDesc::new(TypeFlags::ARRAY, Some(elem.ty.flags as u16)), elem.class))
+	}
+
+	fn numeric(ty: TypeDesc, offset: usize) -> Result<(), VerifyError> {
+		if ty.is_int() || ty.is_float() {
+			Ok(())
+		} else {
+			Err(VerifyError::InvalidType(offset, ty))
+		}
+	}
+
+	fn verify(&mut self, insn: Insn, offset: usize) -> Result<(), VerifyError> {
+		let object = Value::new(TypeDesc::new(TypeFlags::OBJECT, None), None);
+		let int = Value::new(TypeDesc::new(TypeFlags::I64, None), None);
+		match insn {
+			Insn::Nop => {},
+			Insn::Add(ty) | Insn::Sub(ty) | Insn::Mul(ty) | Insn::Div(ty) => {
+				Self::numeric(ty, offset)?;
+				self.pop(Value::new(ty, None), offset)?;
+				self.pop(Value::new(ty, None), offset)?;
+				self.push(Value::new(ty, None));
+			},
+			Insn::Inc(ty) | Insn::Dec(ty) => {
+				Self::numeric(ty, offset)?;
+				self.pop(Value::new(ty, None), offset)?;
+				self.push(Value::new(ty, None));
+			},
+			Insn::Push(ty, local) => {
+				let expected = self.value(ty, offset)?;
+				let found = *self.locals.get(local as usize).ok_or(VerifyError::UndefinedLocal(offset, local))?;
+				self.check_assignable(expected, found, offset)?;
+				self.push(found);
+			},
+			Insn::Pop => {
+				let value = self.stack.pop().ok_or(VerifyError::StackUnderflow(offset))?;
+				self.locals.push(value);
+			},
+			Insn::Cast(from, to) => {
+				Self::numeric(from, offset)?;
+				Self::numeric(to, offset)?;
+				self.pop(Value::new(from, None), offset)?;
+				self.push(Value::new(to, None));
+			},
+			Insn::Call(function) => {
+				let ctx = self.ctx;
+				let name = self.str(function, offset)?;
+				let function = ctx.functions.get(name).filter(|_| !ctx.is_hidden(name));
+				match (function, intrinsics::lookup(name)) {
+					(Some(function), _) => {
+						let signature = function.signature();
+						self.pop_args(&signature.args, &signature.arg_classes, offset)?;
+						self.push(Value::new(signature.ret, signature.ret_class.as_deref()));
+					},
+					(None, Some(intrinsic)) => {
+						self.pop_args(intrinsic.args, &vec![None; intrinsic.args.len()], offset)?;
+						self.push(Value::new(intrinsic.ret, None));
+					},
+					(None, None) => return Err(VerifyError::UnknownFunction(offset, name.to_string())),
+				}
+			},
+			Insn::CallVirt(class, method) => {
+				let (class, method) = self.method(class, method, offset)?;
+				let signature = method.signature();
+				if signature.args.is_empty() {
+					return Err(VerifyError::MissingReceiver(offset, method.function().to_string()))
+				}
+				self.pop_args(&signature.args[1..], &signature.arg_classes[1..], offset)?;
+				// the receiver may be of any subclass of the class named by the instruction
+				self.pop(Value::new(object.ty, Some(class.name())), offset)?;
+				self.push(Value::new(signature.ret, signature.ret_class.as_deref()));
+			},
+			Insn::CallTrait(class, method) => {
+				let (_, method) = self.method(class, method, offset)?;
+				let signature = method.signature();
+				if signature.args.is_empty() {
+					return Err(VerifyError::MissingReceiver(offset, method.function().to_string()))
+				}
+				// the receiver is a `dyn` instead of the trait's object
+				self.pop_args(&signature.args[1..], &signature.arg_classes[1..], offset)?;
+				self.pop(Value::new(TypeDesc::new(TypeFlags::DYN, None), None), offset)?;
+				self.push(Value::new(signature.ret, signature.ret_class.as_deref()));
+			},
+			Insn::Ret => {
+				if !self.ret.ty.is_void() {
+					return Err(VerifyError::ReturnMismatch(offset, self.ret.ty))
+				}
+			},
+			Insn::VRet(ty) => {
+				if self.ret.ty.is_void() || !is_assignable(self.ret.ty, ty) {
+					return Err(VerifyError::ReturnMismatch(offset, self.ret.ty))
+				}
+				let expected = self.value(ty, offset)?;
+				self.pop(expected, offset)?;
+				self.check_assignable(self.ret, expected, offset)?;
+			},
+			Insn::Ldc(index) => {
+				let constant = self.ctx.constants.get(index).ok_or(VerifyError::UnresolvedConstant(offset, index))?;
+				let ty = TypeDesc::new(constant.type_flags(), constant.type_operand());
+				let is_str = ty.id() == TypeFlags::STR
+					|| ty.id() == TypeFlags::ARRAY && ty.operand.is_some_and(|elem| types::type_id(elem as u8) == TypeFlags::I8);
+				if is_str && constant.str().is_some() {
+					self.push(Value::new(TypeDesc::new(TypeFlags::STR, None), None));
+				} else if constant.bits().is_some() {
+					self.push(Value::new(ty, None));
+				} else {
+					return Err(VerifyError::UnsupportedConstant(offset, index))
+				}
+			},
+			Insn::New(class) => {
+				let name = self.class(class, offset)?.name();
+				self.push(Value::new(TypeDesc::new(TypeFlags::OBJECT, Some(class)), Some(name)));
+			},
+			Insn::GetField(class, field) => {
+				let (class, field) = self.field(class, field, offset)?;
+				self.pop(Value::new(object.ty, Some(class.name())), offset)?;
+				self.push(Value::new(field.type_desc(), field.class()));
+			},
+			Insn::SetField(class, field) => {
+				let (class, field) = self.field(class, field, offset)?;
+				self.pop(Value::new(field.type_desc(), field.class()), offset)?;
+				self.pop(Value::new(object.ty, Some(class.name())), offset)?;
+			},
+			Insn::ToDyn(class) => {
+				self.class(class, offset)?;
+				self.pop(object, offset)?;
+				self.push(Value::new(TypeDesc::new(TypeFlags::DYN, None), None));
+			},
+			Insn::NewArray(elem) => {
+				if elem.is_void() {
+					return Err(VerifyError::InvalidType(offset, elem))
+				}
+				self.pop(int, offset)?;
+				let array = self.array(elem, offset)?;
+				self.push(array);
+			},
+			Insn::ALoad(elem) => {
+				let expected = self.array(elem, offset)?;
+				self.pop(int, offset)?;
+				let array = self.pop(expected, offset)?;
+				// an element of an array whose class isn't known is of the class named by the instruction
+				self.push(Value::new(elem, array.class.or(expected.class)));
+			},
+			Insn::AStore(elem) => {
+				let expected = self.array(elem, offset)?;
+				self.pop(Value::new(elem, expected.class), offset)?;
+				self.pop(int, offset)?;
+				self.pop(expected, offset)?;
+			},
+			Insn::ALen => {
+				self.pop(Value::new(TypeDesc::new(TypeFlags::ARRAY, None), None), offset)?;
+				self.push(int);
+			},
+		}
+		Ok(())
+	}
+}
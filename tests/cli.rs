@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// An executable with a function `#bar(u32, u32) -> u32` that adds its arguments
+const TEST_EXECUTABLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test.esbin");
+
+const PROGRAM: &str = "
+fn main(i64, i64) -> i64
+	push i64 0
+	push i64 1
+	add i64
+	vret i64
+end
+
+fn div(i64, i64) -> i64
+	push i64 0
+	push i64 1
+	div i64
+	vret i64
+end
+
+fn half(f64) -> f64
+	push f64 0
+	ldc f64 2.0
+	div f64
+	vret f64
+end
+
+fn nothing()
+	ret
+end
+";
+
+/// A fresh directory for the files of a test
+fn workspace(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("esharp-cli-{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+fn esharp(args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_esharp")).args(args).output().unwrap()
+}
+
+/// The exit code, stdout and stderr of a command
+fn result(output: Output) -> (i32, String, String) {
+	(output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn run_entry_functions() {
+	let exec = TEST_EXECUTABLE;
+	for engine in ["--interp", "--jit"] {
+		// integers are printed, and exited with
+		assert_eq!(result(esharp(&["run", engine, "--entry", "#bar", exec, "2", "3"])), (5, String::from("5\n"), String::new()), "{}", engine);
+	}
+
+	let (code, _, stderr) = result(esharp(&["run", exec]));
+	assert_eq!((code, stderr), (1, format!("error: {} has no function main\n", exec)));
+	let (code, _, stderr) = result(esharp(&["run", "--entry", "#bar", exec, "1"]));
+	assert_eq!((code, stderr), (1, String::from("error: function #bar takes 2 arguments, but 1 were given\n")));
+	let (code, _, stderr) = result(esharp(&["run", "--entry", "#bar", exec, "1", "-1"]));
+	assert_eq!(code, 2, "{}", stderr);
+	assert!(stderr.starts_with("error: invalid argument `-1`, expected an unsigned integer\n"), "{}", stderr);
+
+	// a file that isn't an executable
+	let path = std::env::temp_dir().join(format!("esharp-cli-run-{}.esbin", std::process::id()));
+	fs::write(&path, "fn main() -> i64").unwrap();
+	let (code, _, stderr) = result(esharp(&["run", path.to_str().unwrap()]));
+	fs::remove_file(&path).unwrap();
+	assert_eq!(code, 1);
+	assert!(stderr.starts_with(&format!("error: {} is not a valid executable", path.display())), "{}", stderr);
+	let (code, _, stderr) = result(esharp(&["run"]));
+	assert_eq!(code, 2);
+	assert!(stderr.starts_with("error: missing executable\nusage: esharp run"), "{}", stderr);
+}
+
+#[test]
+fn inspect_executables() {
+	let (code, stdout, stderr) = result(esharp(&["inspect", TEST_EXECUTABLE]));
+	assert_eq!(code, 0, "{}", stderr);
+	let json = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+	let functions = json["functions"].as_array().unwrap().iter().map(|function| function["name"].as_str().unwrap()).collect::<Vec<_>>();
+	assert_eq!(functions, ["#bar"]);
+	assert_eq!(json["functions"][0]["args"], serde_json::json!(["u32", "u32"]));
+
+	let (code, _, stderr) = result(esharp(&["inspect"]));
+	assert_eq!(code, 2, "{}", stderr);
+}
+
+#[test]
+fn run_assembled_programs() {
+	let dir = workspace("asm");
+	let src = dir.join("program.esasm");
+	let exec = dir.join("program.esbin");
+	fs::write(&src, PROGRAM).unwrap();
+	assert!(esharp(&["asm", "-o", exec.to_str().unwrap(), src.to_str().unwrap()]).status.success());
+	let exec = exec.to_str().unwrap();
+
+	for engine in ["--interp", "--jit"] {
+		// integers are printed, and exited with
+		assert_eq!(result(esharp(&["run", engine, exec, "2", "3"])), (5, String::from("5\n"), String::new()), "{}", engine);
+		assert_eq!(result(esharp(&["run", engine, "--entry", "half", exec, "5"])), (0, String::from("2.5\n"), String::new()), "{}", engine);
+		assert_eq!(result(esharp(&["run", engine, "--lazy", "--entry", "nothing", exec])), (0, String::new(), String::new()), "{}", engine);
+		// traps are reported as errors, whichever engine raised them
+		assert_eq!(result(esharp(&["run", engine, "--entry", "div", exec, "1", "0"])), (1, String::new(), String::from("error: division by zero\n")), "{}", engine);
+	}
+
+	// a source file that doesn't exist can't be assembled
+	let (code, _, stderr) = result(esharp(&["asm", "-o", exec, dir.join("missing.esasm").to_str().unwrap()]));
+	assert_eq!(code, 1, "{}", stderr);
+
+	fs::remove_dir_all(&dir).unwrap();
+}